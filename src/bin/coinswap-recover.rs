@@ -0,0 +1,72 @@
+use bitcoin::Address;
+use bitcoind::bitcoincore_rpc::{Auth, Client};
+use clap::Parser;
+use coinswap::{
+    utill::{parse_proxy_auth, setup_logger},
+    wallet::{recover_from_backup, SwapBackup, WalletError},
+};
+use std::{path::PathBuf, str::FromStr};
+
+/// Standalone swap recovery tool.
+///
+/// Claims back the funds of unfinished swaps from a swap backup file, exported with `maker-cli export-swap-backup`
+/// or `taker export-swap-backup`. Only a Bitcoin Core RPC connection is required, the original wallet or daemon is not needed.
+///
+/// The tool broadcasts the contract transactions, waits for them to mature and sweeps the timelock or hashlock
+/// outputs to the given address. It keeps running until all the contracts are swept.
+#[derive(Parser, Debug)]
+#[clap(version = option_env ! ("CARGO_PKG_VERSION").unwrap_or("unknown"),
+author = option_env ! ("CARGO_PKG_AUTHORS").unwrap_or(""))]
+struct Cli {
+    /// Path of the swap backup file.
+    #[clap(long, short = 'b')]
+    backup: PathBuf,
+    /// Address to sweep all the recovered funds to.
+    #[clap(long, short = 't')]
+    address: String,
+    /// Bitcoin Core RPC network address.
+    #[clap(
+        name = "ADDRESS:PORT",
+        long,
+        short = 'r',
+        default_value = "127.0.0.1:48332"
+    )]
+    pub rpc: String,
+    /// Bitcoin Core RPC authentication string (username, password).
+    #[clap(
+        name = "USER:PASSWD",
+        short = 'a',
+        long,
+        value_parser = parse_proxy_auth,
+        default_value = "user:password",
+    )]
+    pub auth: (String, String),
+}
+
+fn main() -> Result<(), WalletError> {
+    setup_logger(log::LevelFilter::Info);
+
+    let args = Cli::parse();
+
+    let backup = SwapBackup::read_from_disk(&args.backup)?;
+    if backup.is_empty() {
+        println!("Nothing to recover");
+        return Ok(());
+    }
+
+    let destination = Address::from_str(&args.address)
+        .map_err(|e| WalletError::General(e.to_string()))?
+        .require_network(backup.network())
+        .map_err(|e| WalletError::General(e.to_string()))?;
+
+    let rpc = Client::new(
+        &format!("http://{}", args.rpc),
+        Auth::UserPass(args.auth.0, args.auth.1),
+    )?;
+
+    for txid in recover_from_backup(&backup, &rpc, &destination)? {
+        println!("{}", txid);
+    }
+
+    Ok(())
+}
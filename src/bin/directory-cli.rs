@@ -1,5 +1,6 @@
 use std::{net::TcpStream, time::Duration};
 
+use bitcoin::OutPoint;
 use clap::Parser;
 
 use coinswap::{
@@ -27,6 +28,31 @@ struct App {
 enum Commands {
     /// Lists all the addresses from the directory server
     ListAddresses,
+    /// Lists the details of all makers: bond value, locktime, last heartbeat and remaining TTL
+    ListEntries,
+    /// Removes a maker from the address book. It is listed again on its next post
+    Evict {
+        /// Fidelity bond outpoint of the maker. Ex: <txid>:<vout>
+        outpoint: OutPoint,
+    },
+    /// Bans a fidelity bond outpoint, and evicts the maker using it
+    BanOutpoint {
+        /// Fidelity bond outpoint to ban. Ex: <txid>:<vout>
+        outpoint: OutPoint,
+    },
+    /// Bans a maker address, and evicts the makers using it
+    BanAddress {
+        /// Maker address to ban. Ex: <onion>:<port>
+        address: String,
+    },
+    /// Shows the server statistics and uptime
+    Stats,
+    /// Lists the last makers refused by the admission rules or fidelity checks, with the reasons
+    ListRejections,
+    /// Writes the address book and the ban list to disk
+    Flush,
+    /// Stops the directory server
+    Stop,
 }
 
 fn send_rpc_req(mut stream: TcpStream, req: RpcMsgReq) -> Result<(), DirectoryServerError> {
@@ -48,10 +74,17 @@ fn main() -> Result<(), DirectoryServerError> {
 
     let stream = TcpStream::connect(cli.rpc_port)?;
 
-    match cli.command {
-        Commands::ListAddresses => {
-            send_rpc_req(stream, RpcMsgReq::ListAddresses)?;
-        }
-    }
+    let req = match cli.command {
+        Commands::ListAddresses => RpcMsgReq::ListAddresses,
+        Commands::ListEntries => RpcMsgReq::ListEntries,
+        Commands::Evict { outpoint } => RpcMsgReq::Evict(outpoint),
+        Commands::BanOutpoint { outpoint } => RpcMsgReq::BanOutpoint(outpoint),
+        Commands::BanAddress { address } => RpcMsgReq::BanAddress(address),
+        Commands::Stats => RpcMsgReq::Stats,
+        Commands::ListRejections => RpcMsgReq::ListRejections,
+        Commands::Flush => RpcMsgReq::Flush,
+        Commands::Stop => RpcMsgReq::Stop,
+    };
+    send_rpc_req(stream, req)?;
     Ok(())
 }
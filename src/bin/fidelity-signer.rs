@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+
+use bitcoin::{
+    absolute::LockTime, consensus::encode::serialize_hex, Address, Amount, Network, OutPoint,
+};
+use clap::Parser;
+use coinswap::wallet::{ColdKey, DelegationRequest, WalletError};
+use std::str::FromStr;
+
+/// Offline signer of cold fidelity bonds.
+///
+/// Holds the key of the cold bonds on an offline machine, so it never touches the internet-facing maker server.
+/// makerd only watches the bond, and certifies it with a hot key, delegated by this app until an expiry:
+/// - Create a key with `new-key`, and fund the bond at the address given by `show-address`.
+/// - Import the bond in makerd with `maker-cli import-cold-fidelity`.
+/// - Sign the request of `maker-cli export-delegation-request` with `sign`, and import the signed file with
+///   `maker-cli import-delegation`. Do it again before the delegation expires.
+/// - Once the bond matures, spend it with `redeem`, and broadcast the transaction from an online node.
+///
+/// Files move between the machines by hand. No network connection is ever made.
+#[derive(Parser, Debug)]
+#[clap(version = option_env ! ("CARGO_PKG_VERSION").unwrap_or("unknown"),
+author = option_env ! ("CARGO_PKG_AUTHORS").unwrap_or(""))]
+struct App {
+    /// Path of the cold key file.
+    #[clap(long, short = 'k')]
+    key: PathBuf,
+    /// The command to execute
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Parser, Debug)]
+enum Commands {
+    /// Generates a new cold key, written with its network in the key file. Back the file up, it's the only way
+    /// to redeem the bonds.
+    NewKey {
+        /// Network of the key: bitcoin, testnet, signet or regtest.
+        #[clap(long, short = 'n', default_value = "bitcoin")]
+        network: Network,
+    },
+    /// Shows the public key, and the bond address to fund for a locktime.
+    ShowAddress {
+        /// Block height the bond is locked until.
+        #[clap(long, short = 'l')]
+        locktime: u32,
+    },
+    /// Signs a delegation request exported with `maker-cli export-delegation-request`.
+    Sign {
+        /// Path of the request file.
+        #[clap(long, short = 'r')]
+        request: PathBuf,
+        /// Path of the signed delegation file to write.
+        #[clap(long, short = 'o')]
+        output: PathBuf,
+    },
+    /// Creates the transaction spending a matured bond. Prints it in hex, to broadcast from an online node.
+    Redeem {
+        /// Outpoint of the bond. Ex: <txid>:<vout>
+        #[clap(long, short = 'o')]
+        outpoint: OutPoint,
+        /// Amount of the bond in sats.
+        #[clap(long, short = 'a')]
+        amount: u64,
+        /// Block height the bond is locked until.
+        #[clap(long, short = 'l')]
+        locktime: u32,
+        /// Address to send the funds to.
+        #[clap(long, short = 't')]
+        address: String,
+        /// Total fee to be paid in sats.
+        #[clap(long, short = 'f')]
+        fee: u64,
+    },
+}
+
+fn main() -> Result<(), WalletError> {
+    let app = App::parse();
+
+    match app.command {
+        Commands::NewKey { network } => {
+            let key = ColdKey::generate(&app.key, network)?;
+            println!("{}", key.public_key());
+        }
+        Commands::ShowAddress { locktime } => {
+            let key = ColdKey::read_from_disk(&app.key)?;
+            let lock_time = LockTime::from_height(locktime)?;
+            println!("Public key : {}", key.public_key());
+            println!("Address    : {}", key.fidelity_address(lock_time));
+        }
+        Commands::Sign { request, output } => {
+            let key = ColdKey::read_from_disk(&app.key)?;
+            let request = DelegationRequest::read_from_disk(&request)?;
+            // Check the bond and expiry before moving the file back to the maker.
+            println!("{}", request);
+            request.sign(&key)?.write_to_disk(&output)?;
+            println!("Signed delegation written to {}", output.display());
+        }
+        Commands::Redeem {
+            outpoint,
+            amount,
+            locktime,
+            address,
+            fee,
+        } => {
+            let key = ColdKey::read_from_disk(&app.key)?;
+            let destination = Address::from_str(&address)
+                .map_err(|e| WalletError::General(e.to_string()))?
+                .require_network(key.network())
+                .map_err(|e| WalletError::General(e.to_string()))?;
+            let tx = key.redeem_tx(
+                outpoint,
+                Amount::from_sat(amount),
+                LockTime::from_height(locktime)?,
+                &destination,
+                Amount::from_sat(fee),
+            )?;
+            println!("{}", serialize_hex(&tx));
+        }
+    }
+
+    Ok(())
+}
@@ -1,10 +1,12 @@
-use std::{net::TcpStream, time::Duration};
+use std::{net::TcpStream, path::PathBuf, time::Duration};
 
+use bitcoin::{OutPoint, PublicKey};
 use clap::Parser;
 use coinswap::{
-    maker::{MakerError, RpcMsgReq, RpcMsgResp},
+    maker::{parse_date, EarningsReport, MakerError, RpcMsgReq, RpcMsgResp, SwapRecord},
     utill::{read_message, send_message, setup_maker_logger},
 };
+use serde_json::to_string_pretty;
 
 /// A simple command line app to operate the makerd server.
 ///
@@ -71,8 +73,122 @@ enum Commands {
     },
     /// Show all the fidelity bonds, current and previous, with an (index, {bond_proof, is_spent}) tupple.
     ShowFidelity,
+    /// Shows the advertised fidelity proof in hex. It can be checked with `taker verify-fidelity`, along with
+    /// the maker address.
+    ShowFidelityProof,
+    /// Locks more coins in an additional fidelity bond, advertised along with the existing ones once confirmed.
+    /// Returns the txid of the bond transaction.
+    CreateFidelity {
+        /// Amount to lock in sats
+        #[clap(long, short = 'a')]
+        amount: u64,
+        /// Number of blocks the bond is locked for
+        #[clap(long, short = 't')]
+        timelock: u32,
+    },
+    /// Imports a confirmed cold fidelity bond, funded at the address given by `fidelity-signer show-address`.
+    /// The bond is advertised once a delegation is imported, see `export-delegation-request`.
+    ImportColdFidelity {
+        /// Outpoint of the bond. Ex: <txid>:<vout>
+        #[clap(long, short = 'o')]
+        outpoint: OutPoint,
+        /// Public key of the bond, from `fidelity-signer show-address`
+        #[clap(long, short = 'k')]
+        pubkey: PublicKey,
+        /// Block height the bond is locked until
+        #[clap(long, short = 'l')]
+        locktime: u32,
+    },
+    /// Writes a delegation request for a cold bond, to be signed offline with `fidelity-signer sign`.
+    /// The signed delegation lets makerd certify the bond with a hot key, until its expiry.
+    ExportDelegationRequest {
+        /// Wallet index of the cold bond
+        #[clap(long, short = 'i')]
+        index: u32,
+        /// Path of the request file to write.
+        #[clap(long, short = 'o')]
+        path: PathBuf,
+    },
+    /// Imports a delegation signed with `fidelity-signer sign`. The cold bond is advertised from the next
+    /// directory post.
+    ImportDelegation {
+        /// Path of the signed delegation file.
+        #[clap(long, short = 'f')]
+        path: PathBuf,
+    },
+    /// Lists the recent fidelity bond renewal events: replacement bonds, renewed certificates, redeemed bonds
+    /// and failures.
+    FidelityEvents,
+    /// Lists the settled swaps, oldest first: amounts, hop role, fee earned, mining fees paid, refund
+    /// locktime and outcome (completed, recovered or aborted).
+    SwapHistory {
+        /// Start of the range, as YYYY-MM-DD (UTC) or a unix time.
+        #[clap(long)]
+        from: Option<String>,
+        /// End of the range, excluded, as YYYY-MM-DD (UTC) or a unix time.
+        #[clap(long)]
+        to: Option<String>,
+        /// Output format. csv and json are meant for export, ex: `> swaps.csv`.
+        #[clap(long, short = 'f', possible_values = &["table", "json", "csv"], default_value = "table")]
+        format: String,
+    },
+    /// Shows the earnings of the settled swaps: swap counts, volume, fees earned, mining fees paid and
+    /// net earnings.
+    Earnings {
+        /// Start of the range, as YYYY-MM-DD (UTC) or a unix time.
+        #[clap(long)]
+        from: Option<String>,
+        /// End of the range, excluded, as YYYY-MM-DD (UTC) or a unix time.
+        #[clap(long)]
+        to: Option<String>,
+        /// Output format. csv and json are meant for export, ex: `> earnings.csv`.
+        #[clap(long, short = 'f', possible_values = &["table", "json", "csv"], default_value = "table")]
+        format: String,
+    },
     /// Sync the maker wallet with current blockchain state.
     SyncWallet,
+    /// Export a backup of all unfinished swaps. The backup can be used with `coinswap-recover` to claim
+    /// the swap funds back, even without a running makerd.
+    ExportSwapBackup {
+        /// Path of the backup file to write.
+        #[clap(long, short = 'o')]
+        path: PathBuf,
+    },
+    /// Register all unfinished swaps with a watchtower. The watchtower will broadcast and claim the
+    /// contracts on maker's behalf, if any of them is ever broadcasted.
+    RegisterWatchtower {
+        /// Watchtower address. Ex: 127.0.0.1:8090
+        #[clap(long, short = 'w')]
+        address: String,
+    },
+    /// Update an offer tier advertised in the maker's offer, without restarting makerd.
+    /// Only the given values are changed. Prints all the resulting offer tiers.
+    SetFees {
+        /// Index of the offer tier to update.
+        #[clap(long, default_value = "0")]
+        tier: usize,
+        /// Fixed base fee in sats.
+        #[clap(long)]
+        base_fee: Option<u64>,
+        /// Fee in percentage of the swap amount.
+        #[clap(long)]
+        amount_relative_fee_pct: Option<f64>,
+        /// Fee in percentage of the swap amount, per block of refund locktime.
+        #[clap(long)]
+        time_relative_fee_pct: Option<f64>,
+        /// Minimum difference in blocks between incoming and outgoing contract locktimes.
+        #[clap(long)]
+        minimum_locktime: Option<u16>,
+        /// Confirmations required on the taker's funding transactions.
+        #[clap(long)]
+        required_confirms: Option<u32>,
+        /// Minimum coinswap amount of the tier in sats.
+        #[clap(long)]
+        min_size: Option<u64>,
+        /// Maximum coinswap amount of the tier in sats.
+        #[clap(long)]
+        max_size: Option<u64>,
+    },
 }
 
 fn main() -> Result<(), MakerError> {
@@ -132,22 +248,101 @@ fn main() -> Result<(), MakerError> {
         Commands::ShowFidelity => {
             send_rpc_req(stream, RpcMsgReq::ListFidelity)?;
         }
+        Commands::ShowFidelityProof => {
+            send_rpc_req(stream, RpcMsgReq::FidelityProof)?;
+        }
+        Commands::CreateFidelity { amount, timelock } => {
+            send_rpc_req(stream, RpcMsgReq::CreateFidelity { amount, timelock })?;
+        }
+        Commands::ImportColdFidelity {
+            outpoint,
+            pubkey,
+            locktime,
+        } => {
+            send_rpc_req(
+                stream,
+                RpcMsgReq::ImportColdFidelity {
+                    outpoint,
+                    pubkey,
+                    locktime,
+                },
+            )?;
+        }
+        Commands::ExportDelegationRequest { index, path } => {
+            // The files are read and written by makerd, so resolve relative paths from here.
+            let path = std::env::current_dir()?.join(path);
+            send_rpc_req(stream, RpcMsgReq::ExportDelegationRequest { index, path })?;
+        }
+        Commands::ImportDelegation { path } => {
+            let path = std::env::current_dir()?.join(path);
+            send_rpc_req(stream, RpcMsgReq::ImportDelegation(path))?;
+        }
+        Commands::FidelityEvents => {
+            send_rpc_req(stream, RpcMsgReq::FidelityEvents)?;
+        }
+        Commands::SwapHistory { from, to, format } => {
+            let (from, to) = parse_range(from, to)?;
+            print_ledger(
+                rpc_req(stream, RpcMsgReq::SwapHistory { from, to })?,
+                &format,
+            );
+        }
+        Commands::Earnings { from, to, format } => {
+            let (from, to) = parse_range(from, to)?;
+            print_ledger(rpc_req(stream, RpcMsgReq::Earnings { from, to })?, &format);
+        }
         Commands::SyncWallet => {
             send_rpc_req(stream, RpcMsgReq::SyncWallet)?;
         }
+        Commands::ExportSwapBackup { path } => {
+            // The backup is written by makerd, so resolve relative paths from here.
+            let path = std::env::current_dir()?.join(path);
+            send_rpc_req(stream, RpcMsgReq::ExportSwapBackup(path))?;
+        }
+        Commands::RegisterWatchtower { address } => {
+            send_rpc_req(stream, RpcMsgReq::RegisterWatchtower(address))?;
+        }
+        Commands::SetFees {
+            tier,
+            base_fee,
+            amount_relative_fee_pct,
+            time_relative_fee_pct,
+            minimum_locktime,
+            required_confirms,
+            min_size,
+            max_size,
+        } => {
+            send_rpc_req(
+                stream,
+                RpcMsgReq::SetFees {
+                    tier,
+                    base_fee,
+                    amount_relative_fee_pct,
+                    time_relative_fee_pct,
+                    minimum_locktime,
+                    required_confirms,
+                    min_size,
+                    max_size,
+                },
+            )?;
+        }
     }
 
     Ok(())
 }
 
-fn send_rpc_req(mut stream: TcpStream, req: RpcMsgReq) -> Result<(), MakerError> {
+fn rpc_req(mut stream: TcpStream, req: RpcMsgReq) -> Result<RpcMsgResp, MakerError> {
     // stream.set_read_timeout(Some(Duration::from_secs(20)))?;
     stream.set_write_timeout(Some(Duration::from_secs(20)))?;
 
     send_message(&mut stream, &req)?;
 
     let response_bytes = read_message(&mut stream)?;
-    let response: RpcMsgResp = serde_cbor::from_slice(&response_bytes)?;
+    Ok(serde_cbor::from_slice(&response_bytes)?)
+}
+
+fn send_rpc_req(stream: TcpStream, req: RpcMsgReq) -> Result<(), MakerError> {
+    let response = rpc_req(stream, req)?;
 
     if matches!(response, RpcMsgResp::Pong) {
         println!("success");
@@ -157,3 +352,38 @@ fn send_rpc_req(mut stream: TcpStream, req: RpcMsgReq) -> Result<(), MakerError>
 
     Ok(())
 }
+
+fn parse_range(
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<(Option<u64>, Option<u64>), MakerError> {
+    Ok((
+        from.as_deref().map(parse_date).transpose()?,
+        to.as_deref().map(parse_date).transpose()?,
+    ))
+}
+
+/// Prints a swap ledger response in the given format. Other responses, like errors, are printed as is.
+fn print_ledger(response: RpcMsgResp, format: &str) {
+    match (response, format) {
+        (RpcMsgResp::SwapHistoryResp(records), "json") => println!(
+            "{}",
+            to_string_pretty(&records).expect("records serialization can't fail")
+        ),
+        (RpcMsgResp::SwapHistoryResp(records), "csv") => {
+            println!("{}", SwapRecord::CSV_HEADER);
+            for record in records {
+                println!("{}", record.to_csv());
+            }
+        }
+        (RpcMsgResp::EarningsResp(report), "json") => println!(
+            "{}",
+            to_string_pretty(&report).expect("report serialization can't fail")
+        ),
+        (RpcMsgResp::EarningsResp(report), "csv") => {
+            println!("{}", EarningsReport::CSV_HEADER);
+            println!("{}", report.to_csv());
+        }
+        (response, _) => println!("{}", response),
+    }
+}
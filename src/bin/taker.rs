@@ -1,10 +1,11 @@
 use bitcoin::{Address, Amount};
-use bitcoind::bitcoincore_rpc::{json::ListUnspentResultEntry, Auth};
+use bitcoind::bitcoincore_rpc::{json::ListUnspentResultEntry, Auth, Client};
 use clap::Parser;
 use coinswap::{
+    protocol::messages::FidelityProof,
     taker::{error::TakerError, SwapParams, Taker, TakerBehavior},
     utill::{parse_proxy_auth, setup_taker_logger, ConnectionType, REQUIRED_CONFIRMS},
-    wallet::{Destination, RPCConfig, SendAmount},
+    wallet::{audit_fidelity_proof, Destination, RPCConfig, SendAmount, WalletError},
 };
 use log::LevelFilter;
 use serde_json::{json, to_string_pretty};
@@ -86,6 +87,16 @@ enum Commands {
     },
     /// Update the offerbook with current market offers and display them
     FetchOffers,
+    /// Verify a maker's fidelity proof against the blockchain, and show its bonds and their values.
+    /// Only the Bitcoin Core RPC connection is used, no wallet is loaded.
+    VerifyFidelity {
+        /// The fidelity proof in hex, from `maker-cli show-fidelity-proof` or a directory explorer.
+        #[clap(long, short = 'p')]
+        proof: String,
+        /// The maker address the proof is certified for. Ex: <onion>:6102
+        #[clap(long, short = 'm')]
+        address: String,
+    },
 
     // TODO: Also add ListOffers command to just list the current book.
     /// Initiate the coinswap process
@@ -104,6 +115,20 @@ enum Commands {
     },
     /// Recover from all failed swaps
     Recover,
+    /// Export a backup of all unfinished swaps. The backup can be used with `coinswap-recover` to claim
+    /// the swap funds back, even without this wallet.
+    ExportSwapBackup {
+        /// Path of the backup file to write.
+        #[clap(long, short = 'o')]
+        path: PathBuf,
+    },
+    /// Register all unfinished swaps with a watchtower. The watchtower will broadcast and claim the
+    /// contracts on our behalf, if any of them is ever broadcasted.
+    RegisterWatchtower {
+        /// Watchtower address. Ex: 127.0.0.1:8090
+        #[clap(long, short = 't')]
+        address: String,
+    },
 }
 
 fn main() -> Result<(), TakerError> {
@@ -116,6 +141,18 @@ fn main() -> Result<(), TakerError> {
         ),
     );
 
+    // Auditing a proof doesn't need a wallet.
+    if let Commands::VerifyFidelity { proof, address } = &args.command {
+        let rpc = Client::new(
+            &format!("http://{}", args.rpc),
+            Auth::UserPass(args.auth.0.clone(), args.auth.1.clone()),
+        )
+        .map_err(WalletError::Rpc)?;
+        let proof = FidelityProof::from_hex(proof)?;
+        println!("{}", audit_fidelity_proof(&proof, address, &rpc)?);
+        return Ok(());
+    }
+
     let rpc_config = RPCConfig {
         url: args.rpc,
         auth: Auth::UserPass(args.auth.0, args.auth.1),
@@ -230,6 +267,7 @@ fn main() -> Result<(), TakerError> {
             let offerbook = taker.fetch_offers()?;
             println!("{:#?}", offerbook)
         }
+        Commands::VerifyFidelity { .. } => unreachable!("handled without a wallet"),
         Commands::Coinswap { makers, amount } => {
             let swap_params = SwapParams {
                 send_amount: Amount::from_sat(amount),
@@ -243,6 +281,18 @@ fn main() -> Result<(), TakerError> {
         Commands::Recover => {
             taker.recover_from_swap()?;
         }
+        Commands::ExportSwapBackup { path } => {
+            taker.get_wallet().export_swap_backup(&path)?;
+            println!("{}", path.display());
+        }
+        Commands::RegisterWatchtower { address } => {
+            let count = taker.get_wallet().register_with_watchtower(
+                &address,
+                taker.config.connection_type,
+                taker.config.socks_port,
+            )?;
+            println!("Registered {} contracts", count);
+        }
     }
 
     Ok(())
@@ -4,9 +4,9 @@ use clap::Parser;
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct App {
-    /// the network port
-    #[clap(long, short = 'p')]
-    port: u16,
+    /// the network ports, served on the same onion address. Can be given multiple times.
+    #[clap(long, short = 'p', required = true)]
+    port: Vec<u16>,
     /// the socks port
     #[clap(long, short = 's')]
     socks_port: u16,
@@ -18,7 +18,7 @@ struct App {
 #[cfg(feature = "tor")]
 fn main() -> Result<(), libtor::Error> {
     let args = App::parse();
-    coinswap::tor::start_tor(args.socks_port, args.port, args.base_dir)
+    coinswap::tor::start_tor(args.socks_port, &args.port, args.base_dir)
 }
 
 #[cfg(not(feature = "tor"))]
@@ -0,0 +1,57 @@
+use bitcoind::bitcoincore_rpc::Auth;
+use clap::Parser;
+use coinswap::{
+    utill::{parse_proxy_auth, setup_watchtower_logger},
+    wallet::RPCConfig,
+    watchtower::{start_watchtower_server, Watchtower, WatchtowerError},
+};
+
+use std::{path::PathBuf, sync::Arc};
+
+/// Standalone watchtower daemon.
+///
+/// Makers and takers register the pre-signed recovery transactions of their unfinished swaps with the watchtower.
+/// If any of the registered contracts is broadcasted, the watchtower broadcasts the rest of the contracts and
+/// claims them back on behalf of the registering party.
+#[derive(Parser)]
+#[clap(version = option_env ! ("CARGO_PKG_VERSION").unwrap_or("unknown"),
+author = option_env ! ("CARGO_PKG_AUTHORS").unwrap_or(""))]
+struct Cli {
+    /// Optional watchtower data directory. Default value : "~/.coinswap/watchtower"
+    #[clap(long, short = 'd')]
+    data_directory: Option<PathBuf>,
+    /// Sets the full node address for rpc connection.
+    #[clap(
+        name = "ADDRESS:PORT",
+        long,
+        short = 'r',
+        default_value = "127.0.0.1:48332"
+    )]
+    pub(crate) rpc: String,
+    /// Sets the rpc basic authentication.
+    #[clap(
+        name = "USER:PASSWORD",
+        short = 'a',
+        long,
+        value_parser = parse_proxy_auth,
+        default_value = "user:password",
+    )]
+    pub auth: (String, String),
+}
+
+fn main() -> Result<(), WatchtowerError> {
+    setup_watchtower_logger(log::LevelFilter::Info);
+
+    let args = Cli::parse();
+    let rpc_config = RPCConfig {
+        url: args.rpc,
+        auth: Auth::UserPass(args.auth.0, args.auth.1),
+        wallet_name: "random".to_string(), // we can put anything here as it will get updated in the init.
+    };
+
+    let watchtower = Arc::new(Watchtower::new(args.data_directory)?);
+
+    start_watchtower_server(watchtower, Some(rpc_config))?;
+
+    Ok(())
+}
@@ -26,6 +26,16 @@ pub enum NetError {
 
     /// Error indicating an invalid CLI application network.
     InvalidAppNetwork,
+
+    /// Error indicating the length prefix of an incoming message exceeds the allowed maximum.
+    ///
+    /// Contains the announced message length.
+    MessageTooLarge(u32),
+
+    /// Error indicating the peer stalled in the middle of a message.
+    ///
+    /// The rest of the message may still arrive, so the stream is out of sync and must be dropped.
+    FrameTimeout,
 }
 
 impl std::fmt::Display for NetError {
@@ -6,9 +6,12 @@ extern crate bitcoind;
 pub mod error;
 pub mod maker;
 pub mod market;
+pub(crate) mod net;
 pub mod protocol;
 pub mod taker;
 #[cfg(feature = "tor")]
 pub mod tor;
 pub mod utill;
 pub mod wallet;
+pub(crate) mod watcher;
+pub mod watchtower;
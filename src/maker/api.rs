@@ -10,23 +10,28 @@ use crate::{
     protocol::{
         contract::check_hashvalues_are_equal,
         messages::{FidelityProof, ReqContractSigsForSender},
+        podle::{Podle, MAX_NUMS_INDEX},
         Hash160,
     },
     utill::{
-        get_maker_dir, redeemscript_to_scriptpubkey, ConnectionType, HEART_BEAT_INTERVAL,
-        REQUIRED_CONFIRMS,
+        get_maker_dir, redeemscript_to_scriptpubkey, unix_time, ConnectionType, HEART_BEAT_INTERVAL,
     },
     wallet::{RPCConfig, SwapCoin, WalletSwapCoin},
+    watcher::TxWatcher,
 };
 use bitcoin::{
     ecdsa::Signature,
+    hashes::sha256,
     secp256k1::{self, Secp256k1},
-    OutPoint, PublicKey, ScriptBuf, Transaction,
+    Amount, CompressedPublicKey, OutPoint, PublicKey, ScriptBuf, Transaction, Txid,
 };
 use bitcoind::bitcoincore_rpc::RpcApi;
 use std::{
-    collections::HashMap,
-    path::PathBuf,
+    collections::{HashMap, HashSet, VecDeque},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
     sync::{
         atomic::{AtomicBool, Ordering::Relaxed},
         Arc, Mutex, RwLock,
@@ -38,15 +43,23 @@ use std::{
 use crate::{
     protocol::{
         contract::{
-            check_hashlock_has_pubkey, check_multisig_has_pubkey, check_reedemscript_is_multisig,
-            find_funding_output_index, read_contract_locktime,
+            check_funding_merkle_proof, check_hashlock_has_pubkey, check_multisig_has_pubkey,
+            check_reedemscript_is_multisig, find_funding_output_index, read_contract_locktime,
         },
-        messages::ProofOfFunding,
+        messages::{ErrorCode, ProofOfFunding},
     },
     wallet::{IncomingSwapCoin, OutgoingSwapCoin, Wallet, WalletError},
 };
 
-use super::{config::MakerConfig, error::MakerError};
+use super::{
+    config::{FeePolicy, MakerConfig},
+    error::MakerError,
+    ledger::{SwapLedger, SwapOutcome, SwapTerms},
+    rpc::FidelityEvent,
+};
+
+/// Number of fidelity events kept for the `FidelityEvents` RPC.
+const MAX_FIDELITY_EVENTS: usize = 50;
 
 /// Interval for health checks on a stable RPC connection with bitcoind.
 pub const RPC_PING_INTERVAL: Duration = Duration::from_secs(10);
@@ -71,10 +84,9 @@ pub const MIN_CONTRACT_REACTION_TIME: u16 = 20;
 
 /// # Fee Parameters for Coinswap
 ///
-/// These parameters define the fees charged by Makers in a coinswap transaction.
+/// These parameters define the default fees charged by Makers in a coinswap transaction.
+/// Makers can override them with [FeePolicy] tiers in their config, or at runtime with `maker-cli set-fees`.
 ///
-/// TODO: These parameters are currently hardcoded. Consider making them configurable for Makers in the future.
-///p
 /// - `BASE_FEE`: A fixed base fee charged by the Maker for providing its services
 /// - `AMOUNT_RELATIVE_FEE_PCT`: A percentage fee based on the swap amount.
 /// - `TIME_RELATIVE_FEE_PCT`: A percentage fee based on the refund locktime (duration the Maker must wait for a refund).
@@ -111,6 +123,9 @@ pub const TIME_RELATIVE_FEE_PCT: f64 = 0.005;
 /// Minimum Coinswap amount; makers will not accept amounts below this.
 pub const MIN_SWAP_AMOUNT: u64 = 10_000;
 
+/// Validity of the offers given to takers, in seconds.
+pub const OFFER_VALIDITY: u64 = 60 * 60;
+
 // What's the use of RefundLocktimeStep?
 
 /// Used to configure the maker for testing purposes.
@@ -160,6 +175,14 @@ pub(crate) struct ConnectionState {
     pub(crate) incoming_swapcoins: Vec<IncomingSwapCoin>,
     pub(crate) outgoing_swapcoins: Vec<OutgoingSwapCoin>,
     pub(crate) pending_funding_txes: Vec<Transaction>,
+    /// The PoDLE received in the taker's hello, checked before signing any contract.
+    pub(crate) podle: Option<Podle>,
+    /// Unsigned refund txs of the outgoing swapcoins, created when the taker asks to abort the swap.
+    pub(crate) outgoing_refund_txes: Vec<Transaction>,
+    /// Amounts and fees of the swap, set at the proof of funding and recorded in the ledger once settled.
+    pub(crate) terms: Option<SwapTerms>,
+    /// Offer tier the swap was first validated against. The fees are charged from the same tier.
+    pub(crate) tier: Option<usize>,
 }
 
 pub(crate) struct ThreadPool {
@@ -177,6 +200,8 @@ impl ThreadPool {
 
     pub(crate) fn add_thread(&self, handle: JoinHandle<()>) {
         let mut threads = self.threads.lock().unwrap();
+        // Forget the finished threads, so short lived client threads don't pile up.
+        threads.retain(|thread| !thread.is_finished());
         threads.push(handle);
     }
     #[inline]
@@ -220,20 +245,34 @@ pub struct Maker {
     pub(crate) behavior: MakerBehavior,
     /// Maker configurations
     pub(crate) config: MakerConfig,
+    /// The live offer tiers advertised in the offers. Initialized from the config, never empty.
+    pub(crate) offer_tiers: RwLock<Vec<FeePolicy>>,
     /// Maker's underlying wallet
     pub wallet: RwLock<Wallet>,
     /// A flag to trigger shutdown event
     pub shutdown: AtomicBool,
     /// Map of IP address to Connection State + last Connected instant
     pub(crate) ongoing_swap_state: Mutex<HashMap<String, (ConnectionState, Instant)>>,
-    /// Highest Value Fidelity Proof
-    pub(crate) highest_fidelity_proof: RwLock<Option<FidelityProof>>,
+    /// Swaps whose sender contracts are signed, waiting for their proof of funding. Keyed by swap hash value.
+    pub(crate) signed_swaps: Mutex<HashMap<Hash160, Instant>>,
+    /// Advertised fidelity proof, of all the live bonds. The highest value bond is the primary one.
+    pub(crate) fidelity_proof: RwLock<Option<FidelityProof>>,
+    /// Whether a new fidelity bond, added over RPC or by the renewal, is waiting for confirmation.
+    pub(crate) bond_pending: AtomicBool,
+    /// The recent fidelity bond renewal steps with their unix times, newest last.
+    pub(crate) fidelity_events: Mutex<VecDeque<(u64, FidelityEvent)>>,
+    /// PoDLE commitments already used by takers. Persisted in the `commitments` file of the data directory.
+    pub(crate) used_commitments: Mutex<HashSet<sha256::Hash>>,
+    /// The settled swaps. Persisted in the `swap_ledger` file of the data directory.
+    pub(crate) swap_ledger: SwapLedger,
     /// Is setup complete
     pub is_setup_complete: AtomicBool,
     /// Path for the data directory.
     pub(crate) data_dir: PathBuf,
     /// Thread pool for managing all spawned threads
     pub(crate) thread_pool: Arc<ThreadPool>,
+    /// Watches the swap contracts over bitcoind ZMQ, if configured.
+    pub(crate) tx_watcher: Option<TxWatcher>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -306,22 +345,49 @@ impl Maker {
 
         let port = config.network_port;
 
+        config.offer_tiers.retain(|tier| match tier.validate() {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Ignoring invalid offer tier in config : {} | {:?}", e, tier);
+                false
+            }
+        });
+        if config.offer_tiers.is_empty() {
+            config.offer_tiers.push(FeePolicy::default());
+        }
+
         config.write_to_file(&data_dir.join("config.toml"))?;
 
         log::info!("Initializing wallet sync");
         wallet.sync()?;
         log::info!("Completed wallet sync");
 
+        let used_commitments = load_used_commitments(&data_dir.join("commitments"))?;
+        let swap_ledger = SwapLedger::load(&data_dir.join("swap_ledger"))?;
+
+        let tx_watcher = if config.zmq_address.is_empty() {
+            None
+        } else {
+            Some(TxWatcher::start(&config.zmq_address)?)
+        };
+
         Ok(Self {
             behavior,
+            offer_tiers: RwLock::new(config.offer_tiers.clone()),
             config,
             wallet: RwLock::new(wallet),
             shutdown: AtomicBool::new(false),
             ongoing_swap_state: Mutex::new(HashMap::new()),
-            highest_fidelity_proof: RwLock::new(None),
+            fidelity_proof: RwLock::new(None),
+            bond_pending: AtomicBool::new(false),
+            fidelity_events: Mutex::new(VecDeque::new()),
+            used_commitments: Mutex::new(used_commitments),
+            signed_swaps: Mutex::new(HashMap::new()),
+            swap_ledger,
             is_setup_complete: AtomicBool::new(false),
             data_dir,
             thread_pool: Arc::new(ThreadPool::new(port)),
+            tx_watcher,
         })
     }
 
@@ -329,11 +395,199 @@ impl Maker {
         &self.data_dir
     }
 
+    /// Number of swaps in flight: signed and waiting for their proof of funding, with reserved utxos, or ongoing.
+    pub(crate) fn inflight_swaps(&self) -> Result<usize, MakerError> {
+        let mut ids = self
+            .ongoing_swap_state
+            .lock()?
+            .keys()
+            .cloned()
+            .collect::<HashSet<_>>();
+        ids.extend(self.wallet.read()?.utxo_reservations.swap_ids().cloned());
+        Ok(ids.len() + self.signed_swaps.lock()?.len())
+    }
+
+    /// Checks the taker's PoDLE against the committed UTXO, and records the commitment as used.
+    ///
+    /// The UTXO must be an unspent P2WPKH output of the proof's key, confirmed to `podle_min_confirms`
+    /// and worth at least `podle_min_amount_pct` of the swap amount.
+    ///
+    /// Every maker of a route gets the route's PoDLE. Requests without one are refused.
+    pub(crate) fn check_podle(
+        &self,
+        podle: Option<&Podle>,
+        swap_amount: Amount,
+    ) -> Result<(), MakerError> {
+        let podle = required_podle(podle)?;
+        if podle.index >= self.config.podle_max_index.min(MAX_NUMS_INDEX) {
+            return Err(MakerError::rejected(
+                ErrorCode::InvalidPodle,
+                "PoDLE index too high",
+            ));
+        }
+        let commitment = podle.commitment();
+        if self.used_commitments.lock()?.contains(&commitment) {
+            return Err(MakerError::rejected(
+                ErrorCode::InvalidPodle,
+                "PoDLE commitment already used",
+            ));
+        }
+        podle
+            .verify()
+            .map_err(|e| MakerError::rejected(ErrorCode::InvalidPodle, format!("{:?}", e)))?;
+
+        let txout = self
+            .wallet
+            .read()?
+            .rpc
+            .get_tx_out(&podle.utxo.txid, podle.utxo.vout, Some(false))
+            .map_err(WalletError::Rpc)?
+            .ok_or_else(|| {
+                MakerError::rejected(ErrorCode::InvalidPodle, "PoDLE utxo doesn't exist")
+            })?;
+        let script_pubkey =
+            ScriptBuf::new_p2wpkh(&CompressedPublicKey(podle.pubkey).wpubkey_hash());
+        if txout.script_pub_key.hex != script_pubkey.to_bytes() {
+            return Err(MakerError::rejected(
+                ErrorCode::InvalidPodle,
+                "PoDLE utxo doesn't belong to the PoDLE key",
+            ));
+        }
+        if txout.confirmations < self.config.podle_min_confirms {
+            return Err(MakerError::rejected(
+                ErrorCode::InvalidPodle,
+                "PoDLE utxo not confirmed to required depth",
+            ));
+        }
+        let min_amount = swap_amount.to_sat() as f64 * self.config.podle_min_amount_pct / 100.0;
+        if (txout.value.to_sat() as f64) < min_amount {
+            return Err(MakerError::rejected(
+                ErrorCode::InvalidPodle,
+                "PoDLE utxo value too low",
+            ));
+        }
+
+        // Check and insert under the same lock, the same commitment may be racing on another connection.
+        let mut used_commitments = self.used_commitments.lock()?;
+        if !used_commitments.insert(commitment) {
+            return Err(MakerError::rejected(
+                ErrorCode::InvalidPodle,
+                "PoDLE commitment already used",
+            ));
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.data_dir.join("commitments"))?;
+        writeln!(file, "{}", commitment)?;
+
+        log::info!(
+            "[{}] Accepted PoDLE commitment {} for utxo {}",
+            self.config.network_port,
+            commitment,
+            podle.utxo
+        );
+        Ok(())
+    }
+
+    /// Records a settled swap in the ledger. `funded` tells whether our outgoing funding txs were broadcasted.
+    ///
+    /// Swaps that didn't reach the proof of funding are not recorded. A failure to write the ledger is only
+    /// logged, it must not affect the swap itself.
+    pub(crate) fn record_swap(
+        &self,
+        id: &str,
+        state: &ConnectionState,
+        outcome: SwapOutcome,
+        funded: bool,
+    ) {
+        if let Some(terms) = &state.terms {
+            if let Err(e) = self.swap_ledger.record(terms.settle(id, outcome, funded)) {
+                log::error!(
+                    "[{}] Failed to record swap {} in the ledger: {:?}",
+                    self.config.network_port,
+                    id,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Returns the offer tier at the given index, as chosen by the taker.
+    pub(crate) fn get_offer_tier(&self, index: usize) -> Result<FeePolicy, MakerError> {
+        self.offer_tiers
+            .read()?
+            .get(index)
+            .cloned()
+            .ok_or(MakerError::General("Unknown offer tier"))
+    }
+
+    /// Replaces the live offer tier at the given index and persists all tiers in the config file.
+    /// New offers are advertised with the updated tiers, without a restart.
+    pub(crate) fn set_offer_tier(
+        &self,
+        index: usize,
+        fee_policy: FeePolicy,
+    ) -> Result<Vec<FeePolicy>, MakerError> {
+        let offer_tiers = {
+            let mut offer_tiers = self.offer_tiers.write()?;
+            let tier = offer_tiers
+                .get_mut(index)
+                .ok_or(MakerError::General("Unknown offer tier"))?;
+            *tier = fee_policy;
+            offer_tiers.clone()
+        };
+        let config = MakerConfig {
+            offer_tiers: offer_tiers.clone(),
+            ..self.config.clone()
+        };
+        config.write_to_file(&self.data_dir.join("config.toml"))?;
+        log::info!(
+            "[{}] Offer tier {} updated : {:?}",
+            self.config.network_port,
+            index,
+            offer_tiers[index]
+        );
+        Ok(offer_tiers)
+    }
+
     /// Returns a reference to the Maker's wallet.
     pub fn get_wallet(&self) -> &RwLock<Wallet> {
         &self.wallet
     }
 
+    /// Logs a fidelity bond renewal step, and keeps it for the `FidelityEvents` RPC.
+    pub(crate) fn record_fidelity_event(&self, event: FidelityEvent) -> Result<(), MakerError> {
+        match &event {
+            FidelityEvent::Failed(_) => log::warn!("[{}] {}", self.config.network_port, event),
+            _ => log::info!("[{}] {}", self.config.network_port, event),
+        }
+        let mut events = self.fidelity_events.lock()?;
+        events.push_back((unix_time(), event));
+        if events.len() > MAX_FIDELITY_EVENTS {
+            events.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Creates the hashlock spend of an incoming swapcoin, if the hash preimage is known.
+    ///
+    /// The connection states only hold copies of the swapcoins, so the preimage is looked up from the wallet.
+    pub(crate) fn create_hashlock_spend(
+        &self,
+        incoming: &IncomingSwapCoin,
+    ) -> Result<Option<Transaction>, MakerError> {
+        let wallet = self.wallet.read()?;
+        let swapcoin = wallet
+            .find_incoming_swapcoin(&incoming.get_multisig_redeemscript())
+            .unwrap_or(incoming);
+        if !swapcoin.is_hash_preimage_known() {
+            return Ok(None);
+        }
+        let next_internal_address = &wallet.get_next_internal_addresses(1)?[0];
+        Ok(Some(swapcoin.create_hashlock_spend(next_internal_address)?))
+    }
+
     /// Checks consistency of the [ProofOfFunding] message and return the Hashvalue
     /// used in hashlock transaction.
     pub(crate) fn verify_proof_of_funding(
@@ -344,17 +598,30 @@ impl Maker {
             return Err(MakerError::General("No funding txs provided by Taker"));
         }
 
+        // Check the funding against the offer tier chosen by the taker.
+        let tier = self.get_offer_tier(message.tier)?;
+        let (minimum_locktime, required_confirms) = (tier.minimum_locktime, tier.required_confirms);
+
+        let mut funding_blocks = Vec::new();
+        let mut total_funding_amount = Amount::ZERO;
         for funding_info in &message.confirmed_funding_txes {
             // check that the new locktime is sufficently short enough compared to the
             // locktime in the provided funding tx
             let locktime = read_contract_locktime(&funding_info.contract_redeemscript)?;
-            if locktime - message.refund_locktime < MIN_CONTRACT_REACTION_TIME {
-                return Err(MakerError::General(
+            if locktime - message.refund_locktime < minimum_locktime {
+                return Err(MakerError::rejected(
+                    ErrorCode::BadLocktime,
                     "Next hop locktime too close to current hop locktime",
                 ));
             }
 
             let funding_output_index = find_funding_output_index(funding_info)?;
+            total_funding_amount += funding_info
+                .funding_tx
+                .output
+                .get(funding_output_index as usize)
+                .expect("output at index expected")
+                .value;
 
             //check the funding_tx is confirmed to required depth
             if let Some(txout) = self
@@ -368,7 +635,7 @@ impl Maker {
                 )
                 .map_err(WalletError::Rpc)?
             {
-                if txout.confirmations < REQUIRED_CONFIRMS {
+                if txout.confirmations < required_confirms {
                     return Err(MakerError::General(
                         "funding tx not confirmed to required depth",
                     ));
@@ -377,6 +644,22 @@ impl Maker {
                 return Err(MakerError::General("funding tx output doesnt exist"));
             }
 
+            // check the merkle proof is for a block in the active chain, so a later reorg of it can be detected
+            let funding_block = check_funding_merkle_proof(funding_info)?;
+            let block_confirmations = self
+                .wallet
+                .read()?
+                .rpc
+                .get_block_header_info(&funding_block)
+                .map_err(WalletError::Rpc)?
+                .confirmations;
+            if block_confirmations < required_confirms as i32 {
+                return Err(MakerError::General(
+                    "funding tx merkle proof is not for a block in the active chain",
+                ));
+            }
+            funding_blocks.push((funding_info.funding_tx.compute_txid(), funding_block));
+
             check_reedemscript_is_multisig(&funding_info.multisig_redeemscript)?;
 
             let (_, tweabale_pubkey) = self.wallet.read()?.get_tweakable_keypair()?;
@@ -410,7 +693,31 @@ impl Maker {
             }
         }
 
-        Ok(check_hashvalues_are_equal(message)?)
+        // The fees are charged from the tier, so the funding must be within its size range.
+        let tier = self.advertised_tier(&tier)?;
+        if total_funding_amount.to_sat() < tier.min_size
+            || total_funding_amount.to_sat() > tier.max_size
+        {
+            return Err(MakerError::rejected(
+                ErrorCode::AmountOutOfRange,
+                format!(
+                    "Funding amount {} not within min/max limit of tier {}, min {}, max {}",
+                    total_funding_amount.to_sat(),
+                    message.tier,
+                    tier.min_size,
+                    tier.max_size
+                ),
+            ));
+        }
+
+        let hashvalue = check_hashvalues_are_equal(message)?;
+
+        let mut wallet = self.wallet.write()?;
+        for (txid, blockhash) in funding_blocks {
+            wallet.track_confirmation(txid, blockhash)?;
+        }
+
+        Ok(hashvalue)
     }
 
     /// Verify the contract transaction for Sender and return the signatures.
@@ -418,6 +725,16 @@ impl Maker {
         &self,
         message: &ReqContractSigsForSender,
     ) -> Result<Vec<Signature>, MakerError> {
+        let minimum_locktime = self.get_offer_tier(message.tier)?.minimum_locktime;
+        if message.locktime < minimum_locktime {
+            return Err(MakerError::rejected(
+                ErrorCode::BadLocktime,
+                format!(
+                    "Locktime {} is below the minimum locktime {} of tier {}",
+                    message.locktime, minimum_locktime, message.tier
+                ),
+            ));
+        }
         let mut sigs = Vec::<Signature>::new();
         for txinfo in &message.txs_info {
             if txinfo.senders_contract_tx.input.len() != 1
@@ -461,7 +778,7 @@ impl Maker {
                 &txinfo.timelock_pubkey,
                 &message.hashvalue,
                 &message.locktime,
-                &MIN_CONTRACT_REACTION_TIME,
+                &minimum_locktime,
             )?;
 
             self.wallet.write()?.cache_prevout_to_contract(
@@ -483,119 +800,247 @@ impl Maker {
     }
 }
 
-/// Constantly checks for contract transactions in the bitcoin network for all
-/// unsettled swap.
+/// Refuses a request without a PoDLE, else nothing would stop a taker from locking our utxos for free.
+fn required_podle(podle: Option<&Podle>) -> Result<&Podle, MakerError> {
+    podle.ok_or_else(|| {
+        MakerError::rejected(ErrorCode::InvalidPodle, "A PoDLE commitment is required")
+    })
+}
+
+/// Reads the used PoDLE commitments, one hex commitment per line. A missing file means none are used yet.
+fn load_used_commitments(path: &Path) -> Result<HashSet<sha256::Hash>, MakerError> {
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            sha256::Hash::from_str(line.trim())
+                .map_err(|_| MakerError::General("Invalid entry in commitments file"))
+        })
+        .collect()
+}
+
+/// Checks the bitcoin network for contract transactions of all unsettled swaps.
 ///
-/// If any one of the is ever observed, run the recovery routine.
-pub(crate) fn check_for_broadcasted_contracts(maker: Arc<Maker>) -> Result<(), MakerError> {
+/// If any one of them is observed, run the recovery routine. Run by the maker server, on every ZMQ
+/// notification if a [TxWatcher] is configured, periodically otherwise.
+///
+/// With a watcher, contracts are looked up via RPC only when they are first watched, or if `rpc_fallback` is set.
+pub(crate) fn check_for_broadcasted_contracts(
+    maker: &Arc<Maker>,
+    rpc_fallback: bool,
+) -> Result<(), MakerError> {
     let mut failed_swap_ip = Vec::new();
-    loop {
-        if maker.shutdown.load(Relaxed) {
-            break;
+    let mut lock_onstate = maker.ongoing_swap_state.lock()?;
+
+    let fresh_txids = match &maker.tx_watcher {
+        Some(watcher) => watcher.set_watched(lock_onstate.values().flat_map(|(state, _)| {
+            state
+                .incoming_swapcoins
+                .iter()
+                .map(|is| is.contract_tx.compute_txid())
+                .chain(
+                    state
+                        .outgoing_swapcoins
+                        .iter()
+                        .map(|oc| oc.contract_tx.compute_txid()),
+                )
+        })),
+        None => Vec::new(),
+    };
+    let is_broadcasted = |txid: &Txid| -> Result<bool, MakerError> {
+        if let Some(watcher) = &maker.tx_watcher {
+            if watcher.is_seen(txid) {
+                return Ok(true);
+            }
+            if !rpc_fallback && !fresh_txids.contains(txid) {
+                return Ok(false);
+            }
         }
-        // An extra scope to release all locks when done.
-        {
-            let mut lock_onstate = maker.ongoing_swap_state.lock()?;
-            for (ip, (connection_state, _)) in lock_onstate.iter_mut() {
-                let txids_to_watch = connection_state
-                    .incoming_swapcoins
+        Ok(maker
+            .wallet
+            .read()?
+            .rpc
+            .get_raw_transaction_info(txid, None)
+            .is_ok())
+    };
+    for (ip, (connection_state, _)) in lock_onstate.iter_mut() {
+        let txids_to_watch = connection_state
+            .incoming_swapcoins
+            .iter()
+            .map(|is| is.contract_tx.compute_txid())
+            .chain(
+                connection_state
+                    .outgoing_swapcoins
                     .iter()
-                    .map(|is| is.contract_tx.compute_txid())
-                    .chain(
-                        connection_state
-                            .outgoing_swapcoins
-                            .iter()
-                            .map(|oc| oc.contract_tx.compute_txid()),
-                    )
-                    .collect::<Vec<_>>();
-
-                // No need to check for other contracts in the connection state, if any one of them
-                // is ever observed in the mempool/block, run recovery routine.
-                for txid in txids_to_watch {
-                    if maker
-                        .wallet
-                        .read()?
-                        .rpc
-                        .get_raw_transaction_info(&txid, None)
-                        .is_ok()
-                    {
-                        let mut outgoings = Vec::new();
-                        let mut incomings = Vec::new();
-                        // Something is broadcasted. Report, Recover and Abort.
-                        log::warn!(
-                            "[{}] Contract txs broadcasted!! txid: {} Recovering from ongoing swaps.",
-                            maker.config.network_port,
-                            txid
-                        );
-                        // Extract Incoming and Outgoing contracts, and timelock spends of the contract transactions.
-                        // fully signed.
-                        for (og_sc, ic_sc) in connection_state
-                            .outgoing_swapcoins
-                            .iter()
-                            .zip(connection_state.incoming_swapcoins.iter())
-                        {
-                            let contract_timelock = og_sc.get_timelock()?;
-                            let next_internal_address =
-                                &maker.wallet.read()?.get_next_internal_addresses(1)?[0];
-                            let time_lock_spend =
-                                og_sc.create_timelock_spend(next_internal_address)?;
-
-                            // Sometimes we might not have other's contact signatures.
-                            // This means the protocol have been stopped abruptly.
-                            // This needs more careful consideration as this should not happen
-                            // after funding transactions have been broadcasted for outgoing contracts.
-                            // For incomings, its less lethal as thats mostly the other party's burden.
-                            if let Ok(tx) = og_sc.get_fully_signed_contract_tx() {
-                                outgoings.push((
-                                    (og_sc.get_multisig_redeemscript(), tx),
-                                    (contract_timelock, time_lock_spend),
-                                ));
-                            } else {
-                                log::warn!(
-                                    "[{}] Outgoing contact signature not known. Not Broadcasting",
-                                    maker.config.network_port
-                                );
-                            }
-                            if let Ok(tx) = ic_sc.get_fully_signed_contract_tx() {
-                                incomings.push((ic_sc.get_multisig_redeemscript(), tx));
-                            } else {
-                                log::warn!(
-                                    "[{}] Incoming contact signature not known. Not Broadcasting",
-                                    maker.config.network_port
-                                );
-                            }
-                        }
-                        failed_swap_ip.push(ip.clone());
-
-                        // Spawn a separate thread to wait for contract maturity and broadcasting timelocked.
-                        let maker_clone = maker.clone();
-                        log::info!(
-                            "[{}] Spawning recovery thread after seeing contracts in mempool",
-                            maker.config.network_port
-                        );
-                        let handle = std::thread::Builder::new()
-                            .name("Swap recovery thread".to_string())
-                            .spawn(move || {
-                                if let Err(e) = recover_from_swap(maker_clone, outgoings, incomings)
-                                {
-                                    log::error!("Failed to recover from swap due to: {:?}", e);
-                                }
-                            })?;
-                        maker.thread_pool.add_thread(handle);
-                        // Clear the state value here
-                        *connection_state = ConnectionState::default();
-                        break;
-                    }
-                }
+                    .map(|oc| oc.contract_tx.compute_txid()),
+            )
+            .collect::<Vec<_>>();
+
+        // No need to check for other contracts in the connection state, if any one of them
+        // is ever observed in the mempool/block, run recovery routine.
+        for txid in txids_to_watch {
+            if is_broadcasted(&txid)? {
+                // Something is broadcasted. Report, Recover and Abort.
+                log::warn!(
+                    "[{}] Contract txs broadcasted!! txid: {} Recovering from ongoing swaps.",
+                    maker.config.network_port,
+                    txid
+                );
+                spawn_swap_recovery(maker, connection_state)?;
+                // Clear the state value here, and keep the old one for the ledger.
+                failed_swap_ip.push((ip.clone(), std::mem::take(connection_state)));
+                break;
             }
+        }
+    }
+
+    // Clear the state entry here
+    let mut settled = Vec::new();
+    for (ip, state) in failed_swap_ip {
+        lock_onstate.remove(&ip);
+        let funded = !maker.wallet.write()?.utxo_reservations.release(&ip);
+        settled.push((ip, state, funded));
+    }
+    drop(lock_onstate);
+    // The ledger is written once the swap states are released.
+    for (ip, state, funded) in &settled {
+        maker.record_swap(ip, state, SwapOutcome::Recovered, *funded);
+    }
+
+    Ok(())
+}
 
-            // Clear the state entry here
-            for ip in failed_swap_ip.iter() {
-                lock_onstate.remove(ip);
+/// Spawns a recovery thread for a swap, broadcasting its contracts and waiting for their maturity.
+///
+/// Contracts whose counterparty signature is not known yet are skipped, as the swap might have stopped abruptly.
+fn spawn_swap_recovery(
+    maker: &Arc<Maker>,
+    connection_state: &ConnectionState,
+) -> Result<(), MakerError> {
+    let mut outgoings = Vec::new();
+    let mut incomings = Vec::new();
+    // Extract Incoming and Outgoing contracts, and timelock spends of the contract transactions.
+    // fully signed.
+    for (og_sc, ic_sc) in connection_state
+        .outgoing_swapcoins
+        .iter()
+        .zip(connection_state.incoming_swapcoins.iter())
+    {
+        let contract_timelock = og_sc.get_timelock()?;
+        let next_internal_address = &maker.wallet.read()?.get_next_internal_addresses(1)?[0];
+        let time_lock_spend = og_sc.create_timelock_spend(next_internal_address)?;
+
+        // Sometimes we might not have other's contact signatures.
+        // This means the protocol have been stopped abruptly.
+        // This needs more careful consideration as this should not happen
+        // after funding transactions have been broadcasted for outgoing contracts.
+        // For incomings, its less lethal as thats mostly the other party's burden.
+        if let Ok(tx) = og_sc.get_fully_signed_contract_tx() {
+            outgoings.push((
+                (og_sc.get_multisig_redeemscript(), tx),
+                (contract_timelock, time_lock_spend),
+            ));
+        } else {
+            log::warn!(
+                "[{}] Outgoing contact signature not known. Not Broadcasting",
+                maker.config.network_port
+            );
+        }
+        if let Ok(tx) = ic_sc.get_fully_signed_contract_tx() {
+            let hashlock_spend = maker.create_hashlock_spend(ic_sc)?;
+            incomings.push(((ic_sc.get_multisig_redeemscript(), tx), hashlock_spend));
+        } else {
+            log::warn!(
+                "[{}] Incoming contact signature not known. Not Broadcasting",
+                maker.config.network_port
+            );
+        }
+    }
+
+    // Spawn a separate thread to wait for contract maturity and broadcasting timelocked.
+    let maker_clone = maker.clone();
+    log::info!(
+        "[{}] Spawning swap recovery thread",
+        maker.config.network_port
+    );
+    let handle = std::thread::Builder::new()
+        .name("Swap recovery thread".to_string())
+        .spawn(move || {
+            if let Err(e) = recover_from_swap(maker_clone, outgoings, incomings) {
+                log::error!("Failed to recover from swap due to: {:?}", e);
             }
-        } // All locks are cleared here.
+        })?;
+    maker.thread_pool.add_thread(handle);
+    Ok(())
+}
 
-        std::thread::sleep(HEART_BEAT_INTERVAL);
+/// Checks the incoming funding txs of all unsettled swaps for reorgs. Run by the maker server along with the contract watcher.
+///
+/// A swap whose incoming funding got reorged out is aborted if our outgoing funding txs are not broadcasted yet.
+/// Otherwise its contracts are broadcasted and the swap is recovered, as the taker could now double spend the incoming funding.
+pub(crate) fn check_for_reorged_funding(maker: &Arc<Maker>) -> Result<(), MakerError> {
+    let funding_txids = |state: &ConnectionState| {
+        state
+            .incoming_swapcoins
+            .iter()
+            .map(|sc| sc.contract_tx.input[0].previous_output.txid)
+            .collect::<Vec<_>>()
+    };
+
+    let mut lock_onstate = maker.ongoing_swap_state.lock()?;
+    let watched = lock_onstate
+        .values()
+        .flat_map(|(state, _)| funding_txids(state))
+        .collect::<Vec<_>>();
+    let reorged = maker.wallet.write()?.find_reorged_txs(&watched)?;
+    if reorged.is_empty() {
+        return Ok(());
+    }
+
+    let reorged_swaps = lock_onstate
+        .iter()
+        .filter(|(_, (state, _))| {
+            funding_txids(state)
+                .iter()
+                .any(|txid| reorged.contains(txid))
+        })
+        .map(|(id, _)| id.clone())
+        .collect::<Vec<_>>();
+
+    let mut settled = Vec::new();
+    for id in reorged_swaps {
+        let (state, _) = lock_onstate.remove(&id).expect("swap state expected");
+        let mut wallet = maker.wallet.write()?;
+        for txid in funding_txids(&state) {
+            wallet.untrack_confirmation(&txid)?;
+        }
+        // Our funding txs are reserved until they are broadcasted.
+        if wallet.utxo_reservations.release(&id) {
+            log::warn!(
+                "[{}] Incoming funding of swap {} reorged out before broadcasting our funding. Aborting swap.",
+                maker.config.network_port,
+                id
+            );
+            wallet.refresh_offer_maxsize_cache()?;
+            settled.push((id, state, SwapOutcome::Aborted, false));
+            continue;
+        }
+        drop(wallet);
+        log::warn!(
+            "[{}] Incoming funding of swap {} reorged out after broadcasting our funding. Recovering from swap.",
+            maker.config.network_port,
+            id
+        );
+        spawn_swap_recovery(maker, &state)?;
+        settled.push((id, state, SwapOutcome::Recovered, true));
+    }
+    drop(lock_onstate);
+    // The ledger is written once the swap states are released.
+    for (id, state, outcome, funded) in &settled {
+        maker.record_swap(id, state, *outcome, *funded);
     }
 
     Ok(())
@@ -624,7 +1069,8 @@ pub(crate) fn restore_broadcasted_contracts_on_reboot(maker: Arc<Maker>) -> Resu
 
     for ic_sc in inc.iter() {
         let tx = ic_sc.get_fully_signed_contract_tx()?;
-        incomings.push((ic_sc.get_multisig_redeemscript(), tx));
+        let hashlock_spend = maker.create_hashlock_spend(ic_sc)?;
+        incomings.push(((ic_sc.get_multisig_redeemscript(), tx), hashlock_spend));
     }
 
     // Spawn a separate thread to wait for contract maturity and broadcasting timelocked.
@@ -641,11 +1087,11 @@ pub(crate) fn restore_broadcasted_contracts_on_reboot(maker: Arc<Maker>) -> Resu
     Ok(())
 }
 
-/// Check that if any Taker connection went idle.
+/// Check that if any Taker connection went idle. Run periodically by the maker server.
 ///
 /// If a connection remains idle for more than idle timeout time, thats a potential DOS attack.
 /// Broadcast the contract transactions and claim funds via timelock.
-pub(crate) fn check_for_idle_states(maker: Arc<Maker>) -> Result<(), MakerError> {
+pub(crate) fn check_for_idle_states(maker: &Arc<Maker>) -> Result<(), MakerError> {
     let mut bad_ip = Vec::new();
 
     let conn_timeout = if cfg!(feature = "integration-test") {
@@ -654,92 +1100,198 @@ pub(crate) fn check_for_idle_states(maker: Arc<Maker>) -> Result<(), MakerError>
         IDLE_CONNECTION_TIMEOUT
     };
 
-    loop {
-        if maker.shutdown.load(Relaxed) {
-            break;
-        }
-        let current_time = Instant::now();
-
-        // Extra scope to release all locks when done.
-        {
-            let mut lock_on_state = maker.ongoing_swap_state.lock()?;
-            for (ip, (state, last_connected_time)) in lock_on_state.iter_mut() {
-                let mut outgoings = Vec::new();
-                let mut incomings = Vec::new();
-
-                let no_response_since =
-                    current_time.saturating_duration_since(*last_connected_time);
-
-                if no_response_since > conn_timeout {
-                    log::error!(
-                        "[{}] Potential Dropped Connection from taker. No response since : {} secs. Recovering from swap",
-                        maker.config.network_port,
-                        no_response_since.as_secs()
-                    );
+    let current_time = Instant::now();
+
+    // Takers which never sent the proof of funding.
+    maker
+        .signed_swaps
+        .lock()?
+        .retain(|_, signed_at| current_time.saturating_duration_since(*signed_at) <= conn_timeout);
+
+    // Extra scope to release all locks when done.
+    let mut settled = Vec::new();
+    {
+        let mut lock_on_state = maker.ongoing_swap_state.lock()?;
+        for (ip, (state, last_connected_time)) in lock_on_state.iter_mut() {
+            let mut outgoings = Vec::new();
+            let mut incomings = Vec::new();
+
+            let no_response_since = current_time.saturating_duration_since(*last_connected_time);
+
+            if no_response_since > conn_timeout {
+                log::error!(
+                    "[{}] Potential Dropped Connection from taker. No response since : {} secs. Recovering from swap",
+                    maker.config.network_port,
+                    no_response_since.as_secs()
+                );
 
-                    // Extract Incoming and Outgoing contracts, and timelock spends of the contract transactions.
-                    // fully signed.
-                    for (og_sc, ic_sc) in state
-                        .outgoing_swapcoins
-                        .iter()
-                        .zip(state.incoming_swapcoins.iter())
-                    {
-                        let contract_timelock = og_sc.get_timelock()?;
-                        let contract = og_sc.get_fully_signed_contract_tx()?;
-                        let next_internal_address =
-                            &maker.wallet.read()?.get_next_internal_addresses(1)?[0];
-                        let time_lock_spend = og_sc.create_timelock_spend(next_internal_address)?;
-                        outgoings.push((
-                            (og_sc.get_multisig_redeemscript(), contract),
-                            (contract_timelock, time_lock_spend),
-                        ));
-                        let incoming_contract = ic_sc.get_fully_signed_contract_tx()?;
-                        incomings.push((ic_sc.get_multisig_redeemscript(), incoming_contract));
-                    }
-                    bad_ip.push(ip.clone());
-                    // Spawn a separate thread to wait for contract maturity and broadcasting timelocked.
-                    let maker_clone = maker.clone();
-                    log::info!(
-                        "[{}] Spawning recovery thread after Taker dropped",
-                        maker.config.network_port
-                    );
-                    let handle = std::thread::Builder::new()
-                        .name("Swap Recovery Thread".to_string())
-                        .spawn(move || {
-                            if let Err(e) = recover_from_swap(maker_clone, outgoings, incomings) {
-                                log::error!("Failed to recover from swap due to: {:?}", e);
-                            }
-                        })?;
-                    maker.thread_pool.add_thread(handle);
-                    // Clear the state values here
-                    *state = ConnectionState::default();
-                    break;
+                // Extract Incoming and Outgoing contracts, and timelock spends of the contract transactions.
+                // fully signed.
+                for (og_sc, ic_sc) in state
+                    .outgoing_swapcoins
+                    .iter()
+                    .zip(state.incoming_swapcoins.iter())
+                {
+                    let contract_timelock = og_sc.get_timelock()?;
+                    let contract = og_sc.get_fully_signed_contract_tx()?;
+                    let next_internal_address =
+                        &maker.wallet.read()?.get_next_internal_addresses(1)?[0];
+                    let time_lock_spend = og_sc.create_timelock_spend(next_internal_address)?;
+                    outgoings.push((
+                        (og_sc.get_multisig_redeemscript(), contract),
+                        (contract_timelock, time_lock_spend),
+                    ));
+                    let incoming_contract = ic_sc.get_fully_signed_contract_tx()?;
+                    let hashlock_spend = maker.create_hashlock_spend(ic_sc)?;
+                    incomings.push((
+                        (ic_sc.get_multisig_redeemscript(), incoming_contract),
+                        hashlock_spend,
+                    ));
                 }
+                // Spawn a separate thread to wait for contract maturity and broadcasting timelocked.
+                let maker_clone = maker.clone();
+                log::info!(
+                    "[{}] Spawning recovery thread after Taker dropped",
+                    maker.config.network_port
+                );
+                let handle = std::thread::Builder::new()
+                    .name("Swap Recovery Thread".to_string())
+                    .spawn(move || {
+                        if let Err(e) = recover_from_swap(maker_clone, outgoings, incomings) {
+                            log::error!("Failed to recover from swap due to: {:?}", e);
+                        }
+                    })?;
+                maker.thread_pool.add_thread(handle);
+                // Clear the state values here, and keep the old ones for the ledger.
+                bad_ip.push((ip.clone(), std::mem::take(state)));
+                break;
             }
+        }
 
-            // Clear the state entry here
-            for ip in bad_ip.iter() {
-                lock_on_state.remove(ip);
-            }
-        } // All locks are cleared here
+        // Clear the state entry here
+        for (ip, state) in bad_ip {
+            lock_on_state.remove(&ip);
+            let funded = !maker.wallet.write()?.utxo_reservations.release(&ip);
+            settled.push((ip, state, funded));
+        }
+    } // All locks are cleared here
 
-        std::thread::sleep(HEART_BEAT_INTERVAL);
+    // The ledger is written once the swap states are released.
+    for (ip, state, funded) in &settled {
+        maker.record_swap(ip, state, SwapOutcome::Recovered, *funded);
+    }
+
+    // Release reservations of swaps that never made it to broadcasting their funding txs.
+    {
+        let mut wallet = maker.wallet.write()?;
+        if !wallet
+            .utxo_reservations
+            .release_expired(conn_timeout)
+            .is_empty()
+        {
+            wallet.refresh_offer_maxsize_cache()?;
+        }
     }
 
     Ok(())
 }
 
+/// Broadcast a contract tx missing from the mempool and the chain again, e.g. after it got dropped in a reorg.
+fn rebroadcast_contract(maker: &Maker, contract: &Transaction) -> Result<(), MakerError> {
+    if let Err(e) = maker.wallet.read()?.send_tx(contract) {
+        log::warn!(
+            "[{}] Can't rebroadcast contract: {} | {:?}",
+            maker.config.network_port,
+            contract.compute_txid(),
+            e
+        );
+    } else {
+        log::info!(
+            "[{}] Rebroadcasted Contract : {}",
+            maker.config.network_port,
+            contract.compute_txid()
+        );
+    }
+    Ok(())
+}
+
+/// Broadcast the refund tx of an outgoing swapcoin, if it was refunded in a cooperative abort, and remove the swapcoin.
+/// Returns false if there is no refund tx or it can't be broadcasted, so the contract has to be used instead.
+fn broadcast_refund(maker: &Maker, outgoing_reedemscript: &ScriptBuf) -> Result<bool, MakerError> {
+    let refund_tx = match maker
+        .wallet
+        .read()?
+        .find_outgoing_swapcoin(outgoing_reedemscript)
+        .and_then(|sc| sc.refund_tx.clone())
+    {
+        Some(tx) => tx,
+        None => return Ok(false),
+    };
+    let txid = refund_tx.compute_txid();
+    {
+        let wallet = maker.wallet.read()?;
+        if wallet.rpc.get_raw_transaction_info(&txid, None).is_ok() {
+            log::info!(
+                "[{}] Refund tx already broadcasted: {}",
+                maker.config.network_port,
+                txid
+            );
+        } else if let Err(e) = wallet.send_tx(&refund_tx) {
+            log::warn!(
+                "[{}] Can't broadcast refund tx: {} | {:?}",
+                maker.config.network_port,
+                txid,
+                e
+            );
+            return Ok(false);
+        } else {
+            log::info!(
+                "[{}] Broadcasted refund tx: {}",
+                maker.config.network_port,
+                txid
+            );
+        }
+    }
+    maker
+        .wallet
+        .write()?
+        .remove_outgoing_swapcoin(outgoing_reedemscript)?;
+    log::info!(
+        "[{}] Removed refunded Outgoing Swapcoin from Wallet",
+        maker.config.network_port
+    );
+    Ok(true)
+}
+
 /// Broadcast Incoming and Outgoing Contract transactions & timelock transactions after maturity.
+/// Outgoings refunded in a cooperative abort are spent by their refund txs instead.
+/// Incoming contracts with a known hash preimage are claimed via hashlock as soon as they confirm.
 /// Remove contract transactions from the wallet.
 pub(crate) fn recover_from_swap(
     maker: Arc<Maker>,
     // Tuple of ((Multisig_reedemscript, Contract Tx), (Timelock, Timelock Tx))
     outgoings: Vec<((ScriptBuf, Transaction), (u16, Transaction))>,
-    // Tuple of (Multisig Reedemscript, Contract Tx)
-    incomings: Vec<(ScriptBuf, Transaction)>,
+    // Tuple of ((Multisig Reedemscript, Contract Tx), Hashlock Tx if the preimage is known)
+    incomings: Vec<((ScriptBuf, Transaction), Option<Transaction>)>,
 ) -> Result<(), MakerError> {
-    // broadcast all the incoming contracts and remove them from the wallet.
-    for (incoming_reedemscript, tx) in incomings {
+    // Outgoings refunded in a cooperative abort don't need their contracts.
+    let mut refunded = Vec::new();
+    for ((og_rs, _), _) in outgoings.iter() {
+        if broadcast_refund(&maker, og_rs)? {
+            refunded.push(og_rs.clone());
+        }
+    }
+    let outgoings = outgoings
+        .into_iter()
+        .filter(|((og_rs, _), _)| !refunded.contains(og_rs))
+        .collect::<Vec<_>>();
+
+    // Incoming contracts to be claimed via hashlock.
+    // Tuple of ((Multisig Reedemscript, Contract Tx), Hashlock Tx)
+    let mut hashlock_claims = Vec::new();
+
+    // broadcast all the incoming contracts and remove the unclaimable ones from the wallet.
+    for ((incoming_reedemscript, tx), hashlock_spend) in incomings {
         if maker
             .wallet
             .read()?
@@ -765,16 +1317,22 @@ pub(crate) fn recover_from_swap(
             );
         }
 
+        if let Some(hashlock_spend) = hashlock_spend {
+            hashlock_claims.push(((incoming_reedemscript, tx), hashlock_spend));
+            continue;
+        }
+
         let removed_incoming = maker
             .wallet
             .write()?
-            .remove_incoming_swapcoin(&incoming_reedemscript)?
-            .expect("Incoming swapcoin expected");
-        log::info!(
-            "[{}] Removed Incoming Swapcoin From Wallet, Contract Txid : {}",
-            maker.config.network_port,
-            removed_incoming.contract_tx.compute_txid()
-        );
+            .remove_incoming_swapcoin(&incoming_reedemscript)?;
+        if let Some(removed_incoming) = removed_incoming {
+            log::info!(
+                "[{}] Removed Incoming Swapcoin From Wallet, Contract Txid : {}",
+                maker.config.network_port,
+                removed_incoming.contract_tx.compute_txid()
+            );
+        }
     }
 
     //broadcast all the outgoing contracts
@@ -828,30 +1386,122 @@ pub(crate) fn recover_from_swap(
     log::info!("Wallet file synced and saved to disk.");
 
     // Check for contract confirmations and broadcast timelocked transaction
-    let mut timelock_boardcasted = Vec::new();
+    let mut timelock_boardcasted: Vec<&Transaction> = Vec::new();
+    let mut hashlock_broadcasted: Vec<&Transaction> = Vec::new();
     let trigger_count = if cfg!(feature = "integration-test") {
         10 / HEART_BEAT_INTERVAL.as_secs() // triggers every 10 secs for tests
     } else {
         60 / HEART_BEAT_INTERVAL.as_secs() // triggers every 60 secs for prod
     };
 
+    let contract_txids = hashlock_claims
+        .iter()
+        .map(|((_, contract), _)| contract.compute_txid())
+        .chain(
+            outgoings
+                .iter()
+                .map(|((_, contract), _)| contract.compute_txid()),
+        )
+        .collect::<Vec<_>>();
+
     let mut i = 0;
 
     while !maker.shutdown.load(Relaxed) {
         if i >= trigger_count || i == 0 {
+            // The spends of reorged contracts are reorged out along with them. Broadcast them again
+            // once the contracts confirm again.
+            let reorged = maker.wallet.write()?.find_reorged_txs(&contract_txids)?;
+            for ((_, contract), hashlock_tx) in hashlock_claims.iter() {
+                if reorged.contains(&contract.compute_txid()) {
+                    hashlock_broadcasted
+                        .retain(|tx| tx.compute_txid() != hashlock_tx.compute_txid());
+                }
+            }
+            for ((_, contract), (_, timelocked_tx)) in outgoings.iter() {
+                if reorged.contains(&contract.compute_txid()) {
+                    timelock_boardcasted
+                        .retain(|tx| tx.compute_txid() != timelocked_tx.compute_txid());
+                }
+            }
+
+            for ((incoming_reedemscript, contract), hashlock_tx) in hashlock_claims.iter() {
+                // We have already broadcasted this tx, so skip
+                if hashlock_broadcasted.contains(&hashlock_tx) {
+                    continue;
+                }
+                // The hashlock branch has a relative timelock of 1 block. Wait for the contract to confirm.
+                let tx_from_chain = maker
+                    .wallet
+                    .read()?
+                    .rpc
+                    .get_raw_transaction_info(&contract.compute_txid(), None);
+                let confirmations = match tx_from_chain {
+                    Ok(result) => {
+                        if let Some(blockhash) = result.blockhash {
+                            maker
+                                .wallet
+                                .write()?
+                                .track_confirmation(contract.compute_txid(), blockhash)?;
+                        }
+                        result.confirmations
+                    }
+                    Err(_) => {
+                        rebroadcast_contract(&maker, contract)?;
+                        None
+                    }
+                };
+
+                if confirmations.unwrap_or(0) >= 1 {
+                    log::info!(
+                        "[{}] Broadcasting hashlock tx: {} for Contract Txid : {}",
+                        maker.config.network_port,
+                        hashlock_tx.compute_txid(),
+                        contract.compute_txid()
+                    );
+                    if let Err(e) = maker.wallet.read()?.send_tx(hashlock_tx) {
+                        log::warn!(
+                            "[{}] Can't send hashlock tx: {} | {:?}",
+                            maker.config.network_port,
+                            hashlock_tx.compute_txid(),
+                            e
+                        );
+                        continue;
+                    }
+                    hashlock_broadcasted.push(hashlock_tx);
+
+                    // Already removed if the contract got reorged out and this is a rebroadcast.
+                    let incoming_removed = maker
+                        .wallet
+                        .write()?
+                        .remove_incoming_swapcoin(incoming_reedemscript)?;
+                    if let Some(incoming_removed) = incoming_removed {
+                        log::info!(
+                            "[{}] Removed Incoming Swapcoin from Wallet, Contract Txid: {}",
+                            maker.config.network_port,
+                            incoming_removed.contract_tx.compute_txid()
+                        );
+                    }
+
+                    let mut wallet_write = maker.wallet.write()?;
+                    wallet_write.sync()?;
+                    wallet_write.save_to_disk()?;
+                }
+            }
+
             for ((outgoing_reedemscript, contract), (timelock, timelocked_tx)) in outgoings.iter() {
                 // We have already broadcasted this tx, so skip
                 if timelock_boardcasted.contains(&timelocked_tx) {
                     continue;
                 }
                 // Check if the contract tx has reached required maturity
-                // Failure here means the transaction hasn't been broadcasted yet. So do nothing and try again.
-                let tx_from_chain = if let Ok(result) = maker
+                // Failure here means the transaction is not in the mempool or the chain. It might have been
+                // dropped after a reorg, so rebroadcast it and try again.
+                let tx_from_chain = maker
                     .wallet
                     .read()?
                     .rpc
-                    .get_raw_transaction_info(&contract.compute_txid(), None)
-                {
+                    .get_raw_transaction_info(&contract.compute_txid(), None);
+                let tx_from_chain = if let Ok(result) = tx_from_chain {
                     log::info!(
                         "[{}] Contract Txid : {} reached confirmation : {:?}, Required Confirmation : {}",
                         maker.config.network_port,
@@ -861,8 +1511,15 @@ pub(crate) fn recover_from_swap(
                     );
                     result
                 } else {
+                    rebroadcast_contract(&maker, contract)?;
                     continue;
                 };
+                if let Some(blockhash) = tx_from_chain.blockhash {
+                    maker
+                        .wallet
+                        .write()?
+                        .track_confirmation(contract.compute_txid(), blockhash)?;
+                }
 
                 if let Some(confirmation) = tx_from_chain.confirmations {
                     // Now the transaction is confirmed in a block, check for required maturity
@@ -886,17 +1543,18 @@ pub(crate) fn recover_from_swap(
                             .map_err(WalletError::Rpc)?;
                         timelock_boardcasted.push(timelocked_tx);
 
+                        // Already removed if the contract got reorged out and this is a rebroadcast.
                         let outgoing_removed = maker
                             .wallet
                             .write()?
-                            .remove_outgoing_swapcoin(outgoing_reedemscript)?
-                            .expect("outgoing swapcoin expected");
-
-                        log::info!(
-                            "[{}] Removed Outgoing Swapcoin from Wallet, Contract Txid: {}",
-                            maker.config.network_port,
-                            outgoing_removed.contract_tx.compute_txid()
-                        );
+                            .remove_outgoing_swapcoin(outgoing_reedemscript)?;
+                        if let Some(outgoing_removed) = outgoing_removed {
+                            log::info!(
+                                "[{}] Removed Outgoing Swapcoin from Wallet, Contract Txid: {}",
+                                maker.config.network_port,
+                                outgoing_removed.contract_tx.compute_txid()
+                            );
+                        }
 
                         log::info!("initializing Wallet Sync.");
                         {
@@ -914,15 +1572,26 @@ pub(crate) fn recover_from_swap(
                 outgoings.len(),
                 timelock_boardcasted.len()
             );
+            log::info!(
+                "{} claimable incoming contracts detected | {} hashlock txs broadcasted.",
+                hashlock_claims.len(),
+                hashlock_broadcasted.len()
+            );
 
-            if timelock_boardcasted.len() == outgoings.len() {
+            if timelock_boardcasted.len() == outgoings.len()
+                && hashlock_broadcasted.len() == hashlock_claims.len()
+            {
                 // For tests, terminate the maker at this stage.
                 #[cfg(feature = "integration-test")]
                 maker.shutdown.store(true, Relaxed);
 
                 log::info!(
-                    "All outgoing transactions claimed back via timelock. Recovery loop exiting."
+                    "All outgoing transactions claimed back via timelock and incoming via hashlock. Recovery loop exiting."
                 );
+                let mut wallet = maker.wallet.write()?;
+                for txid in &contract_txids {
+                    wallet.untrack_confirmation(txid)?;
+                }
                 break;
             }
             // Reset counter
@@ -933,3 +1602,14 @@ pub(crate) fn recover_from_swap(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_without_podle_refused() {
+        let err = required_podle(None).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidPodle);
+    }
+}
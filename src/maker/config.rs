@@ -1,13 +1,123 @@
 //! Maker Configuration. Controlling various behaviors.
 
 use crate::utill::parse_toml;
-use std::{io, path::Path};
+use std::{collections::HashMap, io, path::Path};
 
 use std::io::Write;
 
-use crate::utill::{get_maker_dir, parse_field, ConnectionType};
+use crate::utill::{get_maker_dir, parse_field, parse_list, ConnectionType};
 
-use super::api::MIN_SWAP_AMOUNT;
+use serde::{Deserialize, Serialize};
+
+use super::api::{
+    AMOUNT_RELATIVE_FEE_PCT, BASE_FEE, MIN_CONTRACT_REACTION_TIME, MIN_SWAP_AMOUNT,
+    TIME_RELATIVE_FEE_PCT,
+};
+use crate::{protocol::podle::DEFAULT_PODLE_MAX_INDEX, utill::REQUIRED_CONFIRMS};
+
+/// The pricing and limits of one tier of the maker's [Offer](crate::protocol::messages::Offer).
+///
+/// The first tier is read from the top-level config keys. Additional tiers are read from keys
+/// prefixed with `tier1.`, `tier2.`, ..., with unset keys defaulting to the first tier.
+/// Can be updated at runtime via `maker-cli set-fees`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeePolicy {
+    /// Fixed base fee in sats, charged for every swap.
+    pub base_fee: u64,
+    /// Fee in percentage of the swap amount.
+    pub amount_relative_fee_pct: f64,
+    /// Fee in percentage of the swap amount, per block of refund locktime.
+    pub time_relative_fee_pct: f64,
+    /// Minimum difference in blocks between the incoming and outgoing contract locktimes.
+    pub minimum_locktime: u16,
+    /// Confirmations required on the taker's funding transactions.
+    pub required_confirms: u32,
+    /// Minimum coinswap amount of this tier. The advertised min size is at least the config's `min_swap_amount`.
+    pub min_size: u64,
+    /// Maximum coinswap amount of this tier. The advertised max size is capped by the wallet's balance.
+    pub max_size: u64,
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        Self {
+            base_fee: BASE_FEE,
+            amount_relative_fee_pct: AMOUNT_RELATIVE_FEE_PCT,
+            time_relative_fee_pct: TIME_RELATIVE_FEE_PCT,
+            minimum_locktime: MIN_CONTRACT_REACTION_TIME,
+            required_confirms: REQUIRED_CONFIRMS,
+            min_size: 0,
+            max_size: u64::MAX,
+        }
+    }
+}
+
+impl FeePolicy {
+    /// Checks that the policy is sane to be advertised.
+    pub(crate) fn validate(&self) -> Result<(), &'static str> {
+        if !self.amount_relative_fee_pct.is_finite() || self.amount_relative_fee_pct < 0.0 {
+            return Err("amount relative fee must be a non-negative number");
+        }
+        if !self.time_relative_fee_pct.is_finite() || self.time_relative_fee_pct < 0.0 {
+            return Err("time relative fee must be a non-negative number");
+        }
+        if self.minimum_locktime == 0 {
+            return Err("minimum locktime must be at least 1 block");
+        }
+        if self.required_confirms == 0 {
+            return Err("required confirms must be at least 1");
+        }
+        if self.min_size > self.max_size {
+            return Err("min size must not exceed max size");
+        }
+        Ok(())
+    }
+
+    /// Reads the policy from the config keys with the given prefix.
+    fn from_config_map(
+        config_map: &HashMap<String, String>,
+        prefix: &str,
+        default: &FeePolicy,
+    ) -> Self {
+        let get = |key: &str| config_map.get(&format!("{}{}", prefix, key));
+        FeePolicy {
+            base_fee: parse_field(get("base_fee"), default.base_fee),
+            amount_relative_fee_pct: parse_field(
+                get("amount_relative_fee_pct"),
+                default.amount_relative_fee_pct,
+            ),
+            time_relative_fee_pct: parse_field(
+                get("time_relative_fee_pct"),
+                default.time_relative_fee_pct,
+            ),
+            minimum_locktime: parse_field(get("minimum_locktime"), default.minimum_locktime),
+            required_confirms: parse_field(get("required_confirms"), default.required_confirms),
+            min_size: parse_field(get("min_size"), default.min_size),
+            max_size: parse_field(get("max_size"), default.max_size),
+        }
+    }
+
+    /// Serializes the policy into config lines, with the given key prefix.
+    fn to_config_lines(&self, prefix: &str) -> String {
+        format!(
+            "{p}base_fee = {}
+{p}amount_relative_fee_pct = {}
+{p}time_relative_fee_pct = {}
+{p}minimum_locktime = {}
+{p}required_confirms = {}
+{p}min_size = {}
+{p}max_size = {}",
+            self.base_fee,
+            self.amount_relative_fee_pct,
+            self.time_relative_fee_pct,
+            self.minimum_locktime,
+            self.required_confirms,
+            self.min_size,
+            self.max_size,
+            p = prefix,
+        )
+    }
+}
 
 /// Maker Configuration, controlling various maker behavior.
 #[derive(Debug, Clone, PartialEq)]
@@ -20,14 +130,39 @@ pub struct MakerConfig {
     pub min_swap_amount: u64,
     /// Socks port
     pub socks_port: u16,
-    /// Directory server address (can be clearnet or onion)
-    pub directory_server_address: String,
+    /// Directory server addresses (can be clearnet or onion). Makers are listed by any of them.
+    pub directory_server_addresses: Vec<String>,
     /// Fidelity Bond amount
     pub fidelity_amount: u64,
     /// Fidelity Bond timelock in Block heights.
     pub fidelity_timelock: u32,
+    /// Number of blocks before the live bonds mature, at which a replacement bond is created. 0 disables the renewal.
+    pub fidelity_renewal_blocks: u32,
+    /// Minimum confirmations of the UTXO committed by a taker's PoDLE.
+    pub podle_min_confirms: u32,
+    /// Minimum value of the UTXO committed by a taker's PoDLE, in percentage of the swap amount.
+    pub podle_min_amount_pct: f64,
+    /// Number of PoDLE commitments accepted per taker UTXO. At most 10. Takers use at most 3.
+    pub podle_max_index: u8,
+    /// Maximum number of concurrent taker connections.
+    pub max_connections: usize,
+    /// Maximum number of swaps in flight. New swap requests are rejected above it.
+    pub max_inflight_swaps: usize,
+    /// Maximum number of messages per minute from a single peer address, over all its connections. Loopback
+    /// connections, as forwarded by Tor, are limited one by one.
+    pub max_messages_per_minute: u32,
+    /// Maximum size of a taker message in bytes.
+    pub max_message_size: u32,
+    /// Number of threads handling taker messages. The long running background jobs have their own threads.
+    pub worker_threads: usize,
+    /// Fee rate of the cooperative refund txs, in sats per vbyte.
+    pub refund_feerate: u64,
+    /// Bitcoind ZMQ endpoint publishing `rawtx` and `hashblock` notifications. Empty to poll the RPC instead.
+    pub zmq_address: String,
     /// Connection type
     pub connection_type: ConnectionType,
+    /// Offer tiers at startup, never empty. The live tiers are held by the [Maker](super::Maker).
+    pub offer_tiers: Vec<FeePolicy>,
 }
 
 impl Default for MakerConfig {
@@ -37,8 +172,9 @@ impl Default for MakerConfig {
             rpc_port: 6103,
             min_swap_amount: MIN_SWAP_AMOUNT,
             socks_port: 19050,
-            directory_server_address:
+            directory_server_addresses: vec![
                 "bhbzkndgad52ojm75w4goii7xsi6ou73fzyvorxas7swg2snlto4c4ad.onion:8080".to_string(),
+            ],
             #[cfg(feature = "integration-test")]
             fidelity_amount: 5_000_000, // 0.05 BTC for tests
             #[cfg(feature = "integration-test")]
@@ -47,6 +183,23 @@ impl Default for MakerConfig {
             fidelity_amount: 50_000, // 50K sats for production
             #[cfg(not(feature = "integration-test"))]
             fidelity_timelock: 2160, // Approx 15 days of blocks in production
+            #[cfg(feature = "integration-test")]
+            fidelity_renewal_blocks: 0, // Tests manage the bonds themselves
+            #[cfg(not(feature = "integration-test"))]
+            fidelity_renewal_blocks: 144, // Approx 1 day of blocks
+            #[cfg(feature = "integration-test")]
+            podle_min_confirms: 1,
+            #[cfg(not(feature = "integration-test"))]
+            podle_min_confirms: 5,
+            podle_min_amount_pct: 20.0,
+            podle_max_index: DEFAULT_PODLE_MAX_INDEX,
+            max_connections: 100,
+            max_inflight_swaps: 10,
+            max_messages_per_minute: 60,
+            max_message_size: 1024 * 1024,
+            worker_threads: 4,
+            refund_feerate: 2,
+            zmq_address: String::new(),
             connection_type: {
                 #[cfg(feature = "tor")]
                 {
@@ -57,6 +210,7 @@ impl Default for MakerConfig {
                     ConnectionType::CLEARNET
                 }
             },
+            offer_tiers: vec![FeePolicy::default()],
         }
     }
 }
@@ -95,6 +249,17 @@ impl MakerConfig {
             config_path.display()
         );
 
+        let base_tier = FeePolicy::from_config_map(&config_map, "", &default_config.offer_tiers[0]);
+        let mut offer_tiers = vec![base_tier];
+        loop {
+            let prefix = format!("tier{}.", offer_tiers.len());
+            if !config_map.keys().any(|key| key.starts_with(&prefix)) {
+                break;
+            }
+            let tier = FeePolicy::from_config_map(&config_map, &prefix, &offer_tiers[0]);
+            offer_tiers.push(tier);
+        }
+
         Ok(MakerConfig {
             network_port: parse_field(config_map.get("network_port"), default_config.network_port),
             rpc_port: parse_field(config_map.get("rpc_port"), default_config.rpc_port),
@@ -103,9 +268,12 @@ impl MakerConfig {
                 default_config.min_swap_amount,
             ),
             socks_port: parse_field(config_map.get("socks_port"), default_config.socks_port),
-            directory_server_address: parse_field(
-                config_map.get("directory_server_address"),
-                default_config.directory_server_address,
+            // Also accept the single address key of older config files.
+            directory_server_addresses: parse_list(
+                config_map
+                    .get("directory_server_addresses")
+                    .or_else(|| config_map.get("directory_server_address")),
+                default_config.directory_server_addresses,
             ),
             fidelity_amount: parse_field(
                 config_map.get("fidelity_amount"),
@@ -115,32 +283,111 @@ impl MakerConfig {
                 config_map.get("fidelity_timelock"),
                 default_config.fidelity_timelock,
             ),
+            fidelity_renewal_blocks: parse_field(
+                config_map.get("fidelity_renewal_blocks"),
+                default_config.fidelity_renewal_blocks,
+            ),
+            podle_min_confirms: parse_field(
+                config_map.get("podle_min_confirms"),
+                default_config.podle_min_confirms,
+            ),
+            podle_min_amount_pct: parse_field(
+                config_map.get("podle_min_amount_pct"),
+                default_config.podle_min_amount_pct,
+            ),
+            podle_max_index: parse_field(
+                config_map.get("podle_max_index"),
+                default_config.podle_max_index,
+            ),
+            max_connections: parse_field(
+                config_map.get("max_connections"),
+                default_config.max_connections,
+            ),
+            max_inflight_swaps: parse_field(
+                config_map.get("max_inflight_swaps"),
+                default_config.max_inflight_swaps,
+            ),
+            max_messages_per_minute: parse_field(
+                config_map.get("max_messages_per_minute"),
+                default_config.max_messages_per_minute,
+            ),
+            max_message_size: parse_field(
+                config_map.get("max_message_size"),
+                default_config.max_message_size,
+            ),
+            worker_threads: parse_field(
+                config_map.get("worker_threads"),
+                default_config.worker_threads,
+            ),
+            refund_feerate: parse_field(
+                config_map.get("refund_feerate"),
+                default_config.refund_feerate,
+            ),
+            zmq_address: parse_field(config_map.get("zmq_address"), default_config.zmq_address),
             connection_type: parse_field(
                 config_map.get("connection_type"),
                 default_config.connection_type,
             ),
+            offer_tiers,
         })
     }
 
     // Method to serialize the MakerConfig into a TOML string and write it to a file
     pub(crate) fn write_to_file(&self, path: &Path) -> std::io::Result<()> {
+        let tiers_data = self
+            .offer_tiers
+            .iter()
+            .enumerate()
+            .map(|(i, tier)| {
+                if i == 0 {
+                    tier.to_config_lines("")
+                } else {
+                    tier.to_config_lines(&format!("tier{}.", i))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
         let toml_data = format!(
             "network_port = {}
 rpc_port = {}
 min_swap_amount = {}
 socks_port = {}
-directory_server_address = {}
+directory_server_addresses = {}
 fidelity_amount = {}
 fidelity_timelock = {}
-connection_type = {:?}",
+fidelity_renewal_blocks = {}
+podle_min_confirms = {}
+podle_min_amount_pct = {}
+podle_max_index = {}
+max_connections = {}
+max_inflight_swaps = {}
+max_messages_per_minute = {}
+max_message_size = {}
+worker_threads = {}
+refund_feerate = {}
+zmq_address = {}
+connection_type = {:?}
+{}",
             self.network_port,
             self.rpc_port,
             self.min_swap_amount,
             self.socks_port,
-            self.directory_server_address,
+            self.directory_server_addresses.join(","),
             self.fidelity_amount,
             self.fidelity_timelock,
+            self.fidelity_renewal_blocks,
+            self.podle_min_confirms,
+            self.podle_min_amount_pct,
+            self.podle_max_index,
+            self.max_connections,
+            self.max_inflight_swaps,
+            self.max_messages_per_minute,
+            self.max_message_size,
+            self.worker_threads,
+            self.refund_feerate,
+            self.zmq_address,
             self.connection_type,
+            tiers_data,
         );
 
         std::fs::create_dir_all(path.parent().expect("Path should NOT be root!"))?;
@@ -189,6 +436,77 @@ mod tests {
         assert_eq!(config, default_config);
     }
 
+    #[test]
+    fn test_offer_tiers_config() {
+        let contents = r#"
+            base_fee = 500
+            amount_relative_fee_pct = 1.5
+            time_relative_fee_pct = 0.02
+            minimum_locktime = 30
+            required_confirms = 2
+            max_size = 1000000
+            tier1.base_fee = 2000
+            tier1.minimum_locktime = 60
+            tier1.min_size = 1000000
+            tier1.max_size = 5000000
+        "#;
+        let config_path = create_temp_config(contents, "offer_tiers_maker_config.toml");
+        let config = MakerConfig::new(Some(&config_path)).unwrap();
+        remove_temp_config(&config_path);
+
+        let base_tier = FeePolicy {
+            base_fee: 500,
+            amount_relative_fee_pct: 1.5,
+            time_relative_fee_pct: 0.02,
+            minimum_locktime: 30,
+            required_confirms: 2,
+            min_size: 0,
+            max_size: 1_000_000,
+        };
+        let premium_tier = FeePolicy {
+            base_fee: 2000,
+            minimum_locktime: 60,
+            min_size: 1_000_000,
+            max_size: 5_000_000,
+            ..base_tier.clone()
+        };
+        assert_eq!(config.offer_tiers, vec![base_tier.clone(), premium_tier]);
+        assert!(base_tier.validate().is_ok());
+        assert!(FeePolicy {
+            amount_relative_fee_pct: -1.0,
+            ..base_tier.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(FeePolicy {
+            required_confirms: 0,
+            ..base_tier
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_offer_tiers_roundtrip() {
+        let config_path = PathBuf::from("offer_tiers_roundtrip_maker_config.toml");
+        let config = MakerConfig {
+            offer_tiers: vec![
+                FeePolicy::default(),
+                FeePolicy {
+                    base_fee: 42,
+                    max_size: 100_000,
+                    ..FeePolicy::default()
+                },
+            ],
+            ..MakerConfig::default()
+        };
+        config.write_to_file(&config_path).unwrap();
+        let read_config = MakerConfig::new(Some(&config_path)).unwrap();
+        remove_temp_config(&config_path);
+
+        assert_eq!(config, read_config);
+    }
+
     #[test]
     fn test_missing_fields() {
         let contents = r#"
@@ -4,7 +4,11 @@ use std::sync::{MutexGuard, PoisonError, RwLockReadGuard, RwLockWriteGuard};
 
 use bitcoin::secp256k1;
 
-use crate::{error::NetError, protocol::error::ProtocolError, wallet::WalletError};
+use crate::{
+    error::NetError,
+    protocol::{error::ProtocolError, messages::ErrorCode},
+    wallet::WalletError,
+};
 
 use super::MakerBehavior;
 
@@ -38,6 +42,68 @@ pub enum MakerError {
     SpecialBehaviour(MakerBehavior),
     /// Represents a protocol-related error.
     Protocol(ProtocolError),
+    /// The Taker's message is refused, with an error code reported back to the Taker.
+    Rejected {
+        /// The reported error code.
+        code: ErrorCode,
+        /// The reported reason.
+        reason: String,
+    },
+    /// The Taker abandoned the protocol.
+    TakerAborted {
+        /// The error code reported by the Taker.
+        code: ErrorCode,
+        /// The reason reported by the Taker.
+        reason: String,
+    },
+}
+
+impl MakerError {
+    /// Creates a [MakerError::Rejected].
+    pub(crate) fn rejected(code: ErrorCode, reason: impl Into<String>) -> Self {
+        Self::Rejected {
+            code,
+            reason: reason.into(),
+        }
+    }
+
+    /// The error code reported to the Taker.
+    pub(crate) fn code(&self) -> ErrorCode {
+        match self {
+            Self::Rejected { code, .. } | Self::TakerAborted { code, .. } => *code,
+            Self::UnexpectedMessage { .. }
+            | Self::Protocol(ProtocolError::WrongMessage { .. })
+            | Self::Net(NetError::Cbor(_)) => ErrorCode::UnexpectedMessage,
+            Self::Secp(_)
+            | Self::Protocol(ProtocolError::Secp(_))
+            | Self::Wallet(WalletError::Secp(_))
+            | Self::Wallet(WalletError::Protocol(ProtocolError::Secp(_))) => {
+                ErrorCode::InvalidSignature
+            }
+            Self::Protocol(_) | Self::Wallet(WalletError::Protocol(_)) => {
+                ErrorCode::InvalidContract
+            }
+            Self::Wallet(WalletError::InsufficientFund { .. }) => ErrorCode::InsufficientLiquidity,
+            _ => ErrorCode::Internal,
+        }
+    }
+
+    /// The reason reported to the Taker. Internal errors are not detailed.
+    pub(crate) fn reason(&self) -> String {
+        match self {
+            Self::Rejected { reason, .. } | Self::TakerAborted { reason, .. } => reason.clone(),
+            Self::UnexpectedMessage { expected, got } => {
+                format!("Expected {}, got {}", expected, got)
+            }
+            Self::General(reason) => reason.to_string(),
+            Self::Protocol(e) | Self::Wallet(WalletError::Protocol(e)) => format!("{:?}", e),
+            Self::Secp(e) | Self::Wallet(WalletError::Secp(e)) => e.to_string(),
+            Self::Wallet(WalletError::InsufficientFund { .. }) => {
+                "Not enough liquidity for the swap".to_string()
+            }
+            _ => "Internal error".to_string(),
+        }
+    }
 }
 
 impl From<std::io::Error> for MakerError {
@@ -7,20 +7,22 @@
 //! The file includes functions to validate and sign contract transactions, verify proof of funding, and handle unexpected recovery scenarios.
 //! Implements the core functionality for a Maker in a Bitcoin coinswap protocol.
 
-use std::{collections::HashMap, sync::Arc, time::Instant};
+use std::{sync::Arc, time::Instant};
 
 use bitcoin::{
     hashes::Hash,
     secp256k1::{self, Secp256k1},
     Amount, OutPoint, PublicKey, Transaction, Txid,
 };
+use bitcoind::bitcoincore_rpc::RpcApi;
 
 use super::{
     api::{
-        recover_from_swap, ConnectionState, ExpectedMessage, Maker, MakerBehavior,
-        AMOUNT_RELATIVE_FEE_PCT, BASE_FEE, MIN_CONTRACT_REACTION_TIME, TIME_RELATIVE_FEE_PCT,
+        recover_from_swap, ConnectionState, ExpectedMessage, Maker, MakerBehavior, OFFER_VALIDITY,
     },
+    config::FeePolicy,
     error::MakerError,
+    ledger::{SwapOutcome, SwapTerms},
 };
 
 use crate::{
@@ -28,17 +30,19 @@ use crate::{
         contract::{
             calculate_coinswap_fee, create_receivers_contract_tx, find_funding_output_index,
             read_hashvalue_from_contract, read_pubkeys_from_multisig_redeemscript,
+            verify_abort_request,
         },
         error::ProtocolError,
         messages::{
             ContractSigsAsRecvrAndSender, ContractSigsForRecvr, ContractSigsForRecvrAndSender,
-            ContractSigsForSender, HashPreimage, MakerHello, MakerToTakerMessage, MultisigPrivkey,
-            Offer, PrivKeyHandover, ProofOfFunding, ReqContractSigsForRecvr,
-            ReqContractSigsForSender, SenderContractTxInfo, TakerToMakerMessage,
+            ContractSigsForSender, ErrorCode, HashPreimage, MakerHello, MakerToTakerMessage,
+            MultisigPrivkey, Offer, OfferTier, PrivKeyHandover, ProofOfFunding, ReqAbort,
+            ReqContractSigsForRecvr, ReqContractSigsForSender, RespAbort, SenderContractTxInfo,
+            TakerToMakerMessage,
         },
         Hash160,
     },
-    utill::REQUIRED_CONFIRMS,
+    utill::unix_time,
     wallet::{IncomingSwapCoin, SwapCoin, WalletError, WalletSwapCoin},
 };
 
@@ -63,19 +67,44 @@ pub(crate) fn handle_message(
         return Ok(None);
     }
 
+    if let TakerToMakerMessage::Abort { code, reason } = message {
+        return Err(MakerError::TakerAborted { code, reason });
+    }
+
+    // Refuse to start new swaps above the in-flight limit, before any liquidity or commitment is used.
+    if let TakerToMakerMessage::ReqContractSigsForSender(_) = &message {
+        let inflight_swaps = maker.inflight_swaps()?;
+        if inflight_swaps >= maker.config.max_inflight_swaps {
+            log::warn!(
+                "[{}] Rejecting new swap, {} swaps in flight",
+                maker.config.network_port,
+                inflight_swaps
+            );
+            return Ok(Some(MakerToTakerMessage::Error {
+                code: ErrorCode::TooManySwaps,
+                reason: "Too many swaps in flight".to_string(),
+            }));
+        }
+    }
+
     let outgoing_message = match connection_state.allowed_message {
         ExpectedMessage::TakerHello => {
             if let TakerToMakerMessage::TakerHello(m) = message {
                 if m.protocol_version_min != 1 && m.protocol_version_max != 1 {
-                    return Err(ProtocolError::WrongMessage {
-                        expected: "Only protocol version 1 is allowed".to_string(),
-                        received: format!(
-                            "min/max version  = {}/{}",
+                    return Err(MakerError::rejected(
+                        ErrorCode::UnsupportedVersion,
+                        format!(
+                            "Only protocol version 1 is allowed, got min/max version = {}/{}",
                             m.protocol_version_min, m.protocol_version_max
                         ),
-                    }
-                    .into());
+                    ));
+                }
+                if let Some(podle) = &m.podle {
+                    podle.verify().map_err(|e| {
+                        MakerError::rejected(ErrorCode::InvalidPodle, format!("{:?}", e))
+                    })?;
                 }
+                connection_state.podle = m.podle;
                 connection_state.allowed_message = ExpectedMessage::NewlyConnectedTaker;
                 let reply = MakerToTakerMessage::MakerHello(MakerHello {
                     protocol_version_min: 1,
@@ -91,30 +120,13 @@ pub(crate) fn handle_message(
         }
         ExpectedMessage::NewlyConnectedTaker => match message {
             TakerToMakerMessage::ReqGiveOffer(_) => {
-                let (tweakable_point, max_size) = {
-                    let wallet_reader = maker.wallet.read()?;
-                    let max_size = wallet_reader.store.offer_maxsize;
-                    let tweakable_point = wallet_reader.get_tweakable_keypair()?.1;
-                    (tweakable_point, max_size)
-                };
+                let offer = maker.create_offer()?;
                 connection_state.allowed_message = ExpectedMessage::ReqContractSigsForSender;
-                let fidelity = maker.highest_fidelity_proof.read()?;
-                let fidelity = fidelity.as_ref().expect("proof expected");
-                Some(MakerToTakerMessage::RespOffer(Box::new(Offer {
-                    base_fee: BASE_FEE,
-                    amount_relative_fee_pct: AMOUNT_RELATIVE_FEE_PCT,
-                    time_relative_fee_pct: TIME_RELATIVE_FEE_PCT,
-                    required_confirms: REQUIRED_CONFIRMS,
-                    minimum_locktime: MIN_CONTRACT_REACTION_TIME,
-                    max_size,
-                    min_size: maker.config.min_swap_amount,
-                    tweakable_point,
-                    fidelity: fidelity.clone(),
-                })))
+                Some(MakerToTakerMessage::RespOffer(Box::new(offer)))
             }
             TakerToMakerMessage::ReqContractSigsForSender(message) => {
                 connection_state.allowed_message = ExpectedMessage::ProofOfFunding;
-                Some(maker.handle_req_contract_sigs_for_sender(message)?)
+                Some(maker.handle_req_contract_sigs_for_sender(connection_state, message)?)
             }
             TakerToMakerMessage::RespProofOfFunding(proof) => {
                 connection_state.allowed_message =
@@ -129,9 +141,11 @@ pub(crate) fn handle_message(
                 connection_state.allowed_message = ExpectedMessage::PrivateKeyHandover;
                 Some(maker.handle_hash_preimage(message)?)
             }
+            TakerToMakerMessage::ReqAbort(message) => Some(maker.handle_req_abort(message)?),
             _ => {
                 log::info!("Newlyconnected taker stage message: {:?} ", message);
-                return Err(MakerError::General(
+                return Err(MakerError::rejected(
+                    ErrorCode::UnexpectedMessage,
                     "Unexpected Newly Connected Taker message",
                 ));
             }
@@ -139,7 +153,7 @@ pub(crate) fn handle_message(
         ExpectedMessage::ReqContractSigsForSender => {
             if let TakerToMakerMessage::ReqContractSigsForSender(message) = message {
                 connection_state.allowed_message = ExpectedMessage::ProofOfFunding;
-                Some(maker.handle_req_contract_sigs_for_sender(message)?)
+                Some(maker.handle_req_contract_sigs_for_sender(connection_state, message)?)
             } else {
                 return Err(MakerError::UnexpectedMessage {
                     expected: "ReqContractSigsForSender".to_string(),
@@ -178,7 +192,8 @@ pub(crate) fn handle_message(
                     }
                 }
                 _ => {
-                    return Err(MakerError::General(
+                    return Err(MakerError::rejected(
+                        ErrorCode::UnexpectedMessage,
                         "Expected proof of funding or sender's and reciever's contract signatures",
                     ));
                 }
@@ -189,7 +204,8 @@ pub(crate) fn handle_message(
                 connection_state.allowed_message = ExpectedMessage::HashPreimage;
                 Some(maker.handle_req_contract_sigs_for_recvr(message)?)
             } else {
-                return Err(MakerError::General(
+                return Err(MakerError::rejected(
+                    ErrorCode::UnexpectedMessage,
                     "Expected reciever's contract transaction",
                 ));
             }
@@ -199,7 +215,10 @@ pub(crate) fn handle_message(
                 connection_state.allowed_message = ExpectedMessage::PrivateKeyHandover;
                 Some(maker.handle_hash_preimage(message)?)
             } else {
-                return Err(MakerError::General("Expected hash preimgae"));
+                return Err(MakerError::rejected(
+                    ErrorCode::UnexpectedMessage,
+                    "Expected hash preimgae",
+                ));
             }
         }
         ExpectedMessage::PrivateKeyHandover => {
@@ -208,7 +227,10 @@ pub(crate) fn handle_message(
                 maker.handle_private_key_handover(message)?;
                 None
             } else {
-                return Err(MakerError::General("expected privatekey handover"));
+                return Err(MakerError::rejected(
+                    ErrorCode::UnexpectedMessage,
+                    "expected privatekey handover",
+                ));
             }
         }
     };
@@ -217,17 +239,70 @@ pub(crate) fn handle_message(
 }
 
 impl Maker {
+    /// Converts a configured offer tier into the advertised [OfferTier].
+    /// The size range is bounded by the config's minimum swap amount and the wallet's available balance.
+    pub(crate) fn advertised_tier(&self, tier: &FeePolicy) -> Result<OfferTier, MakerError> {
+        let offer_maxsize = self.wallet.read()?.available_offer_maxsize();
+        Ok(OfferTier {
+            base_fee: tier.base_fee,
+            amount_relative_fee_pct: tier.amount_relative_fee_pct,
+            time_relative_fee_pct: tier.time_relative_fee_pct,
+            required_confirms: tier.required_confirms,
+            minimum_locktime: tier.minimum_locktime,
+            max_size: offer_maxsize.min(tier.max_size),
+            min_size: self.config.min_swap_amount.max(tier.min_size),
+        })
+    }
+
+    /// Creates a fresh [Offer] of all the configured tiers, signed with the key of the highest fidelity bond.
+    pub(crate) fn create_offer(&self) -> Result<Offer, MakerError> {
+        let tweakable_point = self.wallet.read()?.get_tweakable_keypair()?.1;
+        let tiers = self
+            .offer_tiers
+            .read()?
+            .iter()
+            .map(|tier| self.advertised_tier(tier))
+            .collect::<Result<Vec<_>, _>>()?;
+        let fidelity = self.fidelity_proof.read()?.clone().expect("proof expected");
+        let bond_privkey = self
+            .wallet
+            .read()?
+            .get_bond_keypair(&fidelity.bond)?
+            .secret_key();
+        let timestamp = unix_time();
+        Ok(Offer::new_signed(
+            tiers,
+            tweakable_point,
+            fidelity,
+            timestamp,
+            timestamp + OFFER_VALIDITY,
+            &bond_privkey,
+        )?)
+    }
+
     /// This is the first message handler for the Maker. It receives a [ReqContractSigsForSender] message,
     /// checks the validity of contract transactions, and provide's the signature for the sender side.
-    /// This will fail if the maker doesn't have enough utxos to fund the next coinswap hop, or the contract
-    /// transaction isn't valid.
+    /// This will fail if the maker doesn't have enough utxos to fund the next coinswap hop, the contract
+    /// transaction isn't valid, or the taker's PoDLE commitment is missing or invalid.
     pub(crate) fn handle_req_contract_sigs_for_sender(
         &self,
+        connection_state: &mut ConnectionState,
         message: ReqContractSigsForSender,
     ) -> Result<MakerToTakerMessage, MakerError> {
         if let MakerBehavior::CloseAtReqContractSigsForSender = self.behavior {
             return Err(self.behavior.into());
         }
+        check_connection_tier(connection_state, message.tier)?;
+
+        let total_funding_amount = message.txs_info.iter().fold(0u64, |acc, txinfo| {
+            acc + txinfo.funding_input_value.to_sat()
+        });
+
+        // Anti-DoS: nothing gets signed without a fresh commitment to a taker's utxo.
+        self.check_podle(
+            connection_state.podle.as_ref(),
+            Amount::from_sat(total_funding_amount),
+        )?;
 
         // Verify and sign the contract transaction, check function definition for all the checks.
         let sigs = self.verify_and_sign_contract_tx(&message)?;
@@ -238,10 +313,6 @@ impl Maker {
             .map(|txinfo| txinfo.senders_contract_tx.input[0].previous_output.txid)
             .collect::<Vec<_>>();
 
-        let total_funding_amount = message.txs_info.iter().fold(0u64, |acc, txinfo| {
-            acc + txinfo.funding_input_value.to_sat()
-        });
-
         log::info!(
             "[{}] Total Funding Amount = {} | Funding Txids = {:?}",
             self.config.network_port,
@@ -249,18 +320,29 @@ impl Maker {
             funding_txids
         );
 
-        let max_size = self.wallet.read()?.store.offer_maxsize;
-        if total_funding_amount >= self.config.min_swap_amount && total_funding_amount <= max_size {
+        let tier = self.advertised_tier(&self.get_offer_tier(message.tier)?)?;
+        if total_funding_amount >= tier.min_size && total_funding_amount <= tier.max_size {
+            connection_state.tier = Some(message.tier);
+            self.signed_swaps
+                .lock()?
+                .insert(message.hashvalue, Instant::now());
             Ok(MakerToTakerMessage::RespContractSigsForSender(
                 ContractSigsForSender { sigs },
             ))
         } else {
             log::error!(
-                "Funding amount not within min/max limit, min {}, max {}",
-                self.config.min_swap_amount,
-                max_size
+                "Funding amount not within min/max limit of tier {}, min {}, max {}",
+                message.tier,
+                tier.min_size,
+                tier.max_size
             );
-            Err(MakerError::General("not enough funds"))
+            Err(MakerError::rejected(
+                ErrorCode::AmountOutOfRange,
+                format!(
+                    "Funding amount {} not within min/max limit of tier {}, min {}, max {}",
+                    total_funding_amount, message.tier, tier.min_size, tier.max_size
+                ),
+            ))
         }
     }
 
@@ -275,9 +357,14 @@ impl Maker {
             return Err(self.behavior.into());
         }
 
+        check_connection_tier(connection_state, message.tier)?;
+
         // Basic verification of ProofOfFunding Message.
         // Check function definition for all the checks performed.
         let hashvalue = self.verify_proof_of_funding(&message)?;
+        connection_state.tier = Some(message.tier);
+        // From now on the swap is counted by its reservation and state.
+        self.signed_swaps.lock()?.remove(&hashvalue);
         log::info!(
             "[{}] Validated Proof of Funding of receiving swap. Adding Incoming Swaps.",
             self.config.network_port
@@ -358,12 +445,13 @@ impl Maker {
                 Ok::<_, MakerError>(acc + txout.value.to_sat())
             })?;
 
+        let tier = self.get_offer_tier(message.tier)?;
         let calc_coinswap_fees = calculate_coinswap_fee(
             incoming_amount,
             message.refund_locktime,
-            BASE_FEE,
-            AMOUNT_RELATIVE_FEE_PCT,
-            TIME_RELATIVE_FEE_PCT,
+            tier.base_fee,
+            tier.amount_relative_fee_pct,
+            tier.time_relative_fee_pct,
         );
 
         // NOTE: The `contract_feerate` currently represents the hardcoded `MINER_FEE` of a transaction, not the fee rate.
@@ -381,29 +469,37 @@ impl Maker {
         {
             a
         } else {
-            return Err(MakerError::General(
-                "Fatal Error! Total swap fee is more than the swap amount. Failing the swap.",
+            return Err(MakerError::rejected(
+                ErrorCode::AmountOutOfRange,
+                "Total swap fee is more than the swap amount",
             ));
         };
 
-        // Create outgoing coinswap of the next hop
+        // Create outgoing coinswap of the next hop, and reserve its funding utxos for this swap
+        // under the same wallet lock, so concurrent swaps can't select them.
         let (my_funding_txes, outgoing_swapcoins, act_funding_txs_fees) = {
-            self.wallet.write()?.initalize_coinswap(
-                Amount::from_sat(outgoing_amount),
-                &message
-                    .next_coinswap_info
-                    .iter()
-                    .map(|next_hop| next_hop.next_multisig_pubkey)
-                    .collect::<Vec<PublicKey>>(),
-                &message
-                    .next_coinswap_info
-                    .iter()
-                    .map(|next_hop| next_hop.next_hashlock_pubkey)
-                    .collect::<Vec<PublicKey>>(),
-                hashvalue,
-                message.refund_locktime,
-                Amount::from_sat(message.contract_feerate),
-            )?
+            let mut wallet = self.wallet.write()?;
+            let (my_funding_txes, outgoing_swapcoins, act_funding_txs_fees) = wallet
+                .initalize_coinswap(
+                    Amount::from_sat(outgoing_amount),
+                    &message
+                        .next_coinswap_info
+                        .iter()
+                        .map(|next_hop| next_hop.next_multisig_pubkey)
+                        .collect::<Vec<PublicKey>>(),
+                    &message
+                        .next_coinswap_info
+                        .iter()
+                        .map(|next_hop| next_hop.next_hashlock_pubkey)
+                        .collect::<Vec<PublicKey>>(),
+                    hashvalue,
+                    message.refund_locktime,
+                    Amount::from_sat(message.contract_feerate),
+                )?;
+            wallet
+                .utxo_reservations
+                .reserve(&message.id, &my_funding_txes, act_funding_txs_fees);
+            (my_funding_txes, outgoing_swapcoins, act_funding_txs_fees)
         };
 
         let act_coinswap_fees = incoming_amount
@@ -431,6 +527,13 @@ impl Maker {
 
         connection_state.pending_funding_txes = my_funding_txes;
         connection_state.outgoing_swapcoins = outgoing_swapcoins;
+        connection_state.terms = Some(SwapTerms {
+            started_at: unix_time(),
+            incoming_amount: Amount::from_sat(incoming_amount),
+            outgoing_amount: Amount::from_sat(outgoing_amount),
+            funding_fees: act_funding_txs_fees,
+            refund_locktime: message.refund_locktime,
+        });
 
         // Save things to disk after Proof of Funding is confirmed.
         {
@@ -493,7 +596,8 @@ impl Maker {
         }
 
         if message.receivers_sigs.len() != connection_state.incoming_swapcoins.len() {
-            return Err(MakerError::General(
+            return Err(MakerError::rejected(
+                ErrorCode::InvalidSignature,
                 "invalid number of reciever's signatures",
             ));
         }
@@ -507,7 +611,10 @@ impl Maker {
         }
 
         if message.senders_sigs.len() != connection_state.outgoing_swapcoins.len() {
-            return Err(MakerError::General("invalid number of sender's signatures"));
+            return Err(MakerError::rejected(
+                ErrorCode::InvalidSignature,
+                "invalid number of sender's signatures",
+            ));
         }
 
         for (senders_sig, outgoing_swapcoin) in message
@@ -520,6 +627,15 @@ impl Maker {
             outgoing_swapcoin.others_contract_sig = Some(*senders_sig);
         }
 
+        // The swap is dropped from the ongoing swaps if it got aborted meanwhile, e.g. when the incoming
+        // funding got reorged out. Hold the lock while broadcasting, so it can't get aborted halfway.
+        let ongoing_swaps = self.ongoing_swap_state.lock()?;
+        if !ongoing_swaps.contains_key(&message.id) {
+            return Err(MakerError::rejected(
+                ErrorCode::UnknownSwap,
+                "Swap aborted, not broadcasting funding txs",
+            ));
+        }
         let mut my_funding_txids = Vec::<Txid>::new();
         for my_funding_tx in &connection_state.pending_funding_txes {
             let txid = self.wallet.read()?.send_tx(my_funding_tx)?;
@@ -527,6 +643,7 @@ impl Maker {
             assert_eq!(txid, my_funding_tx.compute_txid());
             my_funding_txids.push(txid);
         }
+        drop(ongoing_swaps);
         log::info!(
             "[{}] Broadcasted funding txs: {:?}",
             self.config.network_port,
@@ -544,6 +661,9 @@ impl Maker {
                 wallet_writer.add_outgoing_swapcoin(outgoing_sc);
             }
             wallet_writer.save_to_disk()?;
+            // The funding txs are broadcasted, their inputs are now spent.
+            wallet_writer.utxo_reservations.release(&message.id);
+            wallet_writer.refresh_offer_maxsize_cache()?;
         }
 
         // Update the connection state.
@@ -601,7 +721,10 @@ impl Maker {
                 .expect("Incoming swampcoin expected");
             if read_hashvalue_from_contract(&incoming_swapcoin.contract_redeemscript)? != hashvalue
             {
-                return Err(MakerError::General("not correct hash preimage"));
+                return Err(MakerError::rejected(
+                    ErrorCode::InvalidContract,
+                    "not correct hash preimage",
+                ));
             }
             incoming_swapcoin.hash_preimage = Some(message.preimage);
         }
@@ -621,7 +744,10 @@ impl Maker {
                 .expect("outgoing swapcoin expected");
             if read_hashvalue_from_contract(&outgoing_swapcoin.contract_redeemscript)? != hashvalue
             {
-                return Err(MakerError::General("not correct hash preimage"));
+                return Err(MakerError::rejected(
+                    ErrorCode::InvalidContract,
+                    "not correct hash preimage",
+                ));
             } else {
                 outgoing_swapcoin.hash_preimage.replace(message.preimage);
             }
@@ -646,16 +772,42 @@ impl Maker {
     ) -> Result<(), MakerError> {
         // Mark the incoming swapcoins as "done", by adding their's privkey
         for swapcoin_private_key in &message.multisig_privkeys {
-            self.wallet
-                .write()?
+            let mut wallet = self.wallet.write()?;
+            let incoming_swapcoin = wallet
                 .find_incoming_swapcoin_mut(&swapcoin_private_key.multisig_redeemscript)
-                .expect("incoming swapcoin not found")
-                .apply_privkey(swapcoin_private_key.key)?;
+                .expect("incoming swapcoin not found");
+            incoming_swapcoin.apply_privkey(swapcoin_private_key.key)?;
+            // The swap is settled, stop watching its funding for reorgs.
+            let funding_txid = incoming_swapcoin.contract_tx.input[0].previous_output.txid;
+            wallet.untrack_confirmation(&funding_txid)?;
         }
 
-        // Reset the connection state so watchtowers are not triggered.
-        let mut conn_state = self.ongoing_swap_state.lock()?;
-        *conn_state = HashMap::default();
+        // Remove the state of this swap so watchtowers are not triggered, and record it as completed.
+        // Other swaps may still be in flight, so keep their states.
+        let completed = {
+            let mut ongoing_swaps = self.ongoing_swap_state.lock()?;
+            ongoing_swaps
+                .iter()
+                .filter(|(_, (state, _))| {
+                    state.incoming_swapcoins.iter().any(|swapcoin| {
+                        message.multisig_privkeys.iter().any(|key| {
+                            key.multisig_redeemscript == swapcoin.get_multisig_redeemscript()
+                        })
+                    })
+                })
+                .map(|(id, _)| id.clone())
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|id| {
+                    let (state, _) = ongoing_swaps.remove(&id).expect("swap state expected");
+                    (id, state)
+                })
+                .collect::<Vec<_>>()
+        };
+        // The ledger is written once the swap states are released.
+        for (id, state) in &completed {
+            self.record_swap(id, state, SwapOutcome::Completed, true);
+        }
 
         log::info!("initializing Wallet Sync.");
         {
@@ -667,6 +819,226 @@ impl Maker {
         log::info!("Successfully Completed Coinswap");
         Ok(())
     }
+
+    /// Handles a [ReqAbort] message and returns a [MakerToTakerMessage::RespAbort], cooperatively aborting a funded swap.
+    ///
+    /// The outgoing swapcoins are refunded with the receiver's sigs, and the refund txs are broadcasted.
+    /// The incoming swapcoins are given up by signing their refund txs, only once the outgoing swapcoins can't be
+    /// claimed by the receiver anymore, i.e. the outgoing funding was never broadcasted or its refunds are confirmed.
+    pub(crate) fn handle_req_abort(
+        &self,
+        message: ReqAbort,
+    ) -> Result<MakerToTakerMessage, MakerError> {
+        // Hold the lock throughout, so the outgoing funding can't get broadcasted meanwhile.
+        let mut ongoing_swaps = self.ongoing_swap_state.lock()?;
+        let (state, last_message_time) = ongoing_swaps.get_mut(&message.id).ok_or_else(|| {
+            MakerError::rejected(ErrorCode::UnknownSwap, "No ongoing swap to abort")
+        })?;
+
+        // Only the Taker knows the multisig nonce it sent us, anybody else may know the swap id.
+        let multisig_pubkey = state
+            .incoming_swapcoins
+            .first()
+            .map(|sc| sc.get_my_pubkey())
+            .ok_or_else(|| {
+                MakerError::rejected(ErrorCode::UnknownSwap, "No incoming swap to abort")
+            })?;
+        let tweakable_point = self.wallet.read()?.get_tweakable_keypair()?.1;
+        verify_abort_request(
+            &message.id,
+            &message.auth_sig,
+            &tweakable_point,
+            &multisig_pubkey,
+        )
+        .map_err(|_| {
+            MakerError::rejected(
+                ErrorCode::InvalidSignature,
+                "Abort request not signed by the swap's Taker",
+            )
+        })?;
+        *last_message_time = Instant::now();
+
+        let outgoing_redeemscripts = state
+            .outgoing_swapcoins
+            .iter()
+            .map(|sc| sc.get_multisig_redeemscript())
+            .collect::<Vec<_>>();
+
+        let outgoing_funded = {
+            let wallet = self.wallet.read()?;
+            if state.incoming_swapcoins.iter().any(|sc| {
+                wallet
+                    .find_incoming_swapcoin(&sc.get_multisig_redeemscript())
+                    .is_some_and(|sc| sc.is_hash_preimage_known())
+            }) {
+                return Err(MakerError::rejected(
+                    ErrorCode::UnknownSwap,
+                    "Hash preimage already received, not aborting swap",
+                ));
+            }
+            state.pending_funding_txes.iter().any(|tx| {
+                wallet
+                    .rpc
+                    .get_raw_transaction_info(&tx.compute_txid(), None)
+                    .is_ok()
+            })
+        };
+
+        if outgoing_funded && state.outgoing_refund_txes.is_empty() {
+            let addresses = self
+                .wallet
+                .read()?
+                .get_next_internal_addresses(state.outgoing_swapcoins.len() as u32)?;
+            state.outgoing_refund_txes = state
+                .outgoing_swapcoins
+                .iter()
+                .zip(addresses.iter())
+                .map(|(sc, address)| sc.create_refund_tx(address, self.config.refund_feerate))
+                .collect::<Result<_, _>>()?;
+        }
+
+        // Store the fully signed refunds, so they are used over the contracts in a recovery.
+        if !message.outgoing_refund_sigs.is_empty() {
+            if message.outgoing_refund_sigs.len() != state.outgoing_refund_txes.len() {
+                return Err(ProtocolError::WrongNumOfSigs {
+                    expected: state.outgoing_refund_txes.len(),
+                    received: message.outgoing_refund_sigs.len(),
+                }
+                .into());
+            }
+            let mut wallet = self.wallet.write()?;
+            for ((refund_tx, sig), outgoing_swapcoin) in state
+                .outgoing_refund_txes
+                .iter()
+                .zip(message.outgoing_refund_sigs.iter())
+                .zip(state.outgoing_swapcoins.iter())
+            {
+                let signed_refund_tx = outgoing_swapcoin.sign_refund_tx(refund_tx, sig)?;
+                if let Some(swapcoin) = wallet
+                    .find_outgoing_swapcoin_mut(&outgoing_swapcoin.get_multisig_redeemscript())
+                {
+                    swapcoin.refund_tx = Some(signed_refund_tx);
+                }
+            }
+            wallet.save_to_disk()?;
+        }
+
+        // Broadcast the signed refunds, and check whether all of them are confirmed.
+        let outgoing_refunded = if outgoing_funded {
+            let wallet = self.wallet.read()?;
+            let refund_txes = outgoing_redeemscripts
+                .iter()
+                .map(|rs| {
+                    wallet
+                        .find_outgoing_swapcoin(rs)
+                        .and_then(|sc| sc.refund_tx.clone())
+                })
+                .collect::<Option<Vec<_>>>()
+                .unwrap_or_default();
+            let mut confirmed = !refund_txes.is_empty();
+            for refund_tx in &refund_txes {
+                let txid = refund_tx.compute_txid();
+                match wallet.rpc.get_raw_transaction_info(&txid, None) {
+                    Ok(info) => confirmed &= info.confirmations.unwrap_or(0) > 0,
+                    Err(_) => {
+                        wallet.send_tx(refund_tx)?;
+                        log::info!(
+                            "[{}] Broadcasted refund tx: {}",
+                            self.config.network_port,
+                            txid
+                        );
+                        confirmed = false;
+                    }
+                }
+            }
+            confirmed
+        } else {
+            true
+        };
+
+        let outgoing_refund_txs = state.outgoing_refund_txes.clone();
+        if message.incoming_refund_txs.is_empty() {
+            return Ok(MakerToTakerMessage::RespAbort(RespAbort {
+                outgoing_refund_txs,
+                incoming_refund_sigs: Vec::new(),
+            }));
+        }
+        if !outgoing_refunded {
+            log::info!(
+                "[{}] Waiting for the refunds of swap {} to confirm, before signing the incoming refunds",
+                self.config.network_port,
+                message.id
+            );
+            return Ok(MakerToTakerMessage::RespAbort(RespAbort {
+                outgoing_refund_txs,
+                incoming_refund_sigs: Vec::new(),
+            }));
+        }
+
+        if message.incoming_refund_txs.len() != state.incoming_swapcoins.len() {
+            return Err(ProtocolError::WrongNumOfContractTxs {
+                expected: state.incoming_swapcoins.len(),
+                received: message.incoming_refund_txs.len(),
+            }
+            .into());
+        }
+        let incoming_refund_sigs = message
+            .incoming_refund_txs
+            .iter()
+            .zip(state.incoming_swapcoins.iter())
+            .map(|(refund_tx, sc)| sc.sign_senders_refund_tx(refund_tx))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Nothing is left to claim in this swap. Forget it.
+        let (state, _) = ongoing_swaps
+            .remove(&message.id)
+            .expect("swap state expected");
+        drop(ongoing_swaps);
+        self.record_swap(&message.id, &state, SwapOutcome::Aborted, outgoing_funded);
+        {
+            let mut wallet = self.wallet.write()?;
+            for incoming_swapcoin in &state.incoming_swapcoins {
+                wallet.remove_incoming_swapcoin(&incoming_swapcoin.get_multisig_redeemscript())?;
+                let funding_txid = incoming_swapcoin.contract_tx.input[0].previous_output.txid;
+                wallet.untrack_confirmation(&funding_txid)?;
+            }
+            for redeemscript in &outgoing_redeemscripts {
+                wallet.remove_outgoing_swapcoin(redeemscript)?;
+            }
+            // The outgoing funding txs were never broadcasted.
+            if wallet.utxo_reservations.release(&message.id) {
+                wallet.refresh_offer_maxsize_cache()?;
+            }
+            wallet.save_to_disk()?;
+        }
+        log::info!(
+            "[{}] Cooperatively aborted swap {}",
+            self.config.network_port,
+            message.id
+        );
+
+        Ok(MakerToTakerMessage::RespAbort(RespAbort {
+            outgoing_refund_txs,
+            incoming_refund_sigs,
+        }))
+    }
+}
+
+/// Rejects a message choosing another offer tier than the one this connection's swap was validated against.
+fn check_connection_tier(
+    connection_state: &ConnectionState,
+    tier: usize,
+) -> Result<(), MakerError> {
+    match connection_state.tier {
+        Some(validated) if validated != tier => Err(MakerError::rejected(
+            ErrorCode::InvalidContract,
+            format!(
+                "Offer tier {} differs from the tier {} of the swap",
+                tier, validated
+            ),
+        )),
+        _ => Ok(()),
+    }
 }
 
 fn unexpected_recovery(maker: Arc<Maker>) -> Result<(), MakerError> {
@@ -690,7 +1062,11 @@ fn unexpected_recovery(maker: Arc<Maker>) -> Result<(), MakerError> {
                 (contract_timelock, time_lock_spend),
             ));
             let incoming_contract = ic_sc.get_fully_signed_contract_tx()?;
-            incomings.push((ic_sc.get_multisig_redeemscript(), incoming_contract));
+            let hashlock_spend = maker.create_hashlock_spend(ic_sc)?;
+            incomings.push((
+                (ic_sc.get_multisig_redeemscript(), incoming_contract),
+                hashlock_spend,
+            ));
         }
         // Spawn a separate thread to wait for contract maturity and broadcasting timelocked.
         let maker_clone = maker.clone();
@@ -0,0 +1,514 @@
+//! The swap ledger of the Maker.
+//!
+//! Every swap is recorded once it is settled, with its amounts, fees and outcome, so the operator can
+//! tell whether running the maker pays. The ledger is persisted in the `swap_ledger` file of the data
+//! directory, one JSON record per line, and queried with the `SwapHistory` and `Earnings` RPCs.
+//!
+//! Swaps still in flight when makerd stops are not recorded, as their state is only held in memory.
+
+use std::{
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use bitcoin::{Amount, SignedAmount};
+use serde::{Deserialize, Serialize};
+
+use crate::{taker::api::REFUND_LOCKTIME, utill::unix_time};
+
+use super::error::MakerError;
+
+/// The position of the maker in the route of a swap.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HopRole {
+    /// The outgoing coins went to the taker.
+    Last,
+    /// The outgoing coins went to another maker. The first hop can't be told apart from the middle ones.
+    Intermediate,
+}
+
+impl HopRole {
+    /// Infers the role from the refund locktime of the outgoing contracts. Takers give the last hop the
+    /// minimum locktime, and increase it for each hop before.
+    pub(crate) fn from_refund_locktime(refund_locktime: u16) -> Self {
+        if refund_locktime <= REFUND_LOCKTIME {
+            Self::Last
+        } else {
+            Self::Intermediate
+        }
+    }
+}
+
+impl Display for HopRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Last => write!(f, "last"),
+            Self::Intermediate => write!(f, "intermediate"),
+        }
+    }
+}
+
+/// How a swap was settled.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapOutcome {
+    /// The private keys were handed over, the swap succeeded.
+    Completed,
+    /// The contracts were broadcasted, and the funds are claimed back on chain.
+    Recovered,
+    /// The swap was called off before any contract was broadcasted.
+    Aborted,
+}
+
+impl Display for SwapOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Completed => write!(f, "completed"),
+            Self::Recovered => write!(f, "recovered"),
+            Self::Aborted => write!(f, "aborted"),
+        }
+    }
+}
+
+/// A settled swap, as recorded in the ledger.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SwapRecord {
+    /// The swap id given by the taker.
+    pub id: String,
+    /// Unix time of the proof of funding, when the maker committed to the swap.
+    pub started_at: u64,
+    /// Unix time the swap was settled at.
+    pub finished_at: u64,
+    /// The position of the maker in the route.
+    pub role: HopRole,
+    /// Amount received in the incoming swapcoins.
+    pub incoming_amount: Amount,
+    /// Amount sent in the outgoing swapcoins.
+    pub outgoing_amount: Amount,
+    /// The incoming amount kept by the maker. Zero unless the swap completed.
+    pub fee_earned: Amount,
+    /// Mining fees paid for the outgoing funding transactions. Zero if they were never broadcasted.
+    /// Fees of the contract and recovery transactions are not counted.
+    pub miner_fees: Amount,
+    /// Refund locktime of the outgoing contracts, in blocks.
+    pub refund_locktime: u16,
+    /// How the swap was settled.
+    pub outcome: SwapOutcome,
+}
+
+impl SwapRecord {
+    /// Header of the CSV export, matching [SwapRecord::to_csv].
+    pub const CSV_HEADER: &'static str = "id,started_at,finished_at,role,incoming_sats,outgoing_sats,fee_earned_sats,miner_fees_sats,net_sats,refund_locktime,outcome";
+
+    /// The fee earned, less the mining fees paid. Negative for swaps that didn't complete.
+    pub fn net_earnings(&self) -> SignedAmount {
+        self.fee_earned.to_signed().expect("amount within range")
+            - self.miner_fees.to_signed().expect("amount within range")
+    }
+
+    /// The record as a CSV row.
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            self.id,
+            self.started_at,
+            self.finished_at,
+            self.role,
+            self.incoming_amount.to_sat(),
+            self.outgoing_amount.to_sat(),
+            self.fee_earned.to_sat(),
+            self.miner_fees.to_sat(),
+            self.net_earnings().to_sat(),
+            self.refund_locktime,
+            self.outcome
+        )
+    }
+}
+
+impl Display for SwapRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} | {} | {} | {} | in {} | out {} | fee {} | mining {} | net {} sats | locktime {}",
+            format_date(self.finished_at),
+            self.id,
+            self.outcome,
+            self.role,
+            self.incoming_amount.to_sat(),
+            self.outgoing_amount.to_sat(),
+            self.fee_earned.to_sat(),
+            self.miner_fees.to_sat(),
+            self.net_earnings().to_sat(),
+            self.refund_locktime
+        )
+    }
+}
+
+/// Earnings of the swaps settled in a time range, see [SwapRecord].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EarningsReport {
+    /// Start of the range as a unix time, inclusive.
+    pub from: Option<u64>,
+    /// End of the range as a unix time, exclusive.
+    pub to: Option<u64>,
+    /// Number of completed swaps.
+    pub completed: u32,
+    /// Number of recovered swaps.
+    pub recovered: u32,
+    /// Number of aborted swaps.
+    pub aborted: u32,
+    /// Incoming amount of the completed swaps.
+    pub volume: Amount,
+    /// Total fees earned.
+    pub fees_earned: Amount,
+    /// Total mining fees paid.
+    pub miner_fees: Amount,
+    /// Fees earned, less the mining fees paid.
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    pub net_earnings: SignedAmount,
+}
+
+impl EarningsReport {
+    /// Header of the CSV export, matching [EarningsReport::to_csv].
+    pub const CSV_HEADER: &'static str =
+        "from,to,completed,recovered,aborted,volume_sats,fees_earned_sats,miner_fees_sats,net_sats";
+
+    fn new(from: Option<u64>, to: Option<u64>, records: &[SwapRecord]) -> Self {
+        let count = |outcome| records.iter().filter(|r| r.outcome == outcome).count() as u32;
+        Self {
+            from,
+            to,
+            completed: count(SwapOutcome::Completed),
+            recovered: count(SwapOutcome::Recovered),
+            aborted: count(SwapOutcome::Aborted),
+            volume: records
+                .iter()
+                .filter(|r| r.outcome == SwapOutcome::Completed)
+                .map(|r| r.incoming_amount)
+                .sum(),
+            fees_earned: records.iter().map(|r| r.fee_earned).sum(),
+            miner_fees: records.iter().map(|r| r.miner_fees).sum(),
+            net_earnings: records.iter().map(SwapRecord::net_earnings).sum(),
+        }
+    }
+
+    /// The report as a CSV row. Open ends of the range are left empty.
+    pub fn to_csv(&self) -> String {
+        let bound = |b: Option<u64>| b.map(|t| t.to_string()).unwrap_or_default();
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            bound(self.from),
+            bound(self.to),
+            self.completed,
+            self.recovered,
+            self.aborted,
+            self.volume.to_sat(),
+            self.fees_earned.to_sat(),
+            self.miner_fees.to_sat(),
+            self.net_earnings.to_sat()
+        )
+    }
+}
+
+impl Display for EarningsReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bound = |b: Option<u64>, open: &str| b.map(format_date).unwrap_or_else(|| open.into());
+        writeln!(
+            f,
+            "Range            : {} to {}",
+            bound(self.from, "start"),
+            bound(self.to, "now")
+        )?;
+        writeln!(
+            f,
+            "Swaps            : {} completed | {} recovered | {} aborted",
+            self.completed, self.recovered, self.aborted
+        )?;
+        writeln!(f, "Volume           : {} sats", self.volume.to_sat())?;
+        writeln!(f, "Fees earned      : {} sats", self.fees_earned.to_sat())?;
+        writeln!(f, "Mining fees paid : {} sats", self.miner_fees.to_sat())?;
+        write!(f, "Net earnings     : {} sats", self.net_earnings.to_sat())
+    }
+}
+
+/// The terms of a swap, fixed at its proof of funding. Settled into a [SwapRecord].
+#[derive(Debug, Clone)]
+pub(crate) struct SwapTerms {
+    pub(crate) started_at: u64,
+    pub(crate) incoming_amount: Amount,
+    pub(crate) outgoing_amount: Amount,
+    pub(crate) funding_fees: Amount,
+    pub(crate) refund_locktime: u16,
+}
+
+impl SwapTerms {
+    /// Settles the swap. The funding fees are counted only if the outgoing funding txs were broadcasted.
+    pub(crate) fn settle(&self, id: &str, outcome: SwapOutcome, funded: bool) -> SwapRecord {
+        SwapRecord {
+            id: id.to_string(),
+            started_at: self.started_at,
+            finished_at: unix_time(),
+            role: HopRole::from_refund_locktime(self.refund_locktime),
+            incoming_amount: self.incoming_amount,
+            outgoing_amount: self.outgoing_amount,
+            fee_earned: if outcome == SwapOutcome::Completed {
+                self.incoming_amount - self.outgoing_amount
+            } else {
+                Amount::ZERO
+            },
+            miner_fees: if funded {
+                self.funding_fees
+            } else {
+                Amount::ZERO
+            },
+            refund_locktime: self.refund_locktime,
+            outcome,
+        }
+    }
+}
+
+/// The swap ledger, loaded from and appended to the `swap_ledger` file.
+#[derive(Debug)]
+pub(crate) struct SwapLedger {
+    path: PathBuf,
+    records: Mutex<Vec<SwapRecord>>,
+}
+
+impl SwapLedger {
+    /// Loads the ledger at `path`. A missing file means no swap is recorded yet.
+    ///
+    /// Unreadable records, like a last line cut by a crash, are skipped with a warning: the ledger is only a report,
+    /// it must not keep makerd from starting.
+    pub(crate) fn load(path: &Path) -> Result<Self, MakerError> {
+        let mut records = Vec::new();
+        if path.exists() {
+            let content = fs::read_to_string(path)?;
+            for (number, line) in content.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(line) {
+                    Ok(record) => records.push(record),
+                    Err(e) => log::warn!(
+                        "Skipping invalid record at line {} of the swap ledger: {}",
+                        number + 1,
+                        e
+                    ),
+                }
+            }
+            // Start the next record on its own line, after a cut one.
+            if !content.is_empty() && !content.ends_with('\n') {
+                writeln!(OpenOptions::new().append(true).open(path)?)?;
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            records: Mutex::new(records),
+        })
+    }
+
+    /// Appends a settled swap to the ledger.
+    pub(crate) fn record(&self, record: SwapRecord) -> Result<(), MakerError> {
+        let mut records = self.records.lock()?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(
+            file,
+            "{}",
+            serde_json::to_string(&record).expect("record serialization can't fail")
+        )?;
+        log::info!(
+            "Recorded {} swap {} | fee earned {} | mining fees {}",
+            record.outcome,
+            record.id,
+            record.fee_earned,
+            record.miner_fees
+        );
+        records.push(record);
+        Ok(())
+    }
+
+    /// The swaps settled in `[from, to)`, oldest first.
+    pub(crate) fn history(
+        &self,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<Vec<SwapRecord>, MakerError> {
+        Ok(self
+            .records
+            .lock()?
+            .iter()
+            .filter(|r| r.finished_at >= from.unwrap_or(0))
+            .filter(|r| r.finished_at < to.unwrap_or(u64::MAX))
+            .cloned()
+            .collect())
+    }
+
+    /// The earnings of the swaps settled in `[from, to)`.
+    pub(crate) fn earnings(
+        &self,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<EarningsReport, MakerError> {
+        Ok(EarningsReport::new(from, to, &self.history(from, to)?))
+    }
+}
+
+/// Parses a date of the ledger range, either as `YYYY-MM-DD` (midnight UTC) or as a unix time.
+pub fn parse_date(date: &str) -> Result<u64, MakerError> {
+    let invalid = || MakerError::General("Invalid date, expected YYYY-MM-DD or unix time");
+    if let Ok(time) = date.parse::<u64>() {
+        return Ok(time);
+    }
+    let parts = date
+        .split('-')
+        .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match parts[..] {
+        [year, month, day] if (1..=12).contains(&month) && (1..=31).contains(&day) => {
+            let days = days_from_civil(year, month, day);
+            if days < 0 {
+                return Err(invalid());
+            }
+            Ok(days as u64 * 86400)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Formats a unix time as `YYYY-MM-DD HH:MM` UTC.
+fn format_date(time: u64) -> String {
+    let (year, month, day) = civil_from_days((time / 86400) as i64);
+    let seconds = time % 86400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+/// Days since 1970-01-01 of a proleptic gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The proleptic gregorian date of a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, finished_at: u64, outcome: SwapOutcome) -> SwapRecord {
+        let terms = SwapTerms {
+            started_at: finished_at - 600,
+            incoming_amount: Amount::from_sat(100_000),
+            outgoing_amount: Amount::from_sat(98_000),
+            funding_fees: Amount::from_sat(1_000),
+            refund_locktime: REFUND_LOCKTIME,
+        };
+        SwapRecord {
+            finished_at,
+            ..terms.settle(id, outcome, outcome != SwapOutcome::Aborted)
+        }
+    }
+
+    #[test]
+    fn test_swap_ledger() {
+        let path = std::env::temp_dir().join(format!("coinswap-ledger-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let ledger = SwapLedger::load(&path).unwrap();
+        ledger
+            .record(record("a", 1_000, SwapOutcome::Completed))
+            .unwrap();
+        ledger
+            .record(record("b", 2_000, SwapOutcome::Recovered))
+            .unwrap();
+        ledger
+            .record(record("c", 3_000, SwapOutcome::Aborted))
+            .unwrap();
+
+        // Records survive a reload.
+        let ledger = SwapLedger::load(&path).unwrap();
+        assert_eq!(ledger.history(None, None).unwrap().len(), 3);
+        let history = ledger.history(Some(1_000), Some(3_000)).unwrap();
+        assert_eq!(
+            history.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(history[0].role, HopRole::Last);
+        assert_eq!(history[0].net_earnings(), SignedAmount::from_sat(1_000));
+        assert_eq!(history[1].net_earnings(), SignedAmount::from_sat(-1_000));
+        assert_eq!(
+            history[0].to_csv().split(',').count(),
+            SwapRecord::CSV_HEADER.split(',').count()
+        );
+
+        let earnings = ledger.earnings(None, None).unwrap();
+        assert_eq!(
+            (earnings.completed, earnings.recovered, earnings.aborted),
+            (1, 1, 1)
+        );
+        assert_eq!(earnings.volume, Amount::from_sat(100_000));
+        assert_eq!(earnings.fees_earned, Amount::from_sat(2_000));
+        assert_eq!(earnings.miner_fees, Amount::from_sat(2_000));
+        assert_eq!(earnings.net_earnings, SignedAmount::ZERO);
+
+        // A record cut by a crash is skipped, and doesn't spoil the next one.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"id\":\"d\",\"fini").unwrap();
+        let ledger = SwapLedger::load(&path).unwrap();
+        assert_eq!(ledger.history(None, None).unwrap().len(), 3);
+        ledger
+            .record(record("e", 4_000, SwapOutcome::Completed))
+            .unwrap();
+        let ledger = SwapLedger::load(&path).unwrap();
+        assert_eq!(
+            ledger
+                .history(None, None)
+                .unwrap()
+                .iter()
+                .map(|r| r.id.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "b", "c", "e"]
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_ledger_dates() {
+        assert_eq!(parse_date("1970-01-01").unwrap(), 0);
+        assert_eq!(parse_date("2024-03-01").unwrap(), 1_709_251_200);
+        assert_eq!(parse_date("1709251200").unwrap(), 1_709_251_200);
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("yesterday").is_err());
+        assert_eq!(format_date(1_709_251_200 + 3_660), "2024-03-01 01:01");
+        assert_eq!(format_date(951_782_400), "2000-02-29 00:00");
+    }
+}
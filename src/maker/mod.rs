@@ -14,10 +14,13 @@ mod api;
 mod config;
 mod error;
 mod handlers;
+mod ledger;
 mod rpc;
 mod server;
 
 pub use api::{Maker, MakerBehavior};
+pub use config::FeePolicy;
 pub use error::MakerError;
-pub use rpc::{RpcMsgReq, RpcMsgResp};
+pub use ledger::{parse_date, EarningsReport, HopRole, SwapOutcome, SwapRecord};
+pub use rpc::{FidelityEvent, RpcMsgReq, RpcMsgResp};
 pub use server::start_maker_server;
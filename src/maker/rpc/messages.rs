@@ -1,12 +1,18 @@
 use std::{collections::HashMap, fmt::Display};
 
-use bitcoin::Txid;
+use bitcoin::{Amount, OutPoint, PublicKey, Txid};
 use bitcoind::bitcoincore_rpc::json::ListUnspentResultEntry;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, to_string_pretty};
 use std::path::PathBuf;
 
-use crate::wallet::{Balances, FidelityBond};
+use crate::{
+    maker::{
+        config::FeePolicy,
+        ledger::{EarningsReport, SwapRecord},
+    },
+    wallet::{Balances, FidelityBond},
+};
 
 /// Enum representing RPC message requests.
 ///
@@ -47,8 +53,74 @@ pub enum RpcMsgReq {
     RedeemFidelity(u32),
     /// Request to list all active and past fidelity bonds.
     ListFidelity,
+    /// Request the advertised fidelity proof, encoded in hex.
+    FidelityProof,
+    /// Request the recent steps of the fidelity bond renewal, with their unix times.
+    FidelityEvents,
+    /// Request to lock coins in an additional fidelity bond.
+    CreateFidelity {
+        /// The amount to lock, in sats.
+        amount: u64,
+        /// The number of blocks the bond is locked for.
+        timelock: u32,
+    },
+    /// Request to import a confirmed cold fidelity bond, held by `fidelity-signer`, as a watch-only bond.
+    ImportColdFidelity {
+        /// The outpoint of the bond.
+        outpoint: OutPoint,
+        /// The public key of the bond.
+        pubkey: PublicKey,
+        /// The block height the bond is locked until.
+        locktime: u32,
+    },
+    /// Request to write a delegation request for the cold bond at a given index, to be signed offline.
+    ExportDelegationRequest {
+        /// The wallet index of the cold bond.
+        index: u32,
+        /// The path of the request file.
+        path: PathBuf,
+    },
+    /// Request to import the signed delegation at the given path.
+    ImportDelegation(PathBuf),
+    /// Request the settled swaps of the ledger, in a range of unix times.
+    SwapHistory {
+        /// Start of the range, inclusive.
+        from: Option<u64>,
+        /// End of the range, exclusive.
+        to: Option<u64>,
+    },
+    /// Request the earnings of the swaps settled in a range of unix times.
+    Earnings {
+        /// Start of the range, inclusive.
+        from: Option<u64>,
+        /// End of the range, exclusive.
+        to: Option<u64>,
+    },
     /// Request to sync the internal wallet with blockchain.
     SyncWallet,
+    /// Request to export a backup of all unfinished swaps at the given path.
+    ExportSwapBackup(PathBuf),
+    /// Request to register all unfinished swaps with the watchtower at the given address.
+    RegisterWatchtower(String),
+    /// Request to update an offer tier advertised in the offers. Unset fields are left unchanged.
+    SetFees {
+        /// Index of the offer tier to update.
+        tier: usize,
+        /// Fixed base fee in sats.
+        base_fee: Option<u64>,
+        /// Fee in percentage of the swap amount.
+        amount_relative_fee_pct: Option<f64>,
+        /// Fee in percentage of the swap amount, per block of refund locktime.
+        time_relative_fee_pct: Option<f64>,
+        /// Minimum difference in blocks between the incoming and outgoing contract locktimes.
+        minimum_locktime: Option<u16>,
+        /// Confirmations required on the taker's funding transactions.
+        required_confirms: Option<u32>,
+        /// Minimum coinswap amount of the tier in sats.
+        min_size: Option<u64>,
+        /// Maximum coinswap amount of the tier in sats.
+        max_size: Option<u64>,
+    },
 }
 
 /// Enum representing RPC message responses.
@@ -93,10 +165,95 @@ pub enum RpcMsgResp {
     Shutdown,
     /// Response with the fidelity spending txid.
     FidelitySpend(Txid),
+    /// Response with the txid of the new fidelity bond, waiting for confirmation.
+    FidelityFunded(Txid),
+    /// Response with the advertised fidelity proof in hex, if any.
+    FidelityProofResp(Option<String>),
+    /// Response with the wallet index of the imported cold bond.
+    ColdFidelityImported(u32),
+    /// Response with the path of the written delegation request.
+    DelegationRequestResp(PathBuf),
+    /// Response with the wallet index of the cold bond certified by the imported delegation.
+    DelegationImported(u32),
+    /// Response with the recent fidelity bond renewal steps, oldest first, with their unix times.
+    FidelityEventsResp(Vec<(u64, FidelityEvent)>),
+    /// Response with the settled swaps, oldest first.
+    SwapHistoryResp(Vec<SwapRecord>),
+    /// Response with the earnings of the settled swaps.
+    EarningsResp(EarningsReport),
     /// Response with the internal server error.
     ServerError(String),
     /// Response listing all current and past fidelity bonds.
     ListBonds(HashMap<u32, (FidelityBond, bool)>),
+    /// Response with the path of the exported swap backup.
+    SwapBackupResp(PathBuf),
+    /// Response with the number of contracts registered with the watchtower.
+    WatchtowerRegistered(usize),
+    /// Response with all the offer tiers, after an update.
+    OfferTiersResp(Vec<FeePolicy>),
+}
+
+/// A step of the fidelity bond management of the maker, reported over RPC.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum FidelityEvent {
+    /// A replacement bond was broadcasted, as the live bonds are about to mature.
+    ReplacementBroadcast {
+        /// The txid of the bond transaction.
+        txid: Txid,
+        /// The locked amount.
+        amount: Amount,
+    },
+    /// A new bond confirmed, and is stored in the wallet.
+    BondConfirmed {
+        /// The wallet index of the bond.
+        index: u32,
+        /// The txid of the bond transaction.
+        txid: Txid,
+    },
+    /// The certificate of a bond was renewed before its expiry.
+    CertRenewed {
+        /// The outpoint of the bond.
+        outpoint: OutPoint,
+        /// The new certificate expiry, in difficulty adjustment periods.
+        cert_expiry: u64,
+    },
+    /// A matured bond was redeemed to the wallet.
+    Redeemed {
+        /// The wallet index of the bond.
+        index: u32,
+        /// The txid of the redeeming transaction.
+        txid: Txid,
+    },
+    /// A step failed. It is attempted again at the next check.
+    Failed(String),
+}
+
+impl Display for FidelityEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReplacementBroadcast { txid, amount } => write!(
+                f,
+                "Replacement bond of {} sats broadcasted in {}",
+                amount.to_sat(),
+                txid
+            ),
+            Self::BondConfirmed { index, txid } => {
+                write!(f, "Bond {} confirmed, stored at index {}", txid, index)
+            }
+            Self::CertRenewed {
+                outpoint,
+                cert_expiry,
+            } => write!(
+                f,
+                "Certificate of bond {} renewed until period {}",
+                outpoint, cert_expiry
+            ),
+            Self::Redeemed { index, txid } => {
+                write!(f, "Matured bond at index {} redeemed in {}", index, txid)
+            }
+            Self::Failed(e) => write!(f, "Failed: {}", e),
+        }
+    }
 }
 
 impl Display for RpcMsgResp {
@@ -127,8 +284,53 @@ impl Display for RpcMsgResp {
             Self::GetDataDirResp(path) => write!(f, "{}", path.display()),
             Self::Shutdown => write!(f, "Shutdown Initiated"),
             Self::FidelitySpend(txid) => write!(f, "{}", txid),
+            Self::FidelityFunded(txid) => write!(f, "{}", txid),
+            Self::FidelityProofResp(proof) => match proof {
+                Some(proof) => write!(f, "{}", proof),
+                None => write!(f, "No fidelity proof advertised yet"),
+            },
+            Self::ColdFidelityImported(index) => write!(f, "Cold bond imported at index {}", index),
+            Self::DelegationRequestResp(path) => write!(f, "{}", path.display()),
+            Self::DelegationImported(index) => write!(f, "Cold bond {} certified", index),
+            Self::FidelityEventsResp(events) => {
+                for (time, event) in events {
+                    writeln!(f, "{} | {}", time, event)?;
+                }
+                Ok(())
+            }
+            Self::SwapHistoryResp(records) => {
+                if records.is_empty() {
+                    return write!(f, "No swaps recorded");
+                }
+                for record in records {
+                    writeln!(f, "{}", record)?;
+                }
+                Ok(())
+            }
+            Self::EarningsResp(report) => write!(f, "{}", report),
             Self::ServerError(e) => write!(f, "{}", e),
             Self::ListBonds(v) => write!(f, "{:#?}", v),
+            Self::SwapBackupResp(path) => write!(f, "{}", path.display()),
+            Self::WatchtowerRegistered(count) => write!(f, "Registered {} contracts", count),
+            Self::OfferTiersResp(tiers) => write!(
+                f,
+                "{}",
+                to_string_pretty(
+                    &tiers
+                        .iter()
+                        .map(|tier| json!({
+                            "base_fee": tier.base_fee,
+                            "amount_relative_fee_pct": tier.amount_relative_fee_pct,
+                            "time_relative_fee_pct": tier.time_relative_fee_pct,
+                            "minimum_locktime": tier.minimum_locktime,
+                            "required_confirms": tier.required_confirms,
+                            "min_size": tier.min_size,
+                            "max_size": tier.max_size,
+                        }))
+                        .collect::<Vec<_>>()
+                )
+                .unwrap()
+            ),
         }
     }
 }
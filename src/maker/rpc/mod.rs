@@ -1,5 +1,5 @@
 mod messages;
 mod server;
 
-pub use messages::{RpcMsgReq, RpcMsgResp};
-pub(crate) use server::start_rpc_server;
+pub use messages::{FidelityEvent, RpcMsgReq, RpcMsgResp};
+pub(crate) use server::{bind_rpc_server, start_rpc_server};
@@ -1,24 +1,49 @@
 use std::{
-    io::ErrorKind,
-    net::{TcpListener, TcpStream},
+    net::TcpListener,
     sync::{atomic::Ordering::Relaxed, Arc},
-    thread::sleep,
     time::Duration,
 };
 
-use bitcoin::{Address, Amount};
+use bitcoin::{absolute::LockTime, Address, Amount};
 
 use super::messages::RpcMsgReq;
 use crate::{
-    maker::{error::MakerError, rpc::messages::RpcMsgResp, Maker},
-    utill::{get_tor_hostname, read_message, send_message, ConnectionType, HEART_BEAT_INTERVAL},
-    wallet::{Destination, SendAmount},
+    maker::{error::MakerError, rpc::messages::RpcMsgResp, server::add_fidelity_bond, Maker},
+    net::{run_server, NetConfig, Response, Service},
+    utill::{get_tor_hostname, ConnectionType},
+    wallet::{Destination, SendAmount, SignedDelegation, WalletError},
 };
 use std::str::FromStr;
 
-fn handle_request(maker: &Arc<Maker>, socket: &mut TcpStream) -> Result<(), MakerError> {
-    let msg_bytes = read_message(socket)?;
-    let rpc_request: RpcMsgReq = serde_cbor::from_slice(&msg_bytes)?;
+/// Serves `maker-cli` requests, one request per connection.
+struct RpcService {
+    maker: Arc<Maker>,
+}
+
+impl Service for RpcService {
+    type Connection = ();
+
+    fn handle_message(&self, _: &mut (), message: Vec<u8>) -> Response {
+        // Do not cause hard error if a rpc request fails. Send the error back to client.
+        let resp = serde_cbor::from_slice::<RpcMsgReq>(&message)
+            .map_err(MakerError::from)
+            .and_then(|rpc_request| handle_request(&self.maker, rpc_request))
+            .unwrap_or_else(|e| {
+                log::error!("Error processing RPC Request: {:?}", e);
+                RpcMsgResp::ServerError(format!("{:?}", e))
+            });
+
+        match Response::reply(&resp) {
+            Ok(response) => response.and_close(),
+            Err(e) => {
+                log::error!("Error sending RPC response {:?}", e);
+                Response::close()
+            }
+        }
+    }
+}
+
+fn handle_request(maker: &Arc<Maker>, rpc_request: RpcMsgReq) -> Result<RpcMsgResp, MakerError> {
     log::info!("RPC request received: {:?}", rpc_request);
 
     let resp = match rpc_request {
@@ -132,6 +157,52 @@ fn handle_request(maker: &Arc<Maker>, socket: &mut TcpStream) -> Result<(), Make
 
             RpcMsgResp::ListBonds(list)
         }
+        RpcMsgReq::CreateFidelity { amount, timelock } => RpcMsgResp::FidelityFunded(
+            add_fidelity_bond(maker, Amount::from_sat(amount), timelock)?,
+        ),
+        RpcMsgReq::ImportColdFidelity {
+            outpoint,
+            pubkey,
+            locktime,
+        } => {
+            let lock_time = LockTime::from_height(locktime).map_err(WalletError::Locktime)?;
+            let mut wallet = maker.get_wallet().write()?;
+            let index = wallet.import_cold_fidelity(outpoint, pubkey, lock_time)?;
+            wallet.save_to_disk()?;
+            RpcMsgResp::ColdFidelityImported(index)
+        }
+        RpcMsgReq::ExportDelegationRequest { index, path } => {
+            maker
+                .get_wallet()
+                .read()?
+                .get_delegation_request(index)?
+                .write_to_disk(&path)?;
+            RpcMsgResp::DelegationRequestResp(path)
+        }
+        RpcMsgReq::ImportDelegation(path) => {
+            let signed = SignedDelegation::read_from_disk(&path)?;
+            let mut wallet = maker.get_wallet().write()?;
+            let index = wallet.import_delegation(&signed)?;
+            wallet.save_to_disk()?;
+            // The proof with the cold bond is posted at the next directory refresh.
+            RpcMsgResp::DelegationImported(index)
+        }
+        RpcMsgReq::FidelityProof => RpcMsgResp::FidelityProofResp(
+            maker
+                .fidelity_proof
+                .read()?
+                .as_ref()
+                .map(|proof| proof.to_hex()),
+        ),
+        RpcMsgReq::FidelityEvents => {
+            RpcMsgResp::FidelityEventsResp(maker.fidelity_events.lock()?.iter().cloned().collect())
+        }
+        RpcMsgReq::SwapHistory { from, to } => {
+            RpcMsgResp::SwapHistoryResp(maker.swap_ledger.history(from, to)?)
+        }
+        RpcMsgReq::Earnings { from, to } => {
+            RpcMsgResp::EarningsResp(maker.swap_ledger.earnings(from, to)?)
+        }
         RpcMsgReq::SyncWallet => {
             log::info!("Initializing wallet sync");
             if let Err(e) = maker.get_wallet().write()?.sync() {
@@ -141,56 +212,96 @@ fn handle_request(maker: &Arc<Maker>, socket: &mut TcpStream) -> Result<(), Make
                 RpcMsgResp::Pong
             }
         }
-    };
+        RpcMsgReq::ExportSwapBackup(path) => {
+            maker.get_wallet().read()?.export_swap_backup(&path)?;
+            RpcMsgResp::SwapBackupResp(path)
+        }
+        RpcMsgReq::RegisterWatchtower(address) => {
+            match maker.get_wallet().read()?.register_with_watchtower(
+                &address,
+                maker.config.connection_type,
+                maker.config.socks_port,
+            ) {
+                Ok(count) => RpcMsgResp::WatchtowerRegistered(count),
+                Err(e) => RpcMsgResp::ServerError(format!("{:?}", e)),
+            }
+        }
+        RpcMsgReq::SetFees {
+            tier,
+            base_fee,
+            amount_relative_fee_pct,
+            time_relative_fee_pct,
+            minimum_locktime,
+            required_confirms,
+            min_size,
+            max_size,
+        } => match maker.get_offer_tier(tier) {
+            Ok(mut fee_policy) => {
+                if let Some(base_fee) = base_fee {
+                    fee_policy.base_fee = base_fee;
+                }
+                if let Some(pct) = amount_relative_fee_pct {
+                    fee_policy.amount_relative_fee_pct = pct;
+                }
+                if let Some(pct) = time_relative_fee_pct {
+                    fee_policy.time_relative_fee_pct = pct;
+                }
+                if let Some(locktime) = minimum_locktime {
+                    fee_policy.minimum_locktime = locktime;
+                }
+                if let Some(confirms) = required_confirms {
+                    fee_policy.required_confirms = confirms;
+                }
+                if let Some(amount) = min_size {
+                    fee_policy.min_size = amount;
+                }
+                if let Some(amount) = max_size {
+                    fee_policy.max_size = amount;
+                }
 
-    if let Err(e) = send_message(socket, &resp) {
-        log::error!("Error sending RPC response {:?}", e);
-    }
+                match fee_policy.validate() {
+                    Ok(()) => RpcMsgResp::OfferTiersResp(maker.set_offer_tier(tier, fee_policy)?),
+                    Err(e) => RpcMsgResp::ServerError(e.to_string()),
+                }
+            }
+            Err(e) => RpcMsgResp::ServerError(format!("{:?}", e)),
+        },
+    };
 
-    Ok(())
+    Ok(resp)
 }
 
-pub(crate) fn start_rpc_server(maker: Arc<Maker>) -> Result<(), MakerError> {
-    let rpc_port = maker.config.rpc_port;
-    let rpc_socket = format!("127.0.0.1:{}", rpc_port);
-    let listener = Arc::new(TcpListener::bind(&rpc_socket)?);
+/// Binds the RPC socket. Done before the server is reported as set up, so `maker-cli` can connect right away.
+pub(crate) fn bind_rpc_server(maker: &Maker) -> Result<TcpListener, MakerError> {
+    let rpc_socket = format!("127.0.0.1:{}", maker.config.rpc_port);
+    let listener = TcpListener::bind(&rpc_socket)?;
     log::info!(
         "[{}] RPC socket binding successful at {}",
         maker.config.network_port,
         rpc_socket
     );
+    Ok(listener)
+}
 
-    listener.set_nonblocking(true)?;
-
-    while !maker.shutdown.load(Relaxed) {
-        match listener.accept() {
-            Ok((mut stream, addr)) => {
-                log::info!("Got RPC request from: {}", addr);
-                stream.set_read_timeout(Some(Duration::from_secs(20)))?;
-                stream.set_write_timeout(Some(Duration::from_secs(20)))?;
-                // Do not cause hard error if a rpc request fails
-                if let Err(e) = handle_request(&maker, &mut stream) {
-                    log::error!("Error processing RPC Request: {:?}", e);
-                    // Send the error back to client.
-                    if let Err(e) =
-                        send_message(&mut stream, &RpcMsgResp::ServerError(format!("{:?}", e)))
-                    {
-                        log::error!("Error sending RPC response {:?}", e);
-                    };
-                }
-            }
-
-            Err(e) => {
-                if e.kind() == ErrorKind::WouldBlock {
-                    // do nothing
-                } else {
-                    log::error!("Error accepting RPC connection: {:?}", e);
-                }
-            }
-        }
-
-        sleep(HEART_BEAT_INTERVAL);
-    }
+/// Serves RPC requests on `listener`, until the maker shuts down.
+pub(crate) fn start_rpc_server(maker: Arc<Maker>, listener: TcpListener) -> Result<(), MakerError> {
+    let config = NetConfig {
+        max_connections: 10,
+        idle_timeout: Duration::from_secs(20),
+        worker_threads: 1,
+        ..NetConfig::default()
+    };
+    let service = Arc::new(RpcService {
+        maker: maker.clone(),
+    });
+    run_server(
+        "Maker RPC",
+        listener,
+        service,
+        config,
+        Vec::new(),
+        &maker.shutdown,
+    )?;
 
     Ok(())
 }
@@ -1,23 +1,22 @@
 //! The Coinswap Maker Server.
 //!
 //! This module includes all server side code for the coinswap maker.
-//! The server maintains the thread pool for P2P Connection, Watchtower, Bitcoin Backend and RPC Client Request.
+//! The server runs an event loop serving P2P connections, along with periodic tasks for the Watchtower and Bitcoin Backend checks, and an RPC server for client requests.
 //! The server listens at two port 6102 for P2P, and 6103 for RPC Client request.
 
 use std::{
-    io::ErrorKind,
     net::{Ipv4Addr, TcpListener, TcpStream},
     path::Path,
     process::Child,
     sync::{
-        atomic::{AtomicBool, Ordering::Relaxed},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
+        Arc, Mutex,
     },
-    thread::{self, sleep},
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
 };
 
-use bitcoin::{absolute::LockTime, Amount};
+use bitcoin::{absolute::LockTime, Amount, Txid};
 use bitcoind::bitcoincore_rpc::RpcApi;
 
 #[cfg(feature = "tor")]
@@ -29,15 +28,20 @@ use crate::{
     error::NetError,
     maker::{
         api::{
-            check_for_broadcasted_contracts, check_for_idle_states,
+            check_for_broadcasted_contracts, check_for_idle_states, check_for_reorged_funding,
             restore_broadcasted_contracts_on_reboot, ConnectionState,
         },
         handlers::handle_message,
-        rpc::start_rpc_server,
+        rpc::{bind_rpc_server, start_rpc_server, FidelityEvent},
     },
-    protocol::messages::{DnsMetadata, DnsRequest, TakerToMakerMessage},
-    utill::{get_tor_hostname, read_message, send_message, ConnectionType, HEART_BEAT_INTERVAL},
-    wallet::WalletError,
+    net::{run_server, DropReason, NetConfig, PeriodicTask, Response, Service},
+    protocol::messages::{
+        DnsMetadata, DnsRequest, ErrorCode, MakerToTakerMessage, TakerToMakerMessage,
+    },
+    utill::{
+        get_tor_hostname, send_message, unix_time, ConnectionType, HEART_BEAT_INTERVAL, NET_TIMEOUT,
+    },
+    wallet::{FidelityBond, PendingFidelity, WalletError},
 };
 
 #[cfg(feature = "tor")]
@@ -48,24 +52,41 @@ use crate::maker::error::MakerError;
 // Default values for Maker configurations
 pub(crate) const DIRECTORY_SERVERS_REFRESH_INTERVAL_SECS: u64 = 60 * 15; // 15 minutes
 
-/// Fetches the Maker and DNS address, and sends maker address to the DNS server.
+/// Interval of the wallet sync and swap liquidity check.
+const WALLET_SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval of the confirmation checks of a new fidelity bond.
+const FIDELITY_CONF_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval of the fidelity bond renewal checks.
+const FIDELITY_RENEWAL_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// With ZMQ, how long the contract watcher waits for a notification before checking again.
+/// Also bounds how long the watcher delays shutdown.
+const ZMQ_WAIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// With ZMQ, interval of the RPC lookups of the contracts, in case a notification got lost.
+const CONTRACT_RPC_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Fetches the Maker and DNS addresses, and sets up the fidelity bond.
 /// Depending upon ConnectionType and test/prod environment, different maker address and DNS addresses are returned.
-/// Return the Maker address and an optional tor thread handle.
+/// Return an optional tor thread handle, and the tasks posting the maker address to the DNS servers and renewing
+/// the fidelity bonds.
 ///
 /// Tor thread is spawned only if ConnectionType=TOR and --feature=tor is enabled.
 /// Errors if ConncetionType=TOR but, the tor feature is not enabled.
-fn network_bootstrap(maker: Arc<Maker>) -> Result<Option<Child>, MakerError> {
+fn network_bootstrap(maker: Arc<Maker>) -> Result<(Option<Child>, Vec<PeriodicTask>), MakerError> {
     let maker_port = maker.config.network_port;
-    let (maker_address, dns_address, tor_handle) = match maker.config.connection_type {
+    let (maker_address, dns_addresses, tor_handle) = match maker.config.connection_type {
         ConnectionType::CLEARNET => {
             let maker_address = format!("127.0.0.1:{}", maker_port);
-            let dns_address = if cfg!(feature = "integration-test") {
-                format!("127.0.0.1:{}", 8080)
+            let dns_addresses = if cfg!(feature = "integration-test") {
+                vec![format!("127.0.0.1:{}", 8080)]
             } else {
-                maker.config.directory_server_address.clone()
+                maker.config.directory_server_addresses.clone()
             };
 
-            (maker_address, dns_address, None)
+            (maker_address, dns_addresses, None)
         }
         #[cfg(feature = "tor")]
         ConnectionType::TOR => {
@@ -90,7 +111,7 @@ fn network_bootstrap(maker: Arc<Maker>) -> Result<Option<Child>, MakerError> {
 
             let tor_handle = Some(crate::tor::spawn_tor(
                 maker_socks_port,
-                maker_port,
+                &[maker_port],
                 tor_dir.to_str().unwrap().to_owned(),
             )?);
 
@@ -117,15 +138,15 @@ fn network_bootstrap(maker: Arc<Maker>) -> Result<Option<Child>, MakerError> {
             let maker_hostname = get_tor_hostname(&tor_dir)?;
             let maker_address = format!("{}:{}", maker_hostname, maker.config.network_port);
 
-            let dns_address = if cfg!(feature = "integration-test") {
+            let dns_addresses = if cfg!(feature = "integration-test") {
                 let dns_tor_dir = Path::new("/tmp/coinswap/dns/tor");
                 let dns_hostname = get_tor_hostname(dns_tor_dir)?;
-                format!("{}:{}", dns_hostname, 8080)
+                vec![format!("{}:{}", dns_hostname, 8080)]
             } else {
-                maker.config.directory_server_address.clone()
+                maker.config.directory_server_addresses.clone()
             };
 
-            (maker_address, dns_address, tor_handle)
+            (maker_address, dns_addresses, tor_handle)
         }
     };
 
@@ -141,115 +162,186 @@ fn network_bootstrap(maker: Arc<Maker>) -> Result<Option<Child>, MakerError> {
         maker.get_wallet().read()?.store.offer_maxsize
     );
 
-    let proof = maker
-        .highest_fidelity_proof
-        .read()?
-        .as_ref()
-        .unwrap()
-        .clone();
-
-    let dns_metadata = DnsMetadata {
-        url: maker_address.clone(),
-        proof,
-    };
-
-    let request = DnsRequest::Post {
-        metadata: dns_metadata,
-    };
-
-    thread::spawn(move || {
-        let trigger_count = DIRECTORY_SERVERS_REFRESH_INTERVAL_SECS / HEART_BEAT_INTERVAL.as_secs();
-        let mut i = 0;
-
-        while !maker.shutdown.load(Relaxed) {
-            if i >= trigger_count || i == 0 {
-                let stream = match maker.config.connection_type {
-                    ConnectionType::CLEARNET => TcpStream::connect(&dns_address),
-                    #[cfg(feature = "tor")]
-                    ConnectionType::TOR => Socks5Stream::connect(
-                        format!("127.0.0.1:{}", maker.config.socks_port),
-                        dns_address.as_str(),
-                    )
-                    .map(|stream| stream.into_inner()),
-                };
-
-                log::info!(
-                    "[{}] Connecting to DNS: {}",
-                    maker.config.network_port,
-                    dns_address
-                );
+    // Post the address and a fresh offer again every refresh interval, retry every heart beat if no directory
+    // got it. Directories missing a post learn the address from their federated peers in the meantime.
+    // The offer is valid for longer than the refresh interval, so the directories always serve a valid one.
+    let mut tasks = Vec::new();
+    let (poster_maker, poster_address, poster_dns_addresses) =
+        (maker.clone(), maker_address.clone(), dns_addresses.clone());
+    tasks.push(
+        PeriodicTask::new("DNS address poster", move || {
+            // Pick up the bonds added or expired since the last post.
+            match refresh_fidelity_proof(&poster_maker, &poster_address) {
+                Ok(true) => {}
+                Ok(false) => log::warn!(
+                    "[{}] No live fidelity bond left, advertising the last proof",
+                    maker_port
+                ),
+                Err(e) => log::warn!(
+                    "[{}] Failed to refresh the fidelity proof: {:?}",
+                    maker_port,
+                    e
+                ),
+            }
+            if post_to_directories(&poster_maker, &poster_address, &poster_dns_addresses) {
+                Duration::from_secs(DIRECTORY_SERVERS_REFRESH_INTERVAL_SECS)
+            } else {
+                HEART_BEAT_INTERVAL
+            }
+        })
+        .on_own_thread(),
+    );
 
-                let mut stream = match stream {
-                    Ok(s) => s,
+    // Keep the bonds alive, and post the new proof right away when it changes.
+    if maker.config.fidelity_renewal_blocks != 0 {
+        tasks.push(
+            maker_task(&maker, "Fidelity renewal", move |maker| {
+                match renew_fidelity_bonds(maker, &maker_address) {
+                    Ok(true) => {
+                        post_to_directories(maker, &maker_address, &dns_addresses);
+                    }
+                    Ok(false) => {}
                     Err(e) => {
-                        log::warn!(
-                            "[{}] TCP connection error with directory, reattempting: {}",
-                            maker_port,
-                            e
-                        );
-                        thread::sleep(HEART_BEAT_INTERVAL);
-                        continue;
+                        maker.record_fidelity_event(FidelityEvent::Failed(format!("{:?}", e)))?
                     }
-                };
-
-                if let Err(e) = send_message(&mut stream, &request) {
-                    log::warn!(
-                        "[{}] Failed to send our address to directory, reattempting: {}",
-                        maker_port,
-                        e
-                    );
-                    thread::sleep(HEART_BEAT_INTERVAL);
-                    continue;
                 }
+                Ok(FIDELITY_RENEWAL_CHECK_INTERVAL)
+            })
+            .on_own_thread(),
+        );
+    }
 
+    Ok((tor_handle, tasks))
+}
+
+/// Posts the maker address and a fresh offer to all the directory servers. Returns whether any of them got it.
+fn post_to_directories(maker: &Maker, maker_address: &str, dns_addresses: &[String]) -> bool {
+    let maker_port = maker.config.network_port;
+    let offer = match maker.create_offer() {
+        Ok(offer) => offer,
+        Err(e) => {
+            log::warn!(
+                "[{}] Failed to create offer for directory, reattempting: {:?}",
+                maker_port,
+                e
+            );
+            return false;
+        }
+    };
+    let request = DnsRequest::Post {
+        metadata: DnsMetadata {
+            url: maker_address.to_string(),
+            proof: offer.fidelity.clone(),
+            offer: Some(offer),
+        },
+    };
+
+    let mut posted = 0;
+    for dns_address in dns_addresses {
+        log::info!("[{}] Connecting to DNS: {}", maker_port, dns_address);
+        match post_to_directory(maker, dns_address, &request) {
+            Ok(()) => {
                 log::info!(
                     "[{}] Successfully sent our address to DNS at {}",
                     maker_port,
                     dns_address
                 );
-                // Reset counter when success
-                i = 0;
+                posted += 1;
+            }
+            Err(e) => {
+                log::warn!(
+                    "[{}] Failed to send our address to directory {}: {:?}",
+                    maker_port,
+                    dns_address,
+                    e
+                );
             }
-            i += 1;
-            thread::sleep(HEART_BEAT_INTERVAL);
         }
-    });
+    }
 
-    Ok(tor_handle)
+    if posted == 0 {
+        log::warn!("[{}] No directory reachable, reattempting", maker_port);
+    }
+    posted > 0
 }
 
-/// Checks if the wallet already has fidelity bonds. if not, create the first fidelity bond.
-fn setup_fidelity_bond(maker: &Arc<Maker>, maker_address: &str) -> Result<(), MakerError> {
-    let highest_index = maker.get_wallet().read()?.get_highest_fidelity_index()?;
-    if let Some(i) = highest_index {
-        let wallet_read = maker.get_wallet().read()?;
-        let (bond, _, _) = wallet_read.get_fidelity_bonds().get(&i).unwrap();
-
-        let current_height = wallet_read
-            .rpc
-            .get_block_count()
-            .map_err(WalletError::Rpc)? as u32;
-
-        let highest_proof = maker
-            .get_wallet()
-            .read()?
-            .generate_fidelity_proof(i, maker_address)?;
+/// Sends a post request to one directory server.
+fn post_to_directory(
+    maker: &Maker,
+    dns_address: &str,
+    request: &DnsRequest,
+) -> Result<(), NetError> {
+    let mut stream = match maker.config.connection_type {
+        ConnectionType::CLEARNET => TcpStream::connect(dns_address)?,
+        #[cfg(feature = "tor")]
+        ConnectionType::TOR => Socks5Stream::connect(
+            format!("127.0.0.1:{}", maker.config.socks_port),
+            dns_address,
+        )?
+        .into_inner(),
+    };
+    send_message(&mut stream, request)
+}
 
+/// Regenerates the advertised fidelity proof from all the live bonds of the wallet.
+///
+/// Returns false, leaving the proof unchanged, if the wallet has no live bond.
+fn refresh_fidelity_proof(maker: &Maker, maker_address: &str) -> Result<bool, MakerError> {
+    let wallet_read = maker.get_wallet().read()?;
+    let (proof, value) = match wallet_read.generate_combined_fidelity_proof(maker_address)? {
+        Some(proof) => proof,
+        None => return Ok(false),
+    };
+    let current_height = wallet_read
+        .rpc
+        .get_block_count()
+        .map_err(WalletError::Rpc)? as u32;
+    drop(wallet_read);
+
+    let mut advertised = maker.fidelity_proof.write()?;
+    if advertised.as_ref() != Some(&proof) {
+        for bond in proof.bonds() {
+            log::info!(
+                "Advertised bond at outpoint {} | Amount {:?} sats | Remaining Timelock for expiry : {:?} Blocks",
+                bond.outpoint,
+                bond.amount.to_sat(),
+                bond.lock_time
+                    .to_consensus_u32()
+                    .saturating_sub(current_height)
+            );
+        }
         log::info!(
-            "Highest bond at outpoint {} |  index {} | Amount {:?} sats | Remaining Timelock for expiry : {:?} Blocks | Current Bond Value : {:?} sats",
-            highest_proof.bond.outpoint,
-            i,
-            bond.amount.to_sat(),
-            bond.lock_time.to_consensus_u32() - current_height,
-            wallet_read.calculate_bond_value(i)?.to_sat()
+            "Advertising {} fidelity bonds | Aggregated Bond Value : {:?} sats",
+            proof.bonds().count(),
+            value.to_sat()
         );
-        log::info!("Bond amount : {:?}", bond.amount.to_sat());
-        // TODO: work remainig
-        // log::info!("")
+        *advertised = Some(proof);
+    }
+    Ok(true)
+}
 
-        let mut proof = maker.highest_fidelity_proof.write()?;
-        *proof = Some(highest_proof);
-    } else {
+/// Checks if the wallet already has fidelity bonds. if not, create the first fidelity bond.
+///
+/// A bond broadcasted before the last shutdown is waited for again: right away if it's the only bond, else in the
+/// background.
+fn setup_fidelity_bond(maker: &Arc<Maker>, maker_address: &str) -> Result<(), MakerError> {
+    let pending = maker.get_wallet().read()?.get_pending_fidelity();
+    let has_proof = refresh_fidelity_proof(maker, maker_address)?;
+    if let Some(pending) = pending {
+        log::info!(
+            "[{}] Resuming the wait for the fidelity transaction {}",
+            maker.config.network_port,
+            pending.txid
+        );
+        if has_proof {
+            spawn_fidelity_confirmation(maker, pending)?;
+        } else {
+            store_fidelity_on_confirmation(maker, pending)?;
+            refresh_fidelity_proof(maker, maker_address)?;
+        }
+        return Ok(());
+    }
+    if !has_proof {
         // xxxxx
         // No bond in the wallet. Lets attempt to create one.
         let amount = Amount::from_sat(maker.config.fidelity_amount);
@@ -320,12 +412,11 @@ fn setup_fidelity_bond(maker: &Arc<Maker>, maker_address: &str) -> Result<(), Ma
                         "[{}] Successfully created fidelity bond",
                         maker.config.network_port
                     );
-                    let highest_proof = maker
+                    let proof = maker
                         .get_wallet()
                         .read()?
                         .generate_fidelity_proof(i, maker_address)?;
-                    let mut proof = maker.highest_fidelity_proof.write()?;
-                    *proof = Some(highest_proof);
+                    *maker.fidelity_proof.write()? = Some(proof);
 
                     // sync and save the wallet data to disk
                     maker.get_wallet().write()?.sync_no_fail();
@@ -338,91 +429,361 @@ fn setup_fidelity_bond(maker: &Arc<Maker>, maker_address: &str) -> Result<(), Ma
     Ok(())
 }
 
-/// Keep checking if the Bitcoin Core RPC connection is live. Sets the global `accepting_client` flag as per RPC connection status.
+/// Locks `amount` in an additional fidelity bond, maturing `timelock` blocks from now, while the maker runs.
 ///
-/// This will not block. Once Core RPC connection is live, accepting_client will set as `true` again.
-fn check_connection_with_core(
-    maker: Arc<Maker>,
-    accepting_clients: Arc<AtomicBool>,
+/// Returns the bond txid once broadcasted. The wallet is not locked while the bond confirms: a background thread
+/// stores it then, and it is advertised with the other bonds from the next directory post.
+pub(crate) fn add_fidelity_bond(
+    maker: &Arc<Maker>,
+    amount: Amount,
+    timelock: u32,
+) -> Result<Txid, MakerError> {
+    if maker.bond_pending.swap(true, Relaxed) {
+        return Err(MakerError::General(
+            "A fidelity bond is already waiting for confirmation",
+        ));
+    }
+
+    let broadcast = || -> Result<PendingFidelity, MakerError> {
+        let mut wallet = maker.get_wallet().write()?;
+        let current_height = wallet.rpc.get_block_count().map_err(WalletError::Rpc)? as u32;
+        let locktime =
+            LockTime::from_height(current_height + timelock).map_err(WalletError::Locktime)?;
+        Ok(wallet.broadcast_fidelity(amount, locktime)?)
+    };
+    let pending = match broadcast() {
+        Ok(pending) => pending,
+        Err(e) => {
+            maker.bond_pending.store(false, Relaxed);
+            return Err(e);
+        }
+    };
+    let txid = pending.txid;
+    log::info!(
+        "[{}] Fidelity bond of {} sats broadcasted in {}, waiting for confirmation",
+        maker.config.network_port,
+        amount.to_sat(),
+        txid
+    );
+
+    if let Err(e) = spawn_fidelity_confirmation(maker, pending) {
+        maker.bond_pending.store(false, Relaxed);
+        return Err(e);
+    }
+    Ok(txid)
+}
+
+/// Waits for a pending fidelity bond on a background thread, see [`store_fidelity_on_confirmation`].
+fn spawn_fidelity_confirmation(
+    maker: &Arc<Maker>,
+    pending: PendingFidelity,
 ) -> Result<(), MakerError> {
-    let mut rpc_ping_success = false;
-    let mut i = 0;
+    maker.bond_pending.store(true, Relaxed);
+    let maker_clone = maker.clone();
+    let handle = thread::Builder::new()
+        .name("Fidelity Confirmation Thread".to_string())
+        .spawn(move || {
+            if let Err(e) = store_fidelity_on_confirmation(&maker_clone, pending) {
+                log::error!("Failed to store the new fidelity bond: {:?}", e);
+            }
+            maker_clone.bond_pending.store(false, Relaxed);
+        })?;
+    maker.thread_pool.add_thread(handle);
+    Ok(())
+}
+
+/// Waits for a broadcasted fidelity bond to confirm, and stores it in the wallet.
+///
+/// Gives up on shutdown. The bond stays pending in the wallet file, and the wait resumes on the next start.
+fn store_fidelity_on_confirmation(
+    maker: &Maker,
+    pending: PendingFidelity,
+) -> Result<(), MakerError> {
+    let mut last_check: Option<Instant> = None;
     while !maker.shutdown.load(Relaxed) {
-        // If connection is disrupted keep trying at heart_beat_interval (3 sec).
-        // If connection is live, keep tring at rpc_ping_interval (60 sec).
-        let trigger_count = match rpc_ping_success {
-            true => RPC_PING_INTERVAL.as_secs() / HEART_BEAT_INTERVAL.as_secs(),
-            false => 1,
-        };
+        if last_check.is_some_and(|checked| checked.elapsed() < FIDELITY_CONF_CHECK_INTERVAL) {
+            thread::sleep(HEART_BEAT_INTERVAL);
+            continue;
+        }
+        last_check = Some(Instant::now());
+        let conf_height = maker
+            .get_wallet()
+            .read()?
+            .get_fidelity_conf_height(&pending.txid)?;
+        if let Some(conf_height) = conf_height {
+            let mut wallet = maker.get_wallet().write()?;
+            let txid = pending.txid;
+            let index = wallet.store_fidelity(pending, conf_height)?;
+            wallet.save_to_disk()?;
+            drop(wallet);
+            return maker.record_fidelity_event(FidelityEvent::BondConfirmed { index, txid });
+        }
+    }
+    log::info!(
+        "[{}] Fidelity transaction {} still unconfirmed, resuming on next start",
+        maker.config.network_port,
+        pending.txid
+    );
+    Ok(())
+}
 
-        if i >= trigger_count || i == 0 {
-            if let Err(e) = maker.wallet.read()?.rpc.get_blockchain_info() {
-                log::error!(
-                    "[{}] RPC Connection failed. Reattempting {}",
-                    maker.config.network_port,
-                    e
-                );
-                rpc_ping_success = false;
+/// Runs one check of the fidelity bond renewal. Returns whether the advertised proof changed.
+///
+/// - The matured bonds are redeemed to the wallet, except the cold ones.
+/// - The certificates expiring within a difficulty period are renewed.
+/// - When all the live bonds mature within `fidelity_renewal_blocks`, a replacement bond of the total amount of
+///   the hot ones is created, locked for `fidelity_timelock`. It is stored once confirmed, and advertised from the
+///   next check.
+///
+/// Failed steps are reported, and attempted again at the next check.
+fn renew_fidelity_bonds(maker: &Arc<Maker>, maker_address: &str) -> Result<bool, MakerError> {
+    let current_height = maker
+        .get_wallet()
+        .read()?
+        .rpc
+        .get_block_count()
+        .map_err(WalletError::Rpc)? as u32;
+    let remaining_blocks = |bond: &FidelityBond| match bond.lock_time {
+        LockTime::Blocks(height) => height.to_consensus_u32().saturating_sub(current_height),
+        LockTime::Seconds(time) => {
+            (time.to_consensus_u32() as u64).saturating_sub(unix_time()) as u32 / (10 * 60)
+        }
+    };
+    let live_bonds = maker
+        .get_wallet()
+        .read()?
+        .get_fidelity_bonds()
+        .iter()
+        .filter(|(_, (_, _, is_spent))| !is_spent)
+        .map(|(index, (bond, _, _))| (*index, bond.clone()))
+        .collect::<Vec<_>>();
+    // Cold bonds are redeemed offline, and replaced with the maker's own funds.
+    let cold_indexes = maker.get_wallet().read()?.get_cold_fidelity_indexes()?;
+
+    for (index, bond) in live_bonds
+        .iter()
+        .filter(|(index, bond)| remaining_blocks(bond) == 0 && !cold_indexes.contains(index))
+    {
+        let redeemed = maker.get_wallet().write()?.redeem_fidelity(*index);
+        match redeemed {
+            Ok(txid) => {
+                maker.get_wallet().read()?.save_to_disk()?;
+                maker.record_fidelity_event(FidelityEvent::Redeemed {
+                    index: *index,
+                    txid,
+                })?;
+            }
+            Err(e) => maker.record_fidelity_event(FidelityEvent::Failed(format!(
+                "Redeeming bond {}: {:?}",
+                bond.outpoint, e
+            )))?,
+        }
+    }
+
+    let renewed = maker.get_wallet().write()?.renew_fidelity_certs()?;
+    if !renewed.is_empty() {
+        maker.get_wallet().read()?.save_to_disk()?;
+    }
+    for bond in renewed {
+        maker.record_fidelity_event(FidelityEvent::CertRenewed {
+            outpoint: bond.outpoint,
+            cert_expiry: bond.cert_expiry,
+        })?;
+    }
+
+    let renewal_blocks = maker.config.fidelity_renewal_blocks;
+    let expiring_amount = live_bonds
+        .iter()
+        .filter(|(_, bond)| remaining_blocks(bond) > 0)
+        .try_fold(Amount::ZERO, |total, (index, bond)| {
+            // A bond maturing after the renewal window keeps the maker listed.
+            if remaining_blocks(bond) > renewal_blocks {
+                None
+            } else if cold_indexes.contains(index) {
+                Some(total)
             } else {
-                if !rpc_ping_success {
-                    log::info!(
-                        "[{}] Bitcoin Core RPC connection is back online.",
-                        maker.config.network_port
-                    );
-                }
-                rpc_ping_success = true;
+                Some(total + bond.amount)
+            }
+        });
+    if let Some(expiring_amount) = expiring_amount {
+        if renewal_blocks >= maker.config.fidelity_timelock {
+            log::warn!(
+                "[{}] fidelity_renewal_blocks must be below fidelity_timelock, not renewing",
+                maker.config.network_port
+            );
+        } else if !maker.bond_pending.load(Relaxed) {
+            let amount = if expiring_amount == Amount::ZERO {
+                Amount::from_sat(maker.config.fidelity_amount)
+            } else {
+                expiring_amount
+            };
+            match add_fidelity_bond(maker, amount, maker.config.fidelity_timelock) {
+                Ok(txid) => maker
+                    .record_fidelity_event(FidelityEvent::ReplacementBroadcast { txid, amount })?,
+                Err(e) => maker.record_fidelity_event(FidelityEvent::Failed(format!(
+                    "Creating a replacement bond: {:?}",
+                    e
+                )))?,
             }
-            accepting_clients.store(rpc_ping_success, Relaxed);
-            i = 0;
         }
-        i += 1;
-        thread::sleep(HEART_BEAT_INTERVAL);
     }
 
-    Ok(())
+    let previous = maker.fidelity_proof.read()?.clone();
+    refresh_fidelity_proof(maker, maker_address)?;
+    Ok(*maker.fidelity_proof.read()? != previous)
 }
 
-/// Handle a single client connection.
-fn handle_client(maker: Arc<Maker>, stream: &mut TcpStream) -> Result<(), MakerError> {
-    stream.set_nonblocking(false)?; // Block this thread until message is read.
+/// Checks if the Bitcoin Core RPC connection is live. Sets the global `accepting_client` flag as per RPC connection status.
+///
+/// Returns the delay until the next check. If connection is disrupted keep trying at heart_beat_interval (3 sec).
+/// If connection is live, keep trying at rpc_ping_interval.
+fn check_connection_with_core(
+    maker: &Maker,
+    accepting_clients: &AtomicBool,
+) -> Result<Duration, MakerError> {
+    let rpc_ping_success = match maker.wallet.read()?.rpc.get_blockchain_info() {
+        Err(e) => {
+            log::error!(
+                "[{}] RPC Connection failed. Reattempting {}",
+                maker.config.network_port,
+                e
+            );
+            false
+        }
+        Ok(_) => true,
+    };
+    if rpc_ping_success && !accepting_clients.load(Relaxed) {
+        log::info!(
+            "[{}] Bitcoin Core RPC connection is up.",
+            maker.config.network_port
+        );
+    }
+    accepting_clients.store(rpc_ping_success, Relaxed);
 
-    let mut connection_state = ConnectionState::default();
+    Ok(match rpc_ping_success {
+        true => RPC_PING_INTERVAL,
+        false => HEART_BEAT_INTERVAL,
+    })
+}
 
-    while !maker.shutdown.load(Relaxed) {
-        let mut taker_msg_bytes = Vec::new();
-        match read_message(stream) {
-            Ok(b) => taker_msg_bytes = b,
-            Err(e) => {
-                if let NetError::IO(e) = e {
-                    if e.kind() == ErrorKind::UnexpectedEof {
-                        log::info!("[{}] Connection ended.", maker.config.network_port);
-                        break;
-                    } else {
-                        // For any other errors, report them
-                        log::error!("[{}] Net Error: {}", maker.config.network_port, e);
-                        continue;
-                    }
-                }
+/// Syncs the wallet and checks that we have enough swap liquidity.
+/// Raise warning otherwise, the offer then doesn't allow any swap.
+fn sync_and_check_liquidity(maker: &Maker) -> Result<(), MakerError> {
+    maker.get_wallet().write()?.sync_no_fail();
+    let offer_max_size = maker.get_wallet().read()?.available_offer_maxsize();
+    if offer_max_size <= maker.config.min_swap_amount {
+        log::warn!("[WARN!] Swaps are disabled due to low balance, Please put more funds in the wallet | Min required {} sats | Available {} sats", maker.config.min_swap_amount, offer_max_size);
+    } else {
+        log::info!(
+            "Total available balance for swaps: {} sats | Listening for incoming swap requests",
+            offer_max_size
+        );
+    }
+    Ok(())
+}
+
+/// Wraps a fallible maker job into a periodic task. An error in the job shuts down the maker.
+fn maker_task(
+    maker: &Arc<Maker>,
+    name: &'static str,
+    job: impl Fn(&Arc<Maker>) -> Result<Duration, MakerError> + Send + Sync + 'static,
+) -> PeriodicTask {
+    let maker = maker.clone();
+    PeriodicTask::new(name, move || match job(&maker) {
+        Ok(delay) => delay,
+        Err(e) => {
+            log::error!("[{}] {} failed: {:?}", maker.config.network_port, name, e);
+            maker.shutdown.store(true, Relaxed);
+            HEART_BEAT_INTERVAL
+        }
+    })
+}
+
+/// The contract watcher. With a ZMQ [TxWatcher](crate::watcher::TxWatcher) it runs right after every
+/// notification, with RPC lookups only every [`CONTRACT_RPC_CHECK_INTERVAL`]. Without ZMQ it polls the RPC every heart beat.
+/// The incoming funding txs of ongoing swaps are checked for reorgs along the way.
+/// It waits on the notifications most of the time, so it runs on its own thread.
+fn contract_watcher_task(maker: &Arc<Maker>) -> PeriodicTask {
+    let last_event = AtomicU64::new(0);
+    let last_rpc_check = Mutex::new(Instant::now());
+    maker_task(maker, "Contract watcher", move |maker| {
+        let watcher = match &maker.tx_watcher {
+            Some(watcher) => watcher,
+            None => {
+                check_for_broadcasted_contracts(maker, true)?;
+                check_for_reorged_funding(maker)?;
+                return Ok(HEART_BEAT_INTERVAL);
             }
+        };
+        let events = watcher.wait(last_event.load(Relaxed), ZMQ_WAIT_TIMEOUT);
+        let notified = last_event.swap(events, Relaxed) != events;
+
+        let rpc_fallback = {
+            let mut last_rpc_check = last_rpc_check.lock()?;
+            let due = last_rpc_check.elapsed() >= CONTRACT_RPC_CHECK_INTERVAL;
+            if due {
+                *last_rpc_check = Instant::now();
+            }
+            due
+        };
+        check_for_broadcasted_contracts(maker, rpc_fallback)?;
+        // Reorgs only happen with new blocks.
+        if notified || rpc_fallback {
+            check_for_reorged_funding(maker)?;
         }
+        Ok(Duration::ZERO)
+    })
+    .on_own_thread()
+}
 
-        let taker_msg: TakerToMakerMessage = serde_cbor::from_slice(&taker_msg_bytes)?;
-        log::info!("[{}] <=== {}", maker.config.network_port, taker_msg);
+/// Serves the taker P2P protocol. Each taker connection carries its own [`ConnectionState`].
+struct MakerService {
+    maker: Arc<Maker>,
+    /// Global server switch, to turn the p2p network off while Bitcoin Core is unreachable.
+    accepting_clients: Arc<AtomicBool>,
+}
 
-        let reply = handle_message(&maker, &mut connection_state, taker_msg);
+impl Service for MakerService {
+    type Connection = ConnectionState;
 
-        match reply {
-            Ok(reply) => {
-                if let Some(message) = reply {
-                    log::info!("[{}] ===> {} ", maker.config.network_port, message);
-                    if let Err(e) = send_message(stream, &message) {
-                        log::error!("Closing due to IO error in sending message: {:?}", e);
-                        continue;
+    fn handle_message(&self, connection_state: &mut ConnectionState, message: Vec<u8>) -> Response {
+        let maker = &self.maker;
+        if !self.accepting_clients.load(Relaxed) {
+            log::warn!(
+                "[{}] Temporary failure in Bitcoin Core RPC. Dropping client.",
+                maker.config.network_port
+            );
+            return Response::close();
+        }
+
+        let taker_msg: TakerToMakerMessage = match serde_cbor::from_slice(&message) {
+            Ok(msg) => msg,
+            Err(e) => {
+                log::error!(
+                    "[{}] Invalid message from taker: {:?}. Closing connection.",
+                    maker.config.network_port,
+                    e
+                );
+                return Response::close();
+            }
+        };
+        log::info!("[{}] <=== {}", maker.config.network_port, taker_msg);
+
+        match handle_message(maker, connection_state, taker_msg) {
+            Ok(Some(message)) => {
+                log::info!("[{}] ===> {} ", maker.config.network_port, message);
+                match Response::reply(&message) {
+                    Ok(response) if matches!(message, MakerToTakerMessage::Error { .. }) => {
+                        response.and_close()
+                    }
+                    Ok(response) => response,
+                    Err(e) => {
+                        log::error!("Closing due to error in sending message: {:?}", e);
+                        Response::close()
                     }
-                } else {
-                    continue;
                 }
             }
+            Ok(None) => Response::none(),
             Err(err) => {
                 match &err {
                     // Shutdown server if special behavior is set
@@ -433,6 +794,16 @@ fn handle_client(maker: Arc<Maker>, stream: &mut TcpStream) -> Result<(), MakerE
                             sp
                         );
                         maker.shutdown.store(true, Relaxed);
+                        return Response::close();
+                    }
+                    MakerError::TakerAborted { code, reason } => {
+                        log::warn!(
+                            "[{}] Taker aborted with {:?}: {}",
+                            maker.config.network_port,
+                            code,
+                            reason
+                        );
+                        return Response::close();
                     }
                     e => {
                         log::error!(
@@ -442,25 +813,47 @@ fn handle_client(maker: Arc<Maker>, stream: &mut TcpStream) -> Result<(), MakerE
                         );
                     }
                 }
-                return Err(err);
+                // Tell the taker why, so it can decide between retrying, picking another maker or marking us bad.
+                let message = MakerToTakerMessage::Error {
+                    code: err.code(),
+                    reason: err.reason(),
+                };
+                log::info!("[{}] ===> {} ", maker.config.network_port, message);
+                match Response::reply(&message) {
+                    Ok(response) => response.and_close(),
+                    Err(_) => Response::close(),
+                }
             }
         }
     }
 
-    Ok(())
+    fn rejection(&self, reason: DropReason) -> Option<Vec<u8>> {
+        let (code, reason) = match reason {
+            DropReason::TooManyConnections => {
+                (ErrorCode::TooManyConnections, "Too many connections")
+            }
+            DropReason::RateLimited => (ErrorCode::RateLimited, "Too many messages"),
+            DropReason::MessageTooLarge => (ErrorCode::MessageTooLarge, "Message too large"),
+        };
+        serde_cbor::to_vec(&MakerToTakerMessage::Error {
+            code,
+            reason: reason.to_string(),
+        })
+        .ok()
+    }
 }
 
 /// Starts the main Maker Server process.
 ///
 /// This function initializes the Maker server by setting up network connections,
-/// configuring the wallet with fidelity bond, and spawning necessary threads for:
+/// configuring the wallet with fidelity bond, and runs the event loop serving peer-to-peer (P2P) clients
+/// along with the periodic tasks for:
 /// - Checking Bitcoin Core connections.
 /// - Monitoring idle client connections.
 /// - Watching for broadcasted contract transactions.
-/// - Running an RPC server for interacting with `maker-cli`.
+/// - Syncing the wallet and posting the maker address to the DNS.
 ///
-/// It also handles incoming peer-to-peer (P2P) client connections in a loop, where
-/// each connection spawns a dedicated handler thread.
+/// An RPC server for interacting with `maker-cli` runs on its own thread.
 ///
 /// The server continues to run until a shutdown signal is detected, at which point
 /// it performs cleanup tasks, such as saving wallet data and terminating active Tor sessions.
@@ -469,7 +862,7 @@ pub fn start_maker_server(maker: Arc<Maker>) -> Result<(), MakerError> {
     // Initialize network connections.
 
     // Setup the wallet with fidelity bond.
-    let _tor_thread = network_bootstrap(maker.clone())?;
+    let (_tor_thread, directory_tasks) = network_bootstrap(maker.clone())?;
 
     let port = maker.config.network_port;
     let network = maker.get_wallet().read()?.store.network;
@@ -489,84 +882,70 @@ pub fn start_maker_server(maker: Arc<Maker>) -> Result<(), MakerError> {
         maker.config.min_swap_amount
     );
     log::info!("[{}] Maximum Swap Size {} SATS", port, offer_max_size);
+    for (i, tier) in maker.offer_tiers.read()?.iter().enumerate() {
+        log::info!("[{}] Offer Tier {} : {:?}", port, i, tier);
+    }
 
     let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, maker.config.network_port))
         .map_err(NetError::IO)?;
-    listener.set_nonblocking(true)?; // Needed to not block a thread waiting for incoming connection.
 
-    // Global server Mutex, to switch on/off p2p network.
+    // Global server switch, to turn on/off p2p network.
     let accepting_clients = Arc::new(AtomicBool::new(false));
+    check_connection_with_core(&maker, &accepting_clients)?;
+
+    let mut tasks = Vec::new();
 
     if !maker.shutdown.load(Relaxed) {
-        // 1. Bitcoin Core Connection checker thread.
+        // 1. Bitcoin Core Connection checker.
         // Ensures that Bitcoin Core connection is live.
         // If not, it will block p2p connections until Core works again.
-        let maker_clone = maker.clone();
         let acc_client_clone = accepting_clients.clone();
-        let conn_check_thread = thread::Builder::new()
-            .name("Bitcoin Core Connection Checker Thread".to_string())
-            .spawn(move || {
-                log::info!("[{}] Spawning Bitcoin Core connection checker thread", port);
-                if let Err(e) = check_connection_with_core(maker_clone.clone(), acc_client_clone) {
-                    log::error!("[{}] Bitcoin Core connection check failed: {:?}", port, e);
-                    maker_clone.shutdown.store(true, Relaxed);
-                }
-            })?;
-        maker.thread_pool.add_thread(conn_check_thread);
-
-        // 2. Idle Client connection checker thread.
-        // This threads check idelness of peer in live swaps.
+        tasks.push(maker_task(
+            &maker,
+            "Bitcoin Core connection checker",
+            move |maker| check_connection_with_core(maker, &acc_client_clone),
+        ));
+
+        // 2. Idle Client connection checker.
+        // This checks idelness of peer in live swaps.
         // And takes recovery measure if the peer seems to have disappeared in middlle of a swap.
-        let maker_clone = maker.clone();
-        let idle_conn_check_thread = thread::Builder::new()
-            .name("Idle Client Checker Thread".to_string())
-            .spawn(move || {
-                log::info!(
-                    "[{}] Spawning Client connection status checker thread",
-                    port
-                );
-                if let Err(e) = check_for_idle_states(maker_clone.clone()) {
-                    log::error!("Failed checking client's idle state {:?}", e);
-                    maker_clone.shutdown.store(true, Relaxed);
-                }
-            })?;
-        maker.thread_pool.add_thread(idle_conn_check_thread);
+        tasks.push(maker_task(&maker, "Idle client checker", |maker| {
+            check_for_idle_states(maker).map(|_| HEART_BEAT_INTERVAL)
+        }));
 
-        // 3. Watchtower thread.
-        // This thread checks for broadcasted contract transactions, which usually means violation of the protocol.
+        // 3. Watchtower.
+        // This checks for broadcasted contract transactions, which usually means violation of the protocol.
         // When contract transaction detected in mempool it will attempt recovery.
         // This can get triggered even when contracts of adjacent hops are published. Implying the whole swap route is disrupted.
-        let maker_clone = maker.clone();
-        let contract_watcher_thread = thread::Builder::new()
-            .name("Contract Watcher Thread".to_string())
-            .spawn(move || {
-                log::info!("[{}] Spawning contract-watcher thread", port);
-                if let Err(e) = check_for_broadcasted_contracts(maker_clone.clone()) {
-                    maker_clone.shutdown.store(true, Relaxed);
-                    log::error!("Failed checking broadcasted contracts {:?}", e);
-                }
-            })?;
-        maker.thread_pool.add_thread(contract_watcher_thread);
+        tasks.push(contract_watcher_task(&maker));
+
+        // 4. Wallet sync and swap liquidity check.
+        tasks.push(
+            maker_task(&maker, "Wallet sync", |maker| {
+                sync_and_check_liquidity(maker).map(|_| WALLET_SYNC_INTERVAL)
+            })
+            .on_own_thread(),
+        );
 
-        // 4: The RPC server thread.
+        // 5. Posting our address to the DNS, and renewing the fidelity bonds.
+        tasks.extend(directory_tasks);
+
+        // 6: The RPC server thread.
         // User for responding back to `maker-cli` apps.
+        let rpc_listener = bind_rpc_server(&maker)?;
         let maker_clone = maker.clone();
         let rpc_thread = thread::Builder::new()
             .name("RPC Thread".to_string())
             .spawn(move || {
                 log::info!("[{}] Spawning RPC server thread", port);
-                match start_rpc_server(maker_clone.clone()) {
-                    Ok(_) => (),
-                    Err(e) => {
-                        log::error!("Failed starting rpc server {:?}", e);
-                        maker_clone.shutdown.store(true, Relaxed);
-                    }
+                if let Err(e) = start_rpc_server(maker_clone.clone(), rpc_listener) {
+                    log::error!("Failed starting rpc server {:?}", e);
+                    maker_clone.shutdown.store(true, Relaxed);
                 }
             })?;
 
         maker.thread_pool.add_thread(rpc_thread);
 
-        sleep(HEART_BEAT_INTERVAL); // wait for 1 beat, to complete spawns of all the threads.
         maker.is_setup_complete.store(true, Relaxed);
         log::info!("[{}] Server Setup completed!! Use maker-cli to operate the server and the internal wallet.", maker.config.network_port);
     }
@@ -579,66 +958,21 @@ pub fn start_maker_server(maker: Arc<Maker>) -> Result<(), MakerError> {
         restore_broadcasted_contracts_on_reboot(maker_clone.clone())?;
     }
 
-    let mut sync_counter = 0;
-    // The P2P Client connection loop.
-    // Each client connection will spawn a new handler thread, which is added back in the global thread_pool.
-    // This loop beats at `maker.config.heart_beat_interval_secs`
-    while !maker.shutdown.load(Relaxed) {
-        // Check every 30 secs that we have enough swap liquidity.
-        // Raise warning otherwise and don't listen for swap requests.
-        if sync_counter >= 10 || sync_counter == 0 {
-            maker.get_wallet().write()?.sync_no_fail();
-            let offer_max_size = maker.get_wallet().read()?.store.offer_maxsize;
-            if offer_max_size <= maker.config.min_swap_amount {
-                log::warn!("[WARN!] Swaps are disabled due to low balance, Please put more funds in the wallet | Min required {} sats | Available {} sats", maker.config.min_swap_amount, offer_max_size);
-            } else {
-                log::info!(
-                    "Total available balance for swaps: {} sats | Listening for incoming swap requests",
-                    offer_max_size
-                );
-            }
-            sync_counter = 0;
-        }
-        sync_counter += 1;
-
-        let maker = maker.clone(); // This clone is needed to avoid moving the Arc<Maker> in each iterations.
-
-        // Block client connections if accepting_client=false
-        if !accepting_clients.load(Relaxed) {
-            log::warn!(
-                "[{}] Temporary failure in Bitcoin Core RPC.",
-                maker.config.network_port
-            );
-            sleep(HEART_BEAT_INTERVAL);
-            continue;
-        }
-
-        match listener.accept() {
-            Ok((mut stream, _)) => {
-                log::info!(
-                    "[{}] Received incoming connection",
-                    maker.config.network_port
-                );
-
-                if let Err(e) = handle_client(maker, &mut stream) {
-                    log::error!("[{}] Error Handling client request {:?}", port, e);
-                }
-            }
-
-            Err(e) => {
-                if e.kind() == ErrorKind::WouldBlock {
-                    // Do nothing
-                } else {
-                    log::error!(
-                        "[{}] Error accepting incoming connection: {:?}",
-                        maker.config.network_port,
-                        e
-                    );
-                }
-            }
-        };
-
-        sleep(HEART_BEAT_INTERVAL);
+    // The P2P event loop, serving all client connections. Blocks until shutdown.
+    let config = NetConfig {
+        max_connections: maker.config.max_connections,
+        max_message_size: maker.config.max_message_size,
+        max_messages_per_minute: maker.config.max_messages_per_minute,
+        idle_timeout: NET_TIMEOUT,
+        worker_threads: maker.config.worker_threads,
+    };
+    let service = Arc::new(MakerService {
+        maker: maker.clone(),
+        accepting_clients,
+    });
+    if let Err(e) = run_server("Maker", listener, service, config, tasks, &maker.shutdown) {
+        log::error!("[{}] Maker server failed: {:?}", port, e);
+        maker.shutdown.store(true, Relaxed);
     }
 
     log::info!("[{}] Maker is shutting down.", port);
@@ -3,34 +3,46 @@
 //! Handles market-related logic where Makers post their offers. Also provides functions to synchronize
 //! maker addresses from directory servers, post maker addresses to directory servers,
 
-use bitcoin::{transaction::ParseOutPointError, OutPoint};
+use bitcoin::{transaction::ParseOutPointError, Amount, OutPoint};
 use bitcoind::bitcoincore_rpc::{self, Client, RpcApi};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    market::rpc::start_rpc_server_thread,
-    protocol::messages::DnsRequest,
+    market::{
+        explorer::start_explorer_thread,
+        probe::{probe_makers, PROBE_INTERVAL},
+        rpc::start_rpc_server_thread,
+    },
+    net::{run_server, NetConfig, PeriodicTask, Response, Service},
+    protocol::messages::{
+        DirectoryEntry, DirectorySyncEntry, DnsMetadata, DnsRequest, FidelityProof, MakerHealth,
+        Offer,
+    },
     utill::{
-        get_dns_dir, parse_field, parse_toml, read_message, send_message, verify_fidelity_checks,
-        ConnectionType, HEART_BEAT_INTERVAL,
+        get_dns_dir, parse_field, parse_list, parse_toml, read_message, send_message, unix_time,
+        verify_fidelity_checks, ConnectionType, NET_TIMEOUT,
     },
-    wallet::{RPCConfig, WalletError},
+    wallet::{aggregate_bond_values, calculate_bond_value_at, RPCConfig, WalletError},
 };
 
 #[cfg(feature = "tor")]
 use crate::utill::{get_tor_hostname, monitor_log_for_completion};
+#[cfg(feature = "tor")]
+use socks::Socks5Stream;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     convert::TryFrom,
     fs::{self, File},
     io::Write,
     net::{Ipv4Addr, TcpListener, TcpStream},
     path::{Path, PathBuf},
+    str::FromStr,
     sync::{
-        atomic::{AtomicBool, Ordering::Relaxed},
+        atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
         Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
     },
-    thread::{self, sleep},
+    thread,
     time::{Duration, Instant},
 };
 
@@ -62,6 +74,10 @@ pub enum DirectoryServerError {
     ///
     /// This can occur in case of incomplete shutdown or other ways a file can corrupt.
     AddressFileCorrupted(String),
+    /// Error indicating a listed maker failed its liveness probe.
+    ///
+    /// The maker answered with an unexpected message, an error, or an invalid offer.
+    ProbeFailed(String),
 }
 
 impl From<WalletError> for DirectoryServerError {
@@ -121,14 +137,100 @@ pub struct DirectoryServer {
     pub network_port: u16,
     /// Socks port
     pub socks_port: u16,
+    /// Listening port of the read-only HTTP/JSON market explorer. The explorer is disabled if 0.
+    pub http_port: u16,
     /// Connection type
     pub connection_type: ConnectionType,
+    /// Addresses of the peer directory servers the maker address book is synced with.
+    pub peers: Vec<String>,
+    /// Rules a maker's fidelity bond must pass to be listed.
+    pub admission: AdmissionRules,
     /// Directory server data directory
     pub data_dir: PathBuf,
     /// Shutdown flag to stop the directory server
     pub shutdown: AtomicBool,
     /// A store of all the received maker addresses indexed by fidelity bond outpoints.
     pub addresses: Arc<RwLock<HashMap<OutPoint, (String, Instant)>>>,
+    /// The last signed offers posted by the makers, indexed by fidelity bond outpoints.
+    pub(crate) offers: Arc<RwLock<HashMap<OutPoint, Offer>>>,
+    /// The verified fidelity proofs of the makers, shared with the peer directory servers.
+    pub(crate) proofs: Arc<RwLock<HashMap<OutPoint, FidelityProof>>>,
+    /// Primary bond outpoint of the maker holding each bond, its primary bond included. A bond backs one maker only.
+    pub(crate) bond_owners: RwLock<HashMap<OutPoint, OutPoint>>,
+    /// Makers only learnt from peer directory servers, or from disk, and not posted here since the start.
+    pub(crate) synced: RwLock<HashSet<OutPoint>>,
+    /// Result of the last liveness probe of each listed maker.
+    pub(crate) health: Arc<RwLock<HashMap<OutPoint, MakerHealth>>>,
+    /// Fidelity outpoints and maker addresses banned by the operator.
+    pub(crate) banned: RwLock<BanList>,
+    /// Time the directory server was started.
+    pub(crate) start_time: Instant,
+    /// Request counters, reported by the `Stats` RPC.
+    pub(crate) counters: RequestCounters,
+    /// The last refused makers, newest last.
+    pub(crate) rejections: RwLock<VecDeque<Rejection>>,
+}
+
+/// Admission rules of the directory server, against a market of cheap fake makers.
+///
+/// They are checked on every post and peer sync, on top of the fidelity proof checks. The bonds are looked up with
+/// `gettxout` on the directory's own node, so bonds of other networks, or already spent, are refused.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdmissionRules {
+    /// Minimum fidelity bond value, as computed by the wallet's bond value function, aggregated over all the bonds.
+    pub min_bond_value: Amount,
+    /// Minimum confirmations of each fidelity bond.
+    pub min_confirmations: u32,
+    /// Minimum number of blocks before each fidelity bond's locktime matures.
+    pub min_remaining_locktime: u32,
+}
+
+impl Default for AdmissionRules {
+    fn default() -> Self {
+        Self {
+            min_bond_value: Amount::ZERO,
+            min_confirmations: 1,
+            min_remaining_locktime: 144, // Approx 1 day of blocks
+        }
+    }
+}
+
+/// A maker refused by the directory server, reported by the `ListRejections` RPC.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rejection {
+    /// Unix time of the rejection, in seconds.
+    pub time: u64,
+    /// Address of the maker.
+    pub address: String,
+    /// Fidelity bond outpoint of the maker.
+    pub outpoint: OutPoint,
+    /// Why the maker was refused.
+    pub reason: String,
+}
+
+/// Number of rejections kept in memory.
+const MAX_REJECTIONS: usize = 100;
+
+/// Fidelity outpoints and maker addresses the directory server refuses to list.
+#[derive(Debug, Default)]
+pub(crate) struct BanList {
+    pub(crate) outpoints: HashSet<OutPoint>,
+    pub(crate) addresses: HashSet<String>,
+}
+
+impl BanList {
+    pub(crate) fn is_banned(&self, outpoint: &OutPoint, address: &str) -> bool {
+        self.outpoints.contains(outpoint) || self.addresses.contains(address)
+    }
+}
+
+/// Number of requests handled since the directory server started.
+#[derive(Debug, Default)]
+pub(crate) struct RequestCounters {
+    pub(crate) posts: AtomicU64,
+    pub(crate) rejected_posts: AtomicU64,
+    pub(crate) gets: AtomicU64,
+    pub(crate) syncs: AtomicU64,
 }
 
 impl Default for DirectoryServer {
@@ -137,6 +239,7 @@ impl Default for DirectoryServer {
             rpc_port: 4321,
             network_port: 8080,
             socks_port: 19060,
+            http_port: 0,
             connection_type: {
                 #[cfg(feature = "tor")]
                 {
@@ -147,9 +250,20 @@ impl Default for DirectoryServer {
                     ConnectionType::CLEARNET
                 }
             },
+            peers: Vec::new(),
+            admission: AdmissionRules::default(),
             data_dir: get_dns_dir(),
             shutdown: AtomicBool::new(false),
             addresses: Arc::new(RwLock::new(HashMap::new())),
+            offers: Arc::new(RwLock::new(HashMap::new())),
+            proofs: Arc::new(RwLock::new(HashMap::new())),
+            bond_owners: RwLock::new(HashMap::new()),
+            synced: RwLock::new(HashSet::new()),
+            health: Arc::new(RwLock::new(HashMap::new())),
+            banned: RwLock::new(BanList::default()),
+            start_time: Instant::now(),
+            counters: RequestCounters::default(),
+            rejections: RwLock::new(VecDeque::new()),
         }
     }
 }
@@ -210,27 +324,292 @@ impl DirectoryServer {
             config_file.write_all(content.as_bytes())?;
         }
 
-        let addresses = Arc::new(RwLock::new(HashMap::new()));
+        // Makers loaded from disk keep the time they last posted, if their proof was stored. The others are listed
+        // until they miss their next post.
+        let stored_proofs = load_proof_file(&data_dir.join(PROOF_FILE))?;
+        let now = unix_time();
+        let mut proofs = HashMap::new();
+        let mut addresses = HashMap::new();
+        for (outpoint, address) in load_address_file(&data_dir.join(ADDRESS_FILE))? {
+            let seen = match stored_proofs.get(&outpoint) {
+                Some(stored) => {
+                    let age = Duration::from_secs(now.saturating_sub(stored.seen));
+                    match Instant::now().checked_sub(age) {
+                        Some(seen) if age <= ADDRESS_TTL => {
+                            proofs.insert(outpoint, stored.proof.clone());
+                            seen
+                        }
+                        _ => continue,
+                    }
+                }
+                None => Instant::now(),
+            };
+            addresses.insert(outpoint, (address, seen));
+        }
+        let synced = addresses.keys().copied().collect::<HashSet<_>>();
+        let bond_owners = proofs
+            .iter()
+            .flat_map(|(primary, proof)| proof.bonds().map(move |bond| (bond.outpoint, *primary)))
+            .collect::<HashMap<_, _>>();
+        log::info!(
+            "Loaded {} maker addresses and {} proofs from disk",
+            addresses.len(),
+            proofs.len()
+        );
+        let banned = load_ban_file(&data_dir.join(BAN_FILE))?;
         let default_dns = Self::default();
 
         Ok(DirectoryServer {
             rpc_port: parse_field(config_map.get("rpc_port"), default_dns.rpc_port),
             network_port: parse_field(config_map.get("port"), default_dns.network_port),
             socks_port: parse_field(config_map.get("socks_port"), default_dns.socks_port),
+            http_port: parse_field(config_map.get("http_port"), default_dns.http_port),
             data_dir,
             shutdown: AtomicBool::new(false),
             connection_type: parse_field(
                 config_map.get("connection_type"),
                 default_dns.connection_type,
             ),
-            addresses,
+            peers: parse_list(config_map.get("peers"), default_dns.peers),
+            admission: AdmissionRules {
+                min_bond_value: Amount::from_sat(parse_field(
+                    config_map.get("min_bond_value"),
+                    default_dns.admission.min_bond_value.to_sat(),
+                )),
+                min_confirmations: parse_field(
+                    config_map.get("min_bond_confirmations"),
+                    default_dns.admission.min_confirmations,
+                ),
+                min_remaining_locktime: parse_field(
+                    config_map.get("min_remaining_locktime"),
+                    default_dns.admission.min_remaining_locktime,
+                ),
+            },
+            addresses: Arc::new(RwLock::new(addresses)),
+            offers: Arc::new(RwLock::new(HashMap::new())),
+            proofs: Arc::new(RwLock::new(proofs)),
+            bond_owners: RwLock::new(bond_owners),
+            synced: RwLock::new(synced),
+            health: Arc::new(RwLock::new(HashMap::new())),
+            banned: RwLock::new(banned),
+            start_time: Instant::now(),
+            counters: RequestCounters::default(),
+            rejections: RwLock::new(VecDeque::new()),
         })
     }
 
+    /// Writes the maker address book, the makers' proofs and the ban list to the data directory.
+    pub fn flush_to_disk(&self) -> Result<(), DirectoryServerError> {
+        let addresses = self.addresses.read()?;
+        let address_lines = addresses
+            .iter()
+            .map(|(outpoint, (address, _))| format!("{},{}\n", outpoint, address))
+            .collect::<String>();
+        write_atomically(&self.data_dir.join(ADDRESS_FILE), address_lines.as_bytes())?;
+
+        let now = unix_time();
+        let proofs = self
+            .proofs
+            .read()?
+            .iter()
+            .filter_map(|(outpoint, proof)| {
+                let (_, seen) = addresses.get(outpoint)?;
+                let stored = StoredProof {
+                    proof: proof.clone(),
+                    seen: now.saturating_sub(seen.elapsed().as_secs()),
+                };
+                Some((*outpoint, stored))
+            })
+            .collect::<Vec<_>>();
+        drop(addresses);
+        write_atomically(
+            &self.data_dir.join(PROOF_FILE),
+            &serde_cbor::to_vec(&proofs)?,
+        )?;
+
+        let banned = self.banned.read()?;
+        let bans = banned
+            .outpoints
+            .iter()
+            .map(|outpoint| format!("{}\n", outpoint))
+            .chain(
+                banned
+                    .addresses
+                    .iter()
+                    .map(|address| format!("{}\n", address)),
+            )
+            .collect::<String>();
+        write_atomically(&self.data_dir.join(BAN_FILE), bans.as_bytes())?;
+        Ok(())
+    }
+
+    /// Removes a maker from the address book, along with its proof and offer. Returns its address, if it was listed.
+    ///
+    /// An evicted maker is listed again on its next post, unless banned.
+    pub fn evict(&self, outpoint: &OutPoint) -> Result<Option<String>, DirectoryServerError> {
+        let removed = self.addresses.write()?.remove(outpoint);
+        self.proofs.write()?.remove(outpoint);
+        self.bond_owners
+            .write()?
+            .retain(|_, primary| primary != outpoint);
+        self.synced.write()?.remove(outpoint);
+        self.offers.write()?.remove(outpoint);
+        self.health.write()?.remove(outpoint);
+        Ok(removed.map(|(address, _)| address))
+    }
+
+    /// Records a refused maker, and removes it if it was listed.
+    fn reject(&self, metadata: &DnsMetadata, reason: String) -> Result<(), DirectoryServerError> {
+        log::warn!("Refused maker {}: {}", metadata.url, reason);
+        self.counters.rejected_posts.fetch_add(1, Relaxed);
+        let outpoint = metadata.proof.bond.outpoint;
+        if self.evict(&outpoint)?.is_some() {
+            log::warn!("Removed listed maker {}", metadata.url);
+        }
+
+        let mut rejections = self.rejections.write()?;
+        rejections.push_back(Rejection {
+            time: unix_time(),
+            address: metadata.url.clone(),
+            outpoint,
+            reason,
+        });
+        if rejections.len() > MAX_REJECTIONS {
+            rejections.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Opens a connection to a maker or peer directory, through Tor if configured.
+    pub(crate) fn connect_to(&self, address: &str) -> std::io::Result<TcpStream> {
+        let stream = match self.connection_type {
+            ConnectionType::CLEARNET => TcpStream::connect(address)?,
+            #[cfg(feature = "tor")]
+            ConnectionType::TOR => {
+                Socks5Stream::connect(format!("127.0.0.1:{}", self.socks_port), address)?
+                    .into_inner()
+            }
+        };
+        Ok(stream)
+    }
+
+    /// Bans a fidelity outpoint, and evicts the maker using it. Returns the number of evicted makers.
+    pub fn ban_outpoint(&self, outpoint: OutPoint) -> Result<usize, DirectoryServerError> {
+        self.banned.write()?.outpoints.insert(outpoint);
+        // The outpoint can back a maker as its primary bond, or as an additional one.
+        let listed = self
+            .proofs
+            .read()?
+            .iter()
+            .filter(|(_, proof)| proof.bonds().any(|bond| bond.outpoint == outpoint))
+            .map(|(primary, _)| *primary)
+            .chain(std::iter::once(outpoint))
+            .collect::<HashSet<_>>();
+        let mut evicted = 0;
+        for primary in listed {
+            if self.evict(&primary)?.is_some() {
+                evicted += 1;
+            }
+        }
+        Ok(evicted)
+    }
+
+    /// Bans a maker address, and evicts the makers using it. Returns the number of evicted makers.
+    pub fn ban_address(&self, address: String) -> Result<usize, DirectoryServerError> {
+        let outpoints = self
+            .addresses
+            .read()?
+            .iter()
+            .filter(|(_, (url, _))| *url == address)
+            .map(|(outpoint, _)| *outpoint)
+            .collect::<Vec<_>>();
+        self.banned.write()?.addresses.insert(address);
+        for outpoint in &outpoints {
+            self.evict(outpoint)?;
+        }
+        Ok(outpoints.len())
+    }
+
+    /// Indexes all the bonds of a maker's proof to its primary bond. Returns why the proof is refused, if it is.
+    ///
+    /// A proof can't list a bond twice, nor a bond backing a maker listed at another address: a bond is only valued
+    /// once in the market.
+    pub(crate) fn claim_bonds(
+        &self,
+        address: &str,
+        proof: &FidelityProof,
+    ) -> Result<Option<String>, DirectoryServerError> {
+        let primary = proof.bond.outpoint;
+        let mut bond_owners = self.bond_owners.write()?;
+        let addresses = self.addresses.read()?;
+        let mut outpoints = HashSet::new();
+        for bond in proof.bonds() {
+            if !outpoints.insert(bond.outpoint) {
+                return Ok(Some(format!("Bond {} is listed twice", bond.outpoint)));
+            }
+            // The bond may be a listed maker's primary bond, with no proof stored for it.
+            let owner = bond_owners.get(&bond.outpoint).unwrap_or(&bond.outpoint);
+            if *owner == primary {
+                continue;
+            }
+            if let Some((owner_address, _)) = addresses.get(owner) {
+                if owner_address != address {
+                    return Ok(Some(format!(
+                        "Bond {} already backs the maker {}",
+                        bond.outpoint, owner_address
+                    )));
+                }
+            }
+        }
+        drop(addresses);
+
+        // The bonds the maker no longer lists are freed.
+        bond_owners.retain(|_, owner| *owner != primary);
+        bond_owners.extend(outpoints.into_iter().map(|outpoint| (outpoint, primary)));
+        Ok(None)
+    }
+
     /// Updates the in-memory address map. If entry already exists, updates the value. If new entry, inserts the value.
     pub fn updated_address_map(
         &self,
         metadata: (String, OutPoint),
+    ) -> Result<(), DirectoryServerError> {
+        let outpoint = metadata.1;
+        self.update_address_map_at(metadata, Instant::now())?;
+        self.synced.write()?.remove(&outpoint);
+        Ok(())
+    }
+
+    /// Time a maker relayed by a peer, as last posted `age` ago, is listed with. `None` if already expired.
+    ///
+    /// The peer's claim is capped at the address expiry, and it never makes a maker posting here look younger than
+    /// our own observation of it.
+    fn synced_seen(
+        &self,
+        outpoint: &OutPoint,
+        age: Duration,
+    ) -> Result<Option<Instant>, DirectoryServerError> {
+        let seen = match Instant::now().checked_sub(age) {
+            Some(seen) if age <= ADDRESS_TTL => seen,
+            _ => return Ok(None),
+        };
+        if self.synced.read()?.contains(outpoint) {
+            return Ok(Some(seen));
+        }
+        Ok(Some(match self.addresses.read()?.get(outpoint) {
+            Some((_, local)) => seen.min(*local),
+            None => seen,
+        }))
+    }
+
+    /// Updates the in-memory address map with an entry last posted at `seen`.
+    ///
+    /// Entries synced from peer directories keep the time the maker posted to them. An entry never replaces one
+    /// seen more recently, so makers gone from the whole federation still expire.
+    pub(crate) fn update_address_map_at(
+        &self,
+        metadata: (String, OutPoint),
+        seen: Instant,
     ) -> Result<(), DirectoryServerError> {
         let mut write_lock = self.addresses.write()?;
         // Check if the value exists with a different key
@@ -240,7 +619,13 @@ impl DirectoryServer {
                 .find_map(|(k, v)| if v.0 == metadata.0 { Some(*k) } else { None })
         {
             // Update the fielity for the existing address
-            if existing_key != metadata.1 {
+            if existing_key != metadata.1 && write_lock[&existing_key].1 > seen {
+                log::debug!(
+                    "Ignoring outdated fidelity {} for address: {}",
+                    metadata.1,
+                    metadata.0
+                );
+            } else if existing_key != metadata.1 {
                 log::info!(
                     "Fidelity update detected for address: {} | Old fidelity {} | New fidelity {}",
                     metadata.0,
@@ -248,7 +633,7 @@ impl DirectoryServer {
                     metadata.1
                 );
                 write_lock.remove(&existing_key);
-                write_lock.insert(metadata.1, (metadata.0, Instant::now()));
+                write_lock.insert(metadata.1, (metadata.0, seen));
             } else {
                 log::info!(
                     "Maker data already exist for {} | restarted counter",
@@ -256,13 +641,19 @@ impl DirectoryServer {
                 );
                 write_lock
                     .entry(metadata.1)
-                    .and_modify(|(_, instant)| *instant = Instant::now());
+                    .and_modify(|(_, instant)| *instant = (*instant).max(seen));
             }
         } else if write_lock.contains_key(&metadata.1) {
             // Update the address for the existing fidelity
-            if write_lock[&metadata.1].0 != metadata.0 {
+            if write_lock[&metadata.1].1 > seen {
+                log::debug!(
+                    "Ignoring outdated address {} for fidelity: {}",
+                    metadata.0,
+                    metadata.1
+                );
+            } else if write_lock[&metadata.1].0 != metadata.0 {
                 let old_addr = write_lock
-                    .insert(metadata.1, (metadata.0.clone(), Instant::now()))
+                    .insert(metadata.1, (metadata.0.clone(), seen))
                     .expect("value expected");
                 log::info!(
                     "Address updated for fidelity: {} | old address {:?} | new address {}",
@@ -277,11 +668,11 @@ impl DirectoryServer {
                 );
                 write_lock
                     .entry(metadata.1)
-                    .and_modify(|(_, instant)| *instant = Instant::now());
+                    .and_modify(|(_, instant)| *instant = (*instant).max(seen));
             }
         } else {
             // Add a new entry if both fidelity and address are new
-            write_lock.insert(metadata.1, (metadata.0.clone(), Instant::now()));
+            write_lock.insert(metadata.1, (metadata.0.clone(), seen));
             log::info!(
                 "Added new maker info: Fidelity {} | Address {}",
                 metadata.1,
@@ -292,6 +683,80 @@ impl DirectoryServer {
     }
 }
 
+/// File of the maker address book in the data directory, one `outpoint,address` per line.
+const ADDRESS_FILE: &str = "addresses.dat";
+
+/// File of the ban list in the data directory, one fidelity outpoint or maker address per line.
+const BAN_FILE: &str = "banned.dat";
+
+/// File of the verified proofs of the listed makers in the data directory, CBOR encoded.
+const PROOF_FILE: &str = "proofs.cbor";
+
+/// A verified fidelity proof, as stored in the [PROOF_FILE].
+#[derive(Serialize, Deserialize)]
+struct StoredProof {
+    proof: FidelityProof,
+    /// Unix time the maker last posted, here or to a peer directory server.
+    seen: u64,
+}
+
+/// Reads the stored proofs. A missing file holds no proofs.
+fn load_proof_file(path: &Path) -> Result<HashMap<OutPoint, StoredProof>, DirectoryServerError> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let proofs: Vec<(OutPoint, StoredProof)> = serde_cbor::from_slice(&fs::read(path)?)?;
+    Ok(proofs.into_iter().collect())
+}
+
+/// Reads the maker address book. A missing file is an empty address book.
+fn load_address_file(path: &Path) -> Result<Vec<(OutPoint, String)>, DirectoryServerError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (outpoint, address) = line
+                .split_once(',')
+                .ok_or_else(|| DirectoryServerError::AddressFileCorrupted(line.to_string()))?;
+            Ok((
+                OutPoint::from_str(outpoint.trim())?,
+                address.trim().to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Reads the ban list. Lines parsing as an outpoint ban a fidelity bond, the others ban a maker address.
+fn load_ban_file(path: &Path) -> Result<BanList, DirectoryServerError> {
+    let mut banned = BanList::default();
+    if !path.exists() {
+        return Ok(banned);
+    }
+    for line in fs::read_to_string(path)?.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        match OutPoint::from_str(line) {
+            Ok(outpoint) => banned.outpoints.insert(outpoint),
+            Err(_) => banned.addresses.insert(line.to_string()),
+        };
+    }
+    Ok(banned)
+}
+
+/// Writes through a temporary file, so a crash never leaves a truncated file behind.
+fn write_atomically(path: &Path, content: &[u8]) -> Result<(), DirectoryServerError> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
 fn write_default_directory_config(config_path: &Path) -> Result<(), DirectoryServerError> {
     let config_string = String::from(
         "\
@@ -299,6 +764,11 @@ fn write_default_directory_config(config_path: &Path) -> Result<(), DirectorySer
             socks_port = 19060\n\
             connection_type = tor\n\
             rpc_port = 4321\n\
+            http_port = 0\n\
+            peers = \n\
+            min_bond_value = 0\n\
+            min_bond_confirmations = 1\n\
+            min_remaining_locktime = 144\n\
             ",
     );
     std::fs::create_dir_all(config_path.parent().expect("Path should NOT be root!"))?;
@@ -308,29 +778,59 @@ fn write_default_directory_config(config_path: &Path) -> Result<(), DirectorySer
     Ok(())
 }
 
-pub(crate) fn start_address_writer_thread(
-    directory: Arc<DirectoryServer>,
-) -> Result<(), DirectoryServerError> {
-    let interval = 60 * 15;
-    loop {
-        sleep(Duration::from_secs(interval));
-        let mut directory_address_book = directory.addresses.write()?;
-        let ttl = Duration::from_secs(60 * 30);
+/// Interval of the expiry check of the maker addresses.
+const ADDRESS_EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 15);
 
-        let expired_outpoints: Vec<_> = directory_address_book
-            .iter()
-            .filter(|(_, (_, timestamp))| timestamp.elapsed() > ttl)
-            .map(|(outpoint, _)| *outpoint)
-            .collect();
-        for outpoint in &expired_outpoints {
-            log::info!(
-                "No update for 30 mins from maker with fidelity : {}",
-                outpoint
-            );
-            directory_address_book.remove(outpoint);
-            log::info!("Maker entry removed");
-        }
+/// Time after which a maker that didn't post its address is removed.
+pub(crate) const ADDRESS_TTL: Duration = Duration::from_secs(60 * 30);
+
+/// Interval of the address book sync with the peer directory servers.
+const PEER_SYNC_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// Interval of the address book writes to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60 * 5);
+
+/// Removes makers that didn't post their address for 30 mins. Run periodically by the directory server.
+fn remove_expired_addresses(directory: &DirectoryServer) -> Result<(), DirectoryServerError> {
+    let mut directory_address_book = directory.addresses.write()?;
+    let ttl = ADDRESS_TTL;
+
+    let expired_outpoints: Vec<_> = directory_address_book
+        .iter()
+        .filter(|(_, (_, timestamp))| timestamp.elapsed() > ttl)
+        .map(|(outpoint, _)| *outpoint)
+        .collect();
+    for outpoint in &expired_outpoints {
+        log::info!(
+            "No update for 30 mins from maker with fidelity : {}",
+            outpoint
+        );
+        directory_address_book.remove(outpoint);
+        log::info!("Maker entry removed");
     }
+
+    // Drop the proofs, bonds, offers and health of removed makers, and the expired offers.
+    directory
+        .proofs
+        .write()?
+        .retain(|outpoint, _| directory_address_book.contains_key(outpoint));
+    directory
+        .bond_owners
+        .write()?
+        .retain(|_, primary| directory_address_book.contains_key(primary));
+    directory
+        .synced
+        .write()?
+        .retain(|outpoint| directory_address_book.contains_key(outpoint));
+    directory
+        .health
+        .write()?
+        .retain(|outpoint, _| directory_address_book.contains_key(outpoint));
+    let now = unix_time();
+    directory.offers.write()?.retain(|outpoint, offer| {
+        directory_address_book.contains_key(outpoint) && !offer.is_stale(now)
+    });
+    Ok(())
 }
 
 /// Initializes and starts the Directory Server with the provided configuration.
@@ -339,8 +839,8 @@ pub(crate) fn start_address_writer_thread(
 /// It handles both Clearnet and Tor connections (if the `tor` feature is enabled) and performs the following tasks:
 ///
 /// - Sets up the Directory Server for the appropriate connection type.
-/// - Spawns threads for handling RPC requests and writing address data to disk.
-/// - Monitors and manages incoming TCP connections.
+/// - Spawns a thread for handling RPC requests.
+/// - Runs the event loop serving incoming TCP connections, and expiring stale maker addresses.
 /// - Handles shutdown signals gracefully, ensuring all threads are terminated and resources are cleaned up.
 ///
 pub fn start_directory_server(
@@ -379,9 +879,13 @@ pub fn start_directory_server(
 
                 let socks_port = directory.socks_port;
                 let network_port = directory.network_port;
+                let mut ports = vec![network_port];
+                if directory.http_port != 0 {
+                    ports.push(directory.http_port);
+                }
                 tor_handle = Some(crate::tor::spawn_tor(
                     socks_port,
-                    network_port,
+                    &ports,
                     tor_dir.to_str().unwrap().to_string(),
                 )?);
 
@@ -398,6 +902,9 @@ pub fn start_directory_server(
                 let hostname = get_tor_hostname(&tor_dir)?;
 
                 log::info!("DNS is listening at {}:{}", hostname, network_port);
+                if directory.http_port != 0 {
+                    log::info!("Explorer is served at {}:{}", hostname, directory.http_port);
+                }
             }
         }
     }
@@ -409,41 +916,89 @@ pub fn start_directory_server(
         start_rpc_server_thread(directory_clone)
     });
 
+    let explorer_thread = if directory.http_port != 0 {
+        let directory_clone = directory.clone();
+        Some(thread::spawn(move || {
+            log::info!("Spawning Explorer Thread");
+            if let Err(e) = start_explorer_thread(directory_clone) {
+                log::error!("Explorer failed: {:?}", e);
+            }
+        }))
+    } else {
+        None
+    };
+
     let directory_clone = directory.clone();
-    let address_writer_thread = thread::spawn(move || {
-        log::info!("Spawning Address Writer Thread");
-        start_address_writer_thread(directory_clone)
+    let address_expiry_task = PeriodicTask::new("Address expiry", move || {
+        if let Err(e) = remove_expired_addresses(&directory_clone) {
+            log::error!("Error removing expired addresses: {:?}", e);
+        }
+        ADDRESS_EXPIRY_CHECK_INTERVAL
     });
 
-    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, directory.network_port))?;
-
-    while !directory.shutdown.load(Relaxed) {
-        match listener.accept() {
-            Ok((mut stream, _)) => {
-                stream.set_read_timeout(Some(Duration::from_secs(60)))?;
-                stream.set_write_timeout(Some(Duration::from_secs(60)))?;
-                if let Err(e) = handle_client(&mut stream, &directory, &rpc_client) {
-                    log::error!("Error accepting incoming connection: {:?}", e);
-                }
-            }
+    let directory_clone = directory.clone();
+    let flush_task = PeriodicTask::new("Address flush", move || {
+        if let Err(e) = directory_clone.flush_to_disk() {
+            log::error!("Error writing addresses to disk: {:?}", e);
+        }
+        FLUSH_INTERVAL
+    });
 
-            // If no connection received, check for shutdown or save addresses to disk
-            Err(e) => {
-                log::error!("Error accepting incoming connection: {:?}", e);
-            }
+    let directory_clone = directory.clone();
+    // Probing and peer syncing wait on remote hosts, they get threads of their own.
+    let probe_task = PeriodicTask::new("Maker prober", move || {
+        if let Err(e) = probe_makers(&directory_clone) {
+            log::error!("Error probing makers: {:?}", e);
         }
+        PROBE_INTERVAL
+    })
+    .on_own_thread();
+
+    let mut tasks = vec![address_expiry_task, flush_task, probe_task];
+    if !directory.peers.is_empty() {
+        let directory_clone = directory.clone();
+        let peer_rpc = bitcoincore_rpc::Client::try_from(&rpc_config)?;
+        tasks.push(
+            PeriodicTask::new("Directory peer sync", move || {
+                sync_with_peers(&directory_clone, &peer_rpc);
+                PEER_SYNC_INTERVAL
+            })
+            .on_own_thread(),
+        );
+    }
 
-        sleep(HEART_BEAT_INTERVAL);
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, directory.network_port))?;
+
+    let service = Arc::new(DirectoryService {
+        directory: directory.clone(),
+        rpc: rpc_client,
+    });
+    let result = run_server(
+        "Directory",
+        listener,
+        service,
+        NetConfig::default(),
+        tasks,
+        &directory.shutdown,
+    );
+    if let Err(e) = &result {
+        log::error!("Directory server failed: {:?}", e);
+        directory.shutdown.store(true, Relaxed);
     }
 
     log::info!("Shutdown signal received. Stopping directory server.");
 
+    if let Err(e) = directory.flush_to_disk() {
+        log::error!("Error writing addresses to disk: {:?}", e);
+    }
+
     // Its okay to suppress the error here as we are shuting down anyway.
     if let Err(e) = rpc_thread.join() {
         log::error!("Error closing RPC Thread: {:?}", e);
     }
-    if let Err(e) = address_writer_thread.join() {
-        log::error!("Error closing Address Writer Thread : {:?}", e);
+
+    if let Some(Err(e)) = explorer_thread.map(|handle| handle.join()) {
+        log::error!("Error closing Explorer Thread: {:?}", e);
     }
 
     #[cfg(feature = "tor")]
@@ -454,64 +1009,122 @@ pub fn start_directory_server(
         }
     }
 
-    Ok(())
+    Ok(result?)
 }
 
-// The stream should have read and write timeout set.
-fn handle_client(
-    stream: &mut TcpStream,
-    directory: &Arc<DirectoryServer>,
+/// Serves maker address posts and taker address requests, one request per connection.
+struct DirectoryService {
+    directory: Arc<DirectoryServer>,
+    rpc: Client,
+}
+
+impl Service for DirectoryService {
+    type Connection = ();
+
+    fn handle_message(&self, _: &mut (), message: Vec<u8>) -> Response {
+        match handle_request(&message, &self.directory, &self.rpc) {
+            Ok(response) => response.and_close(),
+            Err(e) => {
+                log::error!("Error handling directory request: {:?}", e);
+                Response::close()
+            }
+        }
+    }
+}
+
+fn handle_request(
+    buf: &[u8],
+    directory: &DirectoryServer,
     rpc: &Client,
-) -> Result<(), DirectoryServerError> {
-    let buf = read_message(&mut stream.try_clone()?)?;
-    let dns_request: DnsRequest = serde_cbor::de::from_reader(&buf[..])?;
+) -> Result<Response, DirectoryServerError> {
+    let dns_request: DnsRequest = serde_cbor::de::from_reader(buf)?;
     match dns_request {
         DnsRequest::Post { metadata } => {
             log::info!("Received POST | From {}", &metadata.url);
-
-            let txid = metadata.proof.bond.outpoint.txid;
-            let transaction = rpc.get_raw_transaction(&txid, None)?;
-            let current_height = rpc.get_block_count()?;
-
-            match verify_fidelity_checks(
-                &metadata.proof,
-                &metadata.url,
-                transaction,
-                current_height,
-            ) {
-                Ok(_) => {
-                    log::info!(
-                        "Fidelity verification success from {}. Adding/updating to address data.",
-                        metadata.url
-                    );
-                    directory.updated_address_map((metadata.url, metadata.proof.bond.outpoint))?;
-                }
-                Err(e) => {
-                    log::error!(
-                        "Potentially suspicious maker detected: {:?} | {:?}",
-                        metadata.url,
-                        e
-                    );
-                }
+            directory.counters.posts.fetch_add(1, Relaxed);
+
+            if register_maker(directory, rpc, &metadata, Instant::now())? {
+                update_offer(
+                    directory,
+                    metadata.proof.bond.outpoint,
+                    &metadata.url,
+                    &metadata.proof,
+                    metadata.offer,
+                )?;
             }
         }
         DnsRequest::Get => {
             log::info!("Received GET");
+            directory.counters.gets.fetch_add(1, Relaxed);
+
+            let response = directory
+                .addresses
+                .read()?
+                .values()
+                .filter(|(_, timestamp)| timestamp.elapsed() <= ADDRESS_TTL)
+                .fold(String::new(), |acc, (url, _)| acc + url + "\n");
+
+            log::debug!("Sending Addresses: {}", response);
+            return Ok(Response::reply(&response)?);
+        }
+        DnsRequest::GetMakers => {
+            log::info!("Received GET MAKERS");
+            directory.counters.gets.fetch_add(1, Relaxed);
 
             let addresses = directory.addresses.read()?;
+            let offers = directory.offers.read()?;
+            let health = directory.health.read()?;
+            let now = unix_time();
 
             let response = addresses
                 .iter()
-                .filter(|(_, (_, timestamp))| timestamp.elapsed() <= Duration::from_secs(30 * 60))
-                .fold(String::new(), |acc, (_, addr)| acc + &addr.0 + "\n");
+                .filter(|(_, (_, timestamp))| timestamp.elapsed() <= ADDRESS_TTL)
+                .map(|(outpoint, (url, _))| DirectoryEntry {
+                    url: url.clone(),
+                    offer: offers
+                        .get(outpoint)
+                        .filter(|offer| !offer.is_stale(now))
+                        .cloned(),
+                    health: health.get(outpoint).cloned(),
+                })
+                .collect::<Vec<_>>();
+
+            log::debug!("Sending Makers: {:?}", response);
+            return Ok(Response::reply(&response)?);
+        }
+        DnsRequest::Sync => {
+            log::info!("Received SYNC");
+            directory.counters.syncs.fetch_add(1, Relaxed);
 
-            log::debug!("Sending Addresses: {}", response);
-            send_message(stream, &response)?;
+            let addresses = directory.addresses.read()?;
+            let offers = directory.offers.read()?;
+            let proofs = directory.proofs.read()?;
+            let now = unix_time();
+
+            let response = addresses
+                .iter()
+                .filter(|(_, (_, timestamp))| timestamp.elapsed() <= ADDRESS_TTL)
+                .filter_map(|(outpoint, (url, timestamp))| {
+                    Some(DirectorySyncEntry {
+                        metadata: DnsMetadata {
+                            url: url.clone(),
+                            proof: proofs.get(outpoint)?.clone(),
+                            offer: offers
+                                .get(outpoint)
+                                .filter(|offer| !offer.is_stale(now))
+                                .cloned(),
+                        },
+                        age: timestamp.elapsed().as_secs(),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            log::debug!("Sending {} makers to peer directory", response.len());
+            return Ok(Response::reply(&response)?);
         }
         #[cfg(feature = "integration-test")]
         // Used for IT, only checks the updated_address_map() function.
         DnsRequest::Dummy { url, vout } => {
-            use std::str::FromStr;
             log::info!("Got new maker address: {}", &url);
 
             // Create a constant txid for tests
@@ -525,6 +1138,232 @@ fn handle_client(
             directory.updated_address_map((url, fidelity_op))?;
         }
     }
+    Ok(Response::none())
+}
+
+/// Verifies the fidelity proof of a maker, and adds or updates its address as last posted at `seen`.
+///
+/// Returns whether the maker passed the fidelity checks.
+fn register_maker(
+    directory: &DirectoryServer,
+    rpc: &Client,
+    metadata: &DnsMetadata,
+    seen: Instant,
+) -> Result<bool, DirectoryServerError> {
+    let banned = directory.banned.read()?;
+    if metadata
+        .proof
+        .bonds()
+        .any(|bond| banned.is_banned(&bond.outpoint, &metadata.url))
+    {
+        log::warn!("Ignoring banned maker: {}", metadata.url);
+        directory.counters.rejected_posts.fetch_add(1, Relaxed);
+        return Ok(false);
+    }
+    drop(banned);
+
+    let result = match verify_fidelity_checks(&metadata.proof, &metadata.url, rpc) {
+        Ok(()) => match admission_failure(&directory.admission, rpc, metadata)? {
+            None => directory
+                .claim_bonds(&metadata.url, &metadata.proof)?
+                .map_or(Ok(()), Err),
+            Some(reason) => Err(reason),
+        },
+        Err(e) => {
+            log::error!(
+                "Potentially suspicious maker detected: {:?} | {:?}",
+                metadata.url,
+                e
+            );
+            Err(format!("Fidelity checks failed: {:?}", e))
+        }
+    };
+
+    match result {
+        Ok(()) => {
+            log::info!(
+                "Fidelity verification success from {}. Adding/updating to address data.",
+                metadata.url
+            );
+            let outpoint = metadata.proof.bond.outpoint;
+            directory.update_address_map_at((metadata.url.clone(), outpoint), seen)?;
+            directory
+                .proofs
+                .write()?
+                .insert(outpoint, metadata.proof.clone());
+            Ok(true)
+        }
+        Err(reason) => {
+            directory.reject(metadata, reason)?;
+            Ok(false)
+        }
+    }
+}
+
+/// Checks the fidelity bonds of a maker against the admission rules. Returns why the maker is refused, if it is.
+///
+/// Each bond must be unspent, confirmed and locked for long enough. The minimum value applies to the aggregated
+/// value of all the bonds.
+fn admission_failure(
+    rules: &AdmissionRules,
+    rpc: &Client,
+    metadata: &DnsMetadata,
+) -> Result<Option<String>, DirectoryServerError> {
+    let current_height = rpc.get_block_count()?;
+    let mut bond_values = Vec::new();
+
+    for bond in metadata.proof.bonds() {
+        let utxo = match rpc.get_tx_out(&bond.outpoint.txid, bond.outpoint.vout, Some(false))? {
+            Some(utxo) => utxo,
+            None => return Ok(Some(format!("Bond {} is spent or unknown", bond.outpoint))),
+        };
+        if utxo.confirmations < rules.min_confirmations {
+            return Ok(Some(format!(
+                "Bond {} has {} confirmations, {} required",
+                bond.outpoint, utxo.confirmations, rules.min_confirmations
+            )));
+        }
+
+        let remaining_locktime = bond.remaining_blocks(current_height);
+        if remaining_locktime < rules.min_remaining_locktime as u64 {
+            return Ok(Some(format!(
+                "Bond {} matures in {} blocks, {} required",
+                bond.outpoint, remaining_locktime, rules.min_remaining_locktime
+            )));
+        }
+
+        let conf_height = (current_height + 1).saturating_sub(utxo.confirmations as u64);
+        bond_values.push(calculate_bond_value_at(rpc, bond, conf_height)?);
+    }
+
+    let bond_value = aggregate_bond_values(bond_values);
+    if bond_value < rules.min_bond_value {
+        return Ok(Some(format!(
+            "Bond value {} below the minimum {}",
+            bond_value, rules.min_bond_value
+        )));
+    }
+
+    Ok(None)
+}
+
+/// Fetches the verified makers of a peer directory server.
+fn fetch_peer_makers(
+    directory: &DirectoryServer,
+    peer: &str,
+) -> Result<Vec<DirectorySyncEntry>, DirectoryServerError> {
+    let mut stream = directory.connect_to(peer)?;
+    stream.set_read_timeout(Some(NET_TIMEOUT))?;
+    stream.set_write_timeout(Some(NET_TIMEOUT))?;
+
+    send_message(&mut stream, &DnsRequest::Sync)?;
+    let response = read_message(&mut stream)?;
+    Ok(serde_cbor::de::from_slice(&response)?)
+}
+
+/// Syncs the maker address book with the peer directory servers. Run periodically by the directory server.
+///
+/// Peer entries are not trusted: every fidelity proof is verified again, and each entry keeps the time the maker
+/// last posted to the peer, so it expires here as it does there. Makers posting here keep our own observation.
+fn sync_with_peers(directory: &DirectoryServer, rpc: &Client) {
+    for peer in &directory.peers {
+        let entries = match fetch_peer_makers(directory, peer) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Failed to sync with peer directory {}: {:?}", peer, e);
+                continue;
+            }
+        };
+        log::info!(
+            "Received {} makers from peer directory {}",
+            entries.len(),
+            peer
+        );
+
+        for entry in entries {
+            let url = entry.metadata.url.clone();
+            if let Err(e) = sync_entry(directory, rpc, entry) {
+                log::warn!(
+                    "Failed to verify maker {} from peer directory {}: {:?}",
+                    url,
+                    peer,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Verifies and lists a maker relayed by a peer directory server.
+fn sync_entry(
+    directory: &DirectoryServer,
+    rpc: &Client,
+    entry: DirectorySyncEntry,
+) -> Result<(), DirectoryServerError> {
+    let metadata = entry.metadata;
+    let outpoint = metadata.proof.bond.outpoint;
+    let seen = match directory.synced_seen(&outpoint, Duration::from_secs(entry.age))? {
+        Some(seen) => seen,
+        None => return Ok(()),
+    };
+    let known = directory.addresses.read()?.contains_key(&outpoint);
+    if !register_maker(directory, rpc, &metadata, seen)? {
+        return Ok(());
+    }
+    if !known {
+        directory.synced.write()?.insert(outpoint);
+    }
+    // A peer missing the offer doesn't drop the one we have.
+    if metadata.offer.is_some() {
+        update_offer(
+            directory,
+            outpoint,
+            &metadata.url,
+            &metadata.proof,
+            metadata.offer,
+        )?;
+    }
+    Ok(())
+}
+
+/// Stores the offer posted along with a verified fidelity proof. The offer must be backed by that proof,
+/// signed by its bond key, and valid now. A post without an offer drops the previously stored one.
+/// An offer older than the stored one, as relayed by a lagging peer directory, is ignored.
+pub(crate) fn update_offer(
+    directory: &DirectoryServer,
+    outpoint: OutPoint,
+    url: &str,
+    proof: &FidelityProof,
+    offer: Option<Offer>,
+) -> Result<(), DirectoryServerError> {
+    let mut offers = directory.offers.write()?;
+    let offer = match offer {
+        Some(offer) => offer,
+        None => {
+            offers.remove(&outpoint);
+            return Ok(());
+        }
+    };
+    if offer.fidelity != *proof {
+        log::warn!("Offer from {} is not backed by its fidelity proof", url);
+        offers.remove(&outpoint);
+        return Ok(());
+    }
+    if offers
+        .get(&outpoint)
+        .is_some_and(|stored| stored.timestamp > offer.timestamp)
+    {
+        return Ok(());
+    }
+    match offer.verify(unix_time()) {
+        Ok(()) => {
+            offers.insert(outpoint, offer);
+        }
+        Err(e) => {
+            log::warn!("Invalid offer from {}: {:?}", url, e);
+            offers.remove(&outpoint);
+        }
+    }
     Ok(())
 }
 
@@ -590,6 +1429,229 @@ mod tests {
         temp_dir.close().unwrap();
     }
 
+    #[test]
+    fn test_peers_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let contents = r#"
+            port = 8080
+            peers = abc.onion:8080, def.onion:8080
+        "#;
+        create_temp_config(contents, &temp_dir);
+        let dns = DirectoryServer::new(Some(temp_dir.path().to_path_buf()), None).unwrap();
+
+        assert_eq!(dns.peers, vec!["abc.onion:8080", "def.onion:8080"]);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_admission_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let contents = r#"
+            min_bond_value = 50000
+            min_remaining_locktime = 1000
+        "#;
+        create_temp_config(contents, &temp_dir);
+        let dns = DirectoryServer::new(Some(temp_dir.path().to_path_buf()), None).unwrap();
+
+        assert_eq!(
+            dns.admission,
+            AdmissionRules {
+                min_bond_value: Amount::from_sat(50_000),
+                min_remaining_locktime: 1000,
+                ..AdmissionRules::default()
+            }
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_outdated_peer_entry_ignored() {
+        let dns = DirectoryServer::default();
+        let outpoint = OutPoint::null();
+        let now = Instant::now();
+        let older = now - Duration::from_secs(60);
+
+        dns.update_address_map_at(("new.onion:6102".to_string(), outpoint), now)
+            .unwrap();
+        // An older address for the same bond, relayed by a lagging peer.
+        dns.update_address_map_at(("old.onion:6102".to_string(), outpoint), older)
+            .unwrap();
+        // The same entry, seen earlier, doesn't move its post time back.
+        dns.update_address_map_at(("new.onion:6102".to_string(), outpoint), older)
+            .unwrap();
+
+        let addresses = dns.addresses.read().unwrap();
+        assert_eq!(addresses[&outpoint], ("new.onion:6102".to_string(), now));
+    }
+
+    /// A proof for `outpoint`, not meant to verify.
+    fn dummy_proof(outpoint: OutPoint) -> FidelityProof {
+        use crate::wallet::FidelityBond;
+        use bitcoin::{
+            absolute::LockTime,
+            hashes::Hash,
+            secp256k1::{Message, Secp256k1, SecretKey},
+            PublicKey,
+        };
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&[1; 32]).unwrap();
+        FidelityProof {
+            bond: FidelityBond {
+                outpoint,
+                amount: Amount::from_sat(1_000_000),
+                lock_time: LockTime::from_height(1000).unwrap(),
+                pubkey: PublicKey::new(bitcoin::secp256k1::PublicKey::from_secret_key(&secp, &key)),
+                conf_height: 100,
+                cert_expiry: 1,
+                delegation: None,
+            },
+            cert_hash: Hash::all_zeros(),
+            cert_sig: secp.sign_ecdsa(&Message::from_digest([0; 32]), &key),
+            additional_bonds: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_bond_backs_one_maker() {
+        use crate::protocol::messages::BondProof;
+
+        let dns = DirectoryServer::default();
+        let txid = "c3a04e4bdf3c8684c5cf5c8b2f3c43009670bc194ac6c856b3ec9d3a7a6e2602";
+        let outpoint = |vout| OutPoint::from_str(&format!("{}:{}", txid, vout)).unwrap();
+        let with_bonds = |primary, additional: &[u32]| {
+            let mut proof = dummy_proof(outpoint(primary));
+            proof.additional_bonds = additional
+                .iter()
+                .map(|vout| {
+                    let bond = dummy_proof(outpoint(*vout));
+                    BondProof {
+                        bond: bond.bond,
+                        cert_hash: bond.cert_hash,
+                        cert_sig: bond.cert_sig,
+                    }
+                })
+                .collect();
+            proof
+        };
+        let list = |address: &str, proof: &FidelityProof| {
+            let claim = dns.claim_bonds(address, proof).unwrap();
+            if claim.is_none() {
+                dns.updated_address_map((address.to_string(), proof.bond.outpoint))
+                    .unwrap();
+            }
+            claim
+        };
+
+        assert_eq!(list("a.onion:6102", &with_bonds(0, &[1])), None);
+        // A maker with the bonds of another one, as primary or additional bonds.
+        assert!(list("b.onion:6102", &with_bonds(2, &[1])).is_some());
+        assert!(list("b.onion:6102", &with_bonds(2, &[0])).is_some());
+        assert!(list("b.onion:6102", &with_bonds(1, &[])).is_some());
+        // A bond listed twice.
+        assert!(list("b.onion:6102", &with_bonds(2, &[3, 3])).is_some());
+        assert!(list("b.onion:6102", &with_bonds(2, &[2])).is_some());
+        assert_eq!(list("b.onion:6102", &with_bonds(2, &[3])), None);
+
+        // A maker can move its bonds around, and frees the ones it drops.
+        assert_eq!(list("a.onion:6102", &with_bonds(0, &[])), None);
+        assert_eq!(list("a.onion:6102", &with_bonds(1, &[0])), None);
+        assert_eq!(dns.bond_owners.read().unwrap()[&outpoint(0)], outpoint(1));
+        dns.evict(&outpoint(1)).unwrap();
+        assert_eq!(list("b.onion:6102", &with_bonds(2, &[0, 1, 3])), None);
+        assert_eq!(dns.bond_owners.read().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_synced_entries_and_stored_proofs() {
+        let temp_dir = TempDir::new().unwrap();
+        let dns = DirectoryServer::new(Some(temp_dir.path().to_path_buf()), None).unwrap();
+        let local = OutPoint::from_str(
+            "c3a04e4bdf3c8684c5cf5c8b2f3c43009670bc194ac6c856b3ec9d3a7a6e2602:0",
+        )
+        .unwrap();
+        let relayed = OutPoint { vout: 1, ..local };
+
+        dns.updated_address_map(("local.onion:6102".to_string(), local))
+            .unwrap();
+        thread::sleep(Duration::from_millis(10));
+        // A peer can't make a maker posting here younger than our own observation.
+        let local_seen = dns.addresses.read().unwrap()[&local].1;
+        let seen = dns.synced_seen(&local, Duration::ZERO).unwrap().unwrap();
+        assert_eq!(seen, local_seen);
+        // Relayed makers keep the peer's age, up to the expiry.
+        let seen = dns
+            .synced_seen(&relayed, Duration::from_secs(60))
+            .unwrap()
+            .unwrap();
+        assert!(seen.elapsed() >= Duration::from_secs(60));
+        assert!(dns
+            .synced_seen(&relayed, ADDRESS_TTL + Duration::from_secs(1))
+            .unwrap()
+            .is_none());
+
+        // The proofs survive a restart, along with the time their makers last posted.
+        dns.update_address_map_at(("relayed.onion:6102".to_string(), relayed), seen)
+            .unwrap();
+        for outpoint in [local, relayed] {
+            dns.proofs
+                .write()
+                .unwrap()
+                .insert(outpoint, dummy_proof(outpoint));
+        }
+        dns.flush_to_disk().unwrap();
+        let dns = DirectoryServer::new(Some(temp_dir.path().to_path_buf()), None).unwrap();
+        assert_eq!(dns.proofs.read().unwrap()[&relayed], dummy_proof(relayed));
+        let addresses = dns.addresses.read().unwrap();
+        assert!(addresses[&relayed].1.elapsed() >= Duration::from_secs(60));
+        assert!(addresses[&local].1.elapsed() < Duration::from_secs(60));
+        // Until they post here again.
+        assert!(dns.synced.read().unwrap().contains(&local));
+        drop(addresses);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_flush_and_ban() {
+        let temp_dir = TempDir::new().unwrap();
+        let dns = DirectoryServer::new(Some(temp_dir.path().to_path_buf()), None).unwrap();
+        let kept = OutPoint::from_str(
+            "c3a04e4bdf3c8684c5cf5c8b2f3c43009670bc194ac6c856b3ec9d3a7a6e2602:0",
+        )
+        .unwrap();
+        let banned = OutPoint { vout: 1, ..kept };
+
+        dns.updated_address_map(("kept.onion:6102".to_string(), kept))
+            .unwrap();
+        dns.updated_address_map(("banned.onion:6102".to_string(), banned))
+            .unwrap();
+        assert_eq!(dns.ban_address("banned.onion:6102".to_string()).unwrap(), 1);
+        assert_eq!(dns.ban_outpoint(banned).unwrap(), 0);
+        dns.flush_to_disk().unwrap();
+
+        // The address book and the bans survive a restart.
+        let dns = DirectoryServer::new(Some(temp_dir.path().to_path_buf()), None).unwrap();
+        let addresses = dns.addresses.read().unwrap();
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses[&kept].0, "kept.onion:6102");
+        let bans = dns.banned.read().unwrap();
+        assert!(bans.is_banned(&banned, "other.onion:6102"));
+        assert!(bans.is_banned(&kept, "banned.onion:6102"));
+        assert!(!bans.is_banned(&kept, "kept.onion:6102"));
+        drop(addresses);
+        drop(bans);
+
+        assert_eq!(
+            dns.evict(&kept).unwrap(),
+            Some("kept.onion:6102".to_string())
+        );
+        assert!(dns.addresses.read().unwrap().is_empty());
+
+        temp_dir.close().unwrap();
+    }
+
     #[test]
     fn test_missing_file() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,348 @@
+//! Read-only HTTP/JSON explorer of the market known to the directory server.
+//!
+//! Serves the listed makers, their fidelity bonds, offer fee schedules and health, for public dashboards of
+//! market liquidity. It's disabled unless `http_port` is set in the directory config. Like the directory port,
+//! it's served on localhost, and published on the directory's onion address when running over Tor.
+//!
+//! Endpoints:
+//! - `GET /makers`: The listed makers with their fidelity proofs, see [MakerView].
+//! - `GET /stats`: Market totals, see [MarketStats].
+
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering::Relaxed},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bitcoin::OutPoint;
+use serde::Serialize;
+
+use crate::{
+    market::directory::{DirectoryServer, DirectoryServerError, ADDRESS_TTL},
+    net::WorkerPool,
+    protocol::messages::{MakerHealth, OfferTier},
+    utill::unix_time,
+};
+
+/// Time to read a request and write its response, counted from the connection's accept.
+const REQUEST_DEADLINE: Duration = Duration::from_secs(10);
+
+/// Number of threads serving the requests.
+const HTTP_THREADS: usize = 4;
+
+/// Maximum number of connections accepted and not served yet. Connections above it are closed right away.
+const MAX_PENDING_CONNECTIONS: usize = 64;
+
+/// Maximum size of a request head. Longer requests are refused.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// Interval of the shutdown checks, while no request is pending.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A listed maker, as served by `GET /makers`.
+#[derive(Debug, Serialize)]
+struct MakerView {
+    address: String,
+    outpoint: OutPoint,
+    /// Total amount of the fidelity bonds in sats. Unknown for makers loaded from disk, until they post again.
+    bond_amount: Option<u64>,
+    /// Number of fidelity bonds in the maker's proof.
+    bond_count: usize,
+    /// Locktime of the primary fidelity bond, as a block height or a unix time.
+    bond_locktime: Option<u32>,
+    /// Fee schedules of the maker's last valid offer. Empty if the directory has no offer of the maker.
+    tiers: Vec<OfferTier>,
+    /// Unix time the maker's offer expires at.
+    offer_expiry: Option<u64>,
+    health: Option<MakerHealth>,
+    /// The maker's fidelity proof in hex, to be checked with `taker verify-fidelity`.
+    proof: Option<String>,
+}
+
+/// Market totals, as served by `GET /stats`.
+#[derive(Debug, Serialize)]
+struct MarketStats {
+    makers: usize,
+    reachable_makers: usize,
+    makers_with_offer: usize,
+    /// Sum of the known fidelity bond amounts, in sats.
+    total_bond_amount: u64,
+    /// Sum of the largest swap size each maker offers, in sats.
+    total_liquidity: u64,
+}
+
+/// Serves the explorer on `http_port`, until the directory server shuts down.
+///
+/// Requests are served by a pool of [HTTP_THREADS] threads, and a client is dropped if its request isn't answered
+/// within [REQUEST_DEADLINE], so stalled clients can't hold the explorer.
+pub(crate) fn start_explorer_thread(
+    directory: Arc<DirectoryServer>,
+) -> Result<(), DirectoryServerError> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, directory.http_port))?;
+    listener.set_nonblocking(true)?;
+    log::info!("Explorer listening at 127.0.0.1:{}", directory.http_port);
+
+    let pool = WorkerPool::new("Explorer", HTTP_THREADS)?;
+    let pending = Arc::new(AtomicUsize::new(0));
+    while !directory.shutdown.load(Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let deadline = Instant::now() + REQUEST_DEADLINE;
+                if pending.load(Relaxed) >= MAX_PENDING_CONNECTIONS {
+                    log::debug!("Too many pending explorer connections, closing one");
+                    continue;
+                }
+                pending.fetch_add(1, Relaxed);
+                let directory = directory.clone();
+                let pending = pending.clone();
+                pool.execute(move || {
+                    if let Err(e) = serve_connection(&directory, stream, deadline) {
+                        log::debug!("Error serving explorer request: {:?}", e);
+                    }
+                    pending.fetch_sub(1, Relaxed);
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_INTERVAL),
+            Err(e) => {
+                log::error!("Error accepting explorer connection: {:?}", e);
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+            }
+        }
+    }
+    log::info!("Explorer stopped");
+    Ok(())
+}
+
+fn serve_connection(
+    directory: &DirectoryServer,
+    mut stream: TcpStream,
+    deadline: Instant,
+) -> Result<(), DirectoryServerError> {
+    stream.set_nonblocking(false)?;
+
+    let (status, body) = match read_request_line(&mut stream, deadline)? {
+        Some(request_line) => route(directory, &request_line)?,
+        None => (413, error_body("Request too large")),
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason_phrase(status),
+        body.len(),
+        body
+    );
+    let mut response = response.as_bytes();
+    while !response.is_empty() {
+        stream.set_write_timeout(Some(time_left(deadline)?))?;
+        match stream.write(response)? {
+            0 => return Err(io::Error::from(ErrorKind::WriteZero).into()),
+            written => response = &response[written..],
+        }
+    }
+    Ok(())
+}
+
+/// Time left until `deadline`, as a socket timeout. Fails once the deadline is passed.
+fn time_left(deadline: Instant) -> io::Result<Duration> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|left| !left.is_zero())
+        .ok_or_else(|| io::Error::new(ErrorKind::TimedOut, "Request deadline passed"))
+}
+
+/// Reads the request head before `deadline`, and returns its first line. Returns `None` if the head exceeds
+/// [MAX_REQUEST_SIZE].
+fn read_request_line(
+    stream: &mut TcpStream,
+    deadline: Instant,
+) -> Result<Option<String>, DirectoryServerError> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_SIZE {
+            return Ok(None);
+        }
+        stream.set_read_timeout(Some(time_left(deadline)?))?;
+        let read = stream.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    Ok(Some(head.lines().next().unwrap_or_default().to_string()))
+}
+
+/// Returns the status code and the JSON body answering a request line.
+fn route(
+    directory: &DirectoryServer,
+    request_line: &str,
+) -> Result<(u16, String), DirectoryServerError> {
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return Ok((400, error_body("Malformed request"))),
+    };
+    if method != "GET" {
+        return Ok((405, error_body("Only GET is supported")));
+    }
+    let path = target.split('?').next().unwrap_or_default();
+    let body = match path.trim_end_matches('/') {
+        "/makers" => serde_json::to_string(&list_makers(directory)?),
+        "/stats" => serde_json::to_string(&market_stats(&list_makers(directory)?)),
+        _ => return Ok((404, error_body("Unknown endpoint, try /makers or /stats"))),
+    };
+    match body {
+        Ok(body) => Ok((200, body)),
+        Err(e) => {
+            log::error!("Error serializing explorer response: {:?}", e);
+            Ok((500, error_body("Internal error")))
+        }
+    }
+}
+
+/// Collects the makers served to takers, sorted by decreasing bond amount.
+fn list_makers(directory: &DirectoryServer) -> Result<Vec<MakerView>, DirectoryServerError> {
+    let addresses = directory.addresses.read()?;
+    let offers = directory.offers.read()?;
+    let proofs = directory.proofs.read()?;
+    let health = directory.health.read()?;
+    let now = unix_time();
+
+    let mut makers = addresses
+        .iter()
+        .filter(|(_, (_, timestamp))| timestamp.elapsed() <= ADDRESS_TTL)
+        .map(|(outpoint, (address, _))| {
+            let proof = proofs.get(outpoint);
+            let offer = offers.get(outpoint).filter(|offer| !offer.is_stale(now));
+            MakerView {
+                address: address.clone(),
+                outpoint: *outpoint,
+                bond_amount: proof.map(|proof| proof.total_amount().to_sat()),
+                bond_count: proof.map_or(0, |proof| proof.bonds().count()),
+                bond_locktime: proof.map(|proof| proof.bond.lock_time.to_consensus_u32()),
+                tiers: offer.map(|offer| offer.tiers.clone()).unwrap_or_default(),
+                offer_expiry: offer.map(|offer| offer.expiry),
+                health: health.get(outpoint).cloned(),
+                proof: proof.map(|proof| proof.to_hex()),
+            }
+        })
+        .collect::<Vec<_>>();
+    makers.sort_by(|a, b| {
+        b.bond_amount
+            .cmp(&a.bond_amount)
+            .then_with(|| a.outpoint.cmp(&b.outpoint))
+    });
+    Ok(makers)
+}
+
+fn market_stats(makers: &[MakerView]) -> MarketStats {
+    MarketStats {
+        makers: makers.len(),
+        reachable_makers: makers
+            .iter()
+            .filter(|maker| maker.health.as_ref().is_some_and(|health| health.reachable))
+            .count(),
+        makers_with_offer: makers
+            .iter()
+            .filter(|maker| !maker.tiers.is_empty())
+            .count(),
+        total_bond_amount: makers.iter().filter_map(|maker| maker.bond_amount).sum(),
+        total_liquidity: makers
+            .iter()
+            .filter_map(|maker| maker.tiers.iter().map(|tier| tier.max_size).max())
+            .sum(),
+    }
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_explorer_routes() {
+        let directory = DirectoryServer::default();
+        let outpoint = OutPoint::from_str(
+            "c3a04e4bdf3c8684c5cf5c8b2f3c43009670bc194ac6c856b3ec9d3a7a6e2602:0",
+        )
+        .unwrap();
+        directory
+            .updated_address_map(("maker.onion:6102".to_string(), outpoint))
+            .unwrap();
+        directory.health.write().unwrap().insert(
+            outpoint,
+            MakerHealth {
+                reachable: true,
+                latency_ms: Some(800),
+                last_probe: 100,
+                consecutive_failures: 0,
+            },
+        );
+
+        let (status, body) = route(&directory, "GET /makers HTTP/1.1").unwrap();
+        assert_eq!(status, 200);
+        let makers: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(makers[0]["address"], "maker.onion:6102");
+        assert_eq!(makers[0]["bond_amount"], serde_json::Value::Null);
+        assert_eq!(makers[0]["health"]["latency_ms"], 800);
+
+        let (status, body) = route(&directory, "GET /stats/?pretty HTTP/1.1").unwrap();
+        assert_eq!(status, 200);
+        let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(stats["makers"], 1);
+        assert_eq!(stats["reachable_makers"], 1);
+        assert_eq!(stats["total_liquidity"], 0);
+
+        assert_eq!(route(&directory, "GET /offers HTTP/1.1").unwrap().0, 404);
+        assert_eq!(route(&directory, "POST /makers HTTP/1.1").unwrap().0, 405);
+        assert_eq!(route(&directory, "").unwrap().0, 400);
+    }
+
+    #[test]
+    fn test_stalled_request_deadline() {
+        let directory = DirectoryServer::default();
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        // A client trickling its request in is dropped at the deadline.
+        let start = Instant::now();
+        let writer = thread::spawn(move || {
+            for _ in 0..10 {
+                if client.write_all(b"G").is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+        let deadline = start + Duration::from_millis(200);
+        assert!(serve_connection(&directory, stream, deadline).is_err());
+        assert!(start.elapsed() < Duration::from_millis(400));
+        writer.join().unwrap();
+    }
+}
@@ -1,4 +1,6 @@
 //! (dummy) Current toy implementation of a directory-server.
 
 pub mod directory;
+pub(crate) mod explorer;
+pub(crate) mod probe;
 pub mod rpc;
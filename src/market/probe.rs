@@ -0,0 +1,210 @@
+//! Liveness probing of the makers listed by the directory server.
+//!
+//! Each listed maker is periodically dialed like a taker would: a `TakerHello` handshake followed by an offer
+//! request. The outcome and the round trip time are recorded as the maker's [MakerHealth], served to takers
+//! with the maker addresses, so they don't spend circuits on dead makers.
+
+use std::{
+    net::TcpStream,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bitcoin::OutPoint;
+
+use crate::{
+    market::directory::{update_offer, DirectoryServer, DirectoryServerError},
+    protocol::messages::{
+        GiveOffer, MakerHealth, MakerToTakerMessage, Offer, TakerHello, TakerToMakerMessage,
+    },
+    utill::{read_message, send_message, unix_time},
+};
+
+/// Interval of the liveness probes of the listed makers.
+pub(crate) const PROBE_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// Read and write timeout of a probe connection.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum number of makers probed at once.
+const PROBE_THREADS: usize = 16;
+
+/// Probes all listed makers in parallel, at most [PROBE_THREADS] at once, and records their health.
+///
+/// A valid offer served by a probed maker also refreshes the offer cached by the directory.
+pub(crate) fn probe_makers(directory: &DirectoryServer) -> Result<(), DirectoryServerError> {
+    let targets = directory
+        .addresses
+        .read()?
+        .iter()
+        .map(|(outpoint, (address, _))| (*outpoint, address.clone()))
+        .collect::<Vec<_>>();
+    if targets.is_empty() {
+        return Ok(());
+    }
+    log::info!("Probing {} makers", targets.len());
+
+    // Each prober takes the next maker as soon as it is done, so slow makers don't hold the others back.
+    let pending = Mutex::new(targets.iter());
+    let results = thread::scope(|scope| {
+        let handles = (0..PROBE_THREADS.min(targets.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    while let Some((outpoint, address)) =
+                        pending.lock().ok().and_then(|mut pending| pending.next())
+                    {
+                        let result = probe_maker(directory, outpoint, address)
+                            .map_err(|e| format!("{:?}", e));
+                        results.push((*outpoint, address, result));
+                    }
+                    results
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().ok())
+            .flatten()
+            .collect::<Vec<_>>()
+    });
+
+    let now = unix_time();
+    let mut reachable = 0;
+    for (outpoint, address, result) in results {
+        let latency = match result {
+            Ok((offer, latency)) => {
+                reachable += 1;
+                let proof = directory.proofs.read()?.get(&outpoint).cloned();
+                if let Some(proof) = proof {
+                    update_offer(directory, outpoint, address, &proof, Some(offer))?;
+                }
+                Ok(latency)
+            }
+            Err(e) => {
+                log::warn!("Maker {} failed its liveness probe: {}", address, e);
+                Err(e)
+            }
+        };
+        let mut health = directory.health.write()?;
+        let next = next_health(health.get(&outpoint), &latency, now);
+        health.insert(outpoint, next);
+    }
+    log::info!("{} of {} makers reachable", reachable, targets.len());
+    Ok(())
+}
+
+/// Computes the health of a maker after a probe, from its previous health.
+fn next_health<E>(
+    previous: Option<&MakerHealth>,
+    result: &Result<Duration, E>,
+    now: u64,
+) -> MakerHealth {
+    match result {
+        Ok(latency) => MakerHealth {
+            reachable: true,
+            latency_ms: Some(latency.as_millis() as u64),
+            last_probe: now,
+            consecutive_failures: 0,
+        },
+        Err(_) => MakerHealth {
+            reachable: false,
+            latency_ms: None,
+            last_probe: now,
+            consecutive_failures: previous.map_or(0, |health| health.consecutive_failures) + 1,
+        },
+    }
+}
+
+/// Runs the taker handshake and offer request with a maker. Returns the maker's offer and the round trip time.
+///
+/// The offer must be valid, and backed by the fidelity bond the maker is listed with.
+fn probe_maker(
+    directory: &DirectoryServer,
+    outpoint: &OutPoint,
+    address: &str,
+) -> Result<(Offer, Duration), DirectoryServerError> {
+    let start = Instant::now();
+    let mut socket = directory.connect_to(address)?;
+    socket.set_read_timeout(Some(PROBE_TIMEOUT))?;
+    socket.set_write_timeout(Some(PROBE_TIMEOUT))?;
+
+    send_message(
+        &mut socket,
+        &TakerToMakerMessage::TakerHello(TakerHello {
+            protocol_version_min: 1,
+            protocol_version_max: 1,
+            podle: None,
+        }),
+    )?;
+    match read_maker_message(&mut socket)? {
+        MakerToTakerMessage::MakerHello(_) => {}
+        msg => return Err(unexpected_message("MakerHello", &msg)),
+    }
+
+    send_message(&mut socket, &TakerToMakerMessage::ReqGiveOffer(GiveOffer))?;
+    let offer = match read_maker_message(&mut socket)? {
+        MakerToTakerMessage::RespOffer(offer) => *offer,
+        msg => return Err(unexpected_message("RespOffer", &msg)),
+    };
+    let latency = start.elapsed();
+
+    if offer.fidelity.bond.outpoint != *outpoint {
+        return Err(DirectoryServerError::ProbeFailed(format!(
+            "Offer backed by fidelity {}, listed with {}",
+            offer.fidelity.bond.outpoint, outpoint
+        )));
+    }
+    offer
+        .verify(unix_time())
+        .map_err(|e| DirectoryServerError::ProbeFailed(format!("Invalid offer: {:?}", e)))?;
+
+    Ok((offer, latency))
+}
+
+fn read_maker_message(socket: &mut TcpStream) -> Result<MakerToTakerMessage, DirectoryServerError> {
+    let msg_bytes = read_message(socket)?;
+    Ok(serde_cbor::from_slice(&msg_bytes)?)
+}
+
+fn unexpected_message(expected: &str, received: &MakerToTakerMessage) -> DirectoryServerError {
+    DirectoryServerError::ProbeFailed(format!("Expected {}, received {}", expected, received))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_health() {
+        let unreachable = next_health(None, &Err::<Duration, ()>(()), 100);
+        assert_eq!(
+            unreachable,
+            MakerHealth {
+                reachable: false,
+                latency_ms: None,
+                last_probe: 100,
+                consecutive_failures: 1,
+            }
+        );
+
+        let still_unreachable = next_health(Some(&unreachable), &Err::<Duration, ()>(()), 200);
+        assert_eq!(still_unreachable.consecutive_failures, 2);
+
+        let reachable = next_health::<()>(
+            Some(&still_unreachable),
+            &Ok(Duration::from_millis(1500)),
+            300,
+        );
+        assert_eq!(
+            reachable,
+            MakerHealth {
+                reachable: true,
+                latency_ms: Some(1500),
+                last_probe: 300,
+                consecutive_failures: 0,
+            }
+        );
+    }
+}
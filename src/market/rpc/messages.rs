@@ -1,12 +1,30 @@
-use bitcoin::OutPoint;
+use bitcoin::{absolute::LockTime, Amount, OutPoint};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 
+use crate::{market::directory::Rejection, protocol::messages::MakerHealth};
+
 /// Directory server RPC message request
 #[derive(Serialize, Deserialize, Debug)]
 pub enum RpcMsgReq {
     /// ListAddresses RPC message request variant
     ListAddresses,
+    /// Request the details of all the listed makers.
+    ListEntries,
+    /// Request to remove a maker from the address book. It is listed again on its next post.
+    Evict(OutPoint),
+    /// Request to ban a fidelity bond outpoint, and evict the maker using it.
+    BanOutpoint(OutPoint),
+    /// Request to ban a maker address, and evict the makers using it.
+    BanAddress(String),
+    /// Request the server statistics.
+    Stats,
+    /// Request the last makers refused by the directory, with the reasons.
+    ListRejections,
+    /// Request to write the address book and the ban list to disk.
+    Flush,
+    /// Request to stop the directory server.
+    Stop,
 }
 
 /// Directory message RPC message Response
@@ -14,4 +32,73 @@ pub enum RpcMsgReq {
 pub enum RpcMsgResp {
     /// ListAddressesResp RPC message response variant
     ListAddressesResp(BTreeSet<(OutPoint, String)>),
+    /// Response with the details of all the listed makers.
+    ListEntriesResp(Vec<EntryInfo>),
+    /// Response with the address of the evicted maker, if it was listed.
+    Evicted(Option<String>),
+    /// Response with the number of makers evicted by a ban.
+    Banned(usize),
+    /// Response with the server statistics.
+    StatsResp(DirectoryStats),
+    /// Response with the last refused makers, oldest first.
+    ListRejectionsResp(Vec<Rejection>),
+    /// Response indicating the address book and the ban list were written to disk.
+    Flushed,
+    /// Response indicating the server is shutting down.
+    Shutdown,
+    /// Response with the internal server error.
+    ServerError(String),
+}
+
+/// Details of a maker listed by the directory server.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EntryInfo {
+    /// Fidelity bond outpoint of the maker.
+    pub outpoint: OutPoint,
+    /// Maker address.
+    pub address: String,
+    /// Total amount locked in the fidelity bonds, not their aggregated bond value. Unknown for makers loaded from
+    /// disk without a stored proof, until they post again.
+    pub bond_amount: Option<Amount>,
+    /// Number of fidelity bonds in the maker's proof. Zero for makers loaded from disk without a stored proof,
+    /// until they post again.
+    pub bond_count: usize,
+    /// Locktime of the primary fidelity bond. Unknown for makers loaded from disk without a stored proof, until
+    /// they post again.
+    pub locktime: Option<LockTime>,
+    /// Seconds since the maker last posted its address.
+    pub last_heartbeat_secs: u64,
+    /// Seconds until the maker is removed, if it doesn't post again.
+    pub ttl_remaining_secs: u64,
+    /// Whether the directory serves an offer of the maker.
+    pub has_offer: bool,
+    /// Result of the last liveness probe of the maker. Missing if not probed yet.
+    pub health: Option<MakerHealth>,
+}
+
+/// Statistics of the directory server.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DirectoryStats {
+    /// Seconds since the server started.
+    pub uptime_secs: u64,
+    /// Number of listed makers.
+    pub makers: usize,
+    /// Number of served maker offers.
+    pub offers: usize,
+    /// Number of makers reachable at their last liveness probe.
+    pub reachable_makers: usize,
+    /// Number of banned fidelity outpoints.
+    pub banned_outpoints: usize,
+    /// Number of banned maker addresses.
+    pub banned_addresses: usize,
+    /// Number of configured peer directory servers.
+    pub peers: usize,
+    /// Number of maker posts received.
+    pub posts: u64,
+    /// Number of maker entries rejected, from posts or peer syncs, for failed fidelity checks or bans.
+    pub rejected_posts: u64,
+    /// Number of taker address requests served.
+    pub gets: u64,
+    /// Number of peer directory syncs served.
+    pub syncs: u64,
 }
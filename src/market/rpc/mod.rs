@@ -2,5 +2,5 @@
 mod messages;
 mod server;
 
-pub use messages::{RpcMsgReq, RpcMsgResp};
+pub use messages::{DirectoryStats, EntryInfo, RpcMsgReq, RpcMsgResp};
 pub(crate) use server::start_rpc_server_thread;
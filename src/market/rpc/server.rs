@@ -1,41 +1,119 @@
-use bitcoin::OutPoint;
-
-use super::{RpcMsgReq, RpcMsgResp};
+use super::{
+    messages::{DirectoryStats, EntryInfo},
+    RpcMsgReq, RpcMsgResp,
+};
 use crate::{
     error::NetError,
-    market::directory::{DirectoryServer, DirectoryServerError},
-    utill::{read_message, send_message, HEART_BEAT_INTERVAL},
+    market::directory::{DirectoryServer, DirectoryServerError, ADDRESS_TTL},
+    net::{run_server, NetConfig, Response, Service},
 };
 use std::{
-    collections::{BTreeSet, HashMap},
-    io::ErrorKind,
-    net::{TcpListener, TcpStream},
-    sync::{atomic::Ordering::Relaxed, Arc, RwLock},
-    thread::sleep,
-    time::{Duration, Instant},
+    collections::BTreeSet,
+    net::TcpListener,
+    sync::{atomic::Ordering::Relaxed, Arc},
+    time::Duration,
 };
-fn handle_request(
-    socket: &mut TcpStream,
-    address: Arc<RwLock<HashMap<OutPoint, (String, Instant)>>>,
-) -> Result<(), DirectoryServerError> {
-    let req_bytes = read_message(socket)?;
-    let rpc_request: RpcMsgReq = serde_cbor::from_slice(&req_bytes).map_err(NetError::Cbor)?;
 
-    match rpc_request {
-        RpcMsgReq::ListAddresses => {
-            log::info!("RPC request received: {:?}", rpc_request);
-            let resp = RpcMsgResp::ListAddressesResp(
-                address
-                    .read()?
-                    .iter()
-                    .map(|(op, address)| (*op, address.0.clone()))
-                    .collect::<BTreeSet<_>>(),
-            );
-            send_message(socket, &resp)?;
+/// Serves `directory-cli` requests, one request per connection.
+struct RpcService {
+    directory: Arc<DirectoryServer>,
+}
+
+impl Service for RpcService {
+    type Connection = ();
+
+    fn handle_message(&self, _: &mut (), message: Vec<u8>) -> Response {
+        match handle_request(&message, &self.directory) {
+            Ok(response) => response.and_close(),
+            Err(e) => {
+                log::error!("Error handling RPC request: {:?}", e);
+                Response::close()
+            }
         }
     }
+}
 
-    Ok(())
+fn handle_request(
+    req_bytes: &[u8],
+    directory: &DirectoryServer,
+) -> Result<Response, DirectoryServerError> {
+    let rpc_request: RpcMsgReq = serde_cbor::from_slice(req_bytes).map_err(NetError::Cbor)?;
+    log::info!("RPC request received: {:?}", rpc_request);
+
+    let resp = match rpc_request {
+        RpcMsgReq::ListAddresses => RpcMsgResp::ListAddressesResp(
+            directory
+                .addresses
+                .read()?
+                .iter()
+                .map(|(op, address)| (*op, address.0.clone()))
+                .collect::<BTreeSet<_>>(),
+        ),
+        RpcMsgReq::ListEntries => {
+            let addresses = directory.addresses.read()?;
+            let offers = directory.offers.read()?;
+            let proofs = directory.proofs.read()?;
+            let health = directory.health.read()?;
+            let mut entries = addresses
+                .iter()
+                .map(|(outpoint, (address, last_heartbeat))| {
+                    let proof = proofs.get(outpoint);
+                    EntryInfo {
+                        outpoint: *outpoint,
+                        address: address.clone(),
+                        bond_amount: proof.map(|proof| proof.total_amount()),
+                        bond_count: proof.map_or(0, |proof| proof.bonds().count()),
+                        locktime: proof.map(|proof| proof.bond.lock_time),
+                        last_heartbeat_secs: last_heartbeat.elapsed().as_secs(),
+                        ttl_remaining_secs: ADDRESS_TTL
+                            .saturating_sub(last_heartbeat.elapsed())
+                            .as_secs(),
+                        has_offer: offers.contains_key(outpoint),
+                        health: health.get(outpoint).cloned(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by_key(|entry| entry.outpoint);
+            RpcMsgResp::ListEntriesResp(entries)
+        }
+        RpcMsgReq::Evict(outpoint) => RpcMsgResp::Evicted(directory.evict(&outpoint)?),
+        RpcMsgReq::BanOutpoint(outpoint) => RpcMsgResp::Banned(directory.ban_outpoint(outpoint)?),
+        RpcMsgReq::BanAddress(address) => RpcMsgResp::Banned(directory.ban_address(address)?),
+        RpcMsgReq::Stats => {
+            let banned = directory.banned.read()?;
+            let counters = &directory.counters;
+            RpcMsgResp::StatsResp(DirectoryStats {
+                uptime_secs: directory.start_time.elapsed().as_secs(),
+                makers: directory.addresses.read()?.len(),
+                offers: directory.offers.read()?.len(),
+                reachable_makers: directory
+                    .health
+                    .read()?
+                    .values()
+                    .filter(|health| health.reachable)
+                    .count(),
+                banned_outpoints: banned.outpoints.len(),
+                banned_addresses: banned.addresses.len(),
+                peers: directory.peers.len(),
+                posts: counters.posts.load(Relaxed),
+                rejected_posts: counters.rejected_posts.load(Relaxed),
+                gets: counters.gets.load(Relaxed),
+                syncs: counters.syncs.load(Relaxed),
+            })
+        }
+        RpcMsgReq::ListRejections => {
+            RpcMsgResp::ListRejectionsResp(directory.rejections.read()?.iter().cloned().collect())
+        }
+        RpcMsgReq::Flush => match directory.flush_to_disk() {
+            Ok(()) => RpcMsgResp::Flushed,
+            Err(e) => RpcMsgResp::ServerError(format!("{:?}", e)),
+        },
+        RpcMsgReq::Stop => {
+            directory.shutdown.store(true, Relaxed);
+            RpcMsgResp::Shutdown
+        }
+    };
+    Ok(Response::reply(&resp)?)
 }
 
 pub fn start_rpc_server_thread(
@@ -43,32 +121,26 @@ pub fn start_rpc_server_thread(
 ) -> Result<(), DirectoryServerError> {
     let rpc_port = directory.rpc_port;
     let rpc_socket = format!("127.0.0.1:{}", rpc_port);
-    let listener = Arc::new(TcpListener::bind(&rpc_socket)?);
+    let listener = TcpListener::bind(&rpc_socket)?;
     log::info!("RPC socket binding successful at {}", rpc_socket);
 
-    listener.set_nonblocking(true)?;
-
-    while !directory.shutdown.load(Relaxed) {
-        match listener.accept() {
-            Ok((mut stream, addr)) => {
-                log::info!("Got RPC request from: {}", addr);
-                stream.set_read_timeout(Some(Duration::from_secs(20)))?;
-                stream.set_write_timeout(Some(Duration::from_secs(20)))?;
-                if let Err(e) = handle_request(&mut stream, directory.addresses.clone()) {
-                    log::error!("Error handling RPC request: {:?}", e);
-                }
-            }
-            Err(e) => {
-                if e.kind() == ErrorKind::WouldBlock {
-                    // do nothing
-                } else {
-                    log::error!("Error accepting RPC connection: {:?}", e);
-                }
-            }
-        }
-        // use heart_beat
-        sleep(HEART_BEAT_INTERVAL);
-    }
+    let config = NetConfig {
+        max_connections: 10,
+        idle_timeout: Duration::from_secs(20),
+        worker_threads: 1,
+        ..NetConfig::default()
+    };
+    let service = Arc::new(RpcService {
+        directory: directory.clone(),
+    });
+    run_server(
+        "Directory RPC",
+        listener,
+        service,
+        config,
+        Vec::new(),
+        &directory.shutdown,
+    )?;
 
     Ok(())
 }
@@ -0,0 +1,858 @@
+//! Event driven networking core, shared by makerd and directoryd.
+//!
+//! One event loop thread owns the listening socket and all client sockets. Sockets are non-blocking and
+//! driven by readiness events, so accepting a client or reading its messages never waits on a sleep.
+//! Complete messages are handed to a small pool of worker threads, as the handlers block on wallet locks and
+//! Bitcoin Core RPC. A connection has at most one message in flight, so the protocol stays sequential per client.
+//!
+//! Short periodic background jobs (idle checks, core pings, ...) run on the same worker pool, scheduled by the
+//! event loop. Jobs that block for long (contract watching, directory posting, ...) get a thread of their own,
+//! so they never starve the message handlers. Everything stops as soon as the shutdown flag is raised.
+
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind, Read, Write},
+    net::IpAddr,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use mio::{
+    net::{TcpListener, TcpStream},
+    Events, Interest, Poll, Token, Waker,
+};
+use serde::Serialize;
+
+use crate::{
+    error::NetError,
+    utill::{MAX_MESSAGE_SIZE, NET_TIMEOUT},
+};
+
+const LISTENER: Token = Token(0);
+const WAKER: Token = Token(1);
+const FIRST_CLIENT: usize = 2;
+
+/// Upper bound on how long the event loop sleeps, i.e. the latency to observe the shutdown flag.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Window of the message rate limit.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Why the server drops a client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DropReason {
+    /// The server is already serving `max_connections` clients.
+    TooManyConnections,
+    /// The client sent more than `max_messages_per_minute` messages, see [`RateKey`].
+    RateLimited,
+    /// The client announced a message larger than `max_message_size`.
+    MessageTooLarge,
+}
+
+/// What the message rate limit is counted on.
+///
+/// Remote peers are counted over all the connections of their address. Loopback peers are counted per
+/// connection: behind Tor every peer connects from the local hidden service, so their address says nothing. The
+/// number of those connections is bounded by `max_connections`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RateKey {
+    Address(IpAddr),
+    Connection(Token),
+}
+
+impl RateKey {
+    fn new(peer: IpAddr, token: Token) -> Self {
+        if peer.is_loopback() {
+            RateKey::Connection(token)
+        } else {
+            RateKey::Address(peer)
+        }
+    }
+}
+
+/// Outcome of handling one message.
+#[derive(Debug, Default)]
+pub(crate) struct Response {
+    reply: Option<Vec<u8>>,
+    close: bool,
+}
+
+impl Response {
+    /// Nothing to send back, keep the connection open.
+    pub(crate) fn none() -> Self {
+        Self::default()
+    }
+
+    /// Send back the serialized message, keep the connection open.
+    pub(crate) fn reply(message: &impl Serialize) -> Result<Self, NetError> {
+        Ok(Self {
+            reply: Some(serde_cbor::to_vec(message)?),
+            close: false,
+        })
+    }
+
+    /// Close the connection, after the reply (if any) is sent.
+    pub(crate) fn and_close(mut self) -> Self {
+        self.close = true;
+        self
+    }
+
+    /// Close the connection without a reply.
+    pub(crate) fn close() -> Self {
+        Self::none().and_close()
+    }
+}
+
+/// A request-response service served by the event loop.
+pub(crate) trait Service: Send + Sync + 'static {
+    /// Per connection state, carried from one message of the client to the next.
+    type Connection: Default + Send + 'static;
+
+    /// Handles one complete message of a client. Runs on a worker thread, so it may block.
+    fn handle_message(&self, connection: &mut Self::Connection, message: Vec<u8>) -> Response;
+
+    /// The serialized message sent to a client right before it's dropped. `None` drops silently.
+    fn rejection(&self, _reason: DropReason) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// Limits applied by the event loop.
+#[derive(Debug, Clone)]
+pub(crate) struct NetConfig {
+    /// Maximum number of concurrent client connections.
+    pub(crate) max_connections: usize,
+    /// Maximum size of a single client message.
+    pub(crate) max_message_size: u32,
+    /// Maximum number of messages per minute from a single peer address, or a single connection for loopback
+    /// peers (Tor).
+    pub(crate) max_messages_per_minute: u32,
+    /// Clients silent for longer than this are disconnected. So are clients taking longer than this to send a
+    /// message, counted from its first byte.
+    pub(crate) idle_timeout: Duration,
+    /// Number of worker threads handling messages and the periodic tasks not running on their own thread.
+    pub(crate) worker_threads: usize,
+}
+
+impl Default for NetConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 100,
+            max_message_size: MAX_MESSAGE_SIZE,
+            max_messages_per_minute: 600,
+            idle_timeout: NET_TIMEOUT,
+            worker_threads: 4,
+        }
+    }
+}
+
+type TaskFn = dyn Fn() -> Duration + Send + Sync;
+
+/// A background job run on the worker pool until shutdown.
+pub(crate) struct PeriodicTask {
+    name: &'static str,
+    task: Arc<TaskFn>,
+    next_run: Instant,
+    running: bool,
+    dedicated: bool,
+}
+
+impl PeriodicTask {
+    /// The task first runs right away. It returns the delay until its next run.
+    pub(crate) fn new(
+        name: &'static str,
+        task: impl Fn() -> Duration + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name,
+            task: Arc::new(task),
+            next_run: Instant::now(),
+            running: false,
+            dedicated: false,
+        }
+    }
+
+    /// Runs the task on a thread of its own instead of the worker pool. For tasks that block for long.
+    pub(crate) fn on_own_thread(mut self) -> Self {
+        self.dedicated = true;
+        self
+    }
+
+    /// Runs the task on the current thread, until `stop` returns true.
+    fn run_until(&mut self, stop: impl Fn() -> bool) {
+        while !stop() {
+            let now = Instant::now();
+            if now < self.next_run {
+                thread::sleep((self.next_run - now).min(SHUTDOWN_POLL_INTERVAL));
+                continue;
+            }
+            let delay = run_task(self.name, &*self.task);
+            self.next_run = Instant::now() + delay;
+        }
+    }
+}
+
+/// Runs a task once, returns the delay until its next run.
+fn run_task(name: &str, task: &TaskFn) -> Duration {
+    panic::catch_unwind(AssertUnwindSafe(task)).unwrap_or_else(|_| {
+        log::error!("Task {} panicked", name);
+        NET_TIMEOUT
+    })
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Fixed size pool of worker threads. Dropping the pool waits for the running jobs to finish.
+pub(crate) struct WorkerPool {
+    sender: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub(crate) fn new(name: &str, size: usize) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..size.max(1))
+            .map(|i| {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("{} Worker {}", name, i))
+                    .spawn(move || loop {
+                        let job = match receiver.lock() {
+                            Ok(receiver) => receiver.recv(),
+                            Err(_) => break,
+                        };
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break, // Pool dropped.
+                        }
+                    })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self {
+            sender: Some(sender),
+            workers,
+        })
+    }
+
+    pub(crate) fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        if let Some(sender) = &self.sender {
+            // Workers only stop when the sender is dropped, so this can't fail.
+            let _ = sender.send(Box::new(job));
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                log::error!("Worker thread panicked");
+            }
+        }
+    }
+}
+
+struct Client<C> {
+    stream: TcpStream,
+    rate_key: RateKey,
+    /// Kept for the whole connection, a message may arrive over several reads. Holds at most one maximum size
+    /// message with its prefix, the rest is left in the socket.
+    read_buf: Vec<u8>,
+    write_buf: Vec<u8>,
+    /// `None` while a message of this client is being handled.
+    state: Option<C>,
+    last_activity: Instant,
+    /// When the first byte of the incomplete message at the front of `read_buf` arrived.
+    frame_started: Option<Instant>,
+    /// Peer closed its side. Buffered messages are still handled.
+    read_closed: bool,
+    /// Close as soon as the write buffer is flushed.
+    closing: bool,
+    /// Unrecoverable socket error, drop right away.
+    failed: bool,
+    /// Current registration, `None` while deregistered.
+    interest: Option<Interest>,
+}
+
+impl<C: Default> Client<C> {
+    fn new(stream: TcpStream, rate_key: RateKey) -> Self {
+        Self {
+            stream,
+            rate_key,
+            read_buf: Vec::new(),
+            write_buf: Vec::new(),
+            state: Some(C::default()),
+            last_activity: Instant::now(),
+            frame_started: None,
+            read_closed: false,
+            closing: false,
+            failed: false,
+            interest: Some(Interest::READABLE),
+        }
+    }
+
+    /// Reads everything available, up to `limit` buffered bytes. Readiness events are edge triggered, so read
+    /// until `WouldBlock` or the limit.
+    fn read_available(&mut self, limit: usize) {
+        let mut buf = [0u8; 4096];
+        while self.read_buf.len() < limit {
+            let room = (limit - self.read_buf.len()).min(buf.len());
+            match self.stream.read(&mut buf[..room]) {
+                Ok(0) => {
+                    self.read_closed = true;
+                    break;
+                }
+                Ok(n) => {
+                    self.read_buf.extend_from_slice(&buf[..n]);
+                    self.last_activity = Instant::now();
+                    self.frame_started.get_or_insert(self.last_activity);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.failed = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Writes as much of the write buffer as the socket takes.
+    fn flush(&mut self) {
+        while !self.write_buf.is_empty() {
+            match self.stream.write(&self.write_buf) {
+                Ok(0) => {
+                    self.failed = true;
+                    break;
+                }
+                Ok(n) => {
+                    self.write_buf.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.failed = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Queues a length prefixed message, same framing as [`send_message`](crate::utill::send_message).
+    fn queue(&mut self, message: &[u8]) {
+        self.write_buf
+            .extend_from_slice(&(message.len() as u32).to_be_bytes());
+        self.write_buf.extend_from_slice(message);
+        self.flush();
+    }
+
+    /// Length of the next message announced in the read buffer, if the prefix is complete.
+    fn next_message_len(&self) -> Option<u32> {
+        self.read_buf
+            .get(..4)
+            .map(|prefix| u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]))
+    }
+
+    /// Takes the next complete message out of the read buffer.
+    fn take_message(&mut self) -> Option<Vec<u8>> {
+        let len = self.next_message_len()? as usize;
+        if self.read_buf.len() < 4 + len {
+            return None;
+        }
+        let message = self.read_buf[4..4 + len].to_vec();
+        self.read_buf.drain(..4 + len);
+        // The next message, if already started, is timed from now.
+        self.frame_started = (!self.read_buf.is_empty()).then(Instant::now);
+        Some(message)
+    }
+
+    fn has_message(&self) -> bool {
+        self.next_message_len()
+            .is_some_and(|len| self.read_buf.len() >= 4 + len as usize)
+    }
+
+    /// Whether to read more: not while a complete message is queued or the buffer is full.
+    fn wants_read(&self, limit: usize) -> bool {
+        !self.closing && !self.read_closed && !self.has_message() && self.read_buf.len() < limit
+    }
+
+    fn wanted_interest(&self, limit: usize) -> Option<Interest> {
+        match (self.wants_read(limit), !self.write_buf.is_empty()) {
+            (true, true) => Some(Interest::READABLE | Interest::WRITABLE),
+            (true, false) => Some(Interest::READABLE),
+            (false, true) => Some(Interest::WRITABLE),
+            (false, false) => None,
+        }
+    }
+
+    /// Nothing left to do for this client: no message in flight, nothing to write and nothing more to read.
+    fn is_done(&self) -> bool {
+        self.failed
+            || (self.state.is_some()
+                && self.write_buf.is_empty()
+                && (self.closing || (self.read_closed && !self.has_message())))
+    }
+}
+
+/// Runs the event loop on the current thread, until `shutdown` is raised.
+///
+/// The `listener` is served with `service`, and the `tasks` are run at their own pace, on the worker pool or on
+/// their own thread. Returns once all of them are stopped.
+pub(crate) fn run_server<S: Service>(
+    name: &str,
+    listener: std::net::TcpListener,
+    service: Arc<S>,
+    config: NetConfig,
+    tasks: Vec<PeriodicTask>,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let (dedicated, pooled): (Vec<_>, Vec<_>) = tasks.into_iter().partition(|task| task.dedicated);
+    // Also stops the dedicated tasks when the event loop fails.
+    let stopped = AtomicBool::new(false);
+    let stop = || shutdown.load(Relaxed) || stopped.load(Relaxed);
+    thread::scope(|scope| {
+        let result = dedicated
+            .into_iter()
+            .try_for_each(|mut task| {
+                thread::Builder::new()
+                    .name(format!("{} {}", name, task.name))
+                    .spawn_scoped(scope, move || task.run_until(stop))
+                    .map(drop)
+            })
+            .and_then(|_| event_loop(name, listener, service, config, pooled, shutdown));
+        stopped.store(true, Relaxed);
+        result
+    })
+}
+
+fn event_loop<S: Service>(
+    name: &str,
+    listener: std::net::TcpListener,
+    service: Arc<S>,
+    config: NetConfig,
+    mut tasks: Vec<PeriodicTask>,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let mut listener = TcpListener::from_std(listener);
+    let mut poll = Poll::new()?;
+    poll.registry()
+        .register(&mut listener, LISTENER, Interest::READABLE)?;
+    let waker = Arc::new(Waker::new(poll.registry(), WAKER)?);
+
+    let pool = WorkerPool::new(name, config.worker_threads)?;
+    let (message_done_tx, message_done_rx) = mpsc::channel::<(Token, S::Connection, Response)>();
+    let (task_done_tx, task_done_rx) = mpsc::channel::<(usize, Duration)>();
+
+    let mut clients = HashMap::<Token, Client<S::Connection>>::new();
+    // Message count of each rate key in its current one minute window.
+    let mut rate_windows = HashMap::<RateKey, (Instant, u32)>::new();
+    let read_limit = 4 + config.max_message_size as usize;
+    let mut next_token = FIRST_CLIENT;
+    let mut events = Events::with_capacity(256);
+
+    while !shutdown.load(Relaxed) {
+        let now = Instant::now();
+        let next_task = tasks
+            .iter()
+            .filter(|task| !task.running)
+            .map(|task| task.next_run.saturating_duration_since(now))
+            .min();
+        let next_idle = clients
+            .values()
+            .filter(|client| client.state.is_some())
+            .map(|client| {
+                (client.last_activity + config.idle_timeout).saturating_duration_since(now)
+            })
+            .min();
+        let timeout = next_task
+            .into_iter()
+            .chain(next_idle)
+            .fold(SHUTDOWN_POLL_INTERVAL, Duration::min);
+
+        if let Err(e) = poll.poll(&mut events, Some(timeout)) {
+            if e.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+
+        for event in events.iter() {
+            match event.token() {
+                LISTENER => loop {
+                    let (mut stream, addr) = match listener.accept() {
+                        Ok(accepted) => accepted,
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                        Err(e) => {
+                            log::error!("[{}] Error accepting connection: {:?}", name, e);
+                            break;
+                        }
+                    };
+                    if clients.len() >= config.max_connections {
+                        log::warn!("[{}] Too many connections, rejecting {}", name, addr);
+                        if let Some(rejection) = service.rejection(DropReason::TooManyConnections) {
+                            // Best effort, the socket is fresh so the small message fits its buffer.
+                            let mut framed = (rejection.len() as u32).to_be_bytes().to_vec();
+                            framed.extend(rejection);
+                            let _ = stream.write_all(&framed);
+                        }
+                        continue;
+                    }
+                    let token = Token(next_token);
+                    next_token += 1;
+                    poll.registry()
+                        .register(&mut stream, token, Interest::READABLE)?;
+                    log::debug!("[{}] Accepted connection from {}", name, addr);
+                    clients.insert(token, Client::new(stream, RateKey::new(addr.ip(), token)));
+                },
+                WAKER => {} // Completions are collected below.
+                token => {
+                    if let Some(client) = clients.get_mut(&token) {
+                        if event.is_readable() {
+                            client.read_available(read_limit);
+                        }
+                        if event.is_writable() {
+                            client.flush();
+                        }
+                    }
+                }
+            }
+        }
+
+        // Collect handled messages.
+        while let Ok((token, state, response)) = message_done_rx.try_recv() {
+            if let Some(client) = clients.get_mut(&token) {
+                client.state = Some(state);
+                client.last_activity = Instant::now();
+                // Don't count the handling time against the next message.
+                if client.frame_started.is_some() {
+                    client.frame_started = Some(client.last_activity);
+                }
+                // Already rejected while the message was handled.
+                if client.closing {
+                    continue;
+                }
+                if let Some(reply) = response.reply {
+                    client.queue(&reply);
+                }
+                client.closing |= response.close;
+            }
+        }
+
+        // Collect finished tasks.
+        while let Ok((index, delay)) = task_done_rx.try_recv() {
+            if let Some(task) = tasks.get_mut(index) {
+                task.running = false;
+                task.next_run = Instant::now() + delay;
+            }
+        }
+
+        // Dispatch messages, enforce limits and drop finished clients.
+        let mut finished = Vec::new();
+        for (token, client) in clients.iter_mut() {
+            // Checked as soon as the prefix arrives, even with a message in flight.
+            if !client.closing
+                && client
+                    .next_message_len()
+                    .is_some_and(|len| len > config.max_message_size)
+            {
+                let reason = DropReason::MessageTooLarge;
+                log::warn!("[{}] Dropping client: {:?}", name, reason);
+                if let Some(rejection) = service.rejection(reason) {
+                    client.queue(&rejection);
+                }
+                client.closing = true;
+            }
+            if client.state.is_some() && !client.closing {
+                if let Some(message) = client.take_message() {
+                    let rate_window = rate_windows
+                        .entry(client.rate_key)
+                        .or_insert((Instant::now(), 0));
+                    if rate_window.0.elapsed() > RATE_WINDOW {
+                        *rate_window = (Instant::now(), 0);
+                    }
+                    rate_window.1 += 1;
+                    if rate_window.1 > config.max_messages_per_minute {
+                        log::warn!("[{}] Dropping client: {:?}", name, DropReason::RateLimited);
+                        if let Some(rejection) = service.rejection(DropReason::RateLimited) {
+                            client.queue(&rejection);
+                        }
+                        client.closing = true;
+                    } else if let Some(mut state) = client.state.take() {
+                        let service = service.clone();
+                        let done = message_done_tx.clone();
+                        let waker = waker.clone();
+                        let token = *token;
+                        let name = name.to_string();
+                        pool.execute(move || {
+                            let response = panic::catch_unwind(AssertUnwindSafe(|| {
+                                service.handle_message(&mut state, message)
+                            }))
+                            .unwrap_or_else(|_| {
+                                log::error!("[{}] Message handler panicked", name);
+                                Response::close()
+                            });
+                            let _ = done.send((token, state, response));
+                            let _ = waker.wake();
+                        });
+                    }
+                } else if client
+                    .frame_started
+                    .is_some_and(|started| started.elapsed() > config.idle_timeout)
+                {
+                    // The rest of the message may still trickle in, the connection can't be reused.
+                    log::info!(
+                        "[{}] Dropping client stalled in the middle of a message",
+                        name
+                    );
+                    client.failed = true;
+                } else if client.last_activity.elapsed() > config.idle_timeout {
+                    log::info!("[{}] Closing idle connection", name);
+                    client.closing = true;
+                }
+            }
+
+            // Readiness edges are missed while not reading, catch up with what arrived meanwhile.
+            let reading = client.interest.is_some_and(|i| i.is_readable());
+            if !reading && client.wants_read(read_limit) {
+                client.read_available(read_limit);
+                if client.state.is_some() && client.has_message() {
+                    let _ = waker.wake();
+                }
+            }
+
+            // Only wait for readability while there is room for a message, and for writability while there is
+            // something to write.
+            let interest = client.wanted_interest(read_limit);
+            if interest != client.interest {
+                let registry = poll.registry();
+                let result = match (client.interest, interest) {
+                    (None, Some(interest)) => {
+                        registry.register(&mut client.stream, *token, interest)
+                    }
+                    (Some(_), Some(interest)) => {
+                        registry.reregister(&mut client.stream, *token, interest)
+                    }
+                    (_, None) => registry.deregister(&mut client.stream),
+                };
+                if result.is_err() {
+                    client.failed = true;
+                }
+                client.interest = interest;
+            }
+
+            if client.is_done() {
+                finished.push(*token);
+            }
+        }
+        for token in finished {
+            if let Some(mut client) = clients.remove(&token) {
+                if client.interest.is_some() {
+                    let _ = poll.registry().deregister(&mut client.stream);
+                }
+                if let RateKey::Connection(_) = client.rate_key {
+                    rate_windows.remove(&client.rate_key);
+                }
+            }
+        }
+        // Expired windows are reset on their next message anyway.
+        if rate_windows.len() > clients.len() {
+            rate_windows.retain(|_, (start, _)| start.elapsed() <= RATE_WINDOW);
+        }
+
+        // Start due tasks.
+        let now = Instant::now();
+        for (index, task) in tasks.iter_mut().enumerate() {
+            if task.running || task.next_run > now {
+                continue;
+            }
+            task.running = true;
+            let run = task.task.clone();
+            let done = task_done_tx.clone();
+            let waker = waker.clone();
+            let task_name = task.name;
+            pool.execute(move || {
+                let delay = run_task(task_name, &*run);
+                let _ = done.send((index, delay));
+                let _ = waker.wake();
+            });
+        }
+    }
+
+    log::info!("[{}] Shutting down event loop", name);
+    drop(clients);
+    // Waits for the running handlers and tasks.
+    drop(pool);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utill::{read_message, send_message};
+    use std::net::TcpStream as StdTcpStream;
+
+    /// Replies the number of messages seen on the connection so far.
+    struct Counter;
+
+    impl Service for Counter {
+        type Connection = u32;
+
+        fn handle_message(&self, count: &mut u32, message: Vec<u8>) -> Response {
+            *count += 1;
+            match serde_cbor::from_slice::<String>(&message) {
+                Ok(text) if text == "bye" => Response::close(),
+                _ => Response::reply(count).unwrap(),
+            }
+        }
+
+        fn rejection(&self, reason: DropReason) -> Option<Vec<u8>> {
+            serde_cbor::to_vec(&format!("{:?}", reason)).ok()
+        }
+    }
+
+    #[test]
+    fn test_event_loop() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let ticks = Arc::new(Mutex::new(0u32));
+
+        let config = NetConfig {
+            max_connections: 2,
+            max_message_size: 64,
+            worker_threads: 1,
+            ..NetConfig::default()
+        };
+        let ticks_clone = ticks.clone();
+        let task = PeriodicTask::new("ticker", move || {
+            *ticks_clone.lock().unwrap() += 1;
+            Duration::from_millis(10)
+        });
+        // Would starve the single worker if it ran on the pool.
+        let blocking_task = PeriodicTask::new("blocker", || {
+            thread::sleep(Duration::from_millis(50));
+            Duration::ZERO
+        })
+        .on_own_thread();
+        let shutdown_clone = shutdown.clone();
+        let server = thread::spawn(move || {
+            run_server(
+                "Test",
+                listener,
+                Arc::new(Counter),
+                config,
+                vec![blocking_task, task],
+                &shutdown_clone,
+            )
+        });
+
+        // State is kept per connection.
+        let mut first = StdTcpStream::connect(addr).unwrap();
+        let mut second = StdTcpStream::connect(addr).unwrap();
+        for expected in 1..=3u32 {
+            send_message(&mut first, &"hello").unwrap();
+            let count: u32 = serde_cbor::from_slice(&read_message(&mut first).unwrap()).unwrap();
+            assert_eq!(count, expected);
+        }
+        send_message(&mut second, &"hello").unwrap();
+        let count: u32 = serde_cbor::from_slice(&read_message(&mut second).unwrap()).unwrap();
+        assert_eq!(count, 1);
+
+        // Over the connection limit.
+        let mut third = StdTcpStream::connect(addr).unwrap();
+        let reason: String = serde_cbor::from_slice(&read_message(&mut third).unwrap()).unwrap();
+        assert_eq!(reason, "TooManyConnections");
+
+        // Oversized message.
+        send_message(&mut second, &"x".repeat(100)).unwrap();
+        let reason: String = serde_cbor::from_slice(&read_message(&mut second).unwrap()).unwrap();
+        assert_eq!(reason, "MessageTooLarge");
+        assert!(read_message(&mut second).is_err());
+
+        // Closed by the service.
+        send_message(&mut first, &"bye").unwrap();
+        assert!(read_message(&mut first).is_err());
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while *ticks.lock().unwrap() < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(*ticks.lock().unwrap() > 1);
+
+        let start = Instant::now();
+        shutdown.store(true, Relaxed);
+        server.join().unwrap().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_rate_limit_and_stalled_message() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let config = NetConfig {
+            max_messages_per_minute: 3,
+            idle_timeout: Duration::from_millis(300),
+            ..NetConfig::default()
+        };
+        let shutdown_clone = shutdown.clone();
+        let server = thread::spawn(move || {
+            run_server(
+                "Test",
+                listener,
+                Arc::new(Counter),
+                config,
+                Vec::new(),
+                &shutdown_clone,
+            )
+        });
+
+        // A message stalled halfway drops the client, even if it keeps trickling in.
+        let mut stalled = StdTcpStream::connect(addr).unwrap();
+        stalled.write_all(&10u32.to_be_bytes()).unwrap();
+        for _ in 0..5 {
+            thread::sleep(Duration::from_millis(100));
+            let _ = stalled.write_all(&[0]);
+        }
+        assert!(read_message(&mut stalled).is_err());
+
+        // Loopback peers are rate limited per connection, they may all be Tor peers.
+        let mut first = StdTcpStream::connect(addr).unwrap();
+        let mut second = StdTcpStream::connect(addr).unwrap();
+        for _ in 0..3 {
+            send_message(&mut first, &"hello").unwrap();
+            read_message(&mut first).unwrap();
+        }
+        send_message(&mut second, &"hello").unwrap();
+        let count: u32 = serde_cbor::from_slice(&read_message(&mut second).unwrap()).unwrap();
+        assert_eq!(count, 1);
+        send_message(&mut first, &"hello").unwrap();
+        let reason: String = serde_cbor::from_slice(&read_message(&mut first).unwrap()).unwrap();
+        assert_eq!(reason, "RateLimited");
+
+        // An oversized prefix behind a queued message is rejected once that message is answered.
+        let mut third = StdTcpStream::connect(addr).unwrap();
+        let mut frames = serde_cbor::to_vec(&"hello").unwrap();
+        frames.splice(0..0, (frames.len() as u32).to_be_bytes());
+        frames.extend_from_slice(&u32::MAX.to_be_bytes());
+        third.write_all(&frames).unwrap();
+        let count: u32 = serde_cbor::from_slice(&read_message(&mut third).unwrap()).unwrap();
+        assert_eq!(count, 1);
+        let reason: String = serde_cbor::from_slice(&read_message(&mut third).unwrap()).unwrap();
+        assert_eq!(reason, "MessageTooLarge");
+        assert!(read_message(&mut third).is_err());
+
+        shutdown.store(true, Relaxed);
+        server.join().unwrap().unwrap();
+    }
+}
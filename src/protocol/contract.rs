@@ -10,15 +10,18 @@ use bitcoin::{
         opcodes::{self, all},
         script::{Builder, Instruction, Script},
     },
+    consensus::deserialize,
     ecdsa::Signature,
-    hashes::Hash,
+    hashes::{hex::FromHex, sha256, Hash},
+    merkle_tree::MerkleBlock,
     secp256k1::{
+        ecdsa,
         rand::{rngs::OsRng, RngCore},
         Message, Secp256k1, SecretKey,
     },
     sighash::{EcdsaSighashType, SighashCache},
     transaction::Version,
-    Amount, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+    Amount, BlockHash, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
 };
 
 pub(crate) use bitcoin::hashes::hash160::Hash as Hash160;
@@ -128,6 +131,36 @@ pub(crate) fn calculate_pubkey_from_nonce(
     })
 }
 
+/// Digest signed to authorize a [ReqAbort](super::messages::ReqAbort) of the swap `id`.
+fn abort_request_digest(id: &str) -> Message {
+    Message::from_digest(
+        sha256::Hash::hash(format!("coinswap abort {}", id).as_bytes()).to_byte_array(),
+    )
+}
+
+/// Sign a [ReqAbort](super::messages::ReqAbort) with the multisig nonce of the Maker's first incoming swapcoin.
+/// The nonce is only known to the Taker and that Maker, it is sent to the Maker alone in its [ProofOfFunding].
+pub(crate) fn sign_abort_request(id: &str, multisig_nonce: &SecretKey) -> ecdsa::Signature {
+    Secp256k1::new().sign_ecdsa(&abort_request_digest(id), multisig_nonce)
+}
+
+/// Verify the signature of a [ReqAbort](super::messages::ReqAbort), made with the nonce of `multisig_pubkey`,
+/// the Maker's own key of its first incoming multisig, derived from its `tweakable_point`.
+pub(crate) fn verify_abort_request(
+    id: &str,
+    sig: &ecdsa::Signature,
+    tweakable_point: &PublicKey,
+    multisig_pubkey: &PublicKey,
+) -> Result<(), ProtocolError> {
+    let secp = Secp256k1::new();
+    let nonce_point = tweakable_point
+        .inner
+        .negate(&secp)
+        .combine(&multisig_pubkey.inner)?;
+    secp.verify_ecdsa(&abort_request_digest(id), sig, &nonce_point)?;
+    Ok(())
+}
+
 /// Find the index of the funding output in the funding transaction.
 pub(crate) fn find_funding_output_index(
     funding_tx_info: &FundingTxInfo,
@@ -144,6 +177,28 @@ pub(crate) fn find_funding_output_index(
         ))
 }
 
+/// Check that the merkle proof of a funding tx includes it, and return the hash of the block it got confirmed in.
+///
+/// The proof is a hex encoded merkle block, as returned by `gettxoutproof`.
+pub(crate) fn check_funding_merkle_proof(
+    funding_tx_info: &FundingTxInfo,
+) -> Result<BlockHash, ProtocolError> {
+    let merkle_block = Vec::<u8>::from_hex(&funding_tx_info.funding_tx_merkleproof)
+        .ok()
+        .and_then(|bytes| deserialize::<MerkleBlock>(&bytes).ok())
+        .ok_or(ProtocolError::General("Invalid funding tx merkle proof"))?;
+    let (mut matches, mut indexes) = (Vec::new(), Vec::new());
+    merkle_block
+        .extract_matches(&mut matches, &mut indexes)
+        .map_err(|_| ProtocolError::General("Funding tx merkle proof doesn't match its block"))?;
+    if !matches.contains(&funding_tx_info.funding_tx.compute_txid()) {
+        return Err(ProtocolError::General(
+            "Funding tx merkle proof doesn't include the funding tx",
+        ));
+    }
+    Ok(merkle_block.header.block_hash())
+}
+
 /// Check if the given redeem script is a multisig script.
 pub(crate) fn check_reedemscript_is_multisig(redeemscript: &Script) -> Result<(), ProtocolError> {
     //pattern match to check redeemscript is really a 2of2 multisig
@@ -547,6 +602,26 @@ mod test {
         Ok((hashpub, timepub))
     }
 
+    #[test]
+    fn test_abort_request_auth() {
+        let secp = Secp256k1::new();
+        let tweakable_privkey = SecretKey::from_slice(&[1; 32]).unwrap();
+        let tweakable_point = PublicKey {
+            compressed: true,
+            inner: secp256k1::PublicKey::from_secret_key(&secp, &tweakable_privkey),
+        };
+        let nonce = SecretKey::from_slice(&[2; 32]).unwrap();
+        let multisig_pubkey = calculate_pubkey_from_nonce(&tweakable_point, &nonce).unwrap();
+
+        let sig = sign_abort_request("swap", &nonce);
+        verify_abort_request("swap", &sig, &tweakable_point, &multisig_pubkey).unwrap();
+        // Bound to the swap id and to the nonce.
+        assert!(verify_abort_request("other", &sig, &tweakable_point, &multisig_pubkey).is_err());
+        let other_nonce = SecretKey::from_slice(&[3; 32]).unwrap();
+        let sig = sign_abort_request("swap", &other_nonce);
+        assert!(verify_abort_request("swap", &sig, &tweakable_point, &multisig_pubkey).is_err());
+    }
+
     #[test]
     fn test_maker_pubkey_computation() {
         let secp = Secp256k1::new();
@@ -1296,6 +1371,7 @@ mod test {
             refund_locktime: u16::default(),
             contract_feerate: u64::default(),
             id: "random".to_string(),
+            tier: 0,
         };
 
         // case with same hash value
@@ -1326,6 +1402,7 @@ mod test {
             refund_locktime: u16::default(),
             contract_feerate: u64::default(),
             id: "random".to_string(),
+            tier: 0,
         };
 
         let hash_value_from_fn = check_hashvalues_are_equal(&funding_proof).unwrap_err();
@@ -1339,4 +1416,67 @@ mod test {
             "contract reedemscript doesn't have equal hashvalues"
         );
     }
+
+    #[test]
+    fn test_check_funding_merkle_proof() {
+        use bitcoin::{
+            block::{Header, Version as BlockVersion},
+            consensus::serialize,
+            hex::DisplayHex,
+            Block, CompactTarget, TxMerkleNode,
+        };
+
+        let tx = |vout: u32| Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: bitcoin::Txid::all_zeros(),
+                    vout,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ZERO,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(1000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+        let mut block = Block {
+            header: Header {
+                version: BlockVersion::ONE,
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 0,
+                bits: CompactTarget::from_consensus(0x207fffff),
+                nonce: 0,
+            },
+            txdata: vec![tx(0), tx(1), tx(2)],
+        };
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+        let funding_txid = block.txdata[1].compute_txid();
+        let proof = MerkleBlock::from_block_with_predicate(&block, |txid| *txid == funding_txid);
+
+        let mut funding_info = FundingTxInfo {
+            funding_tx: block.txdata[1].clone(),
+            funding_tx_merkleproof: serialize(&proof).to_lower_hex_string(),
+            multisig_redeemscript: ScriptBuf::new(),
+            multisig_nonce: SecretKey::new(&mut thread_rng()),
+            contract_redeemscript: ScriptBuf::new(),
+            hashlock_nonce: SecretKey::new(&mut thread_rng()),
+        };
+        assert_eq!(
+            check_funding_merkle_proof(&funding_info).unwrap(),
+            block.block_hash()
+        );
+
+        // Proof for another tx of the same block.
+        funding_info.funding_tx = block.txdata[2].clone();
+        assert!(check_funding_merkle_proof(&funding_info).is_err());
+
+        // Garbage proof.
+        funding_info.funding_tx_merkleproof = "deadbeef".to_string();
+        assert!(check_funding_merkle_proof(&funding_info).is_err());
+    }
 }
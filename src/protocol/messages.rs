@@ -54,19 +54,47 @@
 //! Taker -> Maker2: [`TakerToMakerMessage::RespHashPreimage`] (for Maker2-Taker HTLC).
 //! Maker2 -> Taker: [`MakerToTakerMessage::RespPrivKeyHandover`] (For Maker2-Taker funding multisig).
 //! ```
+//!
+//! If the swap fails after some hops got funded, the Taker first tries to cooperatively abort it. Each funded hop is
+//! refunded by a tx spending the funding multisig straight back to its Sender, instead of the contract txs and the
+//! timelock. Refunds go backward from the last funded hop, as a Receiver only gives up its incoming coins once its
+//! own outgoing coins are refunded.
+//!
+//! ```shell
+//! ********* Cooperative Abort *********
+//! Taker -> Maker1: [TakerToMakerMessage::ReqAbort] (Request the refund txs of hop Maker1-Maker2)
+//! Maker1 -> Taker: [MakerToTakerMessage::RespAbort]
+//! Taker -> Maker2: [TakerToMakerMessage::ReqAbort] (Request the refund txs of hop Maker2-Taker)
+//! Maker2 -> Taker: [MakerToTakerMessage::RespAbort]
+//! Taker signs the refund txs of hop Maker2-Taker as the Receiver.
+//! Taker -> Maker2: [TakerToMakerMessage::ReqAbort] (Sigs for hop Maker2-Taker, refund txs of hop Maker1-Maker2)
+//! Maker2 broadcasts its refund txs, and signs the refund txs of hop Maker1-Maker2 once they confirm.
+//! Maker2 -> Taker: [MakerToTakerMessage::RespAbort] (Sigs for hop Maker1-Maker2)
+//! Taker -> Maker1: [TakerToMakerMessage::ReqAbort] (Sigs for hop Maker1-Maker2, refund txs of hop Taker-Maker1)
+//! Maker1 -> Taker: [MakerToTakerMessage::RespAbort] (Sigs for hop Taker-Maker1)
+//! Taker broadcasts its refund txs.
+//! ```
+//!
+//! Every [TakerToMakerMessage::ReqAbort] is signed with the multisig nonce the Taker sent to that Maker, so nobody else
+//! knowing the swap id can abort the swap.
 
 use std::fmt::Display;
 
 use bitcoin::{
-    ecdsa::Signature, hashes::sha256d::Hash, secp256k1::SecretKey, Amount, PublicKey, ScriptBuf,
-    Transaction,
+    ecdsa::Signature,
+    hashes::{sha256d::Hash, Hash as _},
+    hex::{DisplayHex, FromHex},
+    secp256k1::{ecdsa, Message, Secp256k1, SecretKey},
+    Amount, PublicKey, ScriptBuf, Transaction,
 };
 
 use serde::{Deserialize, Serialize};
 
 use bitcoin::hashes::hash160::Hash as Hash160;
 
-use crate::wallet::FidelityBond;
+use crate::wallet::{FidelityBond, WalletError};
+
+use super::{error::ProtocolError, podle::Podle};
 
 /// Defines the length of the Preimage.
 pub(crate) const PREIMAGE_LEN: usize = 32;
@@ -79,6 +107,8 @@ pub(crate) type Preimage = [u8; PREIMAGE_LEN];
 pub(crate) struct TakerHello {
     pub(crate) protocol_version_min: u32,
     pub(crate) protocol_version_max: u32,
+    /// Anti-DoS commitment to a taker UTXO. Required by makers before signing contracts.
+    pub(crate) podle: Option<Podle>,
 }
 
 /// Represents a request to give an offer.
@@ -102,6 +132,8 @@ pub(crate) struct ReqContractSigsForSender {
     pub(crate) txs_info: Vec<ContractTxInfoForSender>,
     pub(crate) hashvalue: Hash160,
     pub(crate) locktime: u16,
+    /// Index of the chosen [OfferTier] in the maker's [Offer].
+    pub(crate) tier: usize,
 }
 
 /// Contract Sigs requesting information for the Receiver side of the hop.
@@ -145,6 +177,8 @@ pub(crate) struct ProofOfFunding {
     pub(crate) refund_locktime: u16,
     pub(crate) contract_feerate: u64,
     pub(crate) id: String,
+    /// Index of the [OfferTier] chosen in [ReqContractSigsForSender].
+    pub(crate) tier: usize,
 }
 
 /// Signatures required for an intermediate Maker to perform receiving and sending of coinswaps.
@@ -185,6 +219,24 @@ pub(crate) struct PrivKeyHandover {
     pub(crate) multisig_privkeys: Vec<MultisigPrivkey>,
 }
 
+/// Request to cooperatively abort a funded swap. Sent to every Maker of the route, starting from the last funded hop.
+///
+/// A Maker only signs the refund txs of its incoming swap once the refund txs of its outgoing swap are confirmed, or
+/// its outgoing funding was never broadcasted. Until then it replies with empty signatures, and the Taker asks again.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ReqAbort {
+    /// Unique ID of the swap to abort.
+    pub(crate) id: String,
+    /// Receiver's sigs for the refund txs of the Maker's outgoing swap, in the order of [RespAbort::outgoing_refund_txs].
+    /// Empty to only request the refund txs.
+    pub(crate) outgoing_refund_sigs: Vec<Signature>,
+    /// Refund txs of the Maker's incoming swap, to be signed by the Maker as the Receiver.
+    pub(crate) incoming_refund_txs: Vec<Transaction>,
+    /// Signature of the swap id with the multisig nonce of the Maker's first incoming swapcoin, see
+    /// [sign_abort_request](super::contract::sign_abort_request).
+    pub(crate) auth_sig: ecdsa::Signature,
+}
+
 /// All messages sent from Taker to Maker.
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) enum TakerToMakerMessage {
@@ -205,6 +257,15 @@ pub(crate) enum TakerToMakerMessage {
     /// Respond by handing over the Private Keys of coinswap multisig. This denotes the completion of the whole swap.
     RespPrivKeyHandover(PrivKeyHandover),
     WaitingFundingConfirmation(String),
+    /// Request to cooperatively abort the swap, refunding the funded hops without the contract txs.
+    ReqAbort(ReqAbort),
+    /// The Taker refuses the Maker's last message and abandons the protocol with this Maker.
+    Abort {
+        /// What went wrong.
+        code: ErrorCode,
+        /// Human readable details.
+        reason: String,
+    },
 }
 
 impl Display for TakerToMakerMessage {
@@ -221,6 +282,8 @@ impl Display for TakerToMakerMessage {
             Self::RespHashPreimage(_) => write!(f, "RespHashPreimage"),
             Self::RespPrivKeyHandover(_) => write!(f, "RespPrivKeyHandover"),
             Self::WaitingFundingConfirmation(_) => write!(f, "WaitingFundingConfirmation"),
+            Self::ReqAbort(_) => write!(f, "ReqAbort"),
+            Self::Abort { code, reason } => write!(f, "Abort({:?}, {})", code, reason),
         }
     }
 }
@@ -233,16 +296,69 @@ pub(crate) struct MakerHello {
 }
 
 /// Contains proof data related to fidelity bond.
+///
+/// A maker can lock several bonds, all certified for its address. `bond` is its primary bond, the highest valued
+/// one: it identifies the maker in the directories, and signs its offers. The other bonds are in
+/// `additional_bonds`, and the maker is valued with all of them, see `aggregate_bond_values`.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FidelityProof {
     pub(crate) bond: FidelityBond,
     pub(crate) cert_hash: Hash,
     pub(crate) cert_sig: bitcoin::secp256k1::ecdsa::Signature,
+    #[serde(default)]
+    pub(crate) additional_bonds: Vec<BondProof>,
 }
 
-/// Represents an offer in the context of the Coinswap protocol.
+/// Certificate of one additional fidelity bond of a [FidelityProof].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub(crate) struct BondProof {
+    pub(crate) bond: FidelityBond,
+    pub(crate) cert_hash: Hash,
+    pub(crate) cert_sig: bitcoin::secp256k1::ecdsa::Signature,
+}
+
+impl FidelityProof {
+    /// All the certified bonds of the proof, the primary bond first.
+    pub(crate) fn certified_bonds(
+        &self,
+    ) -> impl Iterator<Item = (&FidelityBond, &Hash, &bitcoin::secp256k1::ecdsa::Signature)> + '_
+    {
+        std::iter::once((&self.bond, &self.cert_hash, &self.cert_sig)).chain(
+            self.additional_bonds
+                .iter()
+                .map(|proof| (&proof.bond, &proof.cert_hash, &proof.cert_sig)),
+        )
+    }
+
+    /// All the bonds of the proof, the primary bond first.
+    pub(crate) fn bonds(&self) -> impl Iterator<Item = &FidelityBond> + '_ {
+        self.certified_bonds().map(|(bond, _, _)| bond)
+    }
+
+    /// Sum of the amounts of all the bonds.
+    pub(crate) fn total_amount(&self) -> Amount {
+        self.bonds().map(|bond| bond.amount).sum()
+    }
+
+    /// Encodes the proof in hex, to be shared and checked with
+    /// [audit_fidelity_proof](crate::wallet::audit_fidelity_proof).
+    pub fn to_hex(&self) -> String {
+        serde_cbor::to_vec(self)
+            .expect("proof serialization can't fail")
+            .to_lower_hex_string()
+    }
+
+    /// Decodes a proof encoded with [FidelityProof::to_hex].
+    pub fn from_hex(hex: &str) -> Result<Self, WalletError> {
+        let bytes = Vec::<u8>::from_hex(hex.trim())
+            .map_err(|e| WalletError::General(format!("Invalid proof hex: {}", e)))?;
+        Ok(serde_cbor::from_slice(&bytes)?)
+    }
+}
+
+/// A fee schedule and size range, under which a maker accepts swaps.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-pub(crate) struct Offer {
+pub(crate) struct OfferTier {
     pub(crate) base_fee: u64,                // base fee in sats
     pub(crate) amount_relative_fee_pct: f64, // % fee on total amount
     pub(crate) time_relative_fee_pct: f64, // amount * refund_locktime * TRF% = fees for locking the fund.
@@ -250,8 +366,116 @@ pub(crate) struct Offer {
     pub(crate) minimum_locktime: u16,
     pub(crate) max_size: u64,
     pub(crate) min_size: u64,
+}
+
+/// Maximum validity window of an [Offer], in seconds. Offers valid for longer are rejected.
+pub(crate) const MAX_OFFER_VALIDITY: u64 = 24 * 60 * 60;
+
+/// Tolerated clock difference with the Maker, in seconds, when checking an [Offer]'s timestamp.
+pub(crate) const OFFER_CLOCK_SKEW: u64 = 10 * 60;
+
+/// Represents an offer in the context of the Coinswap protocol.
+///
+/// A maker can advertise multiple [OfferTier]s. The taker chooses one of them in [ReqContractSigsForSender].
+///
+/// The offer is signed by the key certifying its fidelity bond, and is only valid until its expiry. So it can be
+/// relayed or cached by third parties, like the directory servers, without them being able to alter it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub(crate) struct Offer {
+    pub(crate) tiers: Vec<OfferTier>,
     pub(crate) tweakable_point: PublicKey,
     pub(crate) fidelity: FidelityProof,
+    /// Unix time the offer was created at.
+    pub(crate) timestamp: u64,
+    /// Unix time the offer expires at.
+    pub(crate) expiry: u64,
+    /// Signature of [Offer::sighash] by the key certifying the fidelity bond.
+    pub(crate) signature: bitcoin::secp256k1::ecdsa::Signature,
+}
+
+impl Offer {
+    /// Creates an offer valid from `timestamp` to `expiry`, signed with the secret key certifying the fidelity bond.
+    pub(crate) fn new_signed(
+        tiers: Vec<OfferTier>,
+        tweakable_point: PublicKey,
+        fidelity: FidelityProof,
+        timestamp: u64,
+        expiry: u64,
+        bond_privkey: &SecretKey,
+    ) -> Result<Self, ProtocolError> {
+        let sighash = offer_sighash(&tiers, &tweakable_point, &fidelity, timestamp, expiry);
+        let signature = Secp256k1::new()
+            .sign_ecdsa(&Message::from_digest(sighash.to_byte_array()), bond_privkey);
+        let offer = Self {
+            tiers,
+            tweakable_point,
+            fidelity,
+            timestamp,
+            expiry,
+            signature,
+        };
+        // Catch signing with a key other than the bond's.
+        offer.verify_signature()?;
+        Ok(offer)
+    }
+
+    /// The hash signed by the fidelity bond key. Commits to all the other fields of the offer.
+    pub(crate) fn sighash(&self) -> Hash {
+        offer_sighash(
+            &self.tiers,
+            &self.tweakable_point,
+            &self.fidelity,
+            self.timestamp,
+            self.expiry,
+        )
+    }
+
+    /// Verifies that the offer is signed by its fidelity bond key, or the delegated hot key of a cold bond.
+    pub(crate) fn verify_signature(&self) -> Result<(), ProtocolError> {
+        Ok(Secp256k1::verification_only().verify_ecdsa(
+            &Message::from_digest(self.sighash().to_byte_array()),
+            &self.signature,
+            &self.fidelity.bond.cert_pubkey()?.inner,
+        )?)
+    }
+
+    /// Whether the offer has expired at Unix time `now`.
+    pub(crate) fn is_stale(&self, now: u64) -> bool {
+        now >= self.expiry
+    }
+
+    /// Verifies the offer's signature and validity window at Unix time `now`.
+    ///
+    /// The fidelity proof itself is not checked here, as it requires the Maker's address and the blockchain.
+    pub(crate) fn verify(&self, now: u64) -> Result<(), ProtocolError> {
+        if self.timestamp > now + OFFER_CLOCK_SKEW {
+            return Err(ProtocolError::General("Offer timestamp is in the future"));
+        }
+        if self.expiry < self.timestamp || self.expiry - self.timestamp > MAX_OFFER_VALIDITY {
+            return Err(ProtocolError::General("Offer validity window is invalid"));
+        }
+        if self.is_stale(now) {
+            return Err(ProtocolError::General("Offer has expired"));
+        }
+        self.verify_signature()
+    }
+}
+
+/// Hash of the signed content of an [Offer].
+fn offer_sighash(
+    tiers: &[OfferTier],
+    tweakable_point: &PublicKey,
+    fidelity: &FidelityProof,
+    timestamp: u64,
+    expiry: u64,
+) -> Hash {
+    let mut msg = b"coinswap-offer|".to_vec();
+    serde_cbor::to_writer(
+        &mut msg,
+        &(tiers, tweakable_point, fidelity, timestamp, expiry),
+    )
+    .expect("offer serialization can't fail");
+    Hash::hash(&msg)
 }
 
 /// Contract Tx signatures provided by a Sender of a Coinswap.
@@ -287,6 +511,97 @@ pub(crate) struct ContractSigsForRecvr {
     pub(crate) sigs: Vec<Signature>,
 }
 
+/// Maker's reply to a [ReqAbort].
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct RespAbort {
+    /// Unsigned refund txs of the Maker's outgoing swap. Empty if its outgoing funding was never broadcasted.
+    pub(crate) outgoing_refund_txs: Vec<Transaction>,
+    /// Maker's sigs for [ReqAbort::incoming_refund_txs]. Empty if the Maker can't give up its incoming swap yet.
+    pub(crate) incoming_refund_sigs: Vec<Signature>,
+}
+
+/// Stable codes of the protocol errors, shared by [MakerToTakerMessage::Error] and [TakerToMakerMessage::Abort].
+///
+/// Codes are serialized as plain numbers. A code unknown to this version deserializes as [ErrorCode::Unknown].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "u16", into = "u16")]
+pub enum ErrorCode {
+    /// The peer doesn't support our protocol version.
+    UnsupportedVersion,
+    /// The message is not expected at this step of the protocol.
+    UnexpectedMessage,
+    /// The swap amount is outside of the offered size range.
+    AmountOutOfRange,
+    /// The contract locktime is not acceptable.
+    BadLocktime,
+    /// The Maker doesn't have enough liquidity to fund its side of the swap.
+    InsufficientLiquidity,
+    /// A signature doesn't verify.
+    InvalidSignature,
+    /// A contract, funding tx or private key doesn't match the swap.
+    InvalidContract,
+    /// The PoDLE commitment is missing or invalid.
+    InvalidPodle,
+    /// The swap is unknown, or not in a state to process the message.
+    UnknownSwap,
+    /// The Maker is serving too many connections.
+    TooManyConnections,
+    /// The Maker has too many swaps in flight to start a new one.
+    TooManySwaps,
+    /// The Taker sent too many messages in a short time.
+    RateLimited,
+    /// The message is larger than accepted.
+    MessageTooLarge,
+    /// An internal error, unrelated to the peer's messages.
+    Internal,
+    /// A code not known to this version.
+    Unknown(u16),
+}
+
+impl From<u16> for ErrorCode {
+    fn from(code: u16) -> Self {
+        match code {
+            1 => Self::UnsupportedVersion,
+            2 => Self::UnexpectedMessage,
+            3 => Self::AmountOutOfRange,
+            4 => Self::BadLocktime,
+            5 => Self::InsufficientLiquidity,
+            6 => Self::InvalidSignature,
+            7 => Self::InvalidContract,
+            8 => Self::InvalidPodle,
+            9 => Self::UnknownSwap,
+            10 => Self::TooManyConnections,
+            11 => Self::TooManySwaps,
+            12 => Self::RateLimited,
+            13 => Self::MessageTooLarge,
+            100 => Self::Internal,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
+impl From<ErrorCode> for u16 {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::UnsupportedVersion => 1,
+            ErrorCode::UnexpectedMessage => 2,
+            ErrorCode::AmountOutOfRange => 3,
+            ErrorCode::BadLocktime => 4,
+            ErrorCode::InsufficientLiquidity => 5,
+            ErrorCode::InvalidSignature => 6,
+            ErrorCode::InvalidContract => 7,
+            ErrorCode::InvalidPodle => 8,
+            ErrorCode::UnknownSwap => 9,
+            ErrorCode::TooManyConnections => 10,
+            ErrorCode::TooManySwaps => 11,
+            ErrorCode::RateLimited => 12,
+            ErrorCode::MessageTooLarge => 13,
+            ErrorCode::Internal => 100,
+            ErrorCode::Unknown(code) => code,
+        }
+    }
+}
+
 /// All messages sent from Maker to Taker.
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) enum MakerToTakerMessage {
@@ -302,6 +617,15 @@ pub(crate) enum MakerToTakerMessage {
     RespContractSigsForRecvr(ContractSigsForRecvr),
     /// Send the multisig private keys of the swap, declaring completion of the contract.
     RespPrivKeyHandover(PrivKeyHandover),
+    /// The Maker refuses the Taker's last message. Sent right before the Maker closes the connection.
+    Error {
+        /// What went wrong.
+        code: ErrorCode,
+        /// Human readable details.
+        reason: String,
+    },
+    /// Send the refund txs and sigs of a cooperative abort.
+    RespAbort(RespAbort),
 }
 
 impl Display for MakerToTakerMessage {
@@ -317,6 +641,8 @@ impl Display for MakerToTakerMessage {
                 write!(f, "RespContractSigsForRecvr")
             }
             Self::RespPrivKeyHandover(_) => write!(f, "RespPrivKeyHandover"),
+            Self::Error { code, reason } => write!(f, "Error({:?}, {})", code, reason),
+            Self::RespAbort(_) => write!(f, "RespAbort"),
         }
     }
 }
@@ -329,6 +655,43 @@ pub struct DnsMetadata {
     pub url: String,
     /// Proof of the maker's fidelity bond funding.
     pub proof: FidelityProof,
+    /// The maker's signed offer, cached and served by the directory server.
+    #[serde(default)]
+    pub(crate) offer: Option<Offer>,
+}
+
+/// A maker listed by the directory server, in response to [DnsRequest::GetMakers].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct DirectoryEntry {
+    /// The maker's address.
+    pub(crate) url: String,
+    /// The maker's last posted offer, if still valid. Missing for makers not posting their offer.
+    pub(crate) offer: Option<Offer>,
+    /// Result of the directory's last liveness probe of the maker. Missing if not probed yet.
+    #[serde(default)]
+    pub(crate) health: Option<MakerHealth>,
+}
+
+/// Liveness of a listed maker, as last probed by the directory server with an offer handshake.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MakerHealth {
+    /// Whether the maker completed the handshake and served a valid offer.
+    pub reachable: bool,
+    /// Round trip time of the handshake and offer request, in milliseconds. Missing if unreachable.
+    pub latency_ms: Option<u64>,
+    /// Unix time of the probe, in seconds.
+    pub last_probe: u64,
+    /// Number of consecutive failed probes.
+    pub consecutive_failures: u32,
+}
+
+/// A verified maker shared between federated directory servers, in response to [DnsRequest::Sync].
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct DirectorySyncEntry {
+    /// The maker's address, fidelity proof and offer, verified again by the receiving directory.
+    pub(crate) metadata: DnsMetadata,
+    /// Seconds since the maker last posted to the sending directory.
+    pub(crate) age: u64,
 }
 
 /// Enum representing DNS request message types.
@@ -342,8 +705,15 @@ pub enum DnsRequest {
         /// Metadata containing the maker's URL and fidelity proof.
         metadata: DnsMetadata,
     },
-    /// A request sent by the taker to fetch all valid maker addresses from the DNS server.
+    /// A request sent by the taker to fetch all valid maker addresses from the DNS server. Answered with the
+    /// addresses, one per line.
     Get,
+    /// A request sent by the taker to fetch all valid makers from the DNS server, along with their cached offers
+    /// and liveness. Answered with a list of `DirectoryEntry`.
+    GetMakers,
+    /// A request sent by a peer directory server to fetch all verified makers, along with their fidelity proofs.
+    /// Answered with a list of `DirectorySyncEntry`.
+    Sync,
     /// Dummy data used for integration tests.
     #[cfg(feature = "integration-test")]
     /// Send a dummy, request, only used in integration tests
@@ -354,3 +724,171 @@ pub enum DnsRequest {
         vout: u32,
     },
 }
+
+/// Fully signed spend of a contract output, registered with a watchtower.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum ContractSpend {
+    /// Spend via the timelock branch. Broadcasted after the contract reaches timelock maturity.
+    Timelock(Transaction),
+    /// Spend via the hashlock branch. Broadcasted as soon as the contract confirms.
+    Hashlock(Transaction),
+}
+
+/// Pre-signed recovery material of a single swap contract, registered with a watchtower.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ContractWatch {
+    /// Fully signed contract transaction.
+    pub contract_tx: Transaction,
+    /// Redeemscript of the contract output.
+    pub contract_redeemscript: ScriptBuf,
+    /// Fully signed spend of the contract output.
+    pub spend: ContractSpend,
+    /// Signature over the registration challenge, by the timelock or hashlock key of the `spend`.
+    pub auth_sig: bitcoin::secp256k1::ecdsa::Signature,
+}
+
+/// Enum representing watchtower request message types.
+///
+/// Registration is done in a single connection. The client asks for a challenge, and then registers
+/// the recovery material, authenticated with the challenge.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum WatchtowerRequest {
+    /// Request a fresh registration challenge.
+    GetChallenge,
+    /// Register the recovery material of a set of contracts, belonging to the same swap(s).
+    /// If any one of them is seen on chain, all of them will be broadcasted and claimed.
+    Register {
+        /// The recovery material of each contract.
+        watches: Vec<ContractWatch>,
+    },
+}
+
+/// Enum representing watchtower response message types.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum WatchtowerResponse {
+    /// A random challenge, to be signed in the following [WatchtowerRequest::Register].
+    Challenge([u8; 32]),
+    /// Registration accepted. Contains the number of registered contracts.
+    Registered(usize),
+    /// Registration rejected, with the reason.
+    Rejected(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code_roundtrip() {
+        for code in 0..=u16::MAX {
+            assert_eq!(u16::from(ErrorCode::from(code)), code);
+        }
+        assert_eq!(ErrorCode::from(3), ErrorCode::AmountOutOfRange);
+        assert_eq!(ErrorCode::from(42), ErrorCode::Unknown(42));
+
+        let message = MakerToTakerMessage::Error {
+            code: ErrorCode::InsufficientLiquidity,
+            reason: "not enough funds".to_string(),
+        };
+        let bytes = serde_cbor::to_vec(&message).unwrap();
+        match serde_cbor::from_slice::<MakerToTakerMessage>(&bytes).unwrap() {
+            MakerToTakerMessage::Error { code, reason } => {
+                assert_eq!(code, ErrorCode::InsufficientLiquidity);
+                assert_eq!(reason, "not enough funds");
+            }
+            other => panic!("unexpected message {}", other),
+        }
+    }
+
+    #[test]
+    fn test_offer_signature_and_expiry() {
+        let secp = Secp256k1::new();
+        let bond_privkey = SecretKey::from_slice(&[1; 32]).unwrap();
+        let bond = FidelityBond {
+            outpoint: bitcoin::OutPoint::null(),
+            amount: Amount::from_sat(1_000_000),
+            lock_time: bitcoin::absolute::LockTime::from_height(1000).unwrap(),
+            pubkey: PublicKey::new(bitcoin::secp256k1::PublicKey::from_secret_key(
+                &secp,
+                &bond_privkey,
+            )),
+            conf_height: 100,
+            cert_expiry: 1,
+            delegation: None,
+        };
+        let cert_hash = bond.generate_cert_hash("127.0.0.1:6102");
+        let fidelity = FidelityProof {
+            bond,
+            cert_sig: secp.sign_ecdsa(
+                &Message::from_digest(cert_hash.to_byte_array()),
+                &bond_privkey,
+            ),
+            cert_hash,
+            additional_bonds: Vec::new(),
+        };
+        assert_eq!(
+            FidelityProof::from_hex(&fidelity.to_hex()).unwrap(),
+            fidelity
+        );
+        assert!(FidelityProof::from_hex("00zz").is_err());
+        let tier = OfferTier {
+            base_fee: 100,
+            amount_relative_fee_pct: 0.1,
+            time_relative_fee_pct: 0.005,
+            required_confirms: 1,
+            minimum_locktime: 20,
+            max_size: 1_000_000,
+            min_size: 10_000,
+        };
+        let tweakable_point = PublicKey::new(bitcoin::secp256k1::PublicKey::from_secret_key(
+            &secp,
+            &SecretKey::from_slice(&[2; 32]).unwrap(),
+        ));
+        let now = 1_700_000_000;
+
+        let offer = Offer::new_signed(
+            vec![tier.clone()],
+            tweakable_point,
+            fidelity.clone(),
+            now,
+            now + 3600,
+            &bond_privkey,
+        )
+        .unwrap();
+        offer.verify(now).unwrap();
+        offer.verify(now + 3599).unwrap();
+        assert!(offer.verify(now + 3600).is_err());
+        assert!(offer.verify(now - OFFER_CLOCK_SKEW - 1).is_err());
+
+        // Only the bond key can sign the offer.
+        assert!(Offer::new_signed(
+            vec![tier.clone()],
+            tweakable_point,
+            fidelity.clone(),
+            now,
+            now + 3600,
+            &SecretKey::from_slice(&[3; 32]).unwrap(),
+        )
+        .is_err());
+
+        // Any change to the fees, or the expiry, invalidates the signature.
+        let mut tampered = offer.clone();
+        tampered.tiers[0].base_fee = 1;
+        assert!(tampered.verify(now).is_err());
+        let mut tampered = offer.clone();
+        tampered.expiry += 60;
+        assert!(tampered.verify(now).is_err());
+
+        // Too long validity windows are rejected.
+        let offer = Offer::new_signed(
+            vec![tier],
+            tweakable_point,
+            fidelity,
+            now,
+            now + MAX_OFFER_VALIDITY + 1,
+            &bond_privkey,
+        )
+        .unwrap();
+        assert!(offer.verify(now).is_err());
+    }
+}
@@ -3,7 +3,8 @@
 pub(crate) mod contract;
 pub mod error;
 pub mod messages;
+pub(crate) mod podle;
 
 pub(crate) use contract::Hash160;
 
-pub use messages::{DnsMetadata, DnsRequest};
+pub use messages::{ContractSpend, ContractWatch, DnsMetadata, DnsRequest};
@@ -0,0 +1,177 @@
+//! Proof of Discrete Log Equivalence (PoDLE), used as an anti-DoS commitment from takers.
+//!
+//! Before a maker signs any contract, the taker has to prove ownership of a confirmed UTXO of adequate value.
+//! Same scheme as JoinMarket:
+//!
+//! - The taker owns the key `x` of a P2WPKH UTXO, with `P = x*G`.
+//! - `J` is a NUMS (nothing up my sleeve) generator, derived from `G` and an index.
+//! - The commitment is `sha256(P2)` with `P2 = x*J`, and the proof shows `log_G(P) == log_J(P2)`.
+//!
+//! Every UTXO yields exactly one commitment per NUMS index. Makers remember used commitments and refuse them,
+//! so a taker can't tie up maker liquidity more than [`DEFAULT_PODLE_MAX_INDEX`] times per UTXO at no cost.
+//!
+//! A route uses a single commitment, sent to every maker of the route. Each maker records it on its own, so it
+//! costs one index per route, and a fresh one on retries. As in JoinMarket, the makers of a route all learn the
+//! committed UTXO.
+
+use bitcoin::{
+    hashes::{sha256, Hash, HashEngine},
+    key::rand::thread_rng,
+    secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey},
+    OutPoint,
+};
+use serde::{Deserialize, Serialize};
+
+use super::error::ProtocolError;
+
+/// Number of NUMS generators, i.e. the number of commitments a single UTXO can produce.
+pub(crate) const MAX_NUMS_INDEX: u8 = 10;
+
+/// Number of commitments per UTXO accepted by makers by default. Takers don't use more than that.
+pub(crate) const DEFAULT_PODLE_MAX_INDEX: u8 = 3;
+
+/// A PoDLE proof, committing to a UTXO owned by the taker.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub(crate) struct Podle {
+    /// The committed UTXO. Must be a P2WPKH output of `pubkey`.
+    pub(crate) utxo: OutPoint,
+    /// `P = x*G`
+    pub(crate) pubkey: PublicKey,
+    /// `P2 = x*J`
+    pub(crate) p2: PublicKey,
+    /// Index of the NUMS generator `J`.
+    pub(crate) index: u8,
+    /// Challenge of the DLEQ proof.
+    pub(crate) e: [u8; 32],
+    /// Response of the DLEQ proof.
+    pub(crate) s: SecretKey,
+}
+
+/// Derives the NUMS generator for the given index, by try-and-increment hashing of `G`.
+fn nums_point(index: u8) -> Result<PublicKey, ProtocolError> {
+    if index >= MAX_NUMS_INDEX {
+        return Err(ProtocolError::General("PoDLE NUMS index out of range"));
+    }
+    let secp = Secp256k1::signing_only();
+    let one = SecretKey::from_slice(&Scalar::ONE.to_be_bytes())?;
+    let generator = PublicKey::from_secret_key(&secp, &one).serialize();
+    for counter in 0..=u8::MAX {
+        let mut engine = sha256::Hash::engine();
+        engine.input(&generator);
+        engine.input(&[index, counter]);
+        let x = sha256::Hash::from_engine(engine);
+        let mut point = [0x02; 33];
+        point[1..].copy_from_slice(x.as_byte_array());
+        if let Ok(point) = PublicKey::from_slice(&point) {
+            return Ok(point);
+        }
+    }
+    Err(ProtocolError::General("Failed to derive PoDLE NUMS point"))
+}
+
+fn challenge(
+    kg: &PublicKey,
+    kj: &PublicKey,
+    pubkey: &PublicKey,
+    p2: &PublicKey,
+) -> Result<Scalar, ProtocolError> {
+    let mut engine = sha256::Hash::engine();
+    for point in [kg, kj, pubkey, p2] {
+        engine.input(&point.serialize());
+    }
+    let e = sha256::Hash::from_engine(engine).to_byte_array();
+    Scalar::from_be_bytes(e).map_err(|_| ProtocolError::General("PoDLE challenge out of range"))
+}
+
+impl Podle {
+    /// Creates a proof for the UTXO with the given private key, with the NUMS generator at `index`.
+    pub(crate) fn generate(
+        privkey: &SecretKey,
+        utxo: OutPoint,
+        index: u8,
+    ) -> Result<Self, ProtocolError> {
+        let secp = Secp256k1::new();
+        let j = nums_point(index)?;
+        let pubkey = PublicKey::from_secret_key(&secp, privkey);
+        let p2 = j.mul_tweak(&secp, &Scalar::from(*privkey))?;
+
+        let k = SecretKey::new(&mut thread_rng());
+        let kg = PublicKey::from_secret_key(&secp, &k);
+        let kj = j.mul_tweak(&secp, &Scalar::from(k))?;
+        let e = challenge(&kg, &kj, &pubkey, &p2)?;
+
+        // s = k + e*x
+        let s = privkey.mul_tweak(&e)?.add_tweak(&Scalar::from(k))?;
+
+        Ok(Self {
+            utxo,
+            pubkey,
+            p2,
+            index,
+            e: e.to_be_bytes(),
+            s,
+        })
+    }
+
+    /// The commitment to be remembered by makers. Unique per UTXO key and NUMS index.
+    pub(crate) fn commitment(&self) -> sha256::Hash {
+        sha256::Hash::hash(&self.p2.serialize())
+    }
+
+    /// Verifies that `pubkey` and `p2` have the same discrete log. Doesn't check the UTXO itself.
+    pub(crate) fn verify(&self) -> Result<(), ProtocolError> {
+        let secp = Secp256k1::new();
+        let j = nums_point(self.index)?;
+        let e = Scalar::from_be_bytes(self.e)
+            .map_err(|_| ProtocolError::General("PoDLE challenge out of range"))?;
+
+        // kG = s*G - e*P, kJ = s*J - e*P2
+        let sg = PublicKey::from_secret_key(&secp, &self.s);
+        let kg = sg.combine(&self.pubkey.mul_tweak(&secp, &e)?.negate(&secp))?;
+        let sj = j.mul_tweak(&secp, &Scalar::from(self.s))?;
+        let kj = sj.combine(&self.p2.mul_tweak(&secp, &e)?.negate(&secp))?;
+
+        if challenge(&kg, &kj, &self.pubkey, &self.p2)? == e {
+            Ok(())
+        } else {
+            Err(ProtocolError::General("Invalid PoDLE proof"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::Txid;
+
+    #[test]
+    fn test_podle_verify() {
+        let privkey = SecretKey::new(&mut thread_rng());
+        let utxo = OutPoint {
+            txid: Txid::all_zeros(),
+            vout: 0,
+        };
+
+        let podle = Podle::generate(&privkey, utxo, 0).unwrap();
+        assert!(podle.verify().is_ok());
+
+        // Same key and index always gives the same commitment, different index gives a new one.
+        let again = Podle::generate(&privkey, utxo, 0).unwrap();
+        assert_eq!(podle.commitment(), again.commitment());
+        let next = Podle::generate(&privkey, utxo, 1).unwrap();
+        assert_ne!(podle.commitment(), next.commitment());
+        assert!(next.verify().is_ok());
+
+        // Proof for another key doesn't verify.
+        let other = SecretKey::new(&mut thread_rng());
+        let mut forged = podle.clone();
+        forged.pubkey = PublicKey::from_secret_key(&Secp256k1::new(), &other);
+        assert!(forged.verify().is_err());
+
+        let mut forged = podle;
+        forged.index = 1;
+        assert!(forged.verify().is_err());
+
+        assert!(Podle::generate(&privkey, utxo, MAX_NUMS_INDEX).is_err());
+    }
+}
@@ -25,6 +25,7 @@ use socks::Socks5Stream;
 
 use bitcoin::{
     consensus::encode::deserialize,
+    ecdsa::Signature,
     hashes::{hash160::Hash as Hash160, Hash},
     hex::{Case, DisplayHex},
     secp256k1::{
@@ -36,7 +37,9 @@ use bitcoin::{
 
 use super::{
     error::TakerError,
-    offers::{fetch_addresses_from_dns, fetch_offer_from_makers, MakerAddress, OfferAndAddress},
+    offers::{
+        fetch_makers_from_dns, fetch_offer_from_makers, ListedMaker, MakerAddress, OfferAndAddress,
+    },
     routines::*,
 };
 use crate::{
@@ -45,8 +48,9 @@ use crate::{
         messages::{
             ContractSigsAsRecvrAndSender, ContractSigsForRecvr, ContractSigsForRecvrAndSender,
             ContractSigsForSender, FundingTxInfo, MultisigPrivkey, Preimage, PrivKeyHandover,
-            TakerToMakerMessage,
+            RespAbort, TakerToMakerMessage,
         },
+        podle::{Podle, DEFAULT_PODLE_MAX_INDEX},
     },
     taker::{config::TakerConfig, offers::OfferBook},
     utill::*,
@@ -54,6 +58,7 @@ use crate::{
         IncomingSwapCoin, OutgoingSwapCoin, RPCConfig, SwapCoin, Wallet, WalletError,
         WalletSwapCoin, WatchOnlySwapCoin,
     },
+    watcher::TxWatcher,
 };
 
 #[cfg(feature = "tor")]
@@ -139,12 +144,18 @@ struct OngoingSwapState {
     pub(crate) taker_position: TakerPosition,
     /// Unique ID for a swap
     pub(crate) id: String,
+    /// Makers unsuitable for this swap round, skipped without being marked bad.
+    pub(crate) skipped_makers: Vec<OfferAndAddress>,
+    /// PoDLE commitment of this swap round, sent to every maker of the route.
+    pub(crate) podle: Option<Podle>,
 }
 
 /// Information for the next maker in the hop.
 #[derive(Debug, Clone)]
 struct NextPeerInfo {
     peer: OfferAndAddress,
+    /// Index of the offer tier chosen for this peer.
+    tier: usize,
     multisig_pubkeys: Vec<PublicKey>,
     multisig_nonces: Vec<SecretKey>,
     hashlock_nonces: Vec<SecretKey>,
@@ -175,6 +186,8 @@ pub struct Taker {
     behavior: TakerBehavior,
     tor_handle: Option<Child>,
     data_dir: PathBuf,
+    /// Watches the funding and contract txs over bitcoind ZMQ, if configured.
+    tx_watcher: Option<TxWatcher>,
 }
 
 impl Drop for Taker {
@@ -258,8 +271,12 @@ impl Taker {
         let offerbook = if offerbook_path.exists() {
             // If read fails, recreate a fresh offerbook.
             match OfferBook::read_from_disk(&offerbook_path) {
-                Ok(offerbook) => {
+                Ok(mut offerbook) => {
                     log::info!("Succesfully loaded offerbook at : {:?}", offerbook_path);
+                    let dropped = offerbook.drop_stale_offers();
+                    if dropped > 0 {
+                        log::info!("Dropped {} expired offers", dropped);
+                    }
                     offerbook
                 }
                 Err(e) => {
@@ -282,6 +299,12 @@ impl Taker {
         wallet.sync()?;
         log::info!("Completed wallet sync");
 
+        let tx_watcher = if config.zmq_address.is_empty() {
+            None
+        } else {
+            Some(TxWatcher::start(&config.zmq_address)?)
+        };
+
         Ok(Self {
             wallet,
             config,
@@ -290,6 +313,7 @@ impl Taker {
             behavior,
             tor_handle: None,
             data_dir,
+            tx_watcher,
         })
     }
 
@@ -333,7 +357,7 @@ impl Taker {
 
                 let handle = Some(crate::tor::spawn_tor(
                     self.config.socks_port,
-                    self.config.network_port,
+                    &[self.config.network_port],
                     tor_dir.to_str().unwrap().to_owned(),
                 )?);
 
@@ -406,6 +430,7 @@ impl Taker {
         self.ongoing_swap_state.active_preimage = preimage;
         self.ongoing_swap_state.swap_params = swap_params;
         self.ongoing_swap_state.id = unique_id;
+        self.ongoing_swap_state.podle = None;
 
         // Try first hop. Abort if error happens.
         if let Err(e) = self.init_first_hop() {
@@ -464,8 +489,8 @@ impl Taker {
                 };
 
             // Watch for both expected and unexpected transactions.
-            // This errors in two cases.
-            // TakerError::ContractsBroadcasted and TakerError::FundingTxWaitTimeOut.
+            // This errors in three cases.
+            // TakerError::ContractsBroadcasted, TakerError::FundingTxWaitTimeOut and TakerError::FundingTxReorged.
             // For all cases, abort from swap immediately.
             // For the timeout case also ban the Peer.
             let txids_to_watch = funding_outpoints.iter().map(|op| op.txid).collect();
@@ -508,7 +533,7 @@ impl Taker {
 
         if self.behavior == TakerBehavior::BroadcastContractAfterFullSetup {
             log::error!("Special Behavior BroadcastContractAfterFullSetup");
-            self.recover_from_swap()?;
+            self.recover_via_contracts()?;
             return Ok(());
         }
 
@@ -545,8 +570,12 @@ impl Taker {
 
         // Loop until we find a live maker who responded to our signature request.
         let (maker, funding_txs) = loop {
-            let maker = self.choose_next_maker()?.clone();
-            log::info!("Choosing next maker: {}", maker.address);
+            let (maker, tier) = self.choose_next_maker()?;
+            log::info!(
+                "Choosing next maker: {} | Offer tier {}",
+                maker.address,
+                tier
+            );
             let (multisig_pubkeys, multisig_nonces, hashlock_pubkeys, hashlock_nonces) =
                 generate_maker_keys(
                     &maker.offer.tweakable_point,
@@ -567,18 +596,19 @@ impl Taker {
                 .map(|swapcoin| swapcoin.contract_redeemscript.clone())
                 .collect();
 
-            // Request for Sender's Signatures
+            // Request for Sender's Signatures.
             let contract_sigs = match self.req_sigs_for_sender(
                 &maker.address,
                 &outgoing_swapcoins,
                 &multisig_nonces,
                 &hashlock_nonces,
                 swap_locktime,
+                tier,
             ) {
                 Ok(contract_sigs) => contract_sigs,
                 Err(e) => {
-                    // Bad maker, mark it, and try next one.
-                    self.offerbook.add_bad_maker(&maker);
+                    // Unsuitable or bad maker, skip or mark it, and try next one.
+                    self.drop_maker(&maker, &e);
                     log::error!(
                         "Failed to obtain sender's contract signatures from first_maker {}: {:?}",
                         maker.address,
@@ -592,6 +622,7 @@ impl Taker {
             // // and persist in disk.
             self.ongoing_swap_state.peer_infos.push(NextPeerInfo {
                 peer: maker.clone(),
+                tier,
                 multisig_pubkeys,
                 multisig_nonces,
                 hashlock_nonces,
@@ -658,8 +689,11 @@ impl Taker {
     /// Return a list of confirmed funding txs with their corresponding merkle proofs.
     /// Errors if any watching contract txs have been broadcasted during the time too.
     /// The error contanis the list of broadcasted contract [Txid]s.
+    ///
+    /// Confirmed funding txs that get reorged out are waited for again. If a funding tx of an earlier
+    /// hop gets reorged out, errors with [TakerError::FundingTxReorged].
     fn watch_for_txs(
-        &self,
+        &mut self,
         funding_txids: &Vec<Txid>,
     ) -> Result<(Vec<Transaction>, Vec<String>), TakerError> {
         let mut txid_tx_map = HashMap::<Txid, Transaction>::new();
@@ -673,7 +707,7 @@ impl Taker {
                 self.ongoing_swap_state
                     .peer_infos
                     .last()
-                    .map(|npi| npi.peer.offer.required_confirms)
+                    .map(|npi| npi.peer.offer.tiers[npi.tier].required_confirms)
                     .expect("Maker information expected in swap state")
             };
 
@@ -705,6 +739,13 @@ impl Taker {
 
         let start_time = Instant::now();
 
+        // With ZMQ, wake up as soon as a funding or contract tx is seen, or a block is mined.
+        let mut zmq_events = 0;
+        if let Some(watcher) = &self.tx_watcher {
+            watcher.set_watched(funding_txids.iter().cloned().chain(self.contract_txids()));
+            zmq_events = watcher.events();
+        }
+
         loop {
             // Abort if any of the contract transaction is broadcasted
             // TODO: Find the culprit Maker, and ban it's fidelity bond.
@@ -717,6 +758,32 @@ impl Taker {
                 return Err(TakerError::ContractsBroadcasted(contracts_broadcasted));
             }
 
+            // Pause on reorged funding txs of this hop, until they confirm again.
+            let watched = self
+                .funding_txids()
+                .into_iter()
+                .chain(funding_txids.iter().cloned())
+                .collect::<Vec<_>>();
+            let mut earlier_reorged = Vec::new();
+            for txid in self.wallet.find_reorged_txs(&watched)? {
+                if txid_tx_map.remove(&txid).is_some() {
+                    txid_blockhash_map.remove(&txid);
+                    log::warn!(
+                        "Funding tx {} reorged out, waiting for it to confirm again",
+                        txid
+                    );
+                } else {
+                    earlier_reorged.push(txid);
+                }
+            }
+            if !earlier_reorged.is_empty() {
+                log::error!(
+                    "Funding txs of previous hops reorged out. Txids : {:?}",
+                    earlier_reorged
+                );
+                return Err(TakerError::FundingTxReorged(earlier_reorged));
+            }
+
             // Check for each funding transactions if they are confirmed
             for txid in funding_txids {
                 if txid_tx_map.contains_key(txid) {
@@ -763,13 +830,14 @@ impl Taker {
                 }
 
                 // handle confirmations
-                //TODO handle confirm<0
                 if gettx.confirmations >= Some(required_confirmations) {
+                    let blockhash = gettx.blockhash.expect("Blockhash expected");
                     txid_tx_map.insert(
                         *txid,
                         deserialize::<Transaction>(&gettx.hex).map_err(WalletError::from)?,
                     );
-                    txid_blockhash_map.insert(*txid, gettx.blockhash.expect("Blockhash expected"));
+                    txid_blockhash_map.insert(*txid, blockhash);
+                    self.wallet.track_confirmation(*txid, blockhash)?;
                     log::info!("Tx {} | Confirmed at {}", txid, required_confirmations);
                 }
             }
@@ -802,7 +870,12 @@ impl Taker {
                     .map_err(WalletError::from)?;
                 return Ok((txes, merkleproofs));
             }
-            sleep(Duration::from_secs(sleep_interval));
+            match &self.tx_watcher {
+                Some(watcher) => {
+                    zmq_events = watcher.wait(zmq_events, Duration::from_secs(sleep_interval))
+                }
+                None => sleep(Duration::from_secs(sleep_interval)),
+            }
         }
     }
 
@@ -937,6 +1010,15 @@ impl Taker {
             ii += 1;
             match self.send_sigs_init_next_hop_once(maker_refund_locktime, funding_tx_infos) {
                 Ok(ret) => return Ok(ret),
+                Err(e) if !e.is_retryable() => {
+                    log::warn!(
+                        "Maker {} rejected the next hop, not reattempting | error={:?}",
+                        &maker_oa.address,
+                        e
+                    );
+                    self.drop_maker(&maker_oa, &e);
+                    return Err(e);
+                }
                 Err(e) => {
                     log::warn!(
                         "Failed to connect to maker {} to send signatures and init next hop, \
@@ -970,14 +1052,20 @@ impl Taker {
         maker_refund_locktime: u16,
         funding_tx_infos: &[FundingTxInfo],
     ) -> Result<(NextPeerInfo, ContractSigsAsRecvrAndSender), TakerError> {
-        let this_maker = &self
+        let (this_maker, this_maker_tier) = self
             .ongoing_swap_state
             .peer_infos
             .last()
-            .expect("at least one active maker expected")
-            .peer;
+            .map(|npi| (npi.peer.clone(), npi.tier))
+            .expect("at least one active maker expected");
 
-        let previous_maker = self.ongoing_swap_state.peer_infos.iter().rev().nth(1);
+        let previous_maker = self
+            .ongoing_swap_state
+            .peer_infos
+            .iter()
+            .rev()
+            .nth(1)
+            .map(|npi| npi.peer.clone());
 
         log::info!(
             "Connecting to {} | Send Sigs Init Next Hop",
@@ -999,8 +1087,9 @@ impl Taker {
         socket.set_read_timeout(Some(reconnect_timeout))?;
         socket.set_write_timeout(Some(reconnect_timeout))?;
 
-        handshake_maker(&mut socket)?;
+        handshake_maker(&mut socket, None)?;
         let mut next_maker = this_maker.clone();
+        let mut next_maker_tier = this_maker_tier;
         let (
             next_peer_multisig_pubkeys,
             next_peer_multisig_keys_or_nonces,
@@ -1032,7 +1121,7 @@ impl Taker {
                     my_recv_hashlock_nonce,
                 )
             } else {
-                next_maker = self.choose_next_maker()?.clone();
+                (next_maker, next_maker_tier) = self.choose_next_maker()?;
                 //next_maker is only ever accessed when the next peer is a maker, not a taker
                 //i.e. if its ever used when is_taker_next_peer == true, then thats a bug
                 generate_maker_keys(
@@ -1078,6 +1167,7 @@ impl Taker {
                 funding_tx_infos: funding_tx_infos.to_vec(),
                 this_maker_contract_txs,
                 this_maker_refund_locktime: maker_refund_locktime,
+                this_maker_tier,
             };
 
             let (contract_sigs_as_recvr_sender, next_swap_contract_redeemscripts) =
@@ -1129,12 +1219,13 @@ impl Taker {
                     &next_peer_multisig_keys_or_nonces,
                     &next_peer_hashlock_keys_or_nonces,
                     maker_refund_locktime,
+                    next_maker_tier,
                 ) {
                     Ok(r) => r,
                     Err(e) => {
-                        self.offerbook.add_bad_maker(&next_maker);
+                        self.drop_maker(&next_maker, &e);
                         log::info!(
-                            "Failed to obtain sender's contract tx signature from next_maker {}, Banning Maker: {:?}",
+                            "Failed to obtain sender's contract tx signature from next_maker {}, dropping Maker: {:?}",
                             next_maker.address,
                             e
                         );
@@ -1171,7 +1262,7 @@ impl Taker {
         } else {
             // If Next Maker is the Receiver, and Previous Maker is the Sender, request Previous Maker to sign the Reciever's Contract Tx.
             let previous_maker = previous_maker.expect("Previous Maker should always exists");
-            let previous_maker_addr = &previous_maker.peer.address;
+            let previous_maker_addr = &previous_maker.address;
             let previous_maker_watchonly_swapcoins =
                 if self.ongoing_swap_state.taker_position == TakerPosition::LastPeer {
                     self.ongoing_swap_state
@@ -1192,8 +1283,8 @@ impl Taker {
                 Ok(s) => s.sigs,
                 Err(e) => {
                     log::error!("Could not get Receiver's signatures : {:?}", e);
-                    log::warn!("Banning Maker : {}", previous_maker.peer.address);
-                    self.offerbook.add_bad_maker(&previous_maker.peer);
+                    log::warn!("Dropping Maker : {}", previous_maker.address);
+                    self.drop_maker(&previous_maker, &e);
                     return Err(e);
                 }
             }
@@ -1216,6 +1307,7 @@ impl Taker {
 
         let next_swap_info = NextPeerInfo {
             peer: next_maker.clone(),
+            tier: next_maker_tier,
             multisig_pubkeys: next_peer_multisig_pubkeys,
             multisig_nonces: next_peer_multisig_keys_or_nonces,
             hashlock_nonces: next_peer_hashlock_keys_or_nonces,
@@ -1431,15 +1523,36 @@ impl Taker {
         Ok(())
     }
 
+    /// Creates a fresh PoDLE commitment, required by the makers before they sign contracts.
+    ///
+    /// Commits to the largest confirmed seed utxo which still has unused NUMS indices, up to the makers' default limit.
+    /// The used indices are saved in the wallet file right away.
+    fn next_podle(&mut self) -> Result<Podle, TakerError> {
+        for (outpoint, _, privkey) in self.wallet.list_podle_candidates()? {
+            let index = self.wallet.store.podle_indices.entry(outpoint).or_insert(0);
+            if *index < DEFAULT_PODLE_MAX_INDEX {
+                let podle = Podle::generate(&privkey, outpoint, *index)?;
+                *index += 1;
+                self.wallet.save_to_disk()?;
+                return Ok(podle);
+            }
+        }
+        Err(WalletError::General("No confirmed utxo left for PoDLE commitments".to_string()).into())
+    }
+
     /// Request signatures for sender side of the swap.
     /// Keep trying until `first_connect_attempts` limit, with time delay of `first_connect_sleep_delay_sec`.
+    /// The first attempt carries the route's PoDLE. A retry carries a fresh one, as the maker may have recorded
+    /// the previous one, and the fresh one is used for the rest of the route.
+    #[allow(clippy::too_many_arguments)]
     fn req_sigs_for_sender<S: SwapCoin>(
-        &self,
+        &mut self,
         maker_address: &MakerAddress,
         outgoing_swapcoins: &[S],
         maker_multisig_nonces: &[SecretKey],
         maker_hashlock_nonces: &[SecretKey],
         locktime: u16,
+        tier: usize,
     ) -> Result<ContractSigsForSender, TakerError> {
         let reconnect_time_out = Duration::from_secs(FIRST_CONNECT_ATTEMPT_TIMEOUT_SEC);
         // Configurable reconnection attempts for testing
@@ -1475,6 +1588,14 @@ impl Taker {
 
         loop {
             ii += 1;
+            let podle = match &self.ongoing_swap_state.podle {
+                Some(podle) if ii == 1 => podle.clone(),
+                _ => {
+                    let podle = self.next_podle()?;
+                    self.ongoing_swap_state.podle = Some(podle.clone());
+                    podle
+                }
+            };
             log::info!("===> ReqContractSigsForSender | {}", maker_addr_str);
             match req_sigs_for_sender_once(
                 &mut socket,
@@ -1482,6 +1603,8 @@ impl Taker {
                 maker_multisig_nonces,
                 maker_hashlock_nonces,
                 locktime,
+                tier,
+                &podle,
             ) {
                 Ok(ret) => {
                     return {
@@ -1489,6 +1612,14 @@ impl Taker {
                         Ok(ret)
                     }
                 }
+                Err(e) if !e.is_retryable() => {
+                    log::warn!(
+                        "Maker {} rejected the request for sender's signatures, not reattempting | error={:?}",
+                        &maker_addr_str,
+                        e
+                    );
+                    return Err(e);
+                }
                 Err(e) => {
                     log::warn!(
                         "Failed to connect to maker {} to request signatures for receiver, \
@@ -1571,6 +1702,14 @@ impl Taker {
                     log::info!("<=== RespContractSigsForRecvr | {}", maker_addr_str);
                     return Ok(ret);
                 }
+                Err(e) if !e.is_retryable() => {
+                    log::warn!(
+                        "Maker {} rejected the request for receiver's signatures, not reattempting | error={:?}",
+                        &maker_addr_str,
+                        e
+                    );
+                    return Err(e);
+                }
                 Err(e) => {
                     log::warn!(
                         "Failed to connect to maker {} to request signatures for receiver, \
@@ -1606,6 +1745,17 @@ impl Taker {
     /// Pass around the Maker's multisig privatekeys. Saves all the data in wallet file. This marks
     /// the ends of swap round.
     fn settle_all_swaps(&mut self) -> Result<(), TakerError> {
+        // Don't hand out any privkeys if a funding tx is not in the chain anymore.
+        let funding_txids = self.funding_txids();
+        let reorged = self.wallet.find_reorged_txs(&funding_txids)?;
+        if !reorged.is_empty() {
+            log::error!(
+                "Funding txs reorged out before settlement. Txids : {:?}",
+                reorged
+            );
+            return Err(TakerError::FundingTxReorged(reorged));
+        }
+
         let mut outgoing_privkeys: Option<Vec<MultisigPrivkey>> = None;
 
         // Because the last peer info is the Taker, we take upto (0..n-1), where n = peer_info.len()
@@ -1692,7 +1842,7 @@ impl Taker {
                             reconnect_attempts,
                             e
                         );
-                        if ii <= reconnect_attempts {
+                        if ii <= reconnect_attempts && e.is_retryable() {
                             sleep(Duration::from_secs(
                                 if ii <= SHORT_LONG_SLEEP_DELAY_TRANSITION {
                                     sleep_delay
@@ -1739,7 +1889,7 @@ impl Taker {
 
         socket.set_read_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECONDS)))?;
         socket.set_write_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECONDS)))?;
-        handshake_maker(&mut socket)?;
+        handshake_maker(&mut socket, None)?;
 
         log::info!("===> HashPreimage | {}", maker_address);
         let maker_private_key_handover = send_hash_preimage_and_get_private_keys(
@@ -1797,28 +1947,41 @@ impl Taker {
     // ######## UTILITY AND HELPERS ############
 
     /// Choose a suitable **untried** maker address from the offerbook that fits the swap params.
-    fn choose_next_maker(&self) -> Result<&OfferAndAddress, TakerError> {
+    /// Returns the maker along with the index of its cheapest suitable offer tier.
+    fn choose_next_maker(&self) -> Result<(OfferAndAddress, usize), TakerError> {
         let send_amount = self.ongoing_swap_state.swap_params.send_amount;
         if send_amount == Amount::ZERO {
             return Err(TakerError::SendAmountNotSet);
         }
 
-        // Ensure that we don't select a maker we are already swaping with.
-        Ok(self
-            .offerbook
+        // Ensure that we don't select a maker we are already swaping with, or skipped in this round.
+        self.offerbook
             .all_good_makers()
-            .iter()
-            .find(|oa| {
-                send_amount >= Amount::from_sat(oa.offer.min_size)
-                    && send_amount <= Amount::from_sat(oa.offer.max_size)
-                    && !self
-                        .ongoing_swap_state
-                        .peer_infos
-                        .iter()
-                        .map(|pi| &pi.peer)
-                        .any(|noa| noa == **oa)
+            .into_iter()
+            .filter(|oa| {
+                !self
+                    .ongoing_swap_state
+                    .peer_infos
+                    .iter()
+                    .map(|pi| &pi.peer)
+                    .chain(self.ongoing_swap_state.skipped_makers.iter())
+                    .any(|noa| noa == *oa)
             })
-            .ok_or(TakerError::NotEnoughMakersInOfferBook)?)
+            .find_map(|oa| {
+                oa.select_tier(send_amount, REFUND_LOCKTIME)
+                    .map(|tier| (oa.clone(), tier))
+            })
+            .ok_or(TakerError::NotEnoughMakersInOfferBook)
+    }
+
+    /// Stop using a maker after an error. It is marked bad if the error is its fault, otherwise it is
+    /// only skipped for this swap round.
+    fn drop_maker(&mut self, maker: &OfferAndAddress, e: &TakerError) {
+        if e.is_maker_fault() {
+            self.offerbook.add_bad_maker(maker);
+        } else {
+            self.ongoing_swap_state.skipped_makers.push(maker.clone());
+        }
     }
 
     /// Get the [Preimage] of the ongoing swap. If no swap is in progress will return a `[0u8; 32]`.
@@ -1832,8 +1995,10 @@ impl Taker {
     }
 
     /// Clear the [OngoingSwapState].
-    fn clear_ongoing_swaps(&mut self) {
+    fn clear_ongoing_swaps(&mut self) -> Result<(), TakerError> {
         self.ongoing_swap_state = OngoingSwapState::default();
+        self.wallet.clear_confirmations()?;
+        Ok(())
     }
 
     /// Get all the bad makers
@@ -1863,17 +2028,23 @@ impl Taker {
 
         self.wallet.save_to_disk()?;
 
-        self.clear_ongoing_swaps();
+        self.clear_ongoing_swaps()?;
 
         Ok(())
     }
 
-    /// Checks if any contreact transactions have been broadcasted.
-    /// Returns the txid list of all the broadcasted contract transaction.
-    /// Empty vector if nothing is nothing is broadcasted. (usual case).
-    pub(crate) fn check_for_broadcasted_contract_txes(&self) -> Vec<Txid> {
-        let contract_txids = self
-            .ongoing_swap_state
+    /// Txids of the funding transactions of all the confirmed hops of the ongoing swap.
+    fn funding_txids(&self) -> Vec<Txid> {
+        self.ongoing_swap_state
+            .funding_txs
+            .iter()
+            .flat_map(|(txs, _)| txs.iter().map(|tx| tx.compute_txid()))
+            .collect()
+    }
+
+    /// Txids of all the contract transactions of the ongoing swap.
+    fn contract_txids(&self) -> Vec<Txid> {
+        self.ongoing_swap_state
             .incoming_swapcoins
             .iter()
             .map(|sc| sc.contract_tx.compute_txid())
@@ -1890,35 +2061,238 @@ impl Taker {
                     .flatten()
                     .map(|sc| sc.contract_tx.compute_txid()),
             )
-            .collect::<Vec<_>>();
+            .collect()
+    }
+
+    /// Checks if any contreact transactions have been broadcasted.
+    /// Returns the txid list of all the broadcasted contract transaction.
+    /// Empty vector if nothing is nothing is broadcasted. (usual case).
+    pub(crate) fn check_for_broadcasted_contract_txes(&self) -> Vec<Txid> {
+        let contract_txids = self.contract_txids();
 
         // TODO: Find out which txid was boradcasted first
         // This requires -txindex to be enabled in the node.
         let seen_txids = contract_txids
             .iter()
-            .filter(|txid| self.wallet.rpc.get_raw_transaction_info(txid, None).is_ok())
+            .filter(|txid| {
+                self.tx_watcher
+                    .as_ref()
+                    .is_some_and(|watcher| watcher.is_seen(txid))
+                    || self.wallet.rpc.get_raw_transaction_info(txid, None).is_ok()
+            })
             .cloned()
             .collect::<Vec<Txid>>();
 
         seen_txids
     }
 
-    /// Recover from a bad swap
+    /// Recover from a bad swap.
+    ///
+    /// First tries to cooperatively abort the ongoing swap with its Makers. Swapcoins that can't be refunded
+    /// that way are recovered by broadcasting their contract txs and claiming them back after the timelock.
     pub fn recover_from_swap(&mut self) -> Result<(), TakerError> {
-        let (incomings, outgoings) = self.wallet.find_unfinished_swapcoins();
+        if let Err(e) = self.cooperative_abort() {
+            log::warn!(
+                "Cooperative abort failed, recovering via contract txs. Error : {:?}",
+                e
+            );
+        }
+        self.recover_via_contracts()
+    }
 
-        let incoming_contracts = incomings
+    /// [Internal] Cooperatively abort the ongoing swap. Every funded hop is refunded to its Sender, by a tx spending
+    /// the funding multisig directly. No contract tx gets broadcasted, and no timelock has to be waited out.
+    ///
+    /// Refunds go backward from the last funded hop, as a Maker only signs the refunds of its incoming swap once
+    /// the refunds of its outgoing swap are confirmed. The Taker's own refunds are broadcasted by [Taker::recover_via_contracts].
+    fn cooperative_abort(&mut self) -> Result<(), TakerError> {
+        // Makers whose incoming funding got confirmed. Only they have a state to abort. Requests are signed with
+        // the nonce of their first incoming multisig key.
+        let maker_count = self.ongoing_swap_state.swap_params.maker_count;
+        let makers = self
+            .ongoing_swap_state
+            .peer_infos
             .iter()
-            .map(|incoming| {
-                Ok((
-                    incoming.get_fully_signed_contract_tx()?,
+            .take(maker_count.min(self.ongoing_swap_state.funding_txs.len()))
+            .map(|pi| Some((pi.peer.address.clone(), *pi.multisig_nonces.first()?)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ProtocolError::General("Missing multisig nonce of a maker"))?;
+        if makers.is_empty() {
+            return Ok(());
+        }
+        log::info!("Cooperatively aborting swap {}", self.ongoing_swap_state.id);
+
+        // Collect the refund txs of every Maker's outgoing swap.
+        let mut makers_refund_txs = Vec::new();
+        for (maker_address, multisig_nonce) in &makers {
+            let resp = self.req_abort_once(maker_address, multisig_nonce, &[], &[])?;
+            makers_refund_txs.push(resp.outgoing_refund_txs);
+        }
+
+        // The Taker is the Receiver of the last hop. Nobody else knows the preimage, so its incoming swapcoins
+        // can be given up right away.
+        let mut sigs = Vec::new();
+        if makers.len() == maker_count {
+            let last_refund_txs = makers_refund_txs.last().expect("refund txs expected");
+            if !last_refund_txs.is_empty() {
+                let incomings = &self.ongoing_swap_state.incoming_swapcoins;
+                if incomings.len() != last_refund_txs.len() {
+                    return Err(ProtocolError::WrongNumOfContractTxs {
+                        expected: incomings.len(),
+                        received: last_refund_txs.len(),
+                    }
+                    .into());
+                }
+                sigs = incomings
+                    .iter()
+                    .zip(last_refund_txs.iter())
+                    .map(|(sc, refund_tx)| sc.sign_senders_refund_tx(refund_tx))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for incoming in &self.ongoing_swap_state.incoming_swapcoins {
+                    self.wallet
+                        .remove_incoming_swapcoin(&incoming.get_multisig_redeemscript())?;
+                }
+                self.ongoing_swap_state.incoming_swapcoins.clear();
+                self.wallet.save_to_disk()?;
+            }
+        }
+
+        let my_refund_txs = {
+            let addresses = self.wallet.get_next_internal_addresses(
+                self.ongoing_swap_state.outgoing_swapcoins.len() as u32,
+            )?;
+            self.ongoing_swap_state
+                .outgoing_swapcoins
+                .iter()
+                .zip(addresses.iter())
+                .map(|(sc, address)| sc.create_refund_tx(address, self.config.refund_feerate))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        // Each Maker's confirmed refunds take a block. Don't wait longer than the contracts would take.
+        let block_wait_time = if cfg!(feature = "integration-test") {
+            Duration::from_secs(10)
+        } else {
+            Duration::from_secs(10 * 60)
+        };
+
+        for (index, (maker_address, multisig_nonce)) in makers.iter().enumerate().rev() {
+            if sigs.len() != makers_refund_txs[index].len() {
+                return Err(
+                    ProtocolError::General("Outgoing swap of maker can't be refunded").into(),
+                );
+            }
+            let incoming_refund_txs = if index == 0 {
+                &my_refund_txs
+            } else {
+                &makers_refund_txs[index - 1]
+            };
+            let mut attempts = 0;
+            sigs = loop {
+                let resp =
+                    self.req_abort_once(maker_address, multisig_nonce, &sigs, incoming_refund_txs)?;
+                if !resp.incoming_refund_sigs.is_empty() {
+                    break resp.incoming_refund_sigs;
+                }
+                attempts += 1;
+                if attempts > REFUND_LOCKTIME {
+                    return Err(
+                        ProtocolError::General("Timed out waiting for maker's refunds").into(),
+                    );
+                }
+                log::info!(
+                    "Waiting for the refunds of maker {} to confirm",
+                    maker_address
+                );
+                sleep(block_wait_time);
+            };
+        }
+
+        // Store our refunds fully signed, so they are broadcasted instead of the contracts.
+        for (outgoing, (refund_tx, sig)) in self
+            .ongoing_swap_state
+            .outgoing_swapcoins
+            .iter()
+            .zip(my_refund_txs.iter().zip(sigs.iter()))
+        {
+            let signed_refund_tx = outgoing.sign_refund_tx(refund_tx, sig)?;
+            if let Some(swapcoin) = self
+                .wallet
+                .find_outgoing_swapcoin_mut(&outgoing.get_multisig_redeemscript())
+            {
+                swapcoin.refund_tx = Some(signed_refund_tx);
+            }
+        }
+        self.wallet.save_to_disk()?;
+        log::info!("Cooperatively aborted swap {}", self.ongoing_swap_state.id);
+        Ok(())
+    }
+
+    /// [Internal] Connect to a Maker and request it to cooperatively abort the ongoing swap.
+    fn req_abort_once(
+        &self,
+        maker_address: &MakerAddress,
+        multisig_nonce: &SecretKey,
+        outgoing_refund_sigs: &[Signature],
+        incoming_refund_txs: &[Transaction],
+    ) -> Result<RespAbort, TakerError> {
+        let maker_addr_str = maker_address.to_string();
+        let mut socket = match self.config.connection_type {
+            ConnectionType::CLEARNET => TcpStream::connect(maker_addr_str.clone())?,
+            #[cfg(feature = "tor")]
+            ConnectionType::TOR => Socks5Stream::connect(
+                format!("127.0.0.1:{}", self.config.socks_port).as_str(),
+                &*maker_addr_str,
+            )?
+            .into_inner(),
+        };
+
+        socket.set_read_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECONDS)))?;
+        socket.set_write_timeout(Some(Duration::from_secs(TCP_TIMEOUT_SECONDS)))?;
+        handshake_maker(&mut socket, None)?;
+
+        log::info!("===> ReqAbort | {}", maker_address);
+        let resp = req_abort(
+            &mut socket,
+            &self.ongoing_swap_state.id,
+            multisig_nonce,
+            outgoing_refund_sigs,
+            incoming_refund_txs,
+        )?;
+        log::info!("<=== RespAbort | {}", maker_address);
+        Ok(resp)
+    }
+
+    /// [Internal] Recover the unfinished swapcoins of the wallet by broadcasting their contract txs. Incoming contracts
+    /// are claimed via hashlock, as the Taker knows the preimage. Outgoing ones are claimed back after the timelock,
+    /// or spent by their refund txs if they were refunded in a cooperative abort.
+    fn recover_via_contracts(&mut self) -> Result<(), TakerError> {
+        let (incomings, outgoings) = self.wallet.find_unfinished_swapcoins();
+
+        // Tuple of ((Multisig Reedemscript, Contract Tx), Hashlock Tx if the preimage is known)
+        let mut incoming_infos = Vec::new();
+        for incoming in &incomings {
+            let hashlock_spend = if incoming.is_hash_preimage_known() {
+                let next_internal = &self.wallet.get_next_internal_addresses(1)?[0];
+                Some(incoming.create_hashlock_spend(next_internal)?)
+            } else {
+                None
+            };
+            incoming_infos.push((
+                (
                     incoming.get_multisig_redeemscript(),
-                ))
-            })
-            .collect::<Result<Vec<_>, TakerError>>()?;
+                    incoming.get_fully_signed_contract_tx()?,
+                ),
+                hashlock_spend,
+            ));
+        }
+
+        // Incoming contracts to be claimed via hashlock.
+        // Tuple of ((Multisig Reedemscript, Contract Tx), Hashlock Tx)
+        let mut hashlock_claims = Vec::new();
 
-        // Broadcasted incoming contracts and remove them from the wallet.
-        for (contract_tx, redeemscript) in &incoming_contracts {
+        // Broadcast the incoming contracts, and remove the unclaimable ones from the wallet.
+        for ((redeemscript, contract_tx), hashlock_spend) in incoming_infos {
             if self
                 .wallet
                 .rpc
@@ -1930,23 +2304,56 @@ impl Taker {
                     contract_tx.compute_txid()
                 );
             } else {
-                self.wallet.send_tx(contract_tx)?;
+                self.wallet.send_tx(&contract_tx)?;
                 log::info!(
-                    "Broadcasting Incoming Contract. Removing from wallet. Txid : {}",
+                    "Broadcasted Incoming Contract. Txid : {}",
                     contract_tx.compute_txid()
                 );
             }
+            if let Some(hashlock_spend) = hashlock_spend {
+                hashlock_claims.push(((redeemscript, contract_tx), hashlock_spend));
+                continue;
+            }
             log::info!(
                 "Incoming Swapcoin removed from wallet, Txid: {}",
                 contract_tx.compute_txid()
             );
-            self.wallet.remove_incoming_swapcoin(redeemscript)?;
+            self.wallet.remove_incoming_swapcoin(&redeemscript)?;
         }
 
         let mut outgoing_infos = Vec::new();
 
         // Broadcast the Outgoing Contracts
         for outgoing in outgoings {
+            if let Some(refund_tx) = &outgoing.refund_tx {
+                let txid = refund_tx.compute_txid();
+                let refunded = if self
+                    .wallet
+                    .rpc
+                    .get_raw_transaction_info(&txid, None)
+                    .is_ok()
+                {
+                    log::info!("Refund tx already broadcasted | Txid: {}", txid);
+                    true
+                } else {
+                    match self.wallet.send_tx(refund_tx) {
+                        Ok(_) => {
+                            log::info!("Broadcasted Refund tx | Txid: {}", txid);
+                            true
+                        }
+                        Err(e) => {
+                            log::warn!("Can't broadcast refund tx: {} | {:?}", txid, e);
+                            false
+                        }
+                    }
+                };
+                if refunded {
+                    self.wallet
+                        .remove_outgoing_swapcoin(&outgoing.get_multisig_redeemscript())?;
+                    log::info!("Refunded Outgoing Swapcoin removed from wallet");
+                    continue;
+                }
+            }
             let contract_tx = outgoing.get_fully_signed_contract_tx()?;
             if self
                 .wallet
@@ -1972,8 +2379,18 @@ impl Taker {
             outgoing_infos.push(((reedemscript, contract_tx), (timelock, timelock_spend)));
         }
 
-        // Check for contract confirmations and broadcast timelocked transaction
+        // Check for contract confirmations and broadcast hashlock and timelocked transactions
         let mut timelock_boardcasted = Vec::new();
+        let mut hashlock_broadcasted = Vec::new();
+        let contract_txids = hashlock_claims
+            .iter()
+            .map(|((_, contract), _)| contract.compute_txid())
+            .chain(
+                outgoing_infos
+                    .iter()
+                    .map(|((_, contract), _)| contract.compute_txid()),
+            )
+            .collect::<Vec<_>>();
 
         // Save the wallet file here before going into the expensive loop.
         self.wallet.sync()?;
@@ -1983,70 +2400,160 @@ impl Taker {
         // Start the loop to keep checking for timelock maturity, and spend from the contract asap.
         loop {
             // Break early if nothing to broadcast.
-            // This happens only when init_first_hop() fails at `NotEnoughMakersInOfferBook`
-            if outgoing_infos.is_empty() {
+            // This happens when init_first_hop() fails at `NotEnoughMakersInOfferBook`, or all the
+            // outgoings got refunded in a cooperative abort.
+            if outgoing_infos.is_empty() && hashlock_claims.is_empty() {
+                self.clear_ongoing_swaps()?;
                 break;
             }
+            // Reorged contracts go back to the mempool, and their maturity count restarts once they confirm again.
+            for txid in self.wallet.find_reorged_txs(&contract_txids)? {
+                log::warn!("Tx {} reorged out during recovery", txid);
+            }
+            for ((reedemscript, contract), hashlock_tx) in hashlock_claims.iter() {
+                // We have already broadcasted this tx, so skip
+                if hashlock_broadcasted.contains(&hashlock_tx) {
+                    continue;
+                }
+                // The hashlock branch has a relative timelock of 1 block. Wait for the contract to confirm.
+                let result = match self
+                    .wallet
+                    .rpc
+                    .get_raw_transaction_info(&contract.compute_txid(), None)
+                {
+                    Ok(result) => result,
+                    Err(_) => {
+                        log::warn!(
+                            "Contract Tx {} not found, rebroadcasting",
+                            contract.compute_txid()
+                        );
+                        if let Err(e) = self.wallet.send_tx(contract) {
+                            log::warn!("Failed to rebroadcast contract tx: {:?}", e);
+                        }
+                        continue;
+                    }
+                };
+                if let Some(blockhash) = result.blockhash {
+                    self.wallet
+                        .track_confirmation(contract.compute_txid(), blockhash)?;
+                }
+                if result.confirmations.unwrap_or(0) >= 1 {
+                    log::info!(
+                        "Broadcasting hashlock tx: {} for Contract Tx : {}",
+                        hashlock_tx.compute_txid(),
+                        contract.compute_txid()
+                    );
+                    if let Err(e) = self.wallet.send_tx(hashlock_tx) {
+                        log::warn!(
+                            "Can't send hashlock tx: {} | {:?}",
+                            hashlock_tx.compute_txid(),
+                            e
+                        );
+                        continue;
+                    }
+                    hashlock_broadcasted.push(hashlock_tx);
+
+                    self.wallet.untrack_confirmation(&contract.compute_txid())?;
+
+                    if let Some(incoming_removed) =
+                        self.wallet.remove_incoming_swapcoin(reedemscript)?
+                    {
+                        log::info!(
+                            "Removed Incoming Swapcoin from Wallet, Contract Txid: {}",
+                            incoming_removed.contract_tx.compute_txid()
+                        );
+                    }
+                    log::info!("Initializing Wallet sync and save");
+                    self.wallet.sync()?;
+                    self.wallet.save_to_disk()?;
+                    log::info!("Completed wallet sync and save");
+                }
+            }
             for ((reedemscript, contract), (timelock, timelocked_tx)) in outgoing_infos.iter() {
                 // We have already broadcasted this tx, so skip
                 if timelock_boardcasted.contains(&timelocked_tx) {
                     continue;
                 }
                 // Check if the contract tx has reached required maturity
-                // Failure here means the transaction hasn't been broadcasted yet. So do nothing and try again.
-                if let Ok(result) = self
+                // Failure here means the transaction is not in the mempool or the chain. It might have been
+                // dropped after a reorg, so rebroadcast it and try again.
+                let result = match self
                     .wallet
                     .rpc
                     .get_raw_transaction_info(&contract.compute_txid(), None)
                 {
-                    log::info!(
-                        "Contract Tx : {}, reached confirmation : {:?}, required : {}",
-                        contract.compute_txid(),
-                        result.confirmations,
-                        timelock
-                    );
-                    if let Some(confirmation) = result.confirmations {
-                        // Now the transaction is confirmed in a block, check for required maturity
-                        if confirmation > (*timelock as u32) {
-                            log::info!(
-                                "Timelock maturity of {} blocks for Contract Tx is reached : {}",
-                                timelock,
-                                contract.compute_txid()
-                            );
-                            log::info!(
-                                "Broadcasting timelocked tx: {}",
-                                timelocked_tx.compute_txid()
-                            );
-                            self.wallet.send_tx(timelocked_tx)?;
-                            timelock_boardcasted.push(timelocked_tx);
+                    Ok(result) => result,
+                    Err(_) => {
+                        log::warn!(
+                            "Contract Tx {} not found, rebroadcasting",
+                            contract.compute_txid()
+                        );
+                        if let Err(e) = self.wallet.send_tx(contract) {
+                            log::warn!("Failed to rebroadcast contract tx: {:?}", e);
+                        }
+                        continue;
+                    }
+                };
+                if let Some(blockhash) = result.blockhash {
+                    self.wallet
+                        .track_confirmation(contract.compute_txid(), blockhash)?;
+                }
+                log::info!(
+                    "Contract Tx : {}, reached confirmation : {:?}, required : {}",
+                    contract.compute_txid(),
+                    result.confirmations,
+                    timelock
+                );
+                if let Some(confirmation) = result.confirmations {
+                    // Now the transaction is confirmed in a block, check for required maturity
+                    if confirmation > (*timelock as u32) {
+                        log::info!(
+                            "Timelock maturity of {} blocks for Contract Tx is reached : {}",
+                            timelock,
+                            contract.compute_txid()
+                        );
+                        log::info!(
+                            "Broadcasting timelocked tx: {}",
+                            timelocked_tx.compute_txid()
+                        );
+                        self.wallet.send_tx(timelocked_tx)?;
+                        timelock_boardcasted.push(timelocked_tx);
 
-                            let outgoing_removed = self
-                                .wallet
-                                .remove_outgoing_swapcoin(reedemscript)?
-                                .expect("outgoing swapcoin expected");
+                        self.wallet.untrack_confirmation(&contract.compute_txid())?;
+
+                        if let Some(outgoing_removed) =
+                            self.wallet.remove_outgoing_swapcoin(reedemscript)?
+                        {
                             log::info!(
                                 "Removed Outgoing Swapcoin from Wallet, Contract Txid: {}",
                                 outgoing_removed.contract_tx.compute_txid()
                             );
-                            log::info!("Initializing Wallet sync and save");
-                            self.wallet.sync()?;
-                            self.wallet.save_to_disk()?;
-                            log::info!("Completed wallet sync and save");
                         }
+                        log::info!("Initializing Wallet sync and save");
+                        self.wallet.sync()?;
+                        self.wallet.save_to_disk()?;
+                        log::info!("Completed wallet sync and save");
                     }
                 }
             }
 
             // Everything is broadcasted. Clear the connectionstate and break the loop
+            log::info!(
+                "{} incoming contracts detected | {} hashlock txs broadcasted.",
+                hashlock_claims.len(),
+                hashlock_broadcasted.len()
+            );
             log::info!(
                 "{} outgoing contracts detected | {} timelock txs broadcasted.",
                 outgoing_infos.len(),
                 timelock_boardcasted.len()
             );
-            if timelock_boardcasted.len() == outgoing_infos.len() {
-                log::info!("All outgoing contracts reedemed. Cleared ongoing swap state");
+            if timelock_boardcasted.len() == outgoing_infos.len()
+                && hashlock_broadcasted.len() == hashlock_claims.len()
+            {
+                log::info!("All contracts reedemed. Cleared ongoing swap state");
                 // TODO: Reevaluate this.
-                self.clear_ongoing_swaps(); // This could be a bug if Taker is in middle of multiple swaps. For now we assume Taker will only do one swap at a time.
+                self.clear_ongoing_swaps()?; // This could be a bug if Taker is in middle of multiple swaps. For now we assume Taker will only do one swap at a time.
                 break;
             }
 
@@ -2065,12 +2572,12 @@ impl Taker {
 
     /// Synchronizes the offer book with addresses obtained from directory servers and local configurations.
     pub fn sync_offerbook(&mut self) -> Result<(), TakerError> {
-        let dns_addr = match self.config.connection_type {
+        let dns_addrs = match self.config.connection_type {
             ConnectionType::CLEARNET => {
                 if cfg!(feature = "integration-test") {
-                    format!("127.0.0.1:{}", 8080)
+                    vec![format!("127.0.0.1:{}", 8080)]
                 } else {
-                    self.config.directory_server_address.clone()
+                    self.config.directory_server_addresses.clone()
                 }
             }
             #[cfg(feature = "tor")]
@@ -2080,9 +2587,9 @@ impl Taker {
 
                     let hostname = get_tor_hostname(tor_dir)?;
                     log::info!("---------------hostname : {:?}", hostname);
-                    format!("{}:{}", hostname, 8080)
+                    vec![format!("{}:{}", hostname, 8080)]
                 } else {
-                    self.config.directory_server_address.clone()
+                    self.config.directory_server_addresses.clone()
                 }
             }
         };
@@ -2097,24 +2604,92 @@ impl Taker {
             None
         };
 
-        log::info!("Fetching addresses from DNS: {}", dns_addr);
-
-        let addresses_from_dns =
-            match fetch_addresses_from_dns(socks_port, dns_addr, self.config.connection_type) {
-                Ok(dns_addrs) => dns_addrs,
+        // Merge the makers listed by all the directories, so a single unreachable directory doesn't hide the market.
+        // For makers listed with different offers, keep the latest valid one. A maker is dead only if no
+        // directory could reach it at its last probe.
+        let now = unix_time();
+        let mut makers: HashMap<String, ListedMaker> = HashMap::new();
+        let mut reached_dns = 0;
+        let mut last_error = None;
+        for dns_addr in dns_addrs {
+            log::info!("Fetching addresses from DNS: {}", dns_addr);
+
+            let makers_from_dns = match fetch_makers_from_dns(
+                socks_port,
+                dns_addr.clone(),
+                self.config.connection_type,
+            ) {
+                Ok(dns_makers) => dns_makers,
                 Err(e) => {
-                    log::error!("Could not connect to DNS Server: {:?}", e);
-                    return Err(e);
+                    log::error!("Could not connect to DNS Server {}: {:?}", dns_addr, e);
+                    last_error = Some(e);
+                    continue;
                 }
             };
+            reached_dns += 1;
+
+            for maker in makers_from_dns {
+                let address = maker.address;
+                let offer = maker.offer.filter(|offer| match offer.verify(now) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        log::warn!("Invalid offer for {} from DNS: {:?}", address, e);
+                        false
+                    }
+                });
+                let entry = makers
+                    .entry(address.to_string())
+                    .or_insert_with(|| ListedMaker {
+                        address,
+                        offer: None,
+                        health: None,
+                    });
+                if offer.as_ref().map(|offer| offer.timestamp)
+                    > entry.offer.as_ref().map(|offer| offer.timestamp)
+                {
+                    entry.offer = offer;
+                }
+                if maker.health.as_ref().map(|h| (h.reachable, h.last_probe))
+                    > entry.health.as_ref().map(|h| (h.reachable, h.last_probe))
+                {
+                    entry.health = maker.health;
+                }
+            }
+        }
+        if reached_dns == 0 {
+            if let Some(e) = last_error {
+                return Err(e);
+            }
+        }
 
-        // For now, ask offers from everyone,
-        // Because we don not have any smart update mechanism, not asking again could cause problem.
-        // if a maker changes their offer without changing tor address, the taker will not ask them again for updated offer.
-        // TODO: Add smarter update mechanism, where DNS would keep a flag for every update of maker offers and taker
-        // will selectively redownload the offer from those makers only.
-        // Further TODO: The Offer book needs to be restructured to store a unqiue value per fidelity bond. Similar to DNS.
-        let offers = fetch_offer_from_makers(addresses_from_dns, &self.config)?;
+        // Use the signed offers cached by the DNS, and only dial the live makers whose offer is missing.
+        let mut offers = Vec::new();
+        let mut missing_addresses = Vec::new();
+        for maker in makers.into_values() {
+            if maker
+                .health
+                .as_ref()
+                .is_some_and(|health| !health.reachable)
+            {
+                log::info!("Skipping maker {} unreachable by the DNS", maker.address);
+                continue;
+            }
+            match maker.offer {
+                Some(offer) => offers.push(OfferAndAddress {
+                    offer,
+                    address: maker.address,
+                }),
+                None => missing_addresses.push(maker.address),
+            }
+        }
+        log::info!(
+            "Got {} offers from DNS, downloading {} from makers",
+            offers.len(),
+            missing_addresses.len()
+        );
+        if !missing_addresses.is_empty() {
+            offers.extend(fetch_offer_from_makers(missing_addresses, &self.config)?);
+        }
 
         // TODO: Use better logic to update offerbook than to just rewrite everything.
         self.offerbook = OfferBook::default();
@@ -3,7 +3,7 @@
 //!  Represents the configuration options for the Taker module, controlling behaviors
 //! such as refund locktime, connection attempts, sleep delays, and timeouts.
 
-use crate::utill::{get_taker_dir, parse_field, parse_toml, ConnectionType};
+use crate::utill::{get_taker_dir, parse_field, parse_list, parse_toml, ConnectionType};
 use std::{io, io::Write, path::Path};
 
 /// Taker configuration with refund, connection, and sleep settings.
@@ -13,10 +13,14 @@ pub struct TakerConfig {
     pub network_port: u16,
     /// Socks proxy port used to connect TOR
     pub socks_port: u16,
-    /// Directory server address (can be clearnet or onion)
-    pub directory_server_address: String,
+    /// Directory server addresses (can be clearnet or onion). Makers are listed by any of them.
+    pub directory_server_addresses: Vec<String>,
     /// Connection type
     pub connection_type: ConnectionType,
+    /// Fee rate of the cooperative refund txs, in sats per vbyte.
+    pub refund_feerate: u64,
+    /// Bitcoind ZMQ endpoint publishing `rawtx` and `hashblock` notifications. Empty to poll the RPC instead.
+    pub zmq_address: String,
 }
 
 impl Default for TakerConfig {
@@ -24,8 +28,9 @@ impl Default for TakerConfig {
         Self {
             network_port: 8000,
             socks_port: 19070,
-            directory_server_address:
+            directory_server_addresses: vec![
                 "bhbzkndgad52ojm75w4goii7xsi6ou73fzyvorxas7swg2snlto4c4ad.onion:8080".to_string(),
+            ],
             connection_type: {
                 #[cfg(feature = "tor")]
                 {
@@ -36,6 +41,8 @@ impl Default for TakerConfig {
                     ConnectionType::CLEARNET
                 }
             },
+            refund_feerate: 2,
+            zmq_address: String::new(),
         }
     }
 }
@@ -76,14 +83,22 @@ impl TakerConfig {
         Ok(TakerConfig {
             network_port: parse_field(config_map.get("network_port"), default_config.network_port),
             socks_port: parse_field(config_map.get("socks_port"), default_config.socks_port),
-            directory_server_address: parse_field(
-                config_map.get("directory_server_address"),
-                default_config.directory_server_address,
+            // Also accept the single address key of older config files.
+            directory_server_addresses: parse_list(
+                config_map
+                    .get("directory_server_addresses")
+                    .or_else(|| config_map.get("directory_server_address")),
+                default_config.directory_server_addresses,
             ),
             connection_type: parse_field(
                 config_map.get("connection_type"),
                 default_config.connection_type,
             ),
+            refund_feerate: parse_field(
+                config_map.get("refund_feerate"),
+                default_config.refund_feerate,
+            ),
+            zmq_address: parse_field(config_map.get("zmq_address"), default_config.zmq_address),
         })
     }
 
@@ -92,9 +107,16 @@ impl TakerConfig {
         let toml_data = format!(
             "network_port = {}
 socks_port = {}
-directory_server_address = {}
-connection_type = {:?}",
-            self.network_port, self.socks_port, self.directory_server_address, self.connection_type
+directory_server_addresses = {}
+connection_type = {:?}
+refund_feerate = {}
+zmq_address = {}",
+            self.network_port,
+            self.socks_port,
+            self.directory_server_addresses.join(","),
+            self.connection_type,
+            self.refund_feerate,
+            self.zmq_address
         );
         std::fs::create_dir_all(path.parent().expect("Path should NOT be root!"))?;
         let mut file = std::fs::File::create(path)?;
@@ -189,6 +211,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_directory_server_addresses() {
+        let contents = r#"
+            directory_server_addresses = abc.onion:8080,def.onion:8080
+        "#;
+        let config_path = create_temp_config(contents, "directory_list_taker_config.toml");
+        let config = TakerConfig::new(Some(&config_path)).unwrap();
+        remove_temp_config(&config_path);
+        assert_eq!(
+            config.directory_server_addresses,
+            vec!["abc.onion:8080", "def.onion:8080"]
+        );
+
+        // Config files with a single directory address still load.
+        let contents = r#"
+            directory_server_address = abc.onion:8080
+        "#;
+        let config_path = create_temp_config(contents, "directory_single_taker_config.toml");
+        let config = TakerConfig::new(Some(&config_path)).unwrap();
+        remove_temp_config(&config_path);
+        assert_eq!(config.directory_server_addresses, vec!["abc.onion:8080"]);
+    }
+
     #[test]
     fn test_missing_file() {
         let config_path = get_taker_dir().join("taker.toml");
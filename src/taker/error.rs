@@ -1,7 +1,10 @@
 //! All Taker-related errors.
 use crate::{
-    error::NetError, market::directory::DirectoryServerError, protocol::error::ProtocolError,
+    error::NetError,
+    market::directory::DirectoryServerError,
+    protocol::{error::ProtocolError, messages::ErrorCode},
     wallet::WalletError,
+    watchtower::WatchtowerError,
 };
 
 /// Represents errors that can occur during Taker operations.
@@ -23,16 +26,100 @@ pub enum TakerError {
     Directory(DirectoryServerError),
     /// Error related to network operations.
     Net(NetError),
+    /// Error encountered during interaction with a watchtower.
+    Watchtower(WatchtowerError),
     /// Error indicating the send amount was not set for a transaction.
     SendAmountNotSet,
     /// Error indicating a timeout while waiting for the funding transaction.
     FundingTxWaitTimeOut,
+    /// Error indicating funding transactions of an earlier hop got reorged out after being confirmed.
+    /// Contains the list of the reorged funding transaction IDs.
+    FundingTxReorged(Vec<bitcoin::Txid>),
     /// Error deserializing data, typically related to CBOR-encoded data.
     Deserialize(serde_cbor::Error),
     /// Error indicating an MPSC channel failure.
     ///
     /// This error occurs during internal thread communication.
     MPSC(String),
+    /// The Maker doesn't support our protocol version.
+    UnsupportedVersion(String),
+    /// The Maker refused the swap amount.
+    AmountOutOfRange(String),
+    /// The Maker refused the contract locktime.
+    BadLocktime(String),
+    /// The Maker doesn't have enough liquidity to fund its side of the swap.
+    InsufficientLiquidity(String),
+    /// The Maker found one of our signatures invalid.
+    InvalidSignature(String),
+    /// The Maker is too busy to serve us right now. Contains the reported [ErrorCode].
+    MakerBusy(ErrorCode),
+    /// Any other error reported by the Maker.
+    MakerRejected {
+        /// The reported error code.
+        code: ErrorCode,
+        /// The reported reason.
+        reason: String,
+    },
+}
+
+impl TakerError {
+    /// Maps an error reported by a Maker to its typed variant.
+    pub(crate) fn from_maker(code: ErrorCode, reason: String) -> Self {
+        match code {
+            ErrorCode::UnsupportedVersion => Self::UnsupportedVersion(reason),
+            ErrorCode::AmountOutOfRange => Self::AmountOutOfRange(reason),
+            ErrorCode::BadLocktime => Self::BadLocktime(reason),
+            ErrorCode::InsufficientLiquidity => Self::InsufficientLiquidity(reason),
+            ErrorCode::InvalidSignature => Self::InvalidSignature(reason),
+            ErrorCode::TooManyConnections | ErrorCode::TooManySwaps | ErrorCode::RateLimited => {
+                Self::MakerBusy(code)
+            }
+            code => Self::MakerRejected { code, reason },
+        }
+    }
+
+    /// Whether repeating the same request to the same Maker may succeed.
+    pub(crate) fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            Self::UnsupportedVersion(_)
+                | Self::AmountOutOfRange(_)
+                | Self::BadLocktime(_)
+                | Self::InsufficientLiquidity(_)
+                | Self::InvalidSignature(_)
+                | Self::MakerRejected { .. }
+        )
+    }
+
+    /// Whether the Maker failed or misbehaved, and should be marked bad.
+    /// Otherwise the Maker is just unsuitable for this swap, and another one should be picked.
+    pub(crate) fn is_maker_fault(&self) -> bool {
+        !matches!(
+            self,
+            Self::UnsupportedVersion(_)
+                | Self::AmountOutOfRange(_)
+                | Self::BadLocktime(_)
+                | Self::InsufficientLiquidity(_)
+                | Self::MakerBusy(_)
+        )
+    }
+
+    /// The code to abort the protocol with, if this error means a Maker's message got rejected.
+    pub(crate) fn abort_code(&self) -> Option<ErrorCode> {
+        let protocol_error = match self {
+            Self::Wallet(WalletError::Protocol(e)) => e,
+            Self::Wallet(WalletError::Secp(_)) => return Some(ErrorCode::InvalidSignature),
+            Self::Deserialize(_) => return Some(ErrorCode::UnexpectedMessage),
+            _ => return None,
+        };
+        Some(match protocol_error {
+            ProtocolError::WrongMessage { .. } => ErrorCode::UnexpectedMessage,
+            ProtocolError::WrongNumOfSigs { .. } | ProtocolError::Secp(_) => {
+                ErrorCode::InvalidSignature
+            }
+            _ => ErrorCode::InvalidContract,
+        })
+    }
 }
 
 impl From<serde_cbor::Error> for TakerError {
@@ -53,6 +140,12 @@ impl From<DirectoryServerError> for TakerError {
     }
 }
 
+impl From<WatchtowerError> for TakerError {
+    fn from(value: WatchtowerError) -> Self {
+        Self::Watchtower(value)
+    }
+}
+
 impl From<std::io::Error> for TakerError {
     fn from(value: std::io::Error) -> Self {
         Self::IO(value)
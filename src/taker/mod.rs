@@ -13,3 +13,4 @@ mod routines;
 pub use self::api::TakerBehavior;
 pub use api::{SwapParams, Taker};
 pub use config::TakerConfig;
+pub use offers::fetch_addresses_from_dns;
@@ -16,6 +16,7 @@ use std::{
     thread::{self, Builder},
 };
 
+use bitcoin::Amount;
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "tor")]
@@ -23,11 +24,17 @@ use socks::Socks5Stream;
 
 use crate::{
     error::NetError,
-    protocol::messages::{DnsRequest, Offer},
-    utill::{read_message, send_message, ConnectionType, GLOBAL_PAUSE, NET_TIMEOUT},
+    protocol::{
+        contract::calculate_coinswap_fee,
+        messages::{DirectoryEntry, DnsRequest, MakerHealth, Offer},
+    },
+    utill::{read_message, send_message, unix_time, ConnectionType, GLOBAL_PAUSE, NET_TIMEOUT},
 };
 
-use super::{config::TakerConfig, error::TakerError, routines::download_maker_offer};
+use super::{
+    api::REFUND_LOCKTIME_STEP, config::TakerConfig, error::TakerError,
+    routines::download_maker_offer,
+};
 
 /// Represents an offer along with the corresponding maker address.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -37,6 +44,34 @@ pub struct OfferAndAddress {
     pub address: MakerAddress,
 }
 
+impl OfferAndAddress {
+    /// Choose the cheapest [OfferTier](crate::protocol::messages::OfferTier) of the offer, usable for swapping `amount`.
+    ///
+    /// Tiers requiring a larger locktime difference than the taker's refund locktime step are skipped.
+    /// Fees are compared at the given refund locktime.
+    pub(crate) fn select_tier(&self, amount: Amount, refund_locktime: u16) -> Option<usize> {
+        self.offer
+            .tiers
+            .iter()
+            .enumerate()
+            .filter(|(_, tier)| {
+                amount >= Amount::from_sat(tier.min_size)
+                    && amount <= Amount::from_sat(tier.max_size)
+                    && tier.minimum_locktime <= REFUND_LOCKTIME_STEP
+            })
+            .min_by_key(|(_, tier)| {
+                calculate_coinswap_fee(
+                    amount.to_sat(),
+                    refund_locktime,
+                    tier.base_fee,
+                    tier.amount_relative_fee_pct,
+                    tier.time_relative_fee_pct,
+                )
+            })
+            .map(|(index, _)| index)
+    }
+}
+
 const _REGTEST_MAKER_ADDRESSES_PORT: &[&str] = &["6102", "16102", "26102", "36102", "46102"];
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -95,27 +130,38 @@ impl OfferBook {
     // - ranking system.
     // - various categories of livelynesss, to smartly distribute try counts.
 
-    /// Gets all "not-bad" offers.
+    /// Gets all "not-bad" offers, that are not expired.
     pub fn all_good_makers(&self) -> Vec<&OfferAndAddress> {
+        let now = unix_time();
         self.all_makers
             .iter()
-            .filter(|offer| !self.bad_makers.contains(offer))
+            .filter(|offer| !self.is_bad_maker(&offer.address) && !offer.offer.is_stale(now))
             .collect()
     }
 
-    /// Adds a new offer to the offer book.
+    /// Adds a new offer to the offer book. Replaces an older offer of the same maker.
     pub(crate) fn add_new_offer(&mut self, offer: &OfferAndAddress) -> bool {
-        if !self.all_makers.contains(offer) {
-            self.all_makers.push(offer.clone());
-            true
-        } else {
-            false
+        match self
+            .all_makers
+            .iter_mut()
+            .find(|oa| oa.address == offer.address)
+        {
+            Some(existing) if existing.offer.timestamp < offer.offer.timestamp => {
+                *existing = offer.clone();
+                true
+            }
+            Some(_) => false,
+            None => {
+                self.all_makers.push(offer.clone());
+                true
+            }
         }
     }
 
-    /// Adds a bad maker to the offer book.
+    /// Adds a bad maker to the offer book. Makers are identified by their address, so their later offers are
+    /// bad too.
     pub(crate) fn add_bad_maker(&mut self, bad_maker: &OfferAndAddress) -> bool {
-        if !self.bad_makers.contains(bad_maker) {
+        if !self.is_bad_maker(&bad_maker.address) {
             self.bad_makers.push(bad_maker.clone());
             true
         } else {
@@ -123,6 +169,18 @@ impl OfferBook {
         }
     }
 
+    fn is_bad_maker(&self, address: &MakerAddress) -> bool {
+        self.bad_makers.iter().any(|bad| &bad.address == address)
+    }
+
+    /// Drops the expired offers. Returns the number of dropped offers.
+    pub(crate) fn drop_stale_offers(&mut self) -> usize {
+        let now = unix_time();
+        let count = self.all_makers.len();
+        self.all_makers.retain(|oa| !oa.offer.is_stale(now));
+        count - self.all_makers.len()
+    }
+
     /// Gets the list of bad makers.
     pub(crate) fn get_bad_makers(&self) -> Vec<&OfferAndAddress> {
         self.bad_makers.iter().collect()
@@ -200,12 +258,106 @@ pub(crate) fn fetch_offer_from_makers(
     Ok(result)
 }
 
+/// A maker listed by a directory server.
+#[derive(Debug)]
+pub(crate) struct ListedMaker {
+    pub(crate) address: MakerAddress,
+    /// The maker's signed offer cached by the directory, if any.
+    pub(crate) offer: Option<Offer>,
+    /// The result of the directory's last liveness probe of the maker, if any.
+    pub(crate) health: Option<MakerHealth>,
+}
+
+/// Number of attempts to fetch the makers from a directory server, before moving on to the next one.
+const DNS_FETCH_ATTEMPTS: u32 = 3;
+
 /// Retrieves advertised maker addresses from directory servers based on the specified network.
 pub fn fetch_addresses_from_dns(
     socks_port: Option<u16>,
     dns_addr: String,
     connection_type: ConnectionType,
 ) -> Result<Vec<MakerAddress>, TakerError> {
+    Ok(
+        fetch_makers_from_dns(socks_port, dns_addr, connection_type)?
+            .into_iter()
+            .map(|maker| maker.address)
+            .collect(),
+    )
+}
+
+/// Retrieves advertised maker addresses from a directory server based on the specified network,
+/// along with the last signed offer each maker posted and its liveness, if known.
+pub(crate) fn fetch_makers_from_dns(
+    socks_port: Option<u16>,
+    dns_addr: String,
+    connection_type: ConnectionType,
+) -> Result<Vec<ListedMaker>, TakerError> {
+    let mut attempt = 1;
+    loop {
+        match fetch_makers_from_dns_once(socks_port, &dns_addr, connection_type) {
+            Ok(makers) => return Ok(makers),
+            Err(e) if attempt < DNS_FETCH_ATTEMPTS => {
+                log::error!(
+                    "Error fetching makers from DNS {}: {:?}. Retrying...",
+                    dns_addr,
+                    e
+                );
+                attempt += 1;
+                thread::sleep(GLOBAL_PAUSE);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn fetch_makers_from_dns_once(
+    socks_port: Option<u16>,
+    dns_addr: &str,
+    connection_type: ConnectionType,
+) -> Result<Vec<ListedMaker>, TakerError> {
+    let mut stream = connect_to_dns(socks_port, dns_addr, connection_type)?;
+    send_message(&mut stream, &DnsRequest::GetMakers)?;
+    let entries = match read_message(&mut stream) {
+        Ok(response) => serde_cbor::de::from_slice::<Vec<DirectoryEntry>>(&response)?,
+        // Directory servers older than `GetMakers` drop the connection, only ask them for the addresses.
+        Err(e) => {
+            log::debug!(
+                "DNS {} didn't answer GetMakers: {:?}, falling back to Get",
+                dns_addr,
+                e
+            );
+            let mut stream = connect_to_dns(socks_port, dns_addr, connection_type)?;
+            send_message(&mut stream, &DnsRequest::Get)?;
+            let response: String = serde_cbor::de::from_slice(&read_message(&mut stream)?)?;
+            response
+                .lines()
+                .map(|url| DirectoryEntry {
+                    url: url.to_string(),
+                    offer: None,
+                    health: None,
+                })
+                .collect()
+        }
+    };
+
+    // Parse and validate the response
+    entries
+        .into_iter()
+        .map(|entry| {
+            Ok(ListedMaker {
+                address: MakerAddress::new(&entry.url)?,
+                offer: entry.offer,
+                health: entry.health,
+            })
+        })
+        .collect()
+}
+
+fn connect_to_dns(
+    socks_port: Option<u16>,
+    dns_addr: &str,
+    connection_type: ConnectionType,
+) -> Result<TcpStream, TakerError> {
     if !cfg!(feature = "tor") {
         assert!(
             socks_port.is_none(),
@@ -213,64 +365,17 @@ pub fn fetch_addresses_from_dns(
         );
     }
 
-    loop {
-        let mut stream = match connection_type {
-            ConnectionType::CLEARNET => match TcpStream::connect(dns_addr.as_str()) {
-                Err(e) => {
-                    log::error!("Error connecting to DNS: {:?}", e);
-                    thread::sleep(GLOBAL_PAUSE);
-                    continue;
-                }
-                Ok(s) => s,
-            },
-            #[cfg(feature = "tor")]
-            ConnectionType::TOR => {
-                let socket_addrs = format!("127.0.0.1:{}", socks_port.expect("Tor port expected"));
-                match Socks5Stream::connect(socket_addrs, dns_addr.as_str()) {
-                    Err(e) => {
-                        log::error!("Error connecting to DNS: {:?}", e);
-                        thread::sleep(GLOBAL_PAUSE);
-                        continue;
-                    }
-                    Ok(s) => s.into_inner(),
-                }
-            }
-        };
-
-        stream.set_read_timeout(Some(NET_TIMEOUT))?;
-        stream.set_write_timeout(Some(NET_TIMEOUT))?;
-        stream.set_nonblocking(false)?;
-
-        if let Err(e) = send_message(&mut stream, &DnsRequest::Get) {
-            log::error!("Failed to send request. Retrying...{}", e);
-            thread::sleep(GLOBAL_PAUSE);
-            continue;
+    let stream = match connection_type {
+        ConnectionType::CLEARNET => TcpStream::connect(dns_addr)?,
+        #[cfg(feature = "tor")]
+        ConnectionType::TOR => {
+            let socket_addrs = format!("127.0.0.1:{}", socks_port.expect("Tor port expected"));
+            Socks5Stream::connect(socket_addrs, dns_addr)?.into_inner()
         }
+    };
 
-        // Read the response
-        let response: String = match read_message(&mut stream) {
-            Ok(resp) => serde_cbor::de::from_slice(&resp[..])?,
-            Err(e) => {
-                log::error!("Error reading DNS response: {}. Retrying...", e);
-                thread::sleep(GLOBAL_PAUSE);
-                continue;
-            }
-        };
-
-        // Parse and validate the response
-        match response
-            .lines()
-            .map(MakerAddress::new)
-            .collect::<Result<Vec<MakerAddress>, _>>()
-        {
-            Ok(addresses) => {
-                return Ok(addresses);
-            }
-            Err(e) => {
-                log::error!("Error decoding DNS response: {:?}. Retrying...", e);
-                thread::sleep(GLOBAL_PAUSE);
-                continue;
-            }
-        }
-    }
+    stream.set_read_timeout(Some(NET_TIMEOUT))?;
+    stream.set_write_timeout(Some(NET_TIMEOUT))?;
+    stream.set_nonblocking(false)?;
+    Ok(stream)
 }
@@ -15,23 +15,24 @@ use crate::{
     protocol::{
         contract::{
             calculate_coinswap_fee, create_contract_redeemscript, find_funding_output_index,
-            validate_contract_tx,
+            sign_abort_request, validate_contract_tx,
         },
         error::ProtocolError,
         messages::{
             ContractSigsAsRecvrAndSender, ContractSigsForRecvr, ContractSigsForSender,
-            ContractTxInfoForRecvr, ContractTxInfoForSender, FundingTxInfo, GiveOffer,
+            ContractTxInfoForRecvr, ContractTxInfoForSender, ErrorCode, FundingTxInfo, GiveOffer,
             HashPreimage, MakerToTakerMessage, NextHopInfo, Offer, Preimage, PrivKeyHandover,
-            ProofOfFunding, ReqContractSigsForRecvr, ReqContractSigsForSender, TakerHello,
-            TakerToMakerMessage,
+            ProofOfFunding, ReqAbort, ReqContractSigsForRecvr, ReqContractSigsForSender, RespAbort,
+            TakerHello, TakerToMakerMessage,
         },
+        podle::Podle,
         Hash160,
     },
     taker::api::MINER_FEE,
-    utill::{read_message, send_message, ConnectionType},
+    utill::{read_message, send_message, unix_time, ConnectionType},
     wallet::WalletError,
 };
-use bitcoin::{secp256k1::SecretKey, Amount, PublicKey, ScriptBuf, Transaction};
+use bitcoin::{ecdsa::Signature, secp256k1::SecretKey, Amount, PublicKey, ScriptBuf, Transaction};
 
 use super::{
     config::TakerConfig,
@@ -60,20 +61,57 @@ pub(crate) struct ContractsInfo {
     pub(crate) wallet_label: String,
 }
 
+/// Read the next message from a Maker. A [MakerToTakerMessage::Error] is returned as the matching [TakerError].
+fn read_maker_message(socket: &mut TcpStream) -> Result<MakerToTakerMessage, TakerError> {
+    let msg_bytes = read_message(socket)?;
+    match serde_cbor::from_slice(&msg_bytes)? {
+        MakerToTakerMessage::Error { code, reason } => {
+            log::warn!("Maker reported error {:?}: {}", code, reason);
+            Err(TakerError::from_maker(code, reason))
+        }
+        msg => Ok(msg),
+    }
+}
+
+/// Tell the Maker we abandon the protocol with it, if the result is a rejection of its last message.
+/// Best effort, the connection is dropped right after anyway.
+fn abort_if_rejected<T>(
+    socket: &mut TcpStream,
+    result: Result<T, TakerError>,
+) -> Result<T, TakerError> {
+    if let Err(e) = &result {
+        if let Some(code) = e.abort_code() {
+            send_abort(socket, code, format!("{:?}", e));
+        }
+    }
+    result
+}
+
+/// Send a [TakerToMakerMessage::Abort]. Best effort, errors are only logged.
+fn send_abort(socket: &mut TcpStream, code: ErrorCode, reason: String) {
+    log::warn!("Aborting with maker, {:?}: {}", code, reason);
+    if let Err(e) = send_message(socket, &TakerToMakerMessage::Abort { code, reason }) {
+        log::debug!("Failed to send abort message: {:?}", e);
+    }
+}
+
 /// Make a handshake with a maker.
 /// Ensures that the Maker is alive and responding.
 ///
 // In future, handshake can be used to find protocol compatibility across multiple versions.
-pub(crate) fn handshake_maker(socket: &mut TcpStream) -> Result<(), TakerError> {
+pub(crate) fn handshake_maker(
+    socket: &mut TcpStream,
+    podle: Option<&Podle>,
+) -> Result<(), TakerError> {
     send_message(
         socket,
         &TakerToMakerMessage::TakerHello(TakerHello {
             protocol_version_min: 1,
             protocol_version_max: 1,
+            podle: podle.cloned(),
         }),
     )?;
-    let msg_bytes = read_message(socket)?;
-    let msg: MakerToTakerMessage = serde_cbor::from_slice(&msg_bytes)?;
+    let msg = read_maker_message(socket)?;
 
     // Check that protocol version is always 1.
     match msg {
@@ -81,21 +119,22 @@ pub(crate) fn handshake_maker(socket: &mut TcpStream) -> Result<(), TakerError>
             if m.protocol_version_max == 1 && m.protocol_version_min == 1 {
                 Ok(())
             } else {
-                Err(ProtocolError::WrongMessage {
-                    expected: "Only protocol version 1 is allowed".to_string(),
-                    received: format!(
-                        "min/max version  = {}/{}",
-                        m.protocol_version_min, m.protocol_version_max
-                    ),
-                }
-                .into())
+                let reason = format!(
+                    "Only protocol version 1 is allowed, got min/max version = {}/{}",
+                    m.protocol_version_min, m.protocol_version_max
+                );
+                send_abort(socket, ErrorCode::UnsupportedVersion, reason.clone());
+                Err(TakerError::UnsupportedVersion(reason))
             }
         }
-        any => Err((ProtocolError::WrongMessage {
-            expected: "MakerHello".to_string(),
-            received: format!("{}", any),
-        })
-        .into()),
+        any => abort_if_rejected(
+            socket,
+            Err((ProtocolError::WrongMessage {
+                expected: "MakerHello".to_string(),
+                received: format!("{}", any),
+            })
+            .into()),
+        ),
     }
 }
 
@@ -106,8 +145,10 @@ pub(crate) fn req_sigs_for_sender_once<S: SwapCoin>(
     maker_multisig_nonces: &[SecretKey],
     maker_hashlock_nonces: &[SecretKey],
     locktime: u16,
+    tier: usize,
+    podle: &Podle,
 ) -> Result<ContractSigsForSender, TakerError> {
-    handshake_maker(socket)?;
+    handshake_maker(socket, Some(podle))?;
     let txs_info = maker_multisig_nonces
         .iter()
         .zip(maker_hashlock_nonces.iter())
@@ -132,40 +173,37 @@ pub(crate) fn req_sigs_for_sender_once<S: SwapCoin>(
             txs_info,
             hashvalue: outgoing_swapcoins[0].get_hashvalue()?,
             locktime,
+            tier,
         }),
     )?;
 
-    let msg_bytes = read_message(socket)?;
-    let msg: MakerToTakerMessage = serde_cbor::from_slice(&msg_bytes)?;
-    let contract_sigs_for_sender = match msg {
+    let msg = read_maker_message(socket)?;
+    let result = match msg {
         MakerToTakerMessage::RespContractSigsForSender(m) => {
             if m.sigs.len() != outgoing_swapcoins.len() {
-                return Err((ProtocolError::WrongNumOfSigs {
+                Err((ProtocolError::WrongNumOfSigs {
                     expected: outgoing_swapcoins.len(),
                     received: m.sigs.len(),
                 })
-                .into());
+                .into())
             } else {
-                m
+                m.sigs
+                    .iter()
+                    .zip(outgoing_swapcoins.iter())
+                    .try_for_each(|(sig, outgoing_swapcoin)| {
+                        outgoing_swapcoin.verify_contract_tx_sender_sig(sig)
+                    })
+                    .map(|_| m)
+                    .map_err(TakerError::from)
             }
         }
-        any => {
-            return Err((ProtocolError::WrongMessage {
-                expected: "RespContractSigsForSender".to_string(),
-                received: format!("{}", any),
-            })
-            .into());
-        }
+        any => Err((ProtocolError::WrongMessage {
+            expected: "RespContractSigsForSender".to_string(),
+            received: format!("{}", any),
+        })
+        .into()),
     };
-
-    for (sig, outgoing_swapcoin) in contract_sigs_for_sender
-        .sigs
-        .iter()
-        .zip(outgoing_swapcoins.iter())
-    {
-        outgoing_swapcoin.verify_contract_tx_sender_sig(sig)?;
-    }
-    Ok(contract_sigs_for_sender)
+    abort_if_rejected(socket, result)
 }
 
 /// Request signatures for receiver side of the hop. Attempt once.
@@ -174,7 +212,7 @@ pub(crate) fn req_sigs_for_recvr_once<S: SwapCoin>(
     incoming_swapcoins: &[S],
     receivers_contract_txes: &[Transaction],
 ) -> Result<ContractSigsForRecvr, TakerError> {
-    handshake_maker(socket)?;
+    handshake_maker(socket, None)?;
 
     let txs_info = incoming_swapcoins
         .iter()
@@ -190,37 +228,31 @@ pub(crate) fn req_sigs_for_recvr_once<S: SwapCoin>(
         &TakerToMakerMessage::ReqContractSigsForRecvr(ReqContractSigsForRecvr { txs: txs_info }),
     )?;
 
-    let msg_bytes = read_message(socket)?;
-    let msg: MakerToTakerMessage = serde_cbor::from_slice(&msg_bytes)?;
-    let contract_sigs_for_recvr = match msg {
+    let msg = read_maker_message(socket)?;
+    let result = match msg {
         MakerToTakerMessage::RespContractSigsForRecvr(m) => {
             if m.sigs.len() != incoming_swapcoins.len() {
-                return Err((ProtocolError::WrongNumOfSigs {
+                Err((ProtocolError::WrongNumOfSigs {
                     expected: incoming_swapcoins.len(),
                     received: m.sigs.len(),
                 })
-                .into());
+                .into())
             } else {
-                m
+                m.sigs
+                    .iter()
+                    .zip(incoming_swapcoins.iter())
+                    .try_for_each(|(sig, swapcoin)| swapcoin.verify_contract_tx_receiver_sig(sig))
+                    .map(|_| m)
+                    .map_err(TakerError::from)
             }
         }
-        any => {
-            return Err((ProtocolError::WrongMessage {
-                expected: "ContractSigsForRecvr".to_string(),
-                received: format!("{}", any),
-            })
-            .into());
-        }
+        any => Err((ProtocolError::WrongMessage {
+            expected: "ContractSigsForRecvr".to_string(),
+            received: format!("{}", any),
+        })
+        .into()),
     };
-
-    for (sig, swapcoin) in contract_sigs_for_recvr
-        .sigs
-        .iter()
-        .zip(incoming_swapcoins.iter())
-    {
-        swapcoin.verify_contract_tx_receiver_sig(sig)?;
-    }
-    Ok(contract_sigs_for_recvr)
+    abort_if_rejected(socket, result)
 }
 
 // Type for information related to `this maker` consisting of:
@@ -231,6 +263,7 @@ pub(crate) struct ThisMakerInfo {
     pub(crate) funding_tx_infos: Vec<FundingTxInfo>,
     pub(crate) this_maker_contract_txs: Vec<Transaction>,
     pub this_maker_refund_locktime: u16,
+    pub(crate) this_maker_tier: usize,
 }
 
 // Type for information related to the next peer // why not next Maker?
@@ -267,13 +300,24 @@ pub(crate) fn send_proof_of_funding_and_init_next_hop(
         refund_locktime: tmi.this_maker_refund_locktime,
         contract_feerate: MINER_FEE,
         id,
+        tier: tmi.this_maker_tier,
     });
 
     send_message(socket, &pof_msg)?;
 
     // Recv ContractSigsAsRecvrAndSender.
-    let msg_bytes = read_message(socket)?;
-    let msg: MakerToTakerMessage = serde_cbor::from_slice(&msg_bytes)?;
+    let msg = read_maker_message(socket)?;
+    let result = check_contract_sigs_as_recvr_and_sender(msg, &tmi, &npi, &hashvalue);
+    abort_if_rejected(socket, result)
+}
+
+/// Validates the Maker's reply to a [ProofOfFunding], and returns it with the contract redeemscripts of the next hop.
+fn check_contract_sigs_as_recvr_and_sender(
+    msg: MakerToTakerMessage,
+    tmi: &ThisMakerInfo,
+    npi: &NextMakerInfo,
+    hashvalue: &Hash160,
+) -> Result<(ContractSigsAsRecvrAndSender, Vec<ScriptBuf>), TakerError> {
     let contract_sigs_as_recvr_and_sender = match msg {
         MakerToTakerMessage::ReqContractSigsAsRecvrAndSender(m) => {
             if m.receivers_contract_txs.len() != tmi.funding_tx_infos.len() {
@@ -324,12 +368,13 @@ pub(crate) fn send_proof_of_funding_and_init_next_hop(
         .map(|i| i.funding_amount)
         .sum::<Amount>();
 
+    let tier = &tmi.this_maker.offer.tiers[tmi.this_maker_tier];
     let coinswap_fees = calculate_coinswap_fee(
         this_amount,
         tmi.this_maker_refund_locktime,
-        tmi.this_maker.offer.base_fee,
-        tmi.this_maker.offer.amount_relative_fee_pct,
-        tmi.this_maker.offer.time_relative_fee_pct,
+        tier.base_fee,
+        tier.amount_relative_fee_pct,
+        tier.time_relative_fee_pct,
     );
 
     let miner_fees_paid_by_taker = (tmi.funding_tx_infos.len() as u64) * MINER_FEE;
@@ -380,7 +425,7 @@ pub(crate) fn send_proof_of_funding_and_init_next_hop(
             create_contract_redeemscript(
                 hashlock_pubkey,
                 &senders_contract_tx_info.timelock_pubkey,
-                &hashvalue,
+                hashvalue,
                 &tmi.this_maker_refund_locktime,
             )
         })
@@ -406,30 +451,68 @@ pub(crate) fn send_hash_preimage_and_get_private_keys(
 
     send_message(socket, &hash_preimage_msg)?;
 
-    let msg_bytes = read_message(socket)?;
-    let msg: MakerToTakerMessage = serde_cbor::from_slice(&msg_bytes)?;
-    let privkey_handover = match msg {
+    let msg = read_maker_message(socket)?;
+    let result = match msg {
         MakerToTakerMessage::RespPrivKeyHandover(m) => {
             if m.multisig_privkeys.len() != receivers_multisig_redeemscripts.len() {
-                return Err((ProtocolError::WrongNumOfPrivkeys {
+                Err((ProtocolError::WrongNumOfPrivkeys {
                     expected: receivers_multisig_redeemscripts.len(),
                     received: m.multisig_privkeys.len(),
                 })
-                .into());
+                .into())
             } else {
-                m
+                Ok(m)
             }
         }
-        any => {
-            return Err((ProtocolError::WrongMessage {
-                expected: "PrivkeyHandover".to_string(),
-                received: format!("{}", any),
-            })
-            .into());
-        }
+        any => Err((ProtocolError::WrongMessage {
+            expected: "PrivkeyHandover".to_string(),
+            received: format!("{}", any),
+        })
+        .into()),
     };
+    abort_if_rejected(socket, result)
+}
+
+/// Request a Maker to cooperatively abort a swap, and return its refund txs and sigs.
+pub(crate) fn req_abort(
+    socket: &mut TcpStream,
+    id: &str,
+    multisig_nonce: &SecretKey,
+    outgoing_refund_sigs: &[Signature],
+    incoming_refund_txs: &[Transaction],
+) -> Result<RespAbort, TakerError> {
+    send_message(
+        socket,
+        &TakerToMakerMessage::ReqAbort(ReqAbort {
+            id: id.to_string(),
+            outgoing_refund_sigs: outgoing_refund_sigs.to_vec(),
+            incoming_refund_txs: incoming_refund_txs.to_vec(),
+            auth_sig: sign_abort_request(id, multisig_nonce),
+        }),
+    )?;
 
-    Ok(privkey_handover)
+    let msg = read_maker_message(socket)?;
+    let result = match msg {
+        MakerToTakerMessage::RespAbort(m) => {
+            if !m.incoming_refund_sigs.is_empty()
+                && m.incoming_refund_sigs.len() != incoming_refund_txs.len()
+            {
+                Err((ProtocolError::WrongNumOfSigs {
+                    expected: incoming_refund_txs.len(),
+                    received: m.incoming_refund_sigs.len(),
+                })
+                .into())
+            } else {
+                Ok(m)
+            }
+        }
+        any => Err((ProtocolError::WrongMessage {
+            expected: "RespAbort".to_string(),
+            received: format!("{}", any),
+        })
+        .into()),
+    };
+    abort_if_rejected(socket, result)
 }
 
 fn download_maker_offer_attempt_once(
@@ -451,12 +534,11 @@ fn download_maker_offer_attempt_once(
     socket.set_read_timeout(Some(Duration::from_secs(FIRST_CONNECT_ATTEMPT_TIMEOUT_SEC)))?;
     socket.set_write_timeout(Some(Duration::from_secs(FIRST_CONNECT_ATTEMPT_TIMEOUT_SEC)))?;
 
-    handshake_maker(&mut socket)?;
+    handshake_maker(&mut socket, None)?;
 
     send_message(&mut socket, &TakerToMakerMessage::ReqGiveOffer(GiveOffer))?;
 
-    let msg_bytes = read_message(&mut socket)?;
-    let msg: MakerToTakerMessage = serde_cbor::from_slice(&msg_bytes)?;
+    let msg = read_maker_message(&mut socket)?;
     let offer = match msg {
         MakerToTakerMessage::RespOffer(offer) => offer,
         msg => {
@@ -468,6 +550,9 @@ fn download_maker_offer_attempt_once(
         }
     };
 
+    // The fidelity proof is verified against the blockchain by the caller.
+    offer.verify(unix_time())?;
+
     log::info!("Got offer from : {} ", maker_addr);
 
     Ok(*offer)
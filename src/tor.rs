@@ -10,39 +10,43 @@ use std::{
 
 use libtor::{HiddenServiceVersion, LogDestination, LogLevel, Tor, TorAddress, TorFlag};
 
-/// Used as the main function in tor binary
-pub fn start_tor(socks_port: u16, port: u16, base_dir: String) -> Result<(), libtor::Error> {
+/// Used as the main function in tor binary. All the `ports` are served on the same onion address.
+pub fn start_tor(socks_port: u16, ports: &[u16], base_dir: String) -> Result<(), libtor::Error> {
     let hs_string = format!("{}/hs-dir/", base_dir);
     let data_dir = format!("{}/", base_dir);
     let log_file = format!("{}/log", base_dir);
-    Tor::new()
-        .flag(TorFlag::DataDirectory(data_dir))
+    let mut tor = Tor::new();
+    tor.flag(TorFlag::DataDirectory(data_dir))
         .flag(TorFlag::LogTo(
             LogLevel::Notice,
             LogDestination::File(log_file),
         ))
         .flag(TorFlag::SocksPort(socks_port))
         .flag(TorFlag::HiddenServiceDir(hs_string))
-        .flag(TorFlag::HiddenServiceVersion(HiddenServiceVersion::V3))
-        .flag(TorFlag::HiddenServicePort(
-            TorAddress::Port(port),
+        .flag(TorFlag::HiddenServiceVersion(HiddenServiceVersion::V3));
+    for port in ports {
+        tor.flag(TorFlag::HiddenServicePort(
+            TorAddress::Port(*port),
             None.into(),
-        ))
-        .start()?;
+        ));
+    }
+    tor.start()?;
     Ok(())
 }
 
 /// Used to programmatically spawn tor process in maker, taker, and dns.
-pub fn spawn_tor(socks_port: u16, port: u16, base_dir: String) -> Result<Child, std::io::Error> {
+pub fn spawn_tor(
+    socks_port: u16,
+    ports: &[u16],
+    base_dir: String,
+) -> Result<Child, std::io::Error> {
     let mut tor_process = Command::new("./target/debug/tor")
-        .args([
-            "-s",
-            &socks_port.to_string(),
-            "-p",
-            &port.to_string(),
-            "-d",
-            &base_dir,
-        ])
+        .args(["-s", &socks_port.to_string(), "-d", &base_dir])
+        .args(
+            ports
+                .iter()
+                .flat_map(|port| ["-p".to_string(), port.to_string()]),
+        )
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()?;
@@ -2,11 +2,12 @@
 
 use bitcoin::{
     absolute::LockTime,
-    hashes::Hash,
+    hashes::{sha256d, Hash},
     key::{rand::thread_rng, Keypair},
-    secp256k1::{Message, Secp256k1, SecretKey},
+    secp256k1::{ecdsa::Signature, Message, Secp256k1, SecretKey},
     Address, PublicKey, ScriptBuf, Transaction, WitnessProgram, WitnessVersion,
 };
+use bitcoind::bitcoincore_rpc::{Client, RpcApi};
 use log::LevelFilter;
 use log4rs::{
     append::{console::ConsoleAppender, file::FileAppender},
@@ -16,7 +17,7 @@ use log4rs::{
 use serde::{Deserialize, Serialize};
 use std::{
     env, fmt,
-    io::{BufReader, BufWriter, ErrorKind, Read},
+    io::{BufWriter, ErrorKind, Read},
     net::TcpStream,
     path::{Path, PathBuf},
     str::FromStr,
@@ -24,11 +25,11 @@ use std::{
 };
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{self, File},
     io::{self, BufRead, Write},
     thread,
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
@@ -38,7 +39,9 @@ use crate::{
         error::ProtocolError,
         messages::{FidelityProof, MultisigPrivkey},
     },
-    wallet::{fidelity_redeemscript, FidelityError, SwapCoin, WalletError},
+    wallet::{
+        fidelity_redeemscript, FidelityBond, FidelityError, SwapCoin, WalletError, MAX_PROOF_BONDS,
+    },
 };
 
 const INPUT_CHARSET: &str =
@@ -61,6 +64,9 @@ pub(crate) const HEART_BEAT_INTERVAL: Duration = Duration::from_secs(3);
 /// Number of confirmation required funding transaction.
 pub const REQUIRED_CONFIRMS: u32 = 1;
 
+/// Default maximum size of a network message, checked before allocating the read buffer.
+pub const MAX_MESSAGE_SIZE: u32 = 4 * 1024 * 1024;
+
 /// Specifies the type of connection: TOR or Clearnet.
 ///
 /// This enum is used to distinguish between different types of network connections
@@ -139,6 +145,11 @@ pub(crate) fn get_dns_dir() -> PathBuf {
     get_data_dir().join("dns")
 }
 
+/// Get the Watchtower Directory
+pub(crate) fn get_watchtower_dir() -> PathBuf {
+    get_data_dir().join("watchtower")
+}
+
 /// Sets up the logger for the taker component.
 ///
 /// This method initializes the logging configuration for the taker, directing logs to both
@@ -237,6 +248,31 @@ pub fn setup_directory_logger(filter: LevelFilter) {
     })
 }
 
+/// Sets up the logger for the watchtower component.
+///
+/// Logs are directed to both the console and a file in the watchtower data directory.
+pub fn setup_watchtower_logger(filter: LevelFilter) {
+    Once::new().call_once(|| {
+        let log_dir = get_watchtower_dir().join("debug.log");
+
+        let stdout = ConsoleAppender::builder().build();
+        let file_appender = FileAppender::builder().build(log_dir).unwrap();
+
+        let config = Config::builder()
+            .appender(Appender::builder().build("stdout", Box::new(stdout)))
+            .appender(Appender::builder().build("file", Box::new(file_appender)))
+            .logger(
+                Logger::builder()
+                    .appender("file")
+                    .build("coinswap::watchtower", filter),
+            )
+            .build(Root::builder().appender("stdout").build(filter))
+            .unwrap();
+
+        log4rs::init_config(config).unwrap();
+    })
+}
+
 /// Setup function that will only run once, even if called multiple times.
 /// Takes log level to set the desired logging verbosity
 // TODO: Use the above setup logger functions.
@@ -296,28 +332,53 @@ pub fn send_message(
 
 /// Reads a response byte_array from a given stream.
 /// Response can be any length-appended data, where the first byte is the length of the actual message.
+/// Messages larger than [`MAX_MESSAGE_SIZE`] are refused.
 pub fn read_message(reader: &mut TcpStream) -> Result<Vec<u8>, NetError> {
-    let mut reader = BufReader::new(reader);
+    read_message_bounded(reader, MAX_MESSAGE_SIZE)
+}
+
+/// Same as [`read_message`], with a custom maximum message size.
+///
+/// Reads straight from the stream, so nothing past the message is consumed. A read timeout before the message
+/// starts is returned as is, and the next message can still be read. A timeout in the middle of the message is a
+/// [`NetError::FrameTimeout`], after which the stream must be dropped.
+pub(crate) fn read_message_bounded(
+    reader: &mut TcpStream,
+    max_size: u32,
+) -> Result<Vec<u8>, NetError> {
     // length of incoming data
     let mut len_buff = [0u8; 4];
-    reader.read_exact(&mut len_buff)?; // This can give UnexpectedEOF error if theres no data to read
+    read_frame_part(reader, &mut len_buff, false)?; // UnexpectedEof if the stream is closed before a message.
     let length = u32::from_be_bytes(len_buff);
+    if length > max_size {
+        return Err(NetError::MessageTooLarge(length));
+    }
 
     // the actual data
     let mut buffer = vec![0; length as usize];
-    let mut total_read = 0;
+    read_frame_part(reader, &mut buffer, true)?;
+    Ok(buffer)
+}
 
-    while total_read < length as usize {
-        match reader.read(&mut buffer[total_read..]) {
-            Ok(0) => return Err(NetError::ReachedEOF), // Connection closed
+/// Fills `buf` from the stream. `in_frame` tells whether part of the message is already read.
+fn read_frame_part(reader: &mut TcpStream, buf: &mut [u8], in_frame: bool) -> Result<(), NetError> {
+    let mut total_read = 0;
+    while total_read < buf.len() {
+        let started = in_frame || total_read > 0;
+        match reader.read(&mut buf[total_read..]) {
+            Ok(0) if started => return Err(NetError::ReachedEOF), // Connection closed
+            Ok(0) => return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
             Ok(n) => total_read += n,
-            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) => {
-                continue
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e)
+                if started && matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                return Err(NetError::FrameTimeout)
             }
             Err(e) => return Err(e.into()),
         }
     }
-    Ok(buffer)
+    Ok(())
 }
 
 /// Apply the maker's privatekey to swapcoins, and check it's the correct privkey for corresponding pubkey.
@@ -412,6 +473,14 @@ pub(crate) fn generate_keypair() -> (PublicKey, SecretKey) {
     (pubkey, keypair.secret_key())
 }
 
+/// Current Unix time, in seconds.
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("This can't error")
+        .as_secs()
+}
+
 /// Convert a redeemscript into p2wsh scriptpubkey.
 pub(crate) fn redeemscript_to_scriptpubkey(
     redeemscript: &ScriptBuf,
@@ -445,6 +514,25 @@ pub(crate) fn parse_field<T: std::str::FromStr>(value: Option<&String>, default:
         .unwrap_or(default)
 }
 
+/// Parses a comma separated list of values from an Option<&String>, returning the default if it is None or empty.
+pub(crate) fn parse_list(value: Option<&String>, default: Vec<String>) -> Vec<String> {
+    let list = value
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if list.is_empty() {
+        default
+    } else {
+        list
+    }
+}
+
 /// Function to check if tor log contains a pattern
 pub(crate) fn monitor_log_for_completion(log_file: &Path, pattern: &str) -> io::Result<()> {
     // TODO: Make this logic work for existing file with previous logs.
@@ -583,21 +671,56 @@ pub enum DnsRequest {
     },
 }
 
+/// Verifies every bond of a fidelity proof for the maker address `addr`, against the bond transactions of `rpc`.
+///
+/// The proof must not list a bond twice, nor more than [MAX_PROOF_BONDS] bonds.
 pub(crate) fn verify_fidelity_checks(
     proof: &FidelityProof,
     addr: &str,
+    rpc: &Client,
+) -> Result<(), WalletError> {
+    if proof.additional_bonds.len() >= MAX_PROOF_BONDS {
+        return Err(FidelityError::TooManyBonds.into());
+    }
+    let outpoints = proof
+        .bonds()
+        .map(|bond| bond.outpoint)
+        .collect::<HashSet<_>>();
+    if outpoints.len() != proof.additional_bonds.len() + 1 {
+        return Err(FidelityError::DuplicateBond.into());
+    }
+
+    let current_height = rpc.get_block_count()?;
+    for (bond, cert_hash, cert_sig) in proof.certified_bonds() {
+        let tx = rpc.get_raw_transaction(&bond.outpoint.txid, None)?;
+        verify_bond_checks(bond, cert_hash, cert_sig, addr, tx, current_height)?;
+    }
+    Ok(())
+}
+
+/// Verifies one certified bond of a fidelity proof, with its transaction.
+fn verify_bond_checks(
+    bond: &FidelityBond,
+    cert_hash: &sha256d::Hash,
+    cert_sig: &Signature,
+    addr: &str,
     tx: Transaction,
     current_height: u64,
 ) -> Result<(), WalletError> {
     // Check if bond lock time has expired
     let lock_time = LockTime::from_height(current_height as u32)?;
-    if lock_time > proof.bond.lock_time {
+    if lock_time > bond.lock_time {
         return Err(FidelityError::BondLocktimeExpired.into());
     }
 
+    // Check if the certificate has expired
+    if bond.is_cert_expired(current_height) {
+        return Err(FidelityError::CertExpired.into());
+    }
+
     // Verify certificate hash
-    let expected_cert_hash = proof.bond.generate_cert_hash(addr);
-    if proof.cert_hash != expected_cert_hash {
+    let expected_cert_hash = bond.generate_cert_hash(addr);
+    if *cert_hash != expected_cert_hash {
         return Err(FidelityError::InvalidCertHash.into());
     }
 
@@ -613,13 +736,12 @@ pub(crate) fn verify_fidelity_checks(
 
     for network in networks {
         // Validate redeem script and corresponding address
-        let fidelity_redeem_script =
-            fidelity_redeemscript(&proof.bond.lock_time, &proof.bond.pubkey);
+        let fidelity_redeem_script = fidelity_redeemscript(&bond.lock_time, &bond.pubkey);
         let expected_address = Address::p2wsh(fidelity_redeem_script.as_script(), network);
 
         let derived_script_pubkey = expected_address.script_pubkey();
         let tx_out = tx
-            .tx_out(proof.bond.outpoint.vout as usize)
+            .tx_out(bond.outpoint.vout as usize)
             .map_err(|_| WalletError::General("Outputs index error".to_string()))?;
 
         if tx_out.script_pubkey == derived_script_pubkey {
@@ -633,10 +755,13 @@ pub(crate) fn verify_fidelity_checks(
         return Err(FidelityError::BondDoesNotExist.into());
     }
 
-    // Verify ECDSA signature
+    // Verify ECDSA signature, by the bond key or its delegated hot key
+    let cert_pubkey = bond
+        .cert_pubkey()
+        .map_err(|_| FidelityError::InvalidDelegation)?;
     let secp = Secp256k1::new();
-    let cert_message = Message::from_digest_slice(proof.cert_hash.as_byte_array())?;
-    secp.verify_ecdsa(&cert_message, &proof.cert_sig, &proof.bond.pubkey.inner)?;
+    let cert_message = Message::from_digest_slice(cert_hash.as_byte_array())?;
+    secp.verify_ecdsa(&cert_message, cert_sig, &cert_pubkey.inner)?;
 
     Ok(())
 }
@@ -684,6 +809,71 @@ mod tests {
         send_message(&mut stream, &message).unwrap();
     }
 
+    #[test]
+    fn test_read_message_too_large() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            read_message_bounded(&mut socket, 16)
+        });
+
+        // Only the length prefix is sent, the reader must refuse before waiting for the data.
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(&u32::MAX.to_be_bytes()).unwrap();
+
+        assert!(matches!(
+            handle.join().unwrap(),
+            Err(NetError::MessageTooLarge(u32::MAX))
+        ));
+    }
+
+    #[test]
+    fn test_read_message_frame_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut stream = TcpStream::connect(address).unwrap();
+        let (mut socket, _) = listener.accept().unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+
+        // Nothing sent yet, the next message can still be read.
+        assert!(matches!(read_message(&mut socket), Err(NetError::IO(_))));
+        send_message(&mut stream, &"hello").unwrap();
+        let message: String = serde_cbor::from_slice(&read_message(&mut socket).unwrap()).unwrap();
+        assert_eq!(message, "hello");
+
+        // Back to back messages are read one by one.
+        send_message(&mut stream, &"one").unwrap();
+        send_message(&mut stream, &"two").unwrap();
+        for expected in ["one", "two"] {
+            let message: String =
+                serde_cbor::from_slice(&read_message(&mut socket).unwrap()).unwrap();
+            assert_eq!(message, expected);
+        }
+
+        // Stalled in the middle of a message.
+        stream.write_all(&10u32.to_be_bytes()).unwrap();
+        stream.write_all(&[0; 5]).unwrap();
+        assert!(matches!(
+            read_message(&mut socket),
+            Err(NetError::FrameTimeout)
+        ));
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let default = vec!["default".to_string()];
+        assert_eq!(parse_list(None, default.clone()), default);
+        assert_eq!(parse_list(Some(&" ".to_string()), default.clone()), default);
+        assert_eq!(
+            parse_list(Some(&"a.onion:8080, b.onion:8080,".to_string()), default),
+            vec!["a.onion:8080".to_string(), "b.onion:8080".to_string()]
+        );
+    }
+
     #[test]
     fn test_redeemscript_to_scriptpubkey_custom() {
         // Create a custom puzzle script
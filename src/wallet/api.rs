@@ -30,6 +30,7 @@ use crate::{
 
 use super::{
     error::WalletError,
+    reservation::UtxoReservations,
     rpc::RPCConfig,
     storage::WalletStore,
     swapcoin::{IncomingSwapCoin, OutgoingSwapCoin, SwapCoin, WalletSwapCoin},
@@ -46,6 +47,8 @@ pub struct Wallet {
     pub(crate) rpc: Client,
     wallet_file_path: PathBuf,
     pub(crate) store: WalletStore,
+    /// UTXOs reserved by ongoing swaps. Not persisted.
+    pub(crate) utxo_reservations: UtxoReservations,
 }
 
 /// Speicfy the keychain derivation path from [`HARDENDED_DERIVATION`]
@@ -185,6 +188,7 @@ impl Wallet {
             rpc,
             wallet_file_path: path.to_path_buf(),
             store,
+            utxo_reservations: UtxoReservations::default(),
         })
     }
 
@@ -222,6 +226,7 @@ impl Wallet {
             rpc,
             wallet_file_path: path.to_path_buf(),
             store,
+            utxo_reservations: UtxoReservations::default(),
         })
     }
 
@@ -744,6 +749,41 @@ impl Wallet {
         Ok(filtered_utxos)
     }
 
+    /// Lists confirmed seed UTXOs with their private keys, largest first. UTXOs locked for funding are skipped.
+    /// Used by the taker to commit to a UTXO it owns, in the anti-DoS PoDLE.
+    pub(crate) fn list_podle_candidates(
+        &self,
+    ) -> Result<Vec<(OutPoint, Amount, secp256k1::SecretKey)>, WalletError> {
+        let secp = Secp256k1::new();
+        let master_private_key = self
+            .store
+            .master_key
+            .derive_priv(&secp, &DerivationPath::from_str(HARDENDED_DERIVATION)?)?;
+        let mut candidates = self
+            .list_descriptor_utxo_spend_info(Some(&self.get_all_locked_utxo()?))?
+            .into_iter()
+            .filter(|(utxo, _)| utxo.confirmations > 0)
+            .map(|(utxo, spend_info)| {
+                let UTXOSpendInfo::SeedCoin { path, .. } = spend_info else {
+                    unreachable!("only seed coins are listed")
+                };
+                let privkey = master_private_key
+                    .derive_priv(&secp, &DerivationPath::from_str(&path)?)?
+                    .private_key;
+                Ok((
+                    OutPoint {
+                        txid: utxo.txid,
+                        vout: utxo.vout,
+                    },
+                    utxo.amount,
+                    privkey,
+                ))
+            })
+            .collect::<Result<Vec<_>, WalletError>>()?;
+        candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.1));
+        Ok(candidates)
+    }
+
     /// Lists swap coin UTXOs along with their [UTXOSpendInfo].
     pub fn list_swap_coin_utxo_spend_info(
         &self,
@@ -893,6 +933,13 @@ impl Wallet {
         Ok(())
     }
 
+    /// The offer maximum size, excluding the value reserved by ongoing swaps.
+    pub(crate) fn available_offer_maxsize(&self) -> u64 {
+        self.store
+            .offer_maxsize
+            .saturating_sub(self.utxo_reservations.reserved_amount().to_sat())
+    }
+
     /// Gets a tweakable key pair from the master key of the wallet.
     pub(crate) fn get_tweakable_keypair(&self) -> Result<(SecretKey, PublicKey), WalletError> {
         let secp = Secp256k1::new();
@@ -1011,10 +1058,16 @@ impl Wallet {
         let mut swap_coin_utxo = self.list_incoming_swap_coin_utxo_spend_info(Some(&all_utxos))?;
         seed_coin_utxo.append(&mut swap_coin_utxo);
 
-        // Fetch utxos, filter out existing fidelity coins
+        // Fetch utxos, filter out existing fidelity coins and utxos reserved by ongoing swaps.
         let mut unspents = seed_coin_utxo
             .into_iter()
-            .filter(|(_, spend_info)| !matches!(spend_info, UTXOSpendInfo::FidelityBondCoin { .. }))
+            .filter(|(utxo, spend_info)| {
+                !matches!(spend_info, UTXOSpendInfo::FidelityBondCoin { .. })
+                    && !self.utxo_reservations.is_reserved(&OutPoint {
+                        txid: utxo.txid,
+                        vout: utxo.vout,
+                    })
+            })
             .collect::<Vec<_>>();
 
         unspents.sort_by(|a, b| b.0.amount.cmp(&a.0.amount));
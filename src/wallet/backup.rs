@@ -0,0 +1,231 @@
+//! Swap backups and standalone swap recovery.
+//!
+//! A [SwapBackup] is an export of all the unfinished swapcoins of a wallet. The swapcoins carry
+//! everything required to claim the funds back on chain: the contract transactions, the contract redeemscripts,
+//! the timelock/hashlock private keys and the hash preimages (if known).
+//!
+//! [recover_from_backup] only needs a Bitcoin Core RPC connection. It broadcasts the contract transactions,
+//! waits for them to mature and sweeps the timelock or hashlock outputs to a given address. This works
+//! even if the original maker/taker wallet or daemon is no longer available.
+
+use std::{fs, path::Path, thread::sleep};
+
+use bitcoin::{Address, Network, Transaction, Txid};
+use bitcoind::bitcoincore_rpc::{Client, RpcApi};
+use serde::{Deserialize, Serialize};
+
+use crate::utill::HEART_BEAT_INTERVAL;
+
+use super::{
+    error::WalletError,
+    swapcoin::{IncomingSwapCoin, OutgoingSwapCoin, SwapCoin, WalletSwapCoin},
+    Wallet,
+};
+
+/// Recovery data of all unfinished swaps of a wallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwapBackup {
+    network: Network,
+    incoming_swapcoins: Vec<IncomingSwapCoin>,
+    outgoing_swapcoins: Vec<OutgoingSwapCoin>,
+}
+
+impl SwapBackup {
+    /// The network the backed up swaps happened on.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Returns true if there is nothing to recover in this backup.
+    pub fn is_empty(&self) -> bool {
+        self.incoming_swapcoins.is_empty() && self.outgoing_swapcoins.is_empty()
+    }
+
+    /// Write the backup at the given path. Overwrites any existing file.
+    pub fn write_to_disk(&self, path: &Path) -> Result<(), WalletError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_cbor::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Read a backup from the given path.
+    pub fn read_from_disk(path: &Path) -> Result<Self, WalletError> {
+        let bytes = fs::read(path)?;
+        Ok(serde_cbor::from_slice(&bytes)?)
+    }
+}
+
+impl Wallet {
+    /// Export all unfinished swapcoins of the wallet into a [SwapBackup] file at the given path.
+    pub fn export_swap_backup(&self, path: &Path) -> Result<SwapBackup, WalletError> {
+        let (incoming_swapcoins, outgoing_swapcoins) = self.find_unfinished_swapcoins();
+        let backup = SwapBackup {
+            network: self.store.network,
+            incoming_swapcoins,
+            outgoing_swapcoins,
+        };
+        backup.write_to_disk(path)?;
+        log::info!(
+            "Exported {} incoming and {} outgoing swapcoins to {}",
+            backup.incoming_swapcoins.len(),
+            backup.outgoing_swapcoins.len(),
+            path.display()
+        );
+        Ok(backup)
+    }
+}
+
+/// Broadcast the contract transaction if it is not already known to the node.
+/// Returns false if the contract cannot be broadcasted.
+fn broadcast_contract(rpc: &Client, tx: &Transaction) -> bool {
+    let txid = tx.compute_txid();
+    if rpc
+        .get_tx_out(&txid, 0, Some(true))
+        .ok()
+        .flatten()
+        .is_some()
+    {
+        log::info!("Contract {} already broadcasted", txid);
+        return true;
+    }
+    match rpc.send_raw_transaction(tx) {
+        Ok(_) => {
+            log::info!("Broadcasted contract {}", txid);
+            true
+        }
+        Err(e) => {
+            log::warn!("Failed to broadcast contract {} : {:?}", txid, e);
+            false
+        }
+    }
+}
+
+/// Recover all the swaps of a [SwapBackup], using only a Bitcoin Core RPC connection.
+///
+/// Broadcasts all the contract transactions that can be claimed by us. Outgoing contracts are swept through the
+/// timelock path after they mature. Incoming contracts with a known preimage are swept through the hashlock path
+/// as soon as they confirm. Blocks until every contract is either swept or found spent by someone else.
+/// Outgoings refunded in a cooperative abort are spent by their refund txs instead, which pay to the wallet itself.
+///
+/// Returns the txids of all the sweeping transactions.
+pub fn recover_from_backup(
+    backup: &SwapBackup,
+    rpc: &Client,
+    destination: &Address,
+) -> Result<Vec<Txid>, WalletError> {
+    let mut pending_incomings = Vec::new();
+    for incoming in &backup.incoming_swapcoins {
+        if !incoming.is_hash_preimage_known() {
+            log::warn!(
+                "Skipping incoming contract {} : preimage unknown, cannot claim via hashlock",
+                incoming.contract_tx.compute_txid()
+            );
+            continue;
+        }
+        match incoming.get_fully_signed_contract_tx() {
+            Ok(tx) => {
+                if broadcast_contract(rpc, &tx) {
+                    pending_incomings.push(incoming);
+                }
+            }
+            Err(e) => log::warn!("Cannot sign incoming contract : {:?}", e),
+        }
+    }
+
+    let mut sweep_txids = Vec::new();
+
+    let mut pending_outgoings = Vec::new();
+    for outgoing in &backup.outgoing_swapcoins {
+        if let Some(refund_tx) = &outgoing.refund_tx {
+            match rpc.send_raw_transaction(refund_tx) {
+                Ok(txid) => {
+                    log::info!("Broadcasted refund tx {}", txid);
+                    sweep_txids.push(txid);
+                    continue;
+                }
+                Err(e) => log::warn!(
+                    "Failed to broadcast refund tx, using the contract : {:?}",
+                    e
+                ),
+            }
+        }
+        match outgoing.get_fully_signed_contract_tx() {
+            Ok(tx) => {
+                if broadcast_contract(rpc, &tx) {
+                    pending_outgoings.push(outgoing);
+                }
+            }
+            Err(e) => log::warn!("Cannot sign outgoing contract : {:?}", e),
+        }
+    }
+
+    while !pending_incomings.is_empty() || !pending_outgoings.is_empty() {
+        let mut claimed_incomings = Vec::new();
+        for (i, incoming) in pending_incomings.iter().enumerate() {
+            let contract_txid = incoming.contract_tx.compute_txid();
+            match rpc.get_tx_out(&contract_txid, 0, Some(true))? {
+                None => {
+                    log::warn!("Incoming contract {} already spent", contract_txid);
+                    claimed_incomings.push(i);
+                }
+                Some(txout) if txout.confirmations >= 1 => {
+                    let tx = incoming.create_hashlock_spend(destination)?;
+                    match rpc.send_raw_transaction(&tx) {
+                        Ok(txid) => {
+                            log::info!("Broadcasted hashlock spend {}", txid);
+                            sweep_txids.push(txid);
+                            claimed_incomings.push(i);
+                        }
+                        Err(e) => log::warn!("Failed to broadcast hashlock spend : {:?}", e),
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+
+        let mut claimed_outgoings = Vec::new();
+        for (i, outgoing) in pending_outgoings.iter().enumerate() {
+            let contract_txid = outgoing.contract_tx.compute_txid();
+            let timelock = outgoing.get_timelock()?;
+            match rpc.get_tx_out(&contract_txid, 0, Some(true))? {
+                None => {
+                    log::warn!("Outgoing contract {} already spent", contract_txid);
+                    claimed_outgoings.push(i);
+                }
+                Some(txout) if txout.confirmations > timelock as u32 => {
+                    let tx = outgoing.create_timelock_spend(destination)?;
+                    match rpc.send_raw_transaction(&tx) {
+                        Ok(txid) => {
+                            log::info!("Broadcasted timelock spend {}", txid);
+                            sweep_txids.push(txid);
+                            claimed_outgoings.push(i);
+                        }
+                        Err(e) => log::warn!("Failed to broadcast timelock spend : {:?}", e),
+                    }
+                }
+                Some(txout) => log::info!(
+                    "Timelock maturity of {} : {}/{} confirmations",
+                    contract_txid,
+                    txout.confirmations,
+                    timelock
+                ),
+            }
+        }
+
+        for i in claimed_incomings.into_iter().rev() {
+            pending_incomings.remove(i);
+        }
+        for i in claimed_outgoings.into_iter().rev() {
+            pending_outgoings.remove(i);
+        }
+
+        if !pending_incomings.is_empty() || !pending_outgoings.is_empty() {
+            sleep(HEART_BEAT_INTERVAL);
+        }
+    }
+
+    log::info!("Recovery complete. Sweep txids : {:?}", sweep_txids);
+    Ok(sweep_txids)
+}
@@ -0,0 +1,545 @@
+//! Cold storage fidelity bonds.
+//!
+//! A cold bond is locked with a key that never touches the maker server. The key is held offline, by the
+//! `fidelity-signer` app, which also gives the bond address to fund and signs the redeem transaction at maturity.
+//! The maker wallet only watches the bond, and certifies it with a hot certificate key derived from its own seed.
+//!
+//! The bond key delegates the certificates to the hot key until an expiry, with a signature exchanged through
+//! files, like the cold storage fidelity bonds of JoinMarket:
+//! 1. The maker exports a [DelegationRequest] for the bond, with `maker-cli export-delegation-request`.
+//! 2. The offline signer checks and signs it into a [SignedDelegation], with `fidelity-signer sign`.
+//! 3. The maker imports the [SignedDelegation], with `maker-cli import-delegation`.
+//!
+//! The delegation commits to the certificate expiry. So a compromised maker server can't advertise the bond past
+//! it, and the exchange must be done again before it expires.
+
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    str::FromStr,
+};
+
+use bitcoin::{
+    absolute::LockTime,
+    bip32::{ChildNumber, DerivationPath},
+    hashes::{sha256d, Hash},
+    secp256k1::{
+        self, ecdsa::Signature, rand::rngs::OsRng, Keypair, Message, Secp256k1, SecretKey,
+    },
+    sighash::{EcdsaSighashType, SighashCache},
+    transaction::Version,
+    Address, Amount, Network, OutPoint, PrivateKey, PublicKey, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, Witness,
+};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    fidelity::{fidelity_redeemscript, signed_message_hash, CERT_EXPIRY_PERIOD},
+    FidelityBond, FidelityError, Wallet, WalletError,
+};
+
+/// Derivation path of the hot certificate keys of the cold bonds, by bond index.
+const CERT_DERIVATION_PATH: &str = "m/84'/0'/0'/3";
+
+/// Delegation of the certificates of a cold bond to a hot key, signed by the bond key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct CertDelegation {
+    pub(crate) cert_pubkey: PublicKey,
+    pub(crate) sig: Signature,
+}
+
+/// Hash signed by the bond key to delegate its certificates to `cert_pubkey`, until `cert_expiry`.
+fn delegation_hash(
+    outpoint: &OutPoint,
+    bond_pubkey: &PublicKey,
+    cert_pubkey: &PublicKey,
+    cert_expiry: u64,
+) -> sha256d::Hash {
+    signed_message_hash(&format!(
+        "fidelity-bond-delegation|{}|{}|{}|{}",
+        outpoint, bond_pubkey, cert_pubkey, cert_expiry
+    ))
+}
+
+impl FidelityBond {
+    /// The key certifying the bond and signing the maker offers: the delegated hot key of a cold bond, or else the
+    /// bond key. Errors if the delegation isn't signed by the bond key, for the bond's certificate expiry.
+    pub(crate) fn cert_pubkey(&self) -> Result<PublicKey, secp256k1::Error> {
+        let delegation = match &self.delegation {
+            Some(delegation) => delegation,
+            None => return Ok(self.pubkey),
+        };
+        let hash = delegation_hash(
+            &self.outpoint,
+            &self.pubkey,
+            &delegation.cert_pubkey,
+            self.cert_expiry,
+        );
+        Secp256k1::verification_only().verify_ecdsa(
+            &Message::from_digest(hash.to_byte_array()),
+            &delegation.sig,
+            &self.pubkey.inner,
+        )?;
+        Ok(delegation.cert_pubkey)
+    }
+}
+
+/// Request to the offline signer of a cold bond, to delegate the bond certificates to a hot key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DelegationRequest {
+    outpoint: OutPoint,
+    amount: Amount,
+    lock_time: LockTime,
+    bond_pubkey: PublicKey,
+    cert_pubkey: PublicKey,
+    cert_expiry: u64,
+}
+
+impl DelegationRequest {
+    /// Signs the request with the bond key. Errors if the key isn't the one of the bond.
+    pub fn sign(&self, bond_key: &ColdKey) -> Result<SignedDelegation, WalletError> {
+        if bond_key.public_key() != self.bond_pubkey {
+            return Err(WalletError::General(
+                "The key doesn't match the bond of the request".to_string(),
+            ));
+        }
+        let hash = delegation_hash(
+            &self.outpoint,
+            &self.bond_pubkey,
+            &self.cert_pubkey,
+            self.cert_expiry,
+        );
+        let sig = Secp256k1::new().sign_ecdsa(
+            &Message::from_digest(hash.to_byte_array()),
+            &bond_key.key.inner,
+        );
+        Ok(SignedDelegation {
+            request: self.clone(),
+            sig,
+        })
+    }
+
+    /// Write the request at the given path. Overwrites any existing file.
+    pub fn write_to_disk(&self, path: &Path) -> Result<(), WalletError> {
+        fs::write(path, serde_cbor::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Read a request from the given path.
+    pub fn read_from_disk(path: &Path) -> Result<Self, WalletError> {
+        Ok(serde_cbor::from_slice(&fs::read(path)?)?)
+    }
+}
+
+impl Display for DelegationRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Bond outpoint   : {}", self.outpoint)?;
+        writeln!(f, "Bond amount     : {} sats", self.amount.to_sat())?;
+        writeln!(f, "Bond locktime   : {}", self.lock_time)?;
+        writeln!(f, "Bond public key : {}", self.bond_pubkey)?;
+        writeln!(f, "Hot cert key    : {}", self.cert_pubkey)?;
+        write!(
+            f,
+            "Delegated until : block {}",
+            self.cert_expiry * CERT_EXPIRY_PERIOD
+        )
+    }
+}
+
+/// A [DelegationRequest] signed by the bond key, to be imported in the maker wallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedDelegation {
+    request: DelegationRequest,
+    sig: Signature,
+}
+
+impl SignedDelegation {
+    /// Write the signed delegation at the given path. Overwrites any existing file.
+    pub fn write_to_disk(&self, path: &Path) -> Result<(), WalletError> {
+        fs::write(path, serde_cbor::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Read a signed delegation from the given path.
+    pub fn read_from_disk(path: &Path) -> Result<Self, WalletError> {
+        Ok(serde_cbor::from_slice(&fs::read(path)?)?)
+    }
+}
+
+/// The key of cold bonds, held by the offline signer.
+///
+/// It's stored as a single line with the network and the key in WIF, as WIF doesn't tell test networks apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColdKey {
+    network: Network,
+    key: PrivateKey,
+}
+
+impl ColdKey {
+    /// Generates a new key, and writes it at the given path. Never overwrites an existing file.
+    pub fn generate(path: &Path, network: Network) -> Result<Self, WalletError> {
+        let cold_key = Self {
+            network,
+            key: PrivateKey::new(SecretKey::new(&mut OsRng), network),
+        };
+        let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        writeln!(file, "{} {}", network, cold_key.key.to_wif())?;
+        Ok(cold_key)
+    }
+
+    /// Reads a key written by [ColdKey::generate].
+    pub fn read_from_disk(path: &Path) -> Result<Self, WalletError> {
+        let content = fs::read_to_string(path)?;
+        let invalid = |e: String| WalletError::General(format!("Invalid cold key file: {}", e));
+        let mut fields = content.split_whitespace();
+        let (network, wif) = match (fields.next(), fields.next()) {
+            (Some(network), Some(wif)) => (network, wif),
+            _ => return Err(invalid("expected the network and the key".to_string())),
+        };
+        Ok(Self {
+            network: Network::from_str(network).map_err(|e| invalid(e.to_string()))?,
+            key: PrivateKey::from_wif(wif).map_err(|e| invalid(e.to_string()))?,
+        })
+    }
+
+    /// The network of the key.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// The public key the bonds are locked with.
+    pub fn public_key(&self) -> PublicKey {
+        self.key.public_key(&Secp256k1::new())
+    }
+
+    /// The address to fund, for a bond locked until `lock_time`.
+    pub fn fidelity_address(&self, lock_time: LockTime) -> Address {
+        Address::p2wsh(
+            &fidelity_redeemscript(&lock_time, &self.public_key()),
+            self.network,
+        )
+    }
+
+    /// Creates a signed transaction, spending a matured bond to `destination` minus the `fee`.
+    ///
+    /// The transaction is only valid once the bond locktime has passed. It can be broadcasted from any online node.
+    pub fn redeem_tx(
+        &self,
+        outpoint: OutPoint,
+        amount: Amount,
+        lock_time: LockTime,
+        destination: &Address,
+        fee: Amount,
+    ) -> Result<Transaction, WalletError> {
+        let value = amount
+            .checked_sub(fee)
+            .ok_or(WalletError::InsufficientFund {
+                available: amount.to_sat(),
+                required: fee.to_sat(),
+            })?;
+        let mut tx = Transaction {
+            input: vec![TxIn {
+                previous_output: outpoint,
+                sequence: Sequence(0),
+                script_sig: ScriptBuf::new(),
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                script_pubkey: destination.script_pubkey(),
+                value,
+            }],
+            lock_time,
+            version: Version::TWO,
+        };
+
+        let redeemscript = fidelity_redeemscript(&lock_time, &self.public_key());
+        let sighash = SighashCache::new(&tx).p2wsh_signature_hash(
+            0,
+            &redeemscript,
+            amount,
+            EcdsaSighashType::All,
+        )?;
+        let sig = Secp256k1::new().sign_ecdsa(
+            &Message::from_digest(sighash.to_byte_array()),
+            &self.key.inner,
+        );
+        let mut sig_serialised = sig.serialize_der().to_vec();
+        sig_serialised.push(EcdsaSighashType::All as u8);
+        tx.input[0].witness.push(sig_serialised);
+        tx.input[0].witness.push(redeemscript.as_bytes());
+        Ok(tx)
+    }
+}
+
+// Wallet APIs related to cold fidelity bonds.
+impl Wallet {
+    /// Get the hot certificate [Keypair] of the cold bond at given index.
+    pub(crate) fn get_cert_keypair(&self, index: u32) -> Result<Keypair, WalletError> {
+        let secp = Secp256k1::new();
+        let derivation_path =
+            DerivationPath::from_str(CERT_DERIVATION_PATH)?.child(ChildNumber::Normal { index });
+        Ok(self
+            .store
+            .master_key
+            .derive_priv(&secp, &derivation_path)?
+            .to_keypair(&secp))
+    }
+
+    /// Whether the bond at given index is a cold bond, i.e. not locked with the wallet's fidelity key.
+    pub(crate) fn is_cold_fidelity(&self, index: u32) -> Result<bool, WalletError> {
+        let (bond, _, _) = self
+            .store
+            .fidelity_bond
+            .get(&index)
+            .ok_or(FidelityError::BondDoesNotExist)?;
+        Ok(bond.pubkey.inner != self.get_fidelity_keypair(index)?.public_key())
+    }
+
+    /// Indexes of all the cold bonds of the wallet.
+    pub(crate) fn get_cold_fidelity_indexes(&self) -> Result<HashSet<u32>, WalletError> {
+        let mut indexes = HashSet::new();
+        for index in self.store.fidelity_bond.keys() {
+            if self.is_cold_fidelity(*index)? {
+                indexes.insert(*index);
+            }
+        }
+        Ok(indexes)
+    }
+
+    /// Get the [Keypair] certifying the bond at given index: the hot certificate key of a cold bond, or else the
+    /// bond key. Errors for a cold bond without a live delegation.
+    pub(crate) fn get_certifying_keypair(
+        &self,
+        index: u32,
+        current_height: u64,
+    ) -> Result<Keypair, WalletError> {
+        if !self.is_cold_fidelity(index)? {
+            return self.get_fidelity_keypair(index);
+        }
+        let (bond, _, _) = &self.store.fidelity_bond[&index];
+        if bond.delegation.is_none() {
+            return Err(FidelityError::MissingDelegation.into());
+        }
+        if bond.is_cert_expired(current_height) {
+            return Err(FidelityError::CertExpired.into());
+        }
+        self.get_cert_keypair(index)
+    }
+
+    /// Imports a confirmed cold bond, funded at the [ColdKey::fidelity_address] of `bond_pubkey`. Returns the bond index.
+    ///
+    /// The bond is only watched. It's advertised once a delegation of its certificates is imported.
+    pub(crate) fn import_cold_fidelity(
+        &mut self,
+        outpoint: OutPoint,
+        bond_pubkey: PublicKey,
+        lock_time: LockTime,
+    ) -> Result<u32, WalletError> {
+        if self
+            .store
+            .fidelity_bond
+            .values()
+            .any(|(bond, _, _)| bond.outpoint == outpoint)
+        {
+            return Err(FidelityError::DuplicateBond.into());
+        }
+
+        let tx = self.rpc.get_raw_transaction(&outpoint.txid, None)?;
+        let bond_spk = fidelity_redeemscript(&lock_time, &bond_pubkey).to_p2wsh();
+        let amount = match tx.output.get(outpoint.vout as usize) {
+            Some(txout) if txout.script_pubkey == bond_spk => txout.value,
+            _ => return Err(FidelityError::WrongScriptType.into()),
+        };
+        let conf_height = self
+            .rpc
+            .get_raw_transaction_info(&outpoint.txid, None)?
+            .blockhash
+            .map(|blockhash| self.rpc.get_block_header_info(&blockhash))
+            .transpose()?
+            .ok_or_else(|| WalletError::General("Cold bond is not confirmed yet".to_string()))?
+            .height as u32;
+
+        let index = self.store.next_fidelity_index();
+        let bond = FidelityBond {
+            outpoint,
+            amount,
+            lock_time,
+            pubkey: bond_pubkey,
+            conf_height,
+            cert_expiry: 0,
+            delegation: None,
+        };
+        self.store.insert_fidelity(index, bond, bond_spk)?;
+        self.sync()?;
+        // The bond may predate the wallet, so scan from its confirmation to track it in the core wallet.
+        self.rpc
+            .rescan_blockchain(Some(conf_height as usize), None)?;
+
+        log::info!(
+            "Imported cold fidelity bond {} at index {}",
+            outpoint,
+            index
+        );
+        Ok(index)
+    }
+
+    /// Creates a [DelegationRequest] for the cold bond at given index, until a fresh certificate expiry.
+    pub(crate) fn get_delegation_request(
+        &self,
+        index: u32,
+    ) -> Result<DelegationRequest, WalletError> {
+        if !self.is_cold_fidelity(index)? {
+            return Err(WalletError::General(
+                "Only cold bonds need a delegation".to_string(),
+            ));
+        }
+        let (bond, _, is_spent) = &self.store.fidelity_bond[&index];
+        if *is_spent {
+            return Err(FidelityError::BondAlreadySpent.into());
+        }
+        Ok(DelegationRequest {
+            outpoint: bond.outpoint,
+            amount: bond.amount,
+            lock_time: bond.lock_time,
+            bond_pubkey: bond.pubkey,
+            cert_pubkey: PublicKey {
+                compressed: true,
+                inner: self.get_cert_keypair(index)?.public_key(),
+            },
+            cert_expiry: self.get_fidelity_expiry()?,
+        })
+    }
+
+    /// Imports a [SignedDelegation] of a cold bond of the wallet. Returns the bond index.
+    ///
+    /// The delegation must be signed by the bond key, for the wallet's certificate key, and not be expired.
+    pub(crate) fn import_delegation(
+        &mut self,
+        signed: &SignedDelegation,
+    ) -> Result<u32, WalletError> {
+        let request = &signed.request;
+        let index = self
+            .store
+            .fidelity_bond
+            .iter()
+            .find_map(|(index, (bond, _, _))| (bond.outpoint == request.outpoint).then_some(*index))
+            .ok_or(FidelityError::BondDoesNotExist)?;
+        let cert_pubkey = self.get_cert_keypair(index)?.public_key();
+        let current_height = self.rpc.get_block_count()?;
+
+        let (bond, _, _) = self
+            .store
+            .fidelity_bond
+            .get_mut(&index)
+            .ok_or(FidelityError::BondDoesNotExist)?;
+        if request.bond_pubkey != bond.pubkey || request.cert_pubkey.inner != cert_pubkey {
+            return Err(FidelityError::InvalidDelegation.into());
+        }
+        let mut delegated = bond.clone();
+        delegated.cert_expiry = request.cert_expiry;
+        delegated.delegation = Some(CertDelegation {
+            cert_pubkey: request.cert_pubkey,
+            sig: signed.sig,
+        });
+        delegated
+            .cert_pubkey()
+            .map_err(|_| FidelityError::InvalidDelegation)?;
+        if delegated.is_cert_expired(current_height) {
+            return Err(FidelityError::CertExpired.into());
+        }
+        *bond = delegated;
+
+        log::info!(
+            "Imported the delegation of cold bond {}, until block {}",
+            request.outpoint,
+            request.cert_expiry * CERT_EXPIRY_PERIOD
+        );
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::Txid;
+
+    #[test]
+    fn test_cert_delegation() {
+        let dir = std::env::temp_dir().join(format!("coinswap-cold-key-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let key_path = dir.join("bond.key");
+        let _ = fs::remove_file(&key_path);
+        let bond_key = ColdKey::generate(&key_path, Network::Regtest).unwrap();
+        assert!(ColdKey::generate(&key_path, Network::Regtest).is_err());
+        assert_eq!(ColdKey::read_from_disk(&key_path).unwrap(), bond_key);
+        let cert_key = ColdKey::generate(&dir.join("cert.key"), Network::Regtest);
+        fs::remove_dir_all(&dir).unwrap();
+        let cert_key = cert_key.unwrap();
+
+        let lock_time = LockTime::from_height(1000).unwrap();
+        let outpoint = OutPoint::new(Txid::all_zeros(), 0);
+        let request = DelegationRequest {
+            outpoint,
+            amount: Amount::from_sat(100_000),
+            lock_time,
+            bond_pubkey: bond_key.public_key(),
+            cert_pubkey: cert_key.public_key(),
+            cert_expiry: 10,
+        };
+
+        // Only the bond key signs.
+        assert!(request.sign(&cert_key).is_err());
+        let signed = request.sign(&bond_key).unwrap();
+
+        let mut bond = FidelityBond {
+            outpoint,
+            amount: request.amount,
+            lock_time,
+            pubkey: request.bond_pubkey,
+            conf_height: 100,
+            cert_expiry: 10,
+            delegation: None,
+        };
+        assert_eq!(bond.cert_pubkey().unwrap(), request.bond_pubkey);
+
+        bond.delegation = Some(CertDelegation {
+            cert_pubkey: request.cert_pubkey,
+            sig: signed.sig,
+        });
+        assert_eq!(bond.cert_pubkey().unwrap(), request.cert_pubkey);
+
+        // The hot key can't extend the delegation.
+        bond.cert_expiry = 11;
+        assert!(bond.cert_pubkey().is_err());
+
+        let destination = cert_key.fidelity_address(lock_time);
+        let tx = bond_key
+            .redeem_tx(
+                outpoint,
+                request.amount,
+                lock_time,
+                &destination,
+                Amount::from_sat(1000),
+            )
+            .unwrap();
+        assert_eq!(tx.output[0].value, Amount::from_sat(99_000));
+        assert_eq!(tx.lock_time, lock_time);
+        assert_eq!(
+            tx.input[0].witness.last().unwrap(),
+            fidelity_redeemscript(&lock_time, &request.bond_pubkey).as_bytes()
+        );
+        assert!(bond_key
+            .redeem_tx(
+                outpoint,
+                request.amount,
+                lock_time,
+                &destination,
+                Amount::from_sat(200_000),
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Confirmation tracking and reorg detection.
+//!
+//! Funding and contract transactions of ongoing swaps are tracked along with the hash of the block that confirmed them.
+//! If that block later leaves the active chain, and the transaction has not been mined again in another block,
+//! the transaction is reported as reorged out so the swap can be paused, aborted or recovered.
+
+use std::collections::HashMap;
+
+use bitcoin::{BlockHash, Txid};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use serde::{Deserialize, Serialize};
+
+use super::{Wallet, WalletError};
+
+/// Block hashes of the confirmations of tracked transactions. Persisted in the wallet file.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ConfirmationTracker {
+    confirmed: HashMap<Txid, BlockHash>,
+}
+
+impl ConfirmationTracker {
+    /// Track the block a transaction got confirmed in. Overwrites any previously tracked block.
+    /// Returns true if the tracked block changed.
+    pub(crate) fn track(&mut self, txid: Txid, blockhash: BlockHash) -> bool {
+        let changed = self.confirmed.insert(txid, blockhash) != Some(blockhash);
+        if changed {
+            log::debug!("Tracking confirmation of {} in block {}", txid, blockhash);
+        }
+        changed
+    }
+
+    /// Stop tracking a transaction. Returns true if it was tracked.
+    pub(crate) fn untrack(&mut self, txid: &Txid) -> bool {
+        self.confirmed.remove(txid).is_some()
+    }
+
+    /// Stop tracking all transactions. Returns true if any was tracked.
+    pub(crate) fn clear(&mut self) -> bool {
+        let tracked = !self.confirmed.is_empty();
+        self.confirmed.clear();
+        tracked
+    }
+
+    /// Re-check the tracked confirmations of the given transactions. Untracked transactions are skipped.
+    ///
+    /// `current_block` returns the block a transaction is currently confirmed in, if any. Transactions
+    /// re-mined in another block are tracked at their new block. Transactions not confirmed anymore are
+    /// untracked and returned.
+    pub(crate) fn take_reorged<E>(
+        &mut self,
+        txids: &[Txid],
+        mut current_block: impl FnMut(&Txid, &BlockHash) -> Result<Option<BlockHash>, E>,
+    ) -> Result<Vec<Txid>, E> {
+        let mut reorged = Vec::new();
+        for txid in txids {
+            let blockhash = match self.confirmed.get_mut(txid) {
+                Some(blockhash) => blockhash,
+                None => continue,
+            };
+            match current_block(txid, blockhash)? {
+                Some(current) if current == *blockhash => {}
+                Some(current) => {
+                    log::warn!(
+                        "Block {} confirming {} got reorged out, tx is now confirmed in block {}",
+                        blockhash,
+                        txid,
+                        current
+                    );
+                    *blockhash = current;
+                }
+                None => {
+                    log::warn!(
+                        "Block {} confirming {} got reorged out, tx is unconfirmed",
+                        blockhash,
+                        txid
+                    );
+                    self.confirmed.remove(txid);
+                    reorged.push(*txid);
+                }
+            }
+        }
+        Ok(reorged)
+    }
+}
+
+impl Wallet {
+    /// Track the block confirming a swap transaction, so that a reorg of it can be detected later, even across restarts.
+    pub(crate) fn track_confirmation(
+        &mut self,
+        txid: Txid,
+        blockhash: BlockHash,
+    ) -> Result<(), WalletError> {
+        if self.store.confirmations.track(txid, blockhash) {
+            self.save_to_disk()?;
+        }
+        Ok(())
+    }
+
+    /// Stop tracking the confirmation of a swap transaction.
+    pub(crate) fn untrack_confirmation(&mut self, txid: &Txid) -> Result<(), WalletError> {
+        if self.store.confirmations.untrack(txid) {
+            self.save_to_disk()?;
+        }
+        Ok(())
+    }
+
+    /// Stop tracking the confirmations of all swap transactions.
+    pub(crate) fn clear_confirmations(&mut self) -> Result<(), WalletError> {
+        if self.store.confirmations.clear() {
+            self.save_to_disk()?;
+        }
+        Ok(())
+    }
+
+    /// Check the tracked confirmations of the given transactions against the active chain, and return the ones that got reorged out.
+    ///
+    /// Reorged transactions are not tracked anymore. Track them again once they get re-confirmed.
+    pub(crate) fn find_reorged_txs(&mut self, txids: &[Txid]) -> Result<Vec<Txid>, WalletError> {
+        let rpc = &self.rpc;
+        let confirmations = &mut self.store.confirmations;
+        let reorged = confirmations.take_reorged::<WalletError>(txids, |txid, blockhash| {
+            // Blocks outside the active chain report -1 confirmations.
+            if rpc.get_block_header_info(blockhash)?.confirmations >= 0 {
+                return Ok(Some(*blockhash));
+            }
+            Ok(match rpc.get_raw_transaction_info(txid, None) {
+                Ok(info) if info.confirmations.unwrap_or(0) > 0 => info.blockhash,
+                _ => None,
+            })
+        })?;
+        // A tx re-mined in another block is just found there again after a restart.
+        if !reorged.is_empty() {
+            self.save_to_disk()?;
+        }
+        Ok(reorged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    #[test]
+    fn test_take_reorged() {
+        let txid = |n: u8| Txid::from_byte_array([n; 32]);
+        let block = |n: u8| BlockHash::from_byte_array([n; 32]);
+
+        let mut tracker = ConfirmationTracker::default();
+        tracker.track(txid(1), block(1));
+        tracker.track(txid(2), block(2));
+        tracker.track(txid(3), block(2));
+
+        let all = [txid(1), txid(2), txid(3), txid(4)];
+
+        // Block 2 is stale. Tx 2 got re-mined in block 3, tx 3 went back to the mempool.
+        let reorged = tracker
+            .take_reorged(&all, |txid_, blockhash| {
+                Ok::<_, ()>(match (*txid_, *blockhash) {
+                    (_, b) if b == block(1) => Some(b),
+                    (t, _) if t == txid(2) => Some(block(3)),
+                    _ => None,
+                })
+            })
+            .unwrap();
+        assert_eq!(reorged, vec![txid(3)]);
+        assert!(tracker.confirmed.contains_key(&txid(1)));
+        assert!(tracker.confirmed.contains_key(&txid(2)));
+        assert!(!tracker.confirmed.contains_key(&txid(3)));
+
+        // Nothing changed since.
+        let reorged = tracker
+            .take_reorged(&all, |_, blockhash| {
+                assert_ne!(*blockhash, block(2));
+                Ok::<_, ()>(Some(*blockhash))
+            })
+            .unwrap();
+        assert!(reorged.is_empty());
+
+        // Only the given txs are checked.
+        let reorged = tracker
+            .take_reorged(&[txid(2)], |txid_, _| {
+                assert_eq!(*txid_, txid(2));
+                Ok::<_, ()>(None)
+            })
+            .unwrap();
+        assert_eq!(reorged, vec![txid(2)]);
+        assert!(tracker.confirmed.contains_key(&txid(1)));
+
+        assert!(tracker.untrack(&txid(1)));
+        assert!(!tracker.untrack(&txid(1)));
+    }
+}
@@ -6,9 +6,9 @@ use std::{
 };
 
 use crate::{
-    protocol::messages::FidelityProof,
+    protocol::messages::{BondProof, FidelityProof},
     taker::api::MINER_FEE,
-    utill::{redeemscript_to_scriptpubkey, verify_fidelity_checks},
+    utill::{redeemscript_to_scriptpubkey, unix_time, verify_fidelity_checks},
     wallet::{UTXOSpendInfo, Wallet},
 };
 
@@ -23,10 +23,10 @@ use bitcoin::{
     Address, Amount, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
     Witness,
 };
-use bitcoind::bitcoincore_rpc::RpcApi;
+use bitcoind::bitcoincore_rpc::{Client, RpcApi};
 use serde::{Deserialize, Serialize};
 
-use super::WalletError;
+use super::{cold_fidelity::CertDelegation, storage::WalletStore, WalletError};
 
 // To (strongly) disincentivize Sybil behavior, the value assessment of the bond
 // is based on the (time value of the bond)^x here x is the bond_value_exponent,
@@ -40,6 +40,13 @@ const BOND_VALUE_EXPONENT: f64 = 1.3;
 // Set as a real number, i.e. 1 = 100% and 0.01 = 1%
 const BOND_VALUE_INTEREST_RATE: f64 = 0.015;
 
+/// Maximum number of bonds in a [FidelityProof]. Makers advertise their highest valued bonds, and proofs with more
+/// bonds are refused, as each bond costs a transaction lookup to verify.
+pub(crate) const MAX_PROOF_BONDS: usize = 10;
+
+/// Length of the periods of the fidelity certificate expiries, in blocks. One difficulty adjustment period.
+pub(super) const CERT_EXPIRY_PERIOD: u64 = 2016;
+
 /// Constant representing the derivation path for fidelity addresses.
 const FIDELITY_DERIVATION_PATH: &str = "m/84'/0'/0'/2";
 
@@ -52,9 +59,42 @@ pub enum FidelityError {
     BondLocktimeExpired,
     CertExpired,
     InvalidCertHash,
+    DuplicateBond,
+    TooManyBonds,
+    MissingDelegation,
+    InvalidDelegation,
+    ColdBond,
+    IndexInUse,
     General(String),
 }
 
+impl WalletStore {
+    /// Next free fidelity bond index, past every stored bond and the pending one,
+    /// so that no index is ever reused.
+    pub(super) fn next_fidelity_index(&self) -> u32 {
+        self.fidelity_bond
+            .keys()
+            .copied()
+            .chain(self.pending_fidelity.as_ref().map(|pending| pending.index))
+            .max()
+            .map_or(0, |index| index + 1)
+    }
+
+    /// Stores a fidelity bond at `index`. Refuses an index already holding a bond.
+    pub(super) fn insert_fidelity(
+        &mut self,
+        index: u32,
+        bond: FidelityBond,
+        bond_spk: ScriptBuf,
+    ) -> Result<(), FidelityError> {
+        if self.fidelity_bond.contains_key(&index) {
+            return Err(FidelityError::IndexInUse);
+        }
+        self.fidelity_bond.insert(index, (bond, bond_spk, false));
+        Ok(())
+    }
+}
+
 // ------- Fidelity Helper Scripts -------------
 
 /// Create a Fidelity Timelocked redeemscript.
@@ -118,6 +158,165 @@ pub(crate) fn calculate_fidelity_value(
     Amount::from_sat(((value.to_sat() as f64) * timevalue).powf(BOND_VALUE_EXPONENT) as u64)
 }
 
+/// Aggregates the values of the bonds of one maker, as the value of a single bond of their combined time value.
+///
+/// As in JoinMarket, the value is superlinear in the time value of the locked coins (see [BOND_VALUE_EXPONENT]).
+/// Summing the time values before applying the exponent keeps that: several bonds are worth as much as one bond
+/// of the same total, so makers can add collateral over time, while splitting it among several makers still loses value.
+pub(crate) fn aggregate_bond_values(values: impl IntoIterator<Item = Amount>) -> Amount {
+    let time_value = values
+        .into_iter()
+        .map(|value| (value.to_sat() as f64).powf(1.0 / BOND_VALUE_EXPONENT))
+        .sum::<f64>();
+    Amount::from_sat(time_value.powf(BOND_VALUE_EXPONENT).round() as u64)
+}
+
+/// Calculates the value of a bond confirmed at `conf_height`, at the current time and chain tip of `rpc`.
+///
+/// The maturity time of a bond locked by height is estimated with 10 minute blocks from the tip.
+pub(crate) fn calculate_bond_value_at(
+    rpc: &Client,
+    bond: &FidelityBond,
+    conf_height: u64,
+) -> Result<Amount, WalletError> {
+    let current_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("This can't error")
+        .as_secs();
+
+    let hash = rpc.get_block_hash(conf_height)?;
+
+    let confirmation_time = rpc.get_block_header_info(&hash)?.time as u64;
+
+    let locktime = match bond.lock_time {
+        LockTime::Blocks(blocks) => {
+            let tip_hash = rpc.get_blockchain_info()?.best_block_hash;
+            let (tip_height, tip_time) = {
+                let info = rpc.get_block_header_info(&tip_hash)?;
+                (info.height, info.time as u64)
+            };
+            // Estimated locktime from block height = [current-time + (maturity-height - block-count) * 10 * 60] sec
+            let height_diff =
+                if let Some(x) = blocks.to_consensus_u32().checked_sub(tip_height as u32) {
+                    x as u64
+                } else {
+                    return Err(FidelityError::BondLocktimeExpired.into());
+                };
+
+            tip_time + (height_diff * 10 * 60)
+        }
+        LockTime::Seconds(sec) => sec.to_consensus_u32() as u64,
+    };
+
+    Ok(calculate_fidelity_value(
+        bond.amount,
+        locktime,
+        confirmation_time,
+        current_time,
+    ))
+}
+
+/// Verified details of one bond of a [FidelityProof], see [audit_fidelity_proof].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BondReport {
+    /// Outpoint of the bond.
+    pub outpoint: OutPoint,
+    /// Locked amount.
+    pub amount: Amount,
+    /// Locktime of the bond, as a block height or a unix time.
+    pub lock_time: LockTime,
+    /// Blocks until the bond matures. Estimated with 10 minute blocks for time based locktimes.
+    pub remaining_blocks: u64,
+    /// Confirmations of the bond transaction.
+    pub confirmations: u32,
+    /// Block height the bond certificate expires at.
+    pub cert_expiry_height: u64,
+    /// Whether the bond is held in cold storage, and certified by a delegated hot key.
+    pub cold: bool,
+    /// Current value of the bond.
+    pub value: Amount,
+}
+
+/// Verified details of a [FidelityProof], see [audit_fidelity_proof].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FidelityProofReport {
+    /// The bonds of the proof, the primary bond first.
+    pub bonds: Vec<BondReport>,
+    /// Value of the maker, aggregated from all the bonds.
+    pub total_value: Amount,
+}
+
+impl std::fmt::Display for FidelityProofReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for bond in &self.bonds {
+            writeln!(f, "Outpoint         : {}", bond.outpoint)?;
+            writeln!(f, "Amount           : {} sats", bond.amount.to_sat())?;
+            writeln!(f, "Locktime         : {}", bond.lock_time)?;
+            writeln!(f, "Remaining blocks : {}", bond.remaining_blocks)?;
+            writeln!(f, "Confirmations    : {}", bond.confirmations)?;
+            writeln!(f, "Cert expiry      : block {}", bond.cert_expiry_height)?;
+            writeln!(f, "Cold storage     : {}", bond.cold)?;
+            writeln!(f, "Bond value       : {} sats", bond.value.to_sat())?;
+            writeln!(f)?;
+        }
+        write!(f, "Total value      : {} sats", self.total_value.to_sat())
+    }
+}
+
+/// Verifies a [FidelityProof] advertised by the maker at `addr`, against the blockchain of a Bitcoin Core node.
+///
+/// Runs the checks of the directories and takers: each bond transaction is fetched, and the bond script, locktime,
+/// certificate expiry, certificate hash and signature are checked. The bonds must also be confirmed and unspent.
+/// Returns the details and values of the bonds. No wallet is needed.
+pub fn audit_fidelity_proof(
+    proof: &FidelityProof,
+    addr: &str,
+    rpc: &Client,
+) -> Result<FidelityProofReport, WalletError> {
+    verify_fidelity_checks(proof, addr, rpc)?;
+
+    let current_height = rpc.get_block_count()?;
+    let bonds = proof
+        .bonds()
+        .map(|bond| {
+            let utxo = rpc
+                .get_tx_out(&bond.outpoint.txid, bond.outpoint.vout, Some(false))?
+                .ok_or(FidelityError::BondAlreadySpent)?;
+            if utxo.confirmations == 0 {
+                return Err(WalletError::General(format!(
+                    "Bond {} is not confirmed",
+                    bond.outpoint
+                )));
+            }
+            let conf_height = (current_height + 1).saturating_sub(utxo.confirmations as u64);
+            Ok(BondReport {
+                outpoint: bond.outpoint,
+                amount: bond.amount,
+                lock_time: bond.lock_time,
+                remaining_blocks: bond.remaining_blocks(current_height),
+                confirmations: utxo.confirmations,
+                cert_expiry_height: bond.cert_expiry * CERT_EXPIRY_PERIOD,
+                cold: bond.delegation.is_some(),
+                value: calculate_bond_value_at(rpc, bond, conf_height)?,
+            })
+        })
+        .collect::<Result<Vec<_>, WalletError>>()?;
+    let total_value = aggregate_bond_values(bonds.iter().map(|bond| bond.value));
+
+    Ok(FidelityProofReport { bonds, total_value })
+}
+
+/// A fidelity bond transaction broadcasted by [Wallet::broadcast_fidelity], waiting for confirmation.
+/// Kept in the wallet file until stored, so the bond isn't lost to a restart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct PendingFidelity {
+    pub(crate) index: u32,
+    pub(crate) txid: Txid,
+    amount: Amount,
+    lock_time: LockTime,
+    pubkey: PublicKey,
+}
+
 /// Structure describing a Fidelity Bond.
 /// Fidelity Bonds are described in https://github.com/JoinMarket-Org/joinmarket-clientserver/blob/master/docs/fidelity-bonds.md
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, PartialOrd, Hash)]
@@ -132,6 +331,9 @@ pub struct FidelityBond {
     pub(crate) conf_height: u32,
     // Cert expiry denoted in multiple of difficulty adjustment period (2016 blocks)
     pub(crate) cert_expiry: u64,
+    /// Delegation of the certificates to a hot key, for a cold bond. See `cold_fidelity`.
+    #[serde(default)]
+    pub(crate) delegation: Option<CertDelegation>,
 }
 
 impl FidelityBond {
@@ -145,21 +347,43 @@ impl FidelityBond {
         redeemscript_to_scriptpubkey(&self.redeem_script()).expect("This can never panic as fidelity redeemscript template is hardcoded in a private function.")
     }
 
+    /// Blocks until the bond matures at `current_height`. Estimated with 10 minute blocks for time based locktimes.
+    pub(crate) fn remaining_blocks(&self, current_height: u64) -> u64 {
+        match self.lock_time {
+            LockTime::Blocks(height) => {
+                (height.to_consensus_u32() as u64).saturating_sub(current_height)
+            }
+            LockTime::Seconds(time) => {
+                (time.to_consensus_u32() as u64).saturating_sub(unix_time()) / (10 * 60)
+            }
+        }
+    }
+
+    /// Whether the bond's certificate has expired at `current_height`.
+    pub(crate) fn is_cert_expired(&self, current_height: u64) -> bool {
+        current_height >= self.cert_expiry * CERT_EXPIRY_PERIOD
+    }
+
     /// Generate the bond's certificate hash.
     pub(crate) fn generate_cert_hash(&self, addr: &str) -> sha256d::Hash {
         let cert_msg_str = format!(
             "fidelity-bond-cert|{}|{}|{}|{}|{}|{}",
             self.outpoint, self.pubkey, self.cert_expiry, self.lock_time, self.amount, addr
         );
-        let cert_msg = cert_msg_str.as_bytes();
-        let mut btc_signed_msg = Vec::<u8>::new();
-        btc_signed_msg.extend("\x18Bitcoin Signed Message:\n".as_bytes());
-        btc_signed_msg.push(cert_msg.len() as u8);
-        btc_signed_msg.extend(cert_msg);
-        sha256d::Hash::hash(&btc_signed_msg)
+        signed_message_hash(&cert_msg_str)
     }
 }
 
+/// Hash of a message, in the Bitcoin Signed Message format.
+pub(super) fn signed_message_hash(msg: &str) -> sha256d::Hash {
+    let msg = msg.as_bytes();
+    let mut btc_signed_msg = Vec::<u8>::new();
+    btc_signed_msg.extend("\x18Bitcoin Signed Message:\n".as_bytes());
+    btc_signed_msg.push(msg.len() as u8);
+    btc_signed_msg.extend(msg);
+    sha256d::Hash::hash(&btc_signed_msg)
+}
+
 // Wallet APIs related to fidelity bonds.
 impl Wallet {
     /// Get a reference to the fidelity bond store
@@ -170,12 +394,26 @@ impl Wallet {
     /// Get the highest value fidelity bond. Returns None, if no bond exists.
     pub fn get_highest_fidelity_index(&self) -> Result<Option<u32>, WalletError> {
         Ok(self
+            .get_valued_fidelity_bonds()
+            .first()
+            .map(|(index, _)| *index))
+    }
+
+    /// Get the indexes and values of the live fidelity bonds, highest value first.
+    ///
+    /// Spent bonds, and bonds that can't be valued, like expired ones or cold ones without a live delegation,
+    /// are skipped.
+    pub(crate) fn get_valued_fidelity_bonds(&self) -> Vec<(u32, Amount)> {
+        let mut bonds = self
             .store
             .fidelity_bond
             .iter()
             .filter_map(|(i, (_, _, is_spent))| {
                 if !is_spent {
-                    match self.calculate_bond_value(*i) {
+                    match self
+                        .check_delegation(*i)
+                        .and_then(|_| self.calculate_bond_value(*i))
+                    {
                         Ok(v) => {
                             log::info!("Fidelity Bond found | Index: {}, Value : {}", i, v);
                             Some((i, v))
@@ -190,6 +428,16 @@ impl Wallet {
                                     "Use `maker-cli redeem-fildeity <index>` to redeem the bond"
                                 );
                             }
+                            if matches!(
+                                e,
+                                WalletError::Fidelity(
+                                    FidelityError::MissingDelegation | FidelityError::CertExpired
+                                )
+                            ) {
+                                log::info!(
+                                    "Use `maker-cli export-delegation-request` to certify the cold bond"
+                                );
+                            }
                             None
                         }
                     }
@@ -197,8 +445,19 @@ impl Wallet {
                     None
                 }
             })
-            .max_by(|a, b| a.1.cmp(&b.1))
-            .map(|(i, _)| *i))
+            .map(|(i, v)| (*i, v))
+            .collect::<Vec<_>>();
+        bonds.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        bonds
+    }
+
+    /// Checks that the bond at given index can be certified, i.e. it's a hot bond, or a cold bond with a live
+    /// delegation.
+    fn check_delegation(&self, index: u32) -> Result<(), WalletError> {
+        if self.is_cold_fidelity(index)? {
+            self.get_certifying_keypair(index, self.rpc.get_block_count()?)?;
+        }
+        Ok(())
     }
 
     /// Get the [KeyPair] for the fidelity bond at given index.
@@ -232,15 +491,7 @@ impl Wallet {
         &self,
         locktime: LockTime,
     ) -> Result<(u32, Address, PublicKey), WalletError> {
-        // Check what was the last fidelity address index.
-        // Derive a fidelity address
-        let next_index = self
-            .store
-            .fidelity_bond
-            .keys()
-            .map(|i| *i + 1)
-            .last()
-            .unwrap_or(0);
+        let next_index = self.store.next_fidelity_index();
 
         let fidelity_pubkey = PublicKey {
             compressed: true,
@@ -266,39 +517,8 @@ impl Wallet {
             .fidelity_bond
             .get(&index)
             .ok_or(FidelityError::BondDoesNotExist)?;
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("This can't error")
-            .as_secs();
-
-        let hash = self.rpc.get_block_hash(bond.conf_height as u64)?;
-
-        let confirmation_time = self.rpc.get_block_header_info(&hash)?.time as u64;
-
-        let locktime = match bond.lock_time {
-            LockTime::Blocks(blocks) => {
-                let tip_hash = self.rpc.get_blockchain_info()?.best_block_hash;
-                let (tip_height, tip_time) = {
-                    let info = self.rpc.get_block_header_info(&tip_hash)?;
-                    (info.height, info.time as u64)
-                };
-                // Estimated locktime from block height = [current-time + (maturity-height - block-count) * 10 * 60] sec
-                let height_diff =
-                    if let Some(x) = blocks.to_consensus_u32().checked_sub(tip_height as u32) {
-                        x as u64
-                    } else {
-                        return Err(FidelityError::BondLocktimeExpired.into());
-                    };
-
-                tip_time + (height_diff * 10 * 60)
-            }
-            LockTime::Seconds(sec) => sec.to_consensus_u32() as u64,
-        };
-
-        let bond_value =
-            calculate_fidelity_value(bond.amount, locktime, confirmation_time, current_time);
 
-        Ok(bond_value)
+        calculate_bond_value_at(&self.rpc, bond, bond.conf_height as u64)
     }
 
     /// Create a new fidelity bond with given amount and locktime.
@@ -309,6 +529,37 @@ impl Wallet {
         amount: Amount,
         locktime: LockTime, // The final locktime in blockheight or timestamp
     ) -> Result<u32, WalletError> {
+        let pending = self.broadcast_fidelity(amount, locktime)?;
+
+        let sleep_increment = 10;
+        let mut sleep_multiplier = 0;
+
+        let conf_height = loop {
+            sleep_multiplier += 1;
+
+            if let Some(ht) = self.get_fidelity_conf_height(&pending.txid)? {
+                break ht;
+            } else {
+                log::warn!("ATTENTION ! DO NOT SHUTDOWN THE MAKER UNTIL CONFIRMATION");
+
+                let total_sleep = sleep_increment * sleep_multiplier.min(10 * 60); // Caps at 1 Block interval i.e 10 mins
+                log::info!("Next sync in {:?} secs", total_sleep);
+                thread::sleep(Duration::from_secs(total_sleep));
+            }
+        };
+
+        self.store_fidelity(pending, conf_height)
+    }
+
+    /// Creates the transaction of a new fidelity bond with given amount and locktime, signs and broadcast it.
+    ///
+    /// The bond is only stored in the wallet with [Wallet::store_fidelity], once confirmed. The next bond address
+    /// is derived from the stored bonds, so a single bond can be pending at a time.
+    pub(crate) fn broadcast_fidelity(
+        &mut self,
+        amount: Amount,
+        locktime: LockTime, // The final locktime in blockheight or timestamp
+    ) -> Result<PendingFidelity, WalletError> {
         let (index, fidelity_addr, fidelity_pubkey) = self.get_next_fidelity_address(locktime)?;
 
         let all_utxos = self.get_all_utxo()?;
@@ -398,49 +649,75 @@ impl Wallet {
 
         let txid = self.send_tx(&tx)?;
 
-        let sleep_increment = 10;
-        let mut sleep_multiplier = 0;
+        let pending = PendingFidelity {
+            index,
+            txid,
+            amount,
+            lock_time: locktime,
+            pubkey: fidelity_pubkey,
+        };
+        self.store.pending_fidelity = Some(pending.clone());
+        self.save_to_disk()?;
 
-        let conf_height = loop {
-            sleep_multiplier += 1;
+        Ok(pending)
+    }
+
+    /// The fidelity bond broadcasted but not yet stored, if any.
+    pub(crate) fn get_pending_fidelity(&self) -> Option<PendingFidelity> {
+        self.store.pending_fidelity.clone()
+    }
 
-            let get_tx_result = self.rpc.get_transaction(&txid, None)?;
-            if let Some(ht) = get_tx_result.info.blockheight {
+    /// Get the confirmation height of a fidelity transaction. Returns None while it's unconfirmed.
+    pub(crate) fn get_fidelity_conf_height(&self, txid: &Txid) -> Result<Option<u32>, WalletError> {
+        let get_tx_result = self.rpc.get_transaction(txid, None)?;
+        match get_tx_result.info.blockheight {
+            Some(ht) => {
                 log::info!(
                     "Fidelity Transaction {} confirmed at blockheight: {}",
                     txid,
                     ht
                 );
-                break ht;
-            } else {
+                Ok(Some(ht))
+            }
+            None => {
                 log::info!(
                     "Fidelity Transaction {} seen in mempool, waiting for confirmation.",
                     txid
                 );
-                log::warn!("ATTENTION ! DO NOT SHUTDOWN THE MAKER UNTIL CONFIRMATION");
-
-                let total_sleep = sleep_increment * sleep_multiplier.min(10 * 60); // Caps at 1 Block interval i.e 10 mins
-                log::info!("Next sync in {:?} secs", total_sleep);
-                thread::sleep(Duration::from_secs(total_sleep));
+                Ok(None)
             }
-        };
+        }
+    }
 
+    /// Stores a broadcasted fidelity bond confirmed at `conf_height` in the wallet. Returns the bond index.
+    pub(crate) fn store_fidelity(
+        &mut self,
+        pending: PendingFidelity,
+        conf_height: u32,
+    ) -> Result<u32, WalletError> {
+        let PendingFidelity {
+            index,
+            txid,
+            amount,
+            lock_time,
+            pubkey,
+        } = pending;
         let cert_expiry = self.get_fidelity_expiry()?;
 
         let bond = FidelityBond {
             outpoint: OutPoint::new(txid, 0),
             amount,
-            lock_time: locktime,
-            pubkey: fidelity_pubkey,
+            lock_time,
+            pubkey,
             conf_height,
             cert_expiry,
+            delegation: None,
         };
 
         let bond_spk = bond.script_pub_key();
 
-        self.store
-            .fidelity_bond
-            .insert(index, (bond, bond_spk, false));
+        self.store.insert_fidelity(index, bond, bond_spk)?;
+        self.store.pending_fidelity = None;
 
         self.sync()?;
 
@@ -461,6 +738,11 @@ impl Wallet {
             return Err(FidelityError::BondAlreadySpent.into());
         }
 
+        // Cold bonds are redeemed with `fidelity-signer redeem`.
+        if self.is_cold_fidelity(index)? {
+            return Err(FidelityError::ColdBond.into());
+        }
+
         // create a spending transaction.
         let txin = TxIn {
             previous_output: bond.outpoint,
@@ -513,12 +795,67 @@ impl Wallet {
         Ok(txid)
     }
 
+    /// Get the keypair certifying a fidelity bond of this wallet, i.e. the key its certificates and offers are
+    /// signed with. That's the bond key, or the hot certificate key of a cold bond.
+    pub(crate) fn get_bond_keypair(&self, bond: &FidelityBond) -> Result<Keypair, WalletError> {
+        let index = self
+            .store
+            .fidelity_bond
+            .iter()
+            .find_map(|(i, (b, _, _))| (b == bond).then_some(*i))
+            .ok_or(FidelityError::BondDoesNotExist)?;
+        self.get_certifying_keypair(index, self.rpc.get_block_count()?)
+    }
+
     /// Generate a [FidelityProof] for bond at a given index and a specific onion address.
     pub(crate) fn generate_fidelity_proof(
         &self,
         index: u32,
         maker_addr: &str,
     ) -> Result<FidelityProof, WalletError> {
+        let BondProof {
+            bond,
+            cert_hash,
+            cert_sig,
+        } = self.certify_bond(index, maker_addr)?;
+        Ok(FidelityProof {
+            bond,
+            cert_hash,
+            cert_sig,
+            additional_bonds: Vec::new(),
+        })
+    }
+
+    /// Generate a [FidelityProof] of all the live bonds, up to [MAX_PROOF_BONDS], for a specific onion address.
+    ///
+    /// The highest valued bond is the primary one. Returns the proof with its aggregated value, or None if no bond
+    /// can be advertised.
+    pub(crate) fn generate_combined_fidelity_proof(
+        &self,
+        maker_addr: &str,
+    ) -> Result<Option<(FidelityProof, Amount)>, WalletError> {
+        let bonds = self
+            .get_valued_fidelity_bonds()
+            .into_iter()
+            .take(MAX_PROOF_BONDS)
+            .collect::<Vec<_>>();
+        let (primary_index, _) = match bonds.first() {
+            Some(primary) => *primary,
+            None => return Ok(None),
+        };
+
+        let mut proof = self.generate_fidelity_proof(primary_index, maker_addr)?;
+        proof.additional_bonds = bonds[1..]
+            .iter()
+            .map(|(index, _)| self.certify_bond(*index, maker_addr))
+            .collect::<Result<_, _>>()?;
+        let value = aggregate_bond_values(bonds.iter().map(|(_, value)| *value));
+        Ok(Some((proof, value)))
+    }
+
+    /// Certify the bond at a given index for a specific onion address, with the bond key, or the hot certificate key
+    /// of a cold bond.
+    fn certify_bond(&self, index: u32, maker_addr: &str) -> Result<BondProof, WalletError> {
         // Generate a fidelity bond proof from the fidelity data.
         let (bond, _, is_spent) = self
             .store
@@ -530,7 +867,9 @@ impl Wallet {
             return Err(FidelityError::BondAlreadySpent.into());
         }
 
-        let fidelity_privkey = self.get_fidelity_keypair(index)?.secret_key();
+        let fidelity_privkey = self
+            .get_certifying_keypair(index, self.rpc.get_block_count()?)?
+            .secret_key();
 
         let cert_hash = bond.generate_cert_hash(maker_addr);
 
@@ -540,30 +879,54 @@ impl Wallet {
             &fidelity_privkey,
         );
 
-        Ok(FidelityProof {
+        Ok(BondProof {
             bond: bond.clone(),
             cert_hash,
             cert_sig,
         })
     }
 
-    /// Verify a [FidelityProof] received from the directory servers.
+    /// Verify a [FidelityProof] received from the directory servers, checking each of its bonds.
     pub(crate) fn verify_fidelity_proof(
         &self,
         proof: &FidelityProof,
         onion_addr: &str,
     ) -> Result<(), WalletError> {
-        let txid = proof.bond.outpoint.txid;
-        let transaction = self.rpc.get_raw_transaction(&txid, None)?;
-        let current_height = self.rpc.get_block_count()?;
-
-        verify_fidelity_checks(proof, onion_addr, transaction, current_height)
+        verify_fidelity_checks(proof, onion_addr, &self.rpc)
     }
 
     /// Calculate the expiry value. This depends on the current block height.
     pub(crate) fn get_fidelity_expiry(&self) -> Result<u64, WalletError> {
         let current_height = self.rpc.get_block_count()?;
-        Ok((current_height + 2) /* safety buffer */ / 2016 + 5)
+        Ok((current_height + 2) /* safety buffer */ / CERT_EXPIRY_PERIOD + 5)
+    }
+
+    /// Extends the certificates of the live bonds expiring within a period, to a fresh expiry.
+    ///
+    /// Returns the renewed bonds. Their proofs must be generated again, as the certificates cover the expiry.
+    /// The delegations of the cold bonds can only be renewed offline, so they are reported instead.
+    pub(crate) fn renew_fidelity_certs(&mut self) -> Result<Vec<FidelityBond>, WalletError> {
+        let current_height = self.rpc.get_block_count()?;
+        let cert_expiry = self.get_fidelity_expiry()?;
+        let cold_indexes = self.get_cold_fidelity_indexes()?;
+        let mut renewed = Vec::new();
+        for (index, (bond, _, is_spent)) in self.store.fidelity_bond.iter_mut() {
+            if *is_spent || !bond.is_cert_expired(current_height + CERT_EXPIRY_PERIOD) {
+                continue;
+            }
+            if cold_indexes.contains(index) {
+                log::warn!(
+                    "Delegation of cold bond {} expires at block {}, sign a new one from `maker-cli export-delegation-request {}`",
+                    bond.outpoint,
+                    bond.cert_expiry * CERT_EXPIRY_PERIOD,
+                    index
+                );
+                continue;
+            }
+            bond.cert_expiry = cert_expiry;
+            renewed.push(bond.clone());
+        }
+        Ok(renewed)
     }
 }
 
@@ -693,6 +1056,112 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_aggregate_bond_values() {
+        let year = 60 * 60 * 24 * 365;
+        let value_of =
+            |btc: f64| calculate_fidelity_value(Amount::from_btc(btc).unwrap(), 10 * year, 0, year);
+        let close = |a: Amount, b: Amount| a.to_sat().abs_diff(b.to_sat()) <= 1;
+
+        assert_eq!(aggregate_bond_values(Vec::new()), Amount::ZERO);
+        assert!(close(aggregate_bond_values([value_of(1.0)]), value_of(1.0)));
+
+        // Two bonds of the same locktime are worth as much as one bond of their total.
+        let aggregated = aggregate_bond_values([value_of(1.0), value_of(3.0)]);
+        assert!(close(aggregated, value_of(4.0)));
+        // And more than valuing the bonds separately.
+        assert!(aggregated > value_of(1.0) + value_of(3.0));
+    }
+
+    #[test]
+    fn test_pending_fidelity_persisted() {
+        use crate::wallet::storage::WalletStore;
+        use bitcoin::{bip32::Xpriv, Network};
+
+        let temp_dir = bitcoind::tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("wallet");
+        let master_key = Xpriv::new_master(Network::Regtest, &[7; 16]).unwrap();
+        let mut store = WalletStore::init(
+            "wallet".to_string(),
+            &path,
+            Network::Regtest,
+            master_key,
+            None,
+        )
+        .unwrap();
+
+        let pending = PendingFidelity {
+            index: 0,
+            txid: Txid::from_byte_array([1; 32]),
+            amount: Amount::from_sat(50_000),
+            lock_time: LockTime::from_height(1_000).unwrap(),
+            pubkey: PublicKey::new(master_key.private_key.public_key(&Secp256k1::new())),
+        };
+        store.pending_fidelity = Some(pending.clone());
+        store.write_to_disk(&path).unwrap();
+
+        // A bond broadcasted before a restart is still waited for after it.
+        let read = WalletStore::read_from_disk(&path).unwrap();
+        assert_eq!(read.pending_fidelity, Some(pending));
+    }
+
+    #[test]
+    fn test_next_fidelity_index() {
+        use crate::wallet::storage::WalletStore;
+        use bitcoin::{bip32::Xpriv, Network};
+
+        let temp_dir = bitcoind::tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("wallet");
+        let master_key = Xpriv::new_master(Network::Regtest, &[7; 16]).unwrap();
+        let mut store = WalletStore::init(
+            "wallet".to_string(),
+            &path,
+            Network::Regtest,
+            master_key,
+            None,
+        )
+        .unwrap();
+        let pubkey = PublicKey::new(master_key.private_key.public_key(&Secp256k1::new()));
+        let bond = |vout| FidelityBond {
+            outpoint: OutPoint::new(Txid::from_byte_array([2; 32]), vout),
+            amount: Amount::from_sat(50_000),
+            lock_time: LockTime::from_height(1_000).unwrap(),
+            pubkey,
+            conf_height: 100,
+            cert_expiry: 1,
+            delegation: None,
+        };
+        let spk = ScriptBuf::new();
+
+        assert_eq!(store.next_fidelity_index(), 0);
+
+        // Indexes with a gap, inserted out of order: the next one is past the highest.
+        for index in [3, 0, 1] {
+            store
+                .insert_fidelity(index, bond(index), spk.clone())
+                .unwrap();
+        }
+        assert_eq!(store.next_fidelity_index(), 4);
+
+        // A bond waiting for confirmation holds its index too.
+        store.pending_fidelity = Some(PendingFidelity {
+            index: 4,
+            txid: Txid::from_byte_array([1; 32]),
+            amount: Amount::from_sat(50_000),
+            lock_time: LockTime::from_height(1_000).unwrap(),
+            pubkey,
+        });
+        assert_eq!(store.next_fidelity_index(), 5);
+
+        // An occupied index is never overwritten.
+        assert!(matches!(
+            store.insert_fidelity(1, bond(9), spk.clone()),
+            Err(FidelityError::IndexInUse)
+        ));
+        assert_eq!(store.fidelity_bond[&1].0.outpoint.vout, 1);
+        assert_eq!(store.fidelity_bond.len(), 3);
+    }
 }
 
 #[test]
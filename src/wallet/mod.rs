@@ -1,18 +1,28 @@
 //! The Coinswap Wallet (unsecured). Used by both the Taker and Maker.
 
 mod api;
+mod backup;
+mod cold_fidelity;
+mod confirmations;
 mod direct_send;
 mod error;
 mod fidelity;
 mod funding;
+mod reservation;
 mod rpc;
 mod storage;
 mod swapcoin;
 
 pub(crate) use api::{Balances, UTXOSpendInfo, Wallet};
+pub use backup::{recover_from_backup, SwapBackup};
+pub use cold_fidelity::{ColdKey, DelegationRequest, SignedDelegation};
 pub use direct_send::{Destination, SendAmount};
 pub use error::WalletError;
-pub(crate) use fidelity::{fidelity_redeemscript, FidelityBond, FidelityError};
+pub(crate) use fidelity::{
+    aggregate_bond_values, calculate_bond_value_at, fidelity_redeemscript, FidelityBond,
+    FidelityError, PendingFidelity, MAX_PROOF_BONDS,
+};
+pub use fidelity::{audit_fidelity_proof, BondReport, FidelityProofReport};
 pub use rpc::RPCConfig;
 pub(crate) use swapcoin::{
     IncomingSwapCoin, OutgoingSwapCoin, SwapCoin, WalletSwapCoin, WatchOnlySwapCoin,
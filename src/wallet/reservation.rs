@@ -0,0 +1,182 @@
+//! UTXO reservation ledger.
+//!
+//! A maker serves multiple takers concurrently. The UTXOs spent by the funding transactions of a swap are
+//! reserved for that swap id from the moment the funding transactions are created, until they are broadcasted
+//! or the swap is aborted. Reserved UTXOs are excluded from coin selection, and their value is subtracted from
+//! the advertised offer max size.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use bitcoin::{Amount, OutPoint, Transaction};
+
+#[derive(Debug)]
+struct Reservation {
+    outpoints: Vec<OutPoint>,
+    amount: Amount,
+    reserved_at: Instant,
+}
+
+/// UTXOs reserved by ongoing swaps, keyed by swap id.
+#[derive(Debug, Default)]
+pub(crate) struct UtxoReservations {
+    reservations: HashMap<String, Reservation>,
+}
+
+impl UtxoReservations {
+    /// Reserve all the inputs of the given funding transactions for a swap.
+    ///
+    /// The reserved amount is the total value spent by the funding transactions, i.e. all their outputs plus the miner fees.
+    pub(crate) fn reserve(
+        &mut self,
+        swap_id: &str,
+        funding_txes: &[Transaction],
+        miner_fees: Amount,
+    ) {
+        let outpoints = funding_txes
+            .iter()
+            .flat_map(|tx| tx.input.iter().map(|txin| txin.previous_output))
+            .collect::<Vec<_>>();
+        let amount = funding_txes
+            .iter()
+            .flat_map(|tx| tx.output.iter().map(|txout| txout.value))
+            .sum::<Amount>()
+            + miner_fees;
+
+        log::info!(
+            "Reserved {} utxos worth {} for swap {}",
+            outpoints.len(),
+            amount,
+            swap_id
+        );
+
+        let reservation = self
+            .reservations
+            .entry(swap_id.to_string())
+            .or_insert(Reservation {
+                outpoints: Vec::new(),
+                amount: Amount::ZERO,
+                reserved_at: Instant::now(),
+            });
+        reservation.outpoints.extend(outpoints);
+        reservation.amount += amount;
+        reservation.reserved_at = Instant::now();
+    }
+
+    /// Release the reservation of a swap. Returns true if the swap had any reservation.
+    pub(crate) fn release(&mut self, swap_id: &str) -> bool {
+        match self.reservations.remove(swap_id) {
+            Some(reservation) => {
+                log::info!(
+                    "Released {} utxos worth {} reserved for swap {}",
+                    reservation.outpoints.len(),
+                    reservation.amount,
+                    swap_id
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Release all reservations older than the given timeout. Returns the swap ids of the released reservations.
+    pub(crate) fn release_expired(&mut self, timeout: Duration) -> Vec<String> {
+        let expired = self
+            .reservations
+            .iter()
+            .filter(|(_, reservation)| reservation.reserved_at.elapsed() > timeout)
+            .map(|(swap_id, _)| swap_id.clone())
+            .collect::<Vec<_>>();
+        for swap_id in &expired {
+            log::warn!("UTXO reservation of swap {} expired", swap_id);
+            self.release(swap_id);
+        }
+        expired
+    }
+
+    /// Returns true if the outpoint is reserved by any swap.
+    pub(crate) fn is_reserved(&self, outpoint: &OutPoint) -> bool {
+        self.reservations
+            .values()
+            .any(|reservation| reservation.outpoints.contains(outpoint))
+    }
+
+    /// Ids of the swaps holding a reservation.
+    pub(crate) fn swap_ids(&self) -> impl Iterator<Item = &String> {
+        self.reservations.keys()
+    }
+
+    /// Total value reserved by all ongoing swaps.
+    pub(crate) fn reserved_amount(&self) -> Amount {
+        self.reservations
+            .values()
+            .map(|reservation| reservation.amount)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        absolute::LockTime, hashes::Hash, transaction::Version, ScriptBuf, Sequence, TxIn, TxOut,
+        Txid, Witness,
+    };
+
+    fn funding_tx(prevout_vout: u32, value: u64) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::all_zeros(),
+                    vout: prevout_vout,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ZERO,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_reserve_and_release() {
+        let mut reservations = UtxoReservations::default();
+        let outpoint = |vout| OutPoint {
+            txid: Txid::all_zeros(),
+            vout,
+        };
+
+        reservations.reserve(
+            "swap-1",
+            &[funding_tx(0, 10_000), funding_tx(1, 20_000)],
+            Amount::from_sat(1000),
+        );
+        reservations.reserve("swap-2", &[funding_tx(2, 5_000)], Amount::from_sat(500));
+
+        assert!(reservations.is_reserved(&outpoint(0)));
+        assert!(reservations.is_reserved(&outpoint(2)));
+        assert!(!reservations.is_reserved(&outpoint(3)));
+        assert_eq!(reservations.reserved_amount(), Amount::from_sat(36_500));
+
+        assert!(reservations.release("swap-1"));
+        assert!(!reservations.release("swap-1"));
+        assert!(!reservations.is_reserved(&outpoint(0)));
+        assert_eq!(reservations.reserved_amount(), Amount::from_sat(5_500));
+
+        assert!(reservations
+            .release_expired(Duration::from_secs(60))
+            .is_empty());
+        assert_eq!(
+            reservations.release_expired(Duration::ZERO),
+            vec!["swap-2".to_string()]
+        );
+        assert_eq!(reservations.reserved_amount(), Amount::ZERO);
+    }
+}
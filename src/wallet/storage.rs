@@ -11,7 +11,11 @@ use std::{
     path::Path,
 };
 
-use super::{error::WalletError, fidelity::FidelityBond};
+use super::{
+    confirmations::ConfirmationTracker,
+    error::WalletError,
+    fidelity::{FidelityBond, PendingFidelity},
+};
 
 use super::swapcoin::{IncomingSwapCoin, OutgoingSwapCoin};
 
@@ -40,6 +44,19 @@ pub(crate) struct WalletStore {
     pub(super) last_synced_height: Option<u64>,
 
     pub(super) wallet_birthday: Option<u64>,
+
+    /// Number of PoDLE commitments already made from each utxo, i.e. the next NUMS index to use.
+    /// A commitment is refused once used, so the indices must survive restarts.
+    #[serde(default)]
+    pub(crate) podle_indices: HashMap<OutPoint, u8>,
+
+    /// Blocks confirming the swap transactions, so that a reorg during downtime is noticed after a restart.
+    #[serde(default)]
+    pub(super) confirmations: ConfirmationTracker,
+
+    /// Fidelity bond broadcasted and waiting for confirmation. It's stored with the other bonds once confirmed.
+    #[serde(default)]
+    pub(super) pending_fidelity: Option<PendingFidelity>,
 }
 
 impl WalletStore {
@@ -63,6 +80,9 @@ impl WalletStore {
             fidelity_bond: HashMap::new(),
             last_synced_height: None,
             wallet_birthday,
+            podle_indices: HashMap::new(),
+            confirmations: ConfirmationTracker::default(),
+            pending_fidelity: None,
         };
 
         std::fs::create_dir_all(path.parent().expect("Path should NOT be root!"))?;
@@ -112,6 +132,7 @@ impl WalletStore {
 mod tests {
     use super::*;
     use bip39::rand::{thread_rng, Rng};
+    use bitcoin::{hashes::Hash, BlockHash, Txid};
     use bitcoind::tempfile::tempdir;
 
     #[test]
@@ -124,7 +145,7 @@ mod tests {
             Xpriv::new_master(Network::Bitcoin, &seed).unwrap()
         };
 
-        let original_wallet_store = WalletStore::init(
+        let mut original_wallet_store = WalletStore::init(
             "test_wallet".to_string(),
             &file_path,
             Network::Bitcoin,
@@ -132,6 +153,11 @@ mod tests {
             None,
         )
         .unwrap();
+        // Reorgs during downtime are only noticed if the confirmations survive a restart.
+        original_wallet_store.confirmations.track(
+            Txid::from_byte_array([1; 32]),
+            BlockHash::from_byte_array([2; 32]),
+        );
 
         original_wallet_store.write_to_disk(&file_path).unwrap();
 
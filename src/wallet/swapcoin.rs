@@ -32,6 +32,9 @@ use crate::protocol::{
 
 use super::WalletError;
 
+/// Approximate size of a refund tx, a 2-of-2 multisig spend to a single output.
+const REFUND_TX_VSIZE: u64 = 150;
+
 /// Defines an incoming swapcoin, which can either be currently active or successfully completed.
 ///
 /// ### NOTE:
@@ -81,6 +84,9 @@ pub(crate) struct OutgoingSwapCoin {
     pub(crate) funding_amount: Amount,
     pub(crate) others_contract_sig: Option<Signature>,
     pub(crate) hash_preimage: Option<Preimage>,
+    /// Fully signed refund tx of a cooperative abort, broadcasted instead of the contract tx.
+    #[serde(default)]
+    pub(crate) refund_tx: Option<Transaction>,
 }
 
 /// Represents a watch-only view of a coinswap between two makers.
@@ -359,6 +365,44 @@ impl IncomingSwapCoin {
         )
     }
 
+    /// Create a signed transaction spending the hashlock branch of the contract output to the destination.
+    /// Errors if the hash preimage is not yet known.
+    pub(crate) fn create_hashlock_spend(
+        &self,
+        destination_address: &Address,
+    ) -> Result<Transaction, WalletError> {
+        let preimage = self.hash_preimage.ok_or(WalletError::General(
+            "Unable to create hashlock spend: preimage unknown".to_string(),
+        ))?;
+        let miner_fee = 136 * 2; //136 vbytes x 2 sat/vb, size calculated using testmempoolaccept
+        let mut tx = Transaction {
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: self.contract_tx.compute_txid(),
+                    vout: 0, //contract_tx is one-input-one-output
+                },
+                sequence: Sequence(1), //hashlock spends must have 1 because of the `OP_CSV 1`
+                witness: Witness::new(),
+                script_sig: ScriptBuf::new(),
+            }],
+            output: vec![TxOut {
+                script_pubkey: destination_address.script_pubkey(),
+                value: Amount::from_sat(self.contract_tx.output[0].value.to_sat() - miner_fee),
+            }],
+            lock_time: LockTime::ZERO,
+            version: Version::TWO,
+        };
+        let index = 0;
+        self.sign_hashlocked_transaction_input_given_preimage(
+            index,
+            &tx.clone(),
+            &mut tx.input[0],
+            self.contract_tx.output[0].value,
+            &preimage,
+        )?;
+        Ok(tx)
+    }
+
     pub(crate) fn verify_contract_tx_sig(&self, sig: &Signature) -> Result<(), WalletError> {
         Ok(verify_contract_tx_sig(
             &self.contract_tx,
@@ -368,6 +412,27 @@ impl IncomingSwapCoin {
             &sig.signature,
         )?)
     }
+
+    /// Sign the sender's refund tx, spending the funding output of this swapcoin back to the sender.
+    /// Signing gives up this incoming coin, only do so when cooperatively aborting the swap.
+    pub(crate) fn sign_senders_refund_tx(
+        &self,
+        refund_tx: &Transaction,
+    ) -> Result<Signature, WalletError> {
+        if refund_tx.input.len() != 1
+            || refund_tx.input[0].previous_output != self.contract_tx.input[0].previous_output
+        {
+            return Err(
+                ProtocolError::General("Refund tx doesn't spend the funding output").into(),
+            );
+        }
+        Ok(sign_contract_tx(
+            refund_tx,
+            &self.get_multisig_redeemscript(),
+            self.funding_amount,
+            &self.my_privkey,
+        )?)
+    }
 }
 
 impl OutgoingSwapCoin {
@@ -394,6 +459,7 @@ impl OutgoingSwapCoin {
             funding_amount,
             others_contract_sig: None,
             hash_preimage: None,
+            refund_tx: None,
         })
     }
 
@@ -459,6 +525,70 @@ impl OutgoingSwapCoin {
         Ok(tx)
     }
 
+    /// Create an unsigned refund tx, spending the funding output straight back to the destination, at `feerate`
+    /// sats per vbyte. Once signed by both parties it replaces the contract tx and its timelocked spend.
+    pub(crate) fn create_refund_tx(
+        &self,
+        destination_address: &Address,
+        feerate: u64,
+    ) -> Result<Transaction, WalletError> {
+        let value = REFUND_TX_VSIZE
+            .checked_mul(feerate)
+            .and_then(|fee| self.funding_amount.checked_sub(Amount::from_sat(fee)))
+            .ok_or_else(|| {
+                WalletError::General(format!(
+                    "Refund fee at {} sats/vb exceeds the funding amount {}",
+                    feerate, self.funding_amount
+                ))
+            })?;
+        Ok(Transaction {
+            input: vec![TxIn {
+                previous_output: self.contract_tx.input[0].previous_output,
+                sequence: Sequence::ZERO,
+                witness: Witness::new(),
+                script_sig: ScriptBuf::new(),
+            }],
+            output: vec![TxOut {
+                script_pubkey: destination_address.script_pubkey(),
+                value,
+            }],
+            lock_time: LockTime::ZERO,
+            version: Version::TWO,
+        })
+    }
+
+    /// Verify the receiver's signature on a refund tx and return the fully signed refund tx.
+    pub(crate) fn sign_refund_tx(
+        &self,
+        refund_tx: &Transaction,
+        others_sig: &Signature,
+    ) -> Result<Transaction, WalletError> {
+        let multisig_redeemscript = self.get_multisig_redeemscript();
+        verify_contract_tx_sig(
+            refund_tx,
+            &multisig_redeemscript,
+            self.funding_amount,
+            &self.other_pubkey,
+            &others_sig.signature,
+        )?;
+        let my_sig = sign_contract_tx(
+            refund_tx,
+            &multisig_redeemscript,
+            self.funding_amount,
+            &self.my_privkey,
+        )?;
+        let mut signed_refund_tx = refund_tx.clone();
+        apply_two_signatures_to_2of2_multisig_spend(
+            &self.get_my_pubkey(),
+            &self.other_pubkey,
+            &my_sig,
+            others_sig,
+            &mut signed_refund_tx.input[0],
+            &multisig_redeemscript,
+        );
+        Ok(signed_refund_tx)
+    }
+
     //"_with_my_privkey" as opposed to with other_privkey
     pub(crate) fn sign_contract_tx_with_my_privkey(
         &self,
@@ -779,6 +909,7 @@ mod tests {
             funding_amount: Amount::ZERO,
             others_contract_sig: None,
             hash_preimage: None,
+            refund_tx: None,
         };
         let secret_key_1 =
             SecretKey::from_str("0000000000000000000000000000000000000000000000000000000000000001")
@@ -944,6 +1075,65 @@ mod tests {
         assert!(tx.input[0].witness.len() == 3);
     }
 
+    #[test]
+    fn test_create_hashlock_spend() {
+        let secp = Secp256k1::new();
+        let other_privkey = PrivateKey {
+            compressed: true,
+            network: NetworkKind::Test,
+            inner: secp256k1::SecretKey::from_str(
+                "0000000000000000000000000000000000000000000000000000000000000001",
+            )
+            .unwrap(),
+        };
+        let mut incoming_swapcoin = IncomingSwapCoin {
+            my_privkey: secp256k1::SecretKey::from_str(
+                "0000000000000000000000000000000000000000000000000000000000000003",
+            )
+            .unwrap(),
+            other_privkey: None,
+            other_pubkey: PublicKey::from_private_key(&secp, &other_privkey),
+            contract_tx: Transaction {
+                input: vec![TxIn::default()],
+                output: vec![TxOut {
+                    script_pubkey: ScriptBuf::new(),
+                    value: Amount::from_sat(100_000),
+                }],
+                lock_time: LockTime::ZERO,
+                version: Version::TWO,
+            },
+            contract_redeemscript: ScriptBuf::default(),
+            hashlock_privkey: secp256k1::SecretKey::from_str(
+                "0000000000000000000000000000000000000000000000000000000000000004",
+            )
+            .unwrap(),
+            funding_amount: Amount::from_sat(100_000),
+            others_contract_sig: None,
+            hash_preimage: None,
+        };
+        let destination_address: Address = Address::from_str("32iVBEu4dxkUQk9dJbZUiBiQdmypcEyJRf")
+            .unwrap()
+            .require_network(bitcoin::Network::Bitcoin)
+            .unwrap();
+
+        // Can't spend the hashlock without the preimage.
+        assert!(incoming_swapcoin
+            .create_hashlock_spend(&destination_address)
+            .is_err());
+
+        incoming_swapcoin.hash_preimage = Some(Preimage::from([0; 32]));
+        let tx = incoming_swapcoin
+            .create_hashlock_spend(&destination_address)
+            .unwrap();
+        assert_eq!(
+            tx.input[0].previous_output.txid,
+            incoming_swapcoin.contract_tx.compute_txid()
+        );
+        assert_eq!(tx.input[0].sequence, Sequence(1));
+        assert_eq!(tx.input[0].witness.len(), 3);
+        assert_eq!(tx.output[0].value, Amount::from_sat(100_000 - 136 * 2));
+    }
+
     #[test]
     fn test_sign_hashlocked_transaction_input() {
         let secp = Secp256k1::new();
@@ -1030,4 +1220,103 @@ mod tests {
         );
         assert!(final_return.is_ok());
     }
+
+    #[test]
+    fn test_cooperative_refund() {
+        let secp = Secp256k1::new();
+        let sender_privkey =
+            SecretKey::from_str("0000000000000000000000000000000000000000000000000000000000000002")
+                .unwrap();
+        let receiver_privkey =
+            SecretKey::from_str("0000000000000000000000000000000000000000000000000000000000000003")
+                .unwrap();
+        let pubkey = |privkey: &SecretKey| PublicKey {
+            compressed: true,
+            inner: secp256k1::PublicKey::from_secret_key(&secp, privkey),
+        };
+        let funding_outpoint = OutPoint {
+            txid: Transaction {
+                input: vec![],
+                output: vec![TxOut::NULL],
+                lock_time: LockTime::ZERO,
+                version: Version::TWO,
+            }
+            .compute_txid(),
+            vout: 1,
+        };
+        let contract_tx = Transaction {
+            input: vec![TxIn {
+                previous_output: funding_outpoint,
+                ..TxIn::default()
+            }],
+            output: vec![TxOut::NULL],
+            lock_time: LockTime::ZERO,
+            version: Version::TWO,
+        };
+        let unused_key =
+            SecretKey::from_str("0000000000000000000000000000000000000000000000000000000000000004")
+                .unwrap();
+        let outgoing_swapcoin = OutgoingSwapCoin {
+            my_privkey: sender_privkey,
+            other_pubkey: pubkey(&receiver_privkey),
+            contract_tx: contract_tx.clone(),
+            contract_redeemscript: ScriptBuf::default(),
+            timelock_privkey: unused_key,
+            funding_amount: Amount::from_sat(100_000),
+            others_contract_sig: None,
+            hash_preimage: None,
+            refund_tx: None,
+        };
+        let incoming_swapcoin = IncomingSwapCoin {
+            my_privkey: receiver_privkey,
+            other_pubkey: pubkey(&sender_privkey),
+            other_privkey: None,
+            contract_tx,
+            contract_redeemscript: ScriptBuf::default(),
+            hashlock_privkey: unused_key,
+            funding_amount: Amount::from_sat(100_000),
+            others_contract_sig: None,
+            hash_preimage: None,
+        };
+        let destination_address: Address = Address::from_str("32iVBEu4dxkUQk9dJbZUiBiQdmypcEyJRf")
+            .unwrap()
+            .require_network(bitcoin::Network::Bitcoin)
+            .unwrap();
+
+        let refund_tx = outgoing_swapcoin
+            .create_refund_tx(&destination_address, 2)
+            .unwrap();
+        assert_eq!(refund_tx.input[0].previous_output, funding_outpoint);
+        assert_eq!(
+            refund_tx.output[0].value,
+            Amount::from_sat(100_000 - REFUND_TX_VSIZE * 2)
+        );
+        // The fee can't exceed the funding amount.
+        assert!(outgoing_swapcoin
+            .create_refund_tx(&destination_address, 1000)
+            .is_err());
+
+        let receivers_sig = incoming_swapcoin
+            .sign_senders_refund_tx(&refund_tx)
+            .unwrap();
+        let signed_refund_tx = outgoing_swapcoin
+            .sign_refund_tx(&refund_tx, &receivers_sig)
+            .unwrap();
+        assert_eq!(signed_refund_tx.input[0].witness.len(), 4);
+        assert_eq!(
+            signed_refund_tx.input[0].witness.last().unwrap(),
+            outgoing_swapcoin.get_multisig_redeemscript().as_bytes()
+        );
+
+        // A signature over another tx is rejected.
+        let mut other_tx = refund_tx.clone();
+        other_tx.output[0].value = Amount::from_sat(1_000);
+        assert!(outgoing_swapcoin
+            .sign_refund_tx(&other_tx, &receivers_sig)
+            .is_err());
+
+        // The receiver only signs spends of the funding output.
+        other_tx.input[0].previous_output.vout = 0;
+        assert!(incoming_swapcoin.sign_senders_refund_tx(&other_tx).is_err());
+    }
 }
@@ -0,0 +1,428 @@
+//! Transaction watcher fed by bitcoind ZMQ notifications.
+//!
+//! Watching contract transactions by polling `getrawtransaction` makes the reaction time to a malicious
+//! broadcast depend on the polling interval. When bitcoind is run with
+//! `-zmqpubrawtx=tcp://127.0.0.1:28332 -zmqpubhashblock=tcp://127.0.0.1:28332`, the [`TxWatcher`] subscribes
+//! to these notifications and wakes its waiters the moment a watched transaction hits the mempool
+//! (or a block), and on every new block.
+//!
+//! Only the `SUB` side of ZMTP 3.0 with the `NULL` security mechanism is implemented, which is all bitcoind
+//! publishes. ZMQ notifications are not reliable, so users should keep a slow RPC check as fallback.
+
+use std::{
+    collections::HashSet,
+    convert::TryInto,
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use bitcoin::{consensus::deserialize, Transaction, Txid};
+
+use crate::utill::HEART_BEAT_INTERVAL;
+
+/// How often the subscriber thread checks for shutdown while no notification arrives.
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Timeout for connecting to bitcoind and completing the ZMTP handshake.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Notifications larger than this are dropped along with the connection. Well above the maximum block weight.
+const MAX_FRAME_SIZE: u64 = 8 * 1024 * 1024;
+
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+#[derive(Default)]
+struct WatchState {
+    watched: HashSet<Txid>,
+    seen: HashSet<Txid>,
+    /// Number of notifications relevant to the waiters: watched txs seen and new blocks.
+    events: u64,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<WatchState>,
+    notify: Condvar,
+    stop: AtomicBool,
+}
+
+impl Shared {
+    fn handle_notification(&self, topic: &[u8], body: &[u8]) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        match topic {
+            b"rawtx" => {
+                let txid = match deserialize::<Transaction>(body) {
+                    Ok(tx) => tx.compute_txid(),
+                    Err(e) => {
+                        log::warn!("Invalid rawtx notification: {:?}", e);
+                        return;
+                    }
+                };
+                if !state.watched.contains(&txid) {
+                    return;
+                }
+                log::info!("Watched transaction {} seen via ZMQ", txid);
+                state.seen.insert(txid);
+            }
+            b"hashblock" => {}
+            _ => return,
+        }
+        state.events += 1;
+        self.notify.notify_all();
+    }
+}
+
+/// Watches a set of transactions over a bitcoind ZMQ subscription.
+///
+/// Dropping the watcher stops its subscriber thread.
+pub(crate) struct TxWatcher {
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TxWatcher {
+    /// Spawns the subscriber thread for the bitcoind ZMQ endpoint at `address` (`host:port`, with or without
+    /// the `tcp://` prefix). The thread keeps reconnecting until the watcher is dropped.
+    pub(crate) fn start(address: &str) -> io::Result<Self> {
+        let address = address.trim_start_matches("tcp://").to_string();
+        let shared = Arc::new(Shared::default());
+        let shared_clone = shared.clone();
+        let handle = thread::Builder::new()
+            .name("ZMQ Watcher Thread".to_string())
+            .spawn(move || run_subscriber(&address, &shared_clone))?;
+        Ok(Self {
+            shared,
+            handle: Some(handle),
+        })
+    }
+
+    /// Replaces the set of watched txids. Returns the txids that weren't watched before, which may have been
+    /// broadcasted already and need a one off RPC check.
+    pub(crate) fn set_watched(&self, txids: impl IntoIterator<Item = Txid>) -> Vec<Txid> {
+        let mut state = match self.shared.state.lock() {
+            Ok(state) => state,
+            Err(_) => return Vec::new(),
+        };
+        let watched = txids.into_iter().collect::<HashSet<_>>();
+        let fresh = watched.difference(&state.watched).cloned().collect();
+        state.seen.retain(|txid| watched.contains(txid));
+        state.watched = watched;
+        fresh
+    }
+
+    /// Whether a watched transaction was notified by bitcoind.
+    pub(crate) fn is_seen(&self, txid: &Txid) -> bool {
+        self.shared
+            .state
+            .lock()
+            .map(|state| state.seen.contains(txid))
+            .unwrap_or(false)
+    }
+
+    /// The current event count, to be passed to [`TxWatcher::wait`].
+    pub(crate) fn events(&self) -> u64 {
+        self.shared
+            .state
+            .lock()
+            .map(|state| state.events)
+            .unwrap_or(0)
+    }
+
+    /// Blocks until the event count moves past `since` (a watched tx or a block was notified), or until
+    /// `timeout`. Returns the current event count.
+    pub(crate) fn wait(&self, since: u64, timeout: Duration) -> u64 {
+        let state = match self.shared.state.lock() {
+            Ok(state) => state,
+            Err(_) => return since,
+        };
+        match self
+            .shared
+            .notify
+            .wait_timeout_while(state, timeout, |state| state.events == since)
+        {
+            Ok((state, _)) => state.events,
+            Err(_) => since,
+        }
+    }
+}
+
+impl Drop for TxWatcher {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Relaxed);
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::error!("ZMQ watcher thread panicked");
+            }
+        }
+    }
+}
+
+fn run_subscriber(address: &str, shared: &Shared) {
+    while !shared.stop.load(Relaxed) {
+        match ZmqSubscriber::connect(address, &["rawtx", "hashblock"]) {
+            Ok(mut subscriber) => {
+                log::info!("Subscribed to bitcoind ZMQ notifications at {}", address);
+                while !shared.stop.load(Relaxed) {
+                    match subscriber.next_message() {
+                        Ok(Some(parts)) if parts.len() >= 2 => {
+                            shared.handle_notification(&parts[0], &parts[1])
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            log::warn!("ZMQ connection to {} lost: {:?}", address, e);
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed connecting to bitcoind ZMQ at {}, reattempting: {:?}",
+                    address,
+                    e
+                );
+            }
+        }
+        // Wake up waiters, so they fall back to RPC while disconnected.
+        if let Ok(mut state) = shared.state.lock() {
+            state.events += 1;
+            shared.notify.notify_all();
+        }
+        let mut slept = Duration::ZERO;
+        while slept < HEART_BEAT_INTERVAL && !shared.stop.load(Relaxed) {
+            thread::sleep(READ_TIMEOUT);
+            slept += READ_TIMEOUT;
+        }
+    }
+}
+
+/// A minimal ZMTP 3.0 `SUB` socket over a single TCP connection.
+struct ZmqSubscriber {
+    stream: TcpStream,
+    buf: Vec<u8>,
+}
+
+impl ZmqSubscriber {
+    fn connect(address: &str, topics: &[&str]) -> io::Result<Self> {
+        let addr = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "Invalid ZMQ address"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+
+        // Greeting: signature, version 3.0, NULL mechanism, as-server = false, filler.
+        let mut greeting = [0u8; 64];
+        greeting[0] = 0xFF;
+        greeting[9] = 0x7F;
+        greeting[10] = 3;
+        greeting[12..16].copy_from_slice(b"NULL");
+        stream.write_all(&greeting)?;
+
+        let mut peer_greeting = [0u8; 64];
+        stream.read_exact(&mut peer_greeting)?;
+        if peer_greeting[0] != 0xFF || peer_greeting[9] != 0x7F || peer_greeting[10] < 3 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Peer is not a ZMTP 3 endpoint",
+            ));
+        }
+
+        // NULL mechanism handshake. The peer's READY is skipped along with any other command.
+        let mut ready = vec![5];
+        ready.extend_from_slice(b"READY");
+        ready.push(11);
+        ready.extend_from_slice(b"Socket-Type");
+        ready.extend_from_slice(&3u32.to_be_bytes());
+        ready.extend_from_slice(b"SUB");
+        stream.write_all(&encode_frame(FLAG_COMMAND, &ready))?;
+
+        // ZMTP 3.0 subscriptions are plain messages, prefixed with 0x01.
+        for topic in topics {
+            let mut subscription = vec![1];
+            subscription.extend_from_slice(topic.as_bytes());
+            stream.write_all(&encode_frame(0, &subscription))?;
+        }
+
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        Ok(Self {
+            stream,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Returns the next multipart message, or `None` if nothing complete arrived within the read timeout.
+    fn next_message(&mut self) -> io::Result<Option<Vec<Vec<u8>>>> {
+        loop {
+            if let Some((parts, consumed, is_command)) = decode_message(&self.buf)? {
+                self.buf.drain(..consumed);
+                if is_command {
+                    continue;
+                }
+                return Ok(Some(parts));
+            }
+            let mut chunk = [0u8; 8192];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(None)
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+fn encode_frame(flags: u8, body: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(body.len() + 9);
+    if body.len() > u8::MAX as usize {
+        frame.push(flags | FLAG_LONG);
+        frame.extend_from_slice(&(body.len() as u64).to_be_bytes());
+    } else {
+        frame.push(flags);
+        frame.push(body.len() as u8);
+    }
+    frame.extend_from_slice(body);
+    frame
+}
+
+/// Decodes a complete message (or command) at the start of `buf`.
+/// Returns its parts, the number of bytes consumed and whether it's a command.
+#[allow(clippy::type_complexity)]
+fn decode_message(buf: &[u8]) -> io::Result<Option<(Vec<Vec<u8>>, usize, bool)>> {
+    let mut parts = Vec::new();
+    let mut pos = 0;
+    loop {
+        let flags = match buf.get(pos) {
+            Some(flags) => *flags,
+            None => return Ok(None),
+        };
+        let (size, header) = if flags & FLAG_LONG != 0 {
+            match buf.get(pos + 1..pos + 9) {
+                Some(size) => (
+                    u64::from_be_bytes(size.try_into().expect("8 bytes slice")),
+                    9,
+                ),
+                None => return Ok(None),
+            }
+        } else {
+            match buf.get(pos + 1) {
+                Some(size) => (*size as u64, 2),
+                None => return Ok(None),
+            }
+        };
+        if size > MAX_FRAME_SIZE {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "ZMQ frame too large",
+            ));
+        }
+        let start = pos + header;
+        let end = start + size as usize;
+        let body = match buf.get(start..end) {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        parts.push(body.to_vec());
+        pos = end;
+
+        if flags & FLAG_COMMAND != 0 {
+            return Ok(Some((parts, pos, true)));
+        }
+        if flags & FLAG_MORE == 0 {
+            return Ok(Some((parts, pos, false)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{absolute::LockTime, transaction::Version};
+    use std::{net::TcpListener, time::Instant};
+
+    #[test]
+    fn test_zmq_watcher() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("tcp://{}", listener.local_addr().unwrap());
+
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let txid = tx.compute_txid();
+        let raw_tx = bitcoin::consensus::serialize(&tx);
+
+        // A fake bitcoind publisher.
+        let publisher = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0u8; 64];
+            stream.read_exact(&mut greeting).unwrap();
+            assert_eq!(&greeting[12..16], b"NULL");
+            let mut own_greeting = [0u8; 64];
+            own_greeting[0] = 0xFF;
+            own_greeting[9] = 0x7F;
+            own_greeting[10] = 3;
+            stream.write_all(&own_greeting).unwrap();
+
+            // Expect READY and the two subscriptions.
+            let mut buf = Vec::new();
+            let mut received = Vec::new();
+            while received.len() < 3 {
+                let mut chunk = [0u8; 256];
+                let n = stream.read(&mut chunk).unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                while let Some((parts, consumed, _)) = decode_message(&buf).unwrap() {
+                    buf.drain(..consumed);
+                    received.push(parts[0].clone());
+                }
+            }
+            assert_eq!(received[1], b"\x01rawtx");
+            assert_eq!(received[2], b"\x01hashblock");
+            stream
+                .write_all(&encode_frame(FLAG_COMMAND, b"\x05READY"))
+                .unwrap();
+
+            // Wait for the txid to be watched, then notify it.
+            thread::sleep(Duration::from_millis(200));
+            stream
+                .write_all(&encode_frame(FLAG_MORE, b"rawtx"))
+                .unwrap();
+            stream.write_all(&encode_frame(FLAG_MORE, &raw_tx)).unwrap();
+            stream
+                .write_all(&encode_frame(0, &0u32.to_le_bytes()))
+                .unwrap();
+            // Keep the connection open until the watcher is done.
+            let _ = stream.read(&mut [0u8; 1]);
+        });
+
+        let watcher = TxWatcher::start(&address).unwrap();
+        assert_eq!(watcher.set_watched(vec![txid]), vec![txid]);
+        assert!(watcher.set_watched(vec![txid]).is_empty());
+
+        let start = Instant::now();
+        let mut events = watcher.events();
+        while !watcher.is_seen(&txid) && start.elapsed() < Duration::from_secs(5) {
+            events = watcher.wait(events, Duration::from_secs(1));
+        }
+        assert!(watcher.is_seen(&txid));
+
+        drop(watcher);
+        publisher.join().unwrap();
+    }
+}
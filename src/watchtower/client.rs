@@ -0,0 +1,132 @@
+//! Registration of the wallet's unfinished swaps with a watchtower.
+
+use std::net::TcpStream;
+
+use bitcoin::secp256k1::Secp256k1;
+#[cfg(feature = "tor")]
+use socks::Socks5Stream;
+
+use crate::{
+    protocol::messages::{ContractSpend, ContractWatch, WatchtowerRequest, WatchtowerResponse},
+    utill::{read_message, send_message, ConnectionType, NET_TIMEOUT},
+    wallet::{SwapCoin, Wallet, WalletSwapCoin},
+};
+
+use super::{registration_message, WatchtowerError};
+
+impl Wallet {
+    /// Builds the pre-signed recovery material of all the unfinished swaps, authenticated with the watchtower's challenge.
+    ///
+    /// Outgoing contracts are claimed via timelock. Incoming contracts are claimed via hashlock, only if the preimage is known.
+    /// All the spends pay to a fresh internal address of the wallet.
+    fn watchtower_material(
+        &self,
+        challenge: &[u8; 32],
+    ) -> Result<Vec<ContractWatch>, WatchtowerError> {
+        let secp = Secp256k1::new();
+        let (incomings, outgoings) = self.find_unfinished_swapcoins();
+        let destination = &self.get_next_internal_addresses(1)?[0];
+
+        let mut watches = Vec::new();
+
+        for outgoing in outgoings {
+            let contract_tx = match outgoing.get_fully_signed_contract_tx() {
+                Ok(tx) => tx,
+                Err(e) => {
+                    log::warn!("Outgoing contract signature not known, skipping : {:?}", e);
+                    continue;
+                }
+            };
+            let auth_sig = secp.sign_ecdsa(
+                &registration_message(challenge, &contract_tx.compute_txid()),
+                &outgoing.timelock_privkey,
+            );
+            watches.push(ContractWatch {
+                spend: ContractSpend::Timelock(outgoing.create_timelock_spend(destination)?),
+                contract_tx,
+                contract_redeemscript: outgoing.get_contract_redeemscript(),
+                auth_sig,
+            });
+        }
+
+        for incoming in incomings {
+            if !incoming.is_hash_preimage_known() {
+                continue;
+            }
+            let contract_tx = match incoming.get_fully_signed_contract_tx() {
+                Ok(tx) => tx,
+                Err(e) => {
+                    log::warn!("Incoming contract signature not known, skipping : {:?}", e);
+                    continue;
+                }
+            };
+            let auth_sig = secp.sign_ecdsa(
+                &registration_message(challenge, &contract_tx.compute_txid()),
+                &incoming.hashlock_privkey,
+            );
+            watches.push(ContractWatch {
+                spend: ContractSpend::Hashlock(incoming.create_hashlock_spend(destination)?),
+                contract_tx,
+                contract_redeemscript: incoming.get_contract_redeemscript(),
+                auth_sig,
+            });
+        }
+
+        Ok(watches)
+    }
+
+    /// Registers all the unfinished swaps of the wallet with the watchtower at the given address.
+    /// Onion addresses are reached through the Tor socks port.
+    ///
+    /// Returns the number of registered contracts.
+    #[cfg_attr(not(feature = "tor"), allow(unused_variables))]
+    pub fn register_with_watchtower(
+        &self,
+        address: &str,
+        connection_type: ConnectionType,
+        socks_port: u16,
+    ) -> Result<usize, WatchtowerError> {
+        let (incomings, outgoings) = self.find_unfinished_swapcoins();
+        if incomings.is_empty() && outgoings.is_empty() {
+            log::info!("No unfinished swaps to register with the watchtower");
+            return Ok(0);
+        }
+
+        let mut stream = match connection_type {
+            ConnectionType::CLEARNET => TcpStream::connect(address)?,
+            #[cfg(feature = "tor")]
+            ConnectionType::TOR => {
+                Socks5Stream::connect(format!("127.0.0.1:{}", socks_port).as_str(), address)?
+                    .into_inner()
+            }
+        };
+        stream.set_read_timeout(Some(NET_TIMEOUT))?;
+        stream.set_write_timeout(Some(NET_TIMEOUT))?;
+
+        send_message(&mut stream, &WatchtowerRequest::GetChallenge)?;
+        let challenge = match serde_cbor::from_slice(&read_message(&mut stream)?)? {
+            WatchtowerResponse::Challenge(challenge) => challenge,
+            WatchtowerResponse::Rejected(reason) => return Err(WatchtowerError::Rejected(reason)),
+            resp => {
+                return Err(WatchtowerError::Rejected(format!(
+                    "Unexpected response : {:?}",
+                    resp
+                )))
+            }
+        };
+
+        let watches = self.watchtower_material(&challenge)?;
+        send_message(&mut stream, &WatchtowerRequest::Register { watches })?;
+        match serde_cbor::from_slice(&read_message(&mut stream)?)? {
+            WatchtowerResponse::Registered(count) => {
+                log::info!("Registered {} contracts with watchtower {}", count, address);
+                Ok(count)
+            }
+            WatchtowerResponse::Rejected(reason) => Err(WatchtowerError::Rejected(reason)),
+            resp => Err(WatchtowerError::Rejected(format!(
+                "Unexpected response : {:?}",
+                resp
+            ))),
+        }
+    }
+}
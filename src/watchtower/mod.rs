@@ -0,0 +1,27 @@
+//! A standalone watchtower.
+//!
+//! Swap participants register pre-signed recovery material with the watchtower: fully signed contract transactions,
+//! and fully signed timelock or hashlock spends of the contract outputs. The watchtower watches the chain, and if any
+//! registered contract is ever broadcasted, it broadcasts all the contracts of the registration and claims them on the user's behalf.
+//!
+//! Registration is authenticated. The watchtower hands out a random challenge, which the client signs with the
+//! timelock or hashlock key of each contract. So only a party of the swap can register its contracts.
+
+mod client;
+mod server;
+
+pub use server::{start_watchtower_server, Watchtower, WatchtowerError};
+
+use bitcoin::{
+    hashes::{sha256, Hash, HashEngine},
+    secp256k1::Message,
+    Txid,
+};
+
+/// The message signed by the client to authenticate a contract registration.
+pub(crate) fn registration_message(challenge: &[u8; 32], contract_txid: &Txid) -> Message {
+    let mut engine = sha256::Hash::engine();
+    engine.input(challenge);
+    engine.input(contract_txid.as_byte_array());
+    Message::from_digest(sha256::Hash::from_engine(engine).to_byte_array())
+}
@@ -0,0 +1,924 @@
+//! The watchtower server.
+//!
+//! Accepts authenticated registrations of pre-signed recovery material, persists them in the data directory,
+//! and watches the chain to broadcast contracts and their spends on the user's behalf.
+
+use bitcoin::{
+    ecdsa::Signature,
+    hashes::Hash,
+    secp256k1::{
+        rand::{rngs::OsRng, RngCore},
+        Message, Secp256k1,
+    },
+    sighash::{EcdsaSighashType, SighashCache},
+    OutPoint, PublicKey, Transaction, Txid,
+};
+use bitcoind::bitcoincore_rpc::{self, Client, RpcApi};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "tor")]
+use crate::utill::{get_tor_hostname, monitor_log_for_completion};
+use crate::{
+    error::NetError,
+    net::{run_server, NetConfig, Response, Service},
+    protocol::{
+        contract::{
+            read_contract_locktime, read_hashlock_pubkey_from_contract,
+            read_hashvalue_from_contract, read_timelock_pubkey_from_contract,
+        },
+        error::ProtocolError,
+        messages::{ContractSpend, ContractWatch, WatchtowerRequest, WatchtowerResponse},
+        Hash160,
+    },
+    utill::{
+        get_watchtower_dir, parse_field, parse_toml, redeemscript_to_scriptpubkey, ConnectionType,
+    },
+    wallet::{RPCConfig, WalletError},
+};
+
+use super::registration_message;
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fs::{self, File},
+    io::Write,
+    net::{IpAddr, Ipv4Addr, TcpListener},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    thread::{self, sleep},
+    time::Duration,
+};
+
+/// Interval between two chain checks of the registered contracts.
+const WATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Maximum number of contracts accepted in a single registration.
+const MAX_WATCHES_PER_REGISTRATION: usize = 50;
+
+/// Represents errors that may occur during watchtower operations.
+#[derive(Debug)]
+pub enum WatchtowerError {
+    /// Error originating from standard I/O operations.
+    IO(std::io::Error),
+    /// Error related to network operations.
+    Net(NetError),
+    /// Error indicating a mutex was poisoned.
+    MutexPossion,
+    /// Error related to wallet operations, and bitcoin core RPC.
+    Wallet(WalletError),
+    /// Error indicating the registered recovery material is invalid.
+    InvalidRegistration(String),
+    /// Error indicating the watchtower rejected a registration.
+    Rejected(String),
+}
+
+impl From<WalletError> for WatchtowerError {
+    fn from(value: WalletError) -> Self {
+        Self::Wallet(value)
+    }
+}
+
+impl From<ProtocolError> for WatchtowerError {
+    fn from(value: ProtocolError) -> Self {
+        Self::Wallet(value.into())
+    }
+}
+
+impl From<serde_cbor::Error> for WatchtowerError {
+    fn from(value: serde_cbor::Error) -> Self {
+        Self::Wallet(WalletError::Cbor(value))
+    }
+}
+
+impl From<bitcoind::bitcoincore_rpc::Error> for WatchtowerError {
+    fn from(value: bitcoind::bitcoincore_rpc::Error) -> Self {
+        Self::Wallet(WalletError::Rpc(value))
+    }
+}
+
+impl From<std::io::Error> for WatchtowerError {
+    fn from(value: std::io::Error) -> Self {
+        Self::IO(value)
+    }
+}
+
+impl From<NetError> for WatchtowerError {
+    fn from(value: NetError) -> Self {
+        Self::Net(value)
+    }
+}
+
+impl<'a, T> From<PoisonError<RwLockReadGuard<'a, T>>> for WatchtowerError {
+    fn from(_: PoisonError<RwLockReadGuard<'a, T>>) -> Self {
+        Self::MutexPossion
+    }
+}
+
+impl<'a, T> From<PoisonError<RwLockWriteGuard<'a, T>>> for WatchtowerError {
+    fn from(_: PoisonError<RwLockWriteGuard<'a, T>>) -> Self {
+        Self::MutexPossion
+    }
+}
+
+/// A set of contracts registered together.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Registration {
+    pub(crate) watches: Vec<ContractWatch>,
+    /// Set once any of the contracts is seen on chain. All the contracts are then broadcasted and claimed.
+    pub(crate) triggered: bool,
+    /// Contract txids which are claimed, or found spent by someone else.
+    pub(crate) settled: Vec<Txid>,
+}
+
+/// Watchtower Configuration and state.
+#[derive(Debug)]
+pub struct Watchtower {
+    /// Network listening port
+    pub network_port: u16,
+    /// Address the listener is bound to. With a Tor connection, the hidden service forwards to localhost.
+    pub bind_address: IpAddr,
+    /// Socks port of the Tor instance serving the hidden service.
+    pub socks_port: u16,
+    /// Connection type, Clearnet or Tor.
+    pub connection_type: ConnectionType,
+    /// Maximum number of live contracts authenticated by the same key.
+    pub max_watches_per_key: usize,
+    /// Maximum number of live contracts, over all the registrations.
+    pub max_watches: usize,
+    /// Watchtower data directory
+    pub data_dir: PathBuf,
+    /// Shutdown flag to stop the watchtower
+    pub shutdown: AtomicBool,
+    /// All the live registrations.
+    pub(crate) registrations: RwLock<Vec<Registration>>,
+}
+
+impl Default for Watchtower {
+    fn default() -> Self {
+        Self {
+            network_port: 8090,
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            socks_port: 19070,
+            connection_type: {
+                #[cfg(feature = "tor")]
+                {
+                    ConnectionType::TOR
+                }
+                #[cfg(not(feature = "tor"))]
+                {
+                    ConnectionType::CLEARNET
+                }
+            },
+            max_watches_per_key: 10,
+            max_watches: 10_000,
+            data_dir: get_watchtower_dir(),
+            shutdown: AtomicBool::new(false),
+            registrations: RwLock::new(Vec::new()),
+        }
+    }
+}
+
+impl Watchtower {
+    /// Constructs a [Watchtower] from a specified data directory. Or create default configs and load them.
+    ///
+    /// Existing registrations are loaded from the `registrations.cbor` file in the data directory.
+    ///
+    /// Default data-dir for linux: `~/.coinswap/watchtower`
+    /// Default config locations: `~/.coinswap/watchtower/config.toml`.
+    pub fn new(data_dir: Option<PathBuf>) -> Result<Self, WatchtowerError> {
+        let data_dir = data_dir.unwrap_or(get_watchtower_dir());
+        let config_path = data_dir.join("config.toml");
+
+        if !config_path.exists() || fs::metadata(&config_path)?.len() == 0 {
+            log::warn!(
+                "Watchtower config file not found, creating default config file at path: {}",
+                config_path.display()
+            );
+            write_default_watchtower_config(&config_path)?;
+        }
+
+        let config_map = parse_toml(&config_path)?;
+
+        log::info!(
+            "Successfully loaded config file from : {}",
+            config_path.display()
+        );
+
+        let registrations_path = data_dir.join("registrations.cbor");
+        let registrations = if registrations_path.exists() {
+            serde_cbor::from_slice(&fs::read(&registrations_path)?)?
+        } else {
+            Vec::new()
+        };
+
+        let default_tower = Self::default();
+
+        Ok(Watchtower {
+            network_port: parse_field(config_map.get("port"), default_tower.network_port),
+            bind_address: parse_field(config_map.get("bind_address"), default_tower.bind_address),
+            socks_port: parse_field(config_map.get("socks_port"), default_tower.socks_port),
+            connection_type: parse_field(
+                config_map.get("connection_type"),
+                default_tower.connection_type,
+            ),
+            max_watches_per_key: parse_field(
+                config_map.get("max_watches_per_key"),
+                default_tower.max_watches_per_key,
+            ),
+            max_watches: parse_field(config_map.get("max_watches"), default_tower.max_watches),
+            data_dir,
+            shutdown: AtomicBool::new(false),
+            registrations: RwLock::new(registrations),
+        })
+    }
+
+    /// Writes all the live registrations to disk.
+    fn save_registrations(&self) -> Result<(), WatchtowerError> {
+        let registrations = self.registrations.read()?;
+        fs::create_dir_all(&self.data_dir)?;
+        fs::write(
+            self.data_dir.join("registrations.cbor"),
+            serde_cbor::to_vec(&*registrations)?,
+        )?;
+        Ok(())
+    }
+}
+
+fn write_default_watchtower_config(config_path: &Path) -> Result<(), WatchtowerError> {
+    let config_string = String::from(
+        "\
+            port = 8090\n\
+            bind_address = 127.0.0.1\n\
+            socks_port = 19070\n\
+            connection_type = tor\n\
+            max_watches_per_key = 10\n\
+            max_watches = 10000\n\
+            ",
+    );
+    std::fs::create_dir_all(config_path.parent().expect("Path should NOT be root!"))?;
+    let mut file = File::create(config_path)?;
+    file.write_all(config_string.as_bytes())?;
+    file.flush()?;
+    Ok(())
+}
+
+/// The key authenticating a registered contract: the timelock key for a timelock spend, the hashlock key otherwise.
+fn watch_auth_pubkey(watch: &ContractWatch) -> Result<PublicKey, WatchtowerError> {
+    Ok(match &watch.spend {
+        ContractSpend::Timelock(_) => {
+            read_timelock_pubkey_from_contract(&watch.contract_redeemscript)?
+        }
+        ContractSpend::Hashlock(_) => {
+            read_hashlock_pubkey_from_contract(&watch.contract_redeemscript)?
+        }
+    })
+}
+
+/// Checks the registration against the per key and total limits on the live contracts.
+fn check_watch_limits(
+    tower: &Watchtower,
+    registrations: &[Registration],
+    watches: &[ContractWatch],
+) -> Result<(), WatchtowerError> {
+    let live = registrations
+        .iter()
+        .flat_map(|registration| registration.watches.iter())
+        .collect::<Vec<_>>();
+    if live.len() + watches.len() > tower.max_watches {
+        return Err(WatchtowerError::Rejected(
+            "Watchtower is at capacity".to_string(),
+        ));
+    }
+
+    let mut per_key = HashMap::<PublicKey, usize>::new();
+    for watch in live.into_iter().chain(watches.iter()) {
+        *per_key.entry(watch_auth_pubkey(watch)?).or_default() += 1;
+    }
+    for watch in watches {
+        let key = watch_auth_pubkey(watch)?;
+        if per_key[&key] > tower.max_watches_per_key {
+            return Err(WatchtowerError::Rejected(format!(
+                "Too many contracts registered with key {}",
+                key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the consistency of a registered contract, and its authentication signature over the challenge.
+pub(crate) fn verify_contract_watch(
+    watch: &ContractWatch,
+    challenge: &[u8; 32],
+) -> Result<(), WatchtowerError> {
+    let contract_txid = watch.contract_tx.compute_txid();
+
+    let contract_spk = redeemscript_to_scriptpubkey(&watch.contract_redeemscript)?;
+    if watch.contract_tx.output.len() != 1
+        || watch.contract_tx.output[0].script_pubkey != contract_spk
+    {
+        return Err(WatchtowerError::InvalidRegistration(format!(
+            "Contract {} doesn't pay to the contract redeemscript",
+            contract_txid
+        )));
+    }
+
+    let spend_tx = match &watch.spend {
+        ContractSpend::Timelock(tx) | ContractSpend::Hashlock(tx) => tx,
+    };
+    let auth_pubkey = watch_auth_pubkey(watch)?;
+
+    if spend_tx.input.len() != 1
+        || spend_tx.input[0].previous_output != OutPoint::new(contract_txid, 0)
+    {
+        return Err(WatchtowerError::InvalidRegistration(format!(
+            "Spend doesn't spend the contract {}",
+            contract_txid
+        )));
+    }
+
+    let secp = Secp256k1::new();
+    secp.verify_ecdsa(
+        &registration_message(challenge, &contract_txid),
+        &watch.auth_sig,
+        &auth_pubkey.inner,
+    )
+    .map_err(|_| {
+        WatchtowerError::InvalidRegistration(format!(
+            "Invalid authentication signature for contract {}",
+            contract_txid
+        ))
+    })?;
+
+    verify_contract_spend(watch, spend_tx, &auth_pubkey)
+}
+
+/// Checks that the spend is fully signed for its branch of the contract, by the key authenticating the registration.
+/// An unsigned or wrongly signed spend would only be found out when it's needed.
+fn verify_contract_spend(
+    watch: &ContractWatch,
+    spend_tx: &Transaction,
+    auth_pubkey: &PublicKey,
+) -> Result<(), WatchtowerError> {
+    let contract_txid = watch.contract_tx.compute_txid();
+    let invalid = || {
+        WatchtowerError::InvalidRegistration(format!(
+            "Spend of contract {} is not validly signed",
+            contract_txid
+        ))
+    };
+
+    // <sig> <preimage or empty> <redeemscript>
+    let witness = &spend_tx.input[0].witness;
+    if witness.len() != 3 || witness.nth(2) != Some(watch.contract_redeemscript.as_bytes()) {
+        return Err(invalid());
+    }
+    let branch_arg = witness.nth(1).expect("witness length checked");
+    let branch_ok = match &watch.spend {
+        ContractSpend::Hashlock(_) => {
+            Hash160::hash(branch_arg) == read_hashvalue_from_contract(&watch.contract_redeemscript)?
+        }
+        ContractSpend::Timelock(_) => branch_arg.is_empty(),
+    };
+    if !branch_ok {
+        return Err(invalid());
+    }
+
+    let sig = Signature::from_slice(witness.nth(0).expect("witness length checked"))
+        .map_err(|_| invalid())?;
+    if sig.sighash_type != EcdsaSighashType::All {
+        return Err(invalid());
+    }
+    let sighash = SighashCache::new(spend_tx)
+        .p2wsh_signature_hash(
+            0,
+            &watch.contract_redeemscript,
+            watch.contract_tx.output[0].value,
+            EcdsaSighashType::All,
+        )
+        .map_err(ProtocolError::Sighash)?;
+    Secp256k1::new()
+        .verify_ecdsa(
+            &Message::from_digest(sighash.to_byte_array()),
+            &sig.signature,
+            &auth_pubkey.inner,
+        )
+        .map_err(|_| invalid())
+}
+
+/// Serves the registrations. A client first asks for a challenge, then registers its contracts signed over it.
+struct WatchtowerService {
+    tower: Arc<Watchtower>,
+}
+
+impl Service for WatchtowerService {
+    /// The challenge handed out to the client, if any.
+    type Connection = Option<[u8; 32]>;
+
+    fn handle_message(&self, challenge: &mut Option<[u8; 32]>, message: Vec<u8>) -> Response {
+        match handle_request(&self.tower, challenge, &message) {
+            Ok(response) => response,
+            Err(e) => {
+                log::error!("Error handling client request: {:?}", e);
+                Response::close()
+            }
+        }
+    }
+}
+
+fn handle_request(
+    tower: &Watchtower,
+    challenge: &mut Option<[u8; 32]>,
+    message: &[u8],
+) -> Result<Response, WatchtowerError> {
+    let request: WatchtowerRequest = serde_cbor::from_slice(message)?;
+    let (watches, challenge) = match (request, *challenge) {
+        (WatchtowerRequest::GetChallenge, None) => {
+            let mut fresh = [0u8; 32];
+            OsRng.fill_bytes(&mut fresh);
+            *challenge = Some(fresh);
+            return Ok(Response::reply(&WatchtowerResponse::Challenge(fresh))?);
+        }
+        (WatchtowerRequest::Register { watches }, Some(challenge)) => (watches, challenge),
+        (_, None) => {
+            return Ok(Response::reply(&WatchtowerResponse::Rejected(
+                "Expected GetChallenge".to_string(),
+            ))?
+            .and_close())
+        }
+        (_, Some(_)) => {
+            return Ok(Response::reply(&WatchtowerResponse::Rejected(
+                "Expected Register".to_string(),
+            ))?
+            .and_close())
+        }
+    };
+    if watches.is_empty() || watches.len() > MAX_WATCHES_PER_REGISTRATION {
+        return Ok(Response::reply(&WatchtowerResponse::Rejected(format!(
+            "Number of contracts must be between 1 and {}",
+            MAX_WATCHES_PER_REGISTRATION
+        )))?
+        .and_close());
+    }
+
+    if let Err(e) = watches
+        .iter()
+        .try_for_each(|watch| verify_contract_watch(watch, &challenge))
+    {
+        log::warn!("Rejected registration : {:?}", e);
+        return Ok(Response::reply(&WatchtowerResponse::Rejected(format!("{:?}", e)))?.and_close());
+    }
+
+    let count = watches.len();
+    {
+        let mut registrations = tower.registrations.write()?;
+        if let Err(e) = check_watch_limits(tower, &registrations, &watches) {
+            log::warn!("Rejected registration : {:?}", e);
+            return Ok(
+                Response::reply(&WatchtowerResponse::Rejected(format!("{:?}", e)))?.and_close(),
+            );
+        }
+        registrations.push(Registration {
+            watches,
+            triggered: false,
+            settled: Vec::new(),
+        });
+    }
+    tower.save_registrations()?;
+    log::info!("Registered {} contracts", count);
+
+    Ok(Response::reply(&WatchtowerResponse::Registered(count))?.and_close())
+}
+
+/// Checks a single registration against the chain, and broadcasts whatever is needed.
+/// Returns true if the registration is done and can be removed.
+fn process_registration(
+    rpc: &Client,
+    registration: &mut Registration,
+) -> Result<bool, WatchtowerError> {
+    if !registration.triggered {
+        // Trigger if any of the contracts is ever observed in the mempool/block.
+        if let Some(watch) = registration.watches.iter().find(|watch| {
+            rpc.get_raw_transaction_info(&watch.contract_tx.compute_txid(), None)
+                .is_ok()
+        }) {
+            log::warn!(
+                "Contract txs broadcasted!! txid: {} Broadcasting all registered contracts.",
+                watch.contract_tx.compute_txid()
+            );
+            registration.triggered = true;
+        } else {
+            // If all the funding outputs are spent without any contract, the swaps have settled.
+            let all_funding_spent = registration.watches.iter().all(|watch| {
+                let funding_outpoint = watch.contract_tx.input[0].previous_output;
+                rpc.get_raw_transaction_info(&funding_outpoint.txid, None)
+                    .is_ok()
+                    && matches!(
+                        rpc.get_tx_out(&funding_outpoint.txid, funding_outpoint.vout, Some(true)),
+                        Ok(None)
+                    )
+            });
+            if all_funding_spent {
+                log::info!("All funding outputs spent cooperatively. Removing registration.");
+            }
+            return Ok(all_funding_spent);
+        }
+    }
+
+    for watch in registration.watches.iter() {
+        let contract_txid = watch.contract_tx.compute_txid();
+        if registration.settled.contains(&contract_txid) {
+            continue;
+        }
+
+        let confirmations = match rpc.get_raw_transaction_info(&contract_txid, None) {
+            Ok(info) => info.confirmations.unwrap_or(0),
+            Err(_) => {
+                match rpc.send_raw_transaction(&watch.contract_tx) {
+                    Ok(_) => log::info!("Broadcasted contract : {}", contract_txid),
+                    Err(e) => log::warn!("Can't send contract: {} | {:?}", contract_txid, e),
+                }
+                continue;
+            }
+        };
+
+        if confirmations >= 1 && rpc.get_tx_out(&contract_txid, 0, Some(true))?.is_none() {
+            log::info!("Contract output already spent : {}", contract_txid);
+            registration.settled.push(contract_txid);
+            continue;
+        }
+
+        let (spend_tx, required_confirmations) = match &watch.spend {
+            ContractSpend::Hashlock(tx) => (tx, 1),
+            ContractSpend::Timelock(tx) => (
+                tx,
+                read_contract_locktime(&watch.contract_redeemscript)? as u32 + 1,
+            ),
+        };
+
+        if confirmations >= required_confirmations {
+            match rpc.send_raw_transaction(spend_tx) {
+                Ok(txid) => {
+                    log::info!(
+                        "Broadcasted contract spend : {} for contract : {}",
+                        txid,
+                        contract_txid
+                    );
+                    registration.settled.push(contract_txid);
+                }
+                Err(e) => log::warn!(
+                    "Can't send contract spend for contract: {} | {:?}",
+                    contract_txid,
+                    e
+                ),
+            }
+        } else {
+            log::info!(
+                "Contract : {} reached confirmation : {}, Required : {}",
+                contract_txid,
+                confirmations,
+                required_confirmations
+            );
+        }
+    }
+
+    Ok(registration.settled.len() == registration.watches.len())
+}
+
+/// Constantly checks the chain for all registered contracts.
+///
+/// The chain is checked on a copy of the registrations, so the lock isn't held over the RPC calls and new
+/// registrations are served meanwhile. The results are applied to the live registrations afterwards.
+fn watch_contracts(tower: Arc<Watchtower>, rpc: Client) -> Result<(), WatchtowerError> {
+    while !tower.shutdown.load(Relaxed) {
+        let snapshot = tower.registrations.read()?.clone();
+        // (registration as checked, updated registration or None once done)
+        let mut updates = Vec::new();
+        for registration in snapshot {
+            let mut updated = registration.clone();
+            match process_registration(&rpc, &mut updated) {
+                Ok(true) => updates.push((registration, None)),
+                Ok(false) if updated != registration => updates.push((registration, Some(updated))),
+                Ok(false) => {}
+                Err(e) => log::error!("Error processing registration: {:?}", e),
+            }
+        }
+        if !updates.is_empty() {
+            {
+                let mut registrations = tower.registrations.write()?;
+                for (checked, updated) in updates {
+                    let Some(i) = registrations.iter().position(|r| *r == checked) else {
+                        continue;
+                    };
+                    match updated {
+                        Some(updated) => registrations[i] = updated,
+                        None => {
+                            registrations.remove(i);
+                        }
+                    }
+                }
+            }
+            tower.save_registrations()?;
+        }
+        sleep(WATCH_INTERVAL);
+    }
+    Ok(())
+}
+
+/// Initializes and starts the Watchtower with the provided configuration.
+///
+/// - Sets up the Tor hidden service, if the connection type is Tor.
+/// - Spawns a thread watching the chain for all the registered contracts.
+/// - Runs the event loop serving the registrations.
+/// - Handles shutdown signals gracefully, saving all the registrations to disk.
+pub fn start_watchtower_server(
+    tower: Arc<Watchtower>,
+    rpc_config: Option<RPCConfig>,
+) -> Result<(), WatchtowerError> {
+    #[cfg(feature = "tor")]
+    let mut tor_handle = None;
+
+    let rpc_config = rpc_config.unwrap_or_default();
+
+    let rpc_client = bitcoincore_rpc::Client::try_from(&rpc_config)?;
+
+    // Stop early if bitcoin core connection is wrong
+    if let Err(e) = rpc_client.get_blockchain_info() {
+        log::error!("Cannot connect to bitcoin node {:?}", e);
+        return Err(e.into());
+    } else {
+        log::info!("Bitcoin core connection successful");
+    }
+
+    match tower.connection_type {
+        ConnectionType::CLEARNET => {
+            log::info!(
+                "Watchtower is listening at {}:{}",
+                tower.bind_address,
+                tower.network_port
+            );
+        }
+        #[cfg(feature = "tor")]
+        ConnectionType::TOR => {
+            let tor_dir = tower.data_dir.join("tor");
+            let log_file = tor_dir.join("log");
+            if log_file.exists() {
+                match fs::remove_file(&log_file) {
+                    Ok(_) => log::info!("Previous tor log file deleted successfully"),
+                    Err(_) => log::error!("Error deleting tor log file"),
+                }
+            }
+
+            tor_handle = Some(crate::tor::spawn_tor(
+                tower.socks_port,
+                &[tower.network_port],
+                tor_dir.to_str().unwrap().to_string(),
+            )?);
+
+            log::info!("waiting for tor setup completion.");
+
+            if let Err(e) = monitor_log_for_completion(&log_file, "Bootstrapped 100% (done): Done")
+            {
+                log::error!("Error monitoring tor log file: {}", e);
+            }
+
+            log::info!("tor is ready!!");
+
+            let hostname = get_tor_hostname(&tor_dir)?;
+            log::info!(
+                "Watchtower is listening at {}:{}",
+                hostname,
+                tower.network_port
+            );
+        }
+    }
+
+    let tower_clone = tower.clone();
+    let watcher_thread = thread::Builder::new()
+        .name("Contract watcher thread".to_string())
+        .spawn(move || {
+            log::info!("Spawning Contract Watcher Thread");
+            watch_contracts(tower_clone, rpc_client)
+        })?;
+
+    let listener = TcpListener::bind((tower.bind_address, tower.network_port))?;
+
+    let service = Arc::new(WatchtowerService {
+        tower: tower.clone(),
+    });
+    let result = run_server(
+        "Watchtower",
+        listener,
+        service,
+        NetConfig::default(),
+        Vec::new(),
+        &tower.shutdown,
+    );
+    if let Err(e) = &result {
+        log::error!("Watchtower server failed: {:?}", e);
+        tower.shutdown.store(true, Relaxed);
+    }
+
+    log::info!("Shutdown signal received. Stopping watchtower.");
+
+    match watcher_thread.join() {
+        Ok(Err(e)) => log::error!("Contract Watcher Thread failed: {:?}", e),
+        Err(e) => log::error!("Error closing Contract Watcher Thread: {:?}", e),
+        Ok(Ok(())) => {}
+    }
+
+    tower.save_registrations()?;
+
+    #[cfg(feature = "tor")]
+    {
+        if let Some(mut handle) = tor_handle {
+            crate::tor::kill_tor_handles(&mut handle);
+            log::info!("Watchtower and Tor instance terminated successfully");
+        }
+    }
+
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{contract::create_contract_redeemscript, Hash160};
+    use bitcoin::{
+        absolute::LockTime, hashes::Hash, transaction::Version, Amount, PublicKey, ScriptBuf,
+        Sequence, Transaction, TxIn, TxOut, Witness,
+    };
+    use std::str::FromStr;
+
+    fn contract_watch(challenge: &[u8; 32]) -> ContractWatch {
+        let secp = Secp256k1::new();
+        let hashlock_privkey = bitcoin::secp256k1::SecretKey::from_str(
+            "0000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+        let timelock_privkey = bitcoin::secp256k1::SecretKey::from_str(
+            "0000000000000000000000000000000000000000000000000000000000000002",
+        )
+        .unwrap();
+        let contract_redeemscript = create_contract_redeemscript(
+            &PublicKey::new(hashlock_privkey.public_key(&secp)),
+            &PublicKey::new(timelock_privkey.public_key(&secp)),
+            &Hash160::hash(&[0; 32]),
+            &20,
+        );
+        let contract_tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: Amount::from_sat(100_000),
+                script_pubkey: redeemscript_to_scriptpubkey(&contract_redeemscript).unwrap(),
+            }],
+        };
+        let mut timelock_spend = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(contract_tx.compute_txid(), 0),
+                sequence: Sequence(20),
+                witness: Witness::new(),
+                script_sig: ScriptBuf::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(99_000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+        let sighash = SighashCache::new(&timelock_spend)
+            .p2wsh_signature_hash(
+                0,
+                &contract_redeemscript,
+                Amount::from_sat(100_000),
+                EcdsaSighashType::All,
+            )
+            .unwrap();
+        let spend_sig = Signature {
+            signature: secp.sign_ecdsa(
+                &Message::from_digest(sighash.to_byte_array()),
+                &timelock_privkey,
+            ),
+            sighash_type: EcdsaSighashType::All,
+        };
+        timelock_spend.input[0].witness.push(spend_sig.to_vec());
+        timelock_spend.input[0].witness.push(Vec::new());
+        timelock_spend.input[0]
+            .witness
+            .push(contract_redeemscript.as_bytes());
+        let auth_sig = secp.sign_ecdsa(
+            &registration_message(challenge, &contract_tx.compute_txid()),
+            &timelock_privkey,
+        );
+        ContractWatch {
+            contract_tx,
+            contract_redeemscript,
+            spend: ContractSpend::Timelock(timelock_spend),
+            auth_sig,
+        }
+    }
+
+    #[test]
+    fn test_verify_contract_watch() {
+        let challenge = [1u8; 32];
+        let watch = contract_watch(&challenge);
+        assert!(verify_contract_watch(&watch, &challenge).is_ok());
+
+        // Signature over another challenge.
+        assert!(verify_contract_watch(&watch, &[2u8; 32]).is_err());
+
+        // Signed with the timelock key, but registered as a hashlock spend.
+        let mut hashlock_watch = watch.clone();
+        if let ContractSpend::Timelock(tx) = watch.spend.clone() {
+            hashlock_watch.spend = ContractSpend::Hashlock(tx);
+        }
+        assert!(verify_contract_watch(&hashlock_watch, &challenge).is_err());
+
+        // Spend not spending the contract.
+        let mut bad_spend_watch = watch.clone();
+        if let ContractSpend::Timelock(tx) = &mut bad_spend_watch.spend {
+            tx.input[0].previous_output = OutPoint::null();
+        }
+        assert!(verify_contract_watch(&bad_spend_watch, &challenge).is_err());
+
+        // Unsigned spend.
+        let mut unsigned_watch = watch.clone();
+        if let ContractSpend::Timelock(tx) = &mut unsigned_watch.spend {
+            tx.input[0].witness = Witness::new();
+        }
+        assert!(verify_contract_watch(&unsigned_watch, &challenge).is_err());
+
+        // Signature not covering the spend as registered.
+        let mut tampered_watch = watch.clone();
+        if let ContractSpend::Timelock(tx) = &mut tampered_watch.spend {
+            tx.output[0].value = Amount::from_sat(10_000);
+        }
+        assert!(verify_contract_watch(&tampered_watch, &challenge).is_err());
+    }
+
+    #[test]
+    fn test_registration_limits() {
+        let tower = Watchtower {
+            data_dir: std::env::temp_dir()
+                .join(format!("coinswap-watchtower-{}", std::process::id())),
+            max_watches_per_key: 1,
+            max_watches: 2,
+            ..Watchtower::default()
+        };
+
+        // Registering is only possible after asking for a challenge.
+        let mut challenge = None;
+        let register = |watch: ContractWatch| {
+            serde_cbor::to_vec(&WatchtowerRequest::Register {
+                watches: vec![watch],
+            })
+            .unwrap()
+        };
+        handle_request(&tower, &mut challenge, &register(contract_watch(&[0; 32]))).unwrap();
+        assert!(tower.registrations.read().unwrap().is_empty());
+
+        let get_challenge = serde_cbor::to_vec(&WatchtowerRequest::GetChallenge).unwrap();
+        handle_request(&tower, &mut challenge, &get_challenge).unwrap();
+        let fresh = challenge.expect("challenge handed out");
+        handle_request(&tower, &mut challenge, &register(contract_watch(&fresh))).unwrap();
+        assert_eq!(tower.registrations.read().unwrap().len(), 1);
+
+        // Same key again.
+        let watch = contract_watch(&fresh);
+        assert!(check_watch_limits(
+            &tower,
+            &tower.registrations.read().unwrap(),
+            std::slice::from_ref(&watch)
+        )
+        .is_err());
+
+        // Another key, up to the total limit of 2 contracts.
+        let mut other = watch.clone();
+        other.spend = match watch.spend.clone() {
+            ContractSpend::Timelock(tx) => ContractSpend::Hashlock(tx),
+            spend => spend,
+        };
+        assert!(check_watch_limits(
+            &tower,
+            &tower.registrations.read().unwrap(),
+            std::slice::from_ref(&other)
+        )
+        .is_ok());
+        let mut full = tower.registrations.read().unwrap().clone();
+        full.push(Registration {
+            watches: vec![watch],
+            triggered: false,
+            settled: Vec::new(),
+        });
+        assert!(check_watch_limits(&tower, &full, &[other]).is_err());
+
+        fs::remove_dir_all(&tower.data_dir).unwrap();
+    }
+}
@@ -22,7 +22,8 @@ use test_framework::*;
 /// transactions and reclaims their funds via timelock.
 ///
 /// The Taker after coming live again will see unfinished coinswaps in his wallet. He can reclaim his funds via
-/// broadcasting his contract transactions and claiming via timelock.
+/// broadcasting his contract transactions and claiming via timelock. As the Taker knows the preimage, his incoming
+/// contracts are claimed via hashlock.
 #[test]
 fn test_stop_taker_after_setup() {
     // ---- Setup ----
@@ -142,8 +143,19 @@ fn test_stop_taker_after_setup() {
     //Run Recovery script
     // TODO: do something about this?
     warn!("Starting Taker recovery process");
+    // 3 outgoing and 3 incoming swapcoins.
+    assert_eq!(taker.get_wallet().get_swapcoins_count(), 6);
     taker.recover_from_swap().unwrap();
 
+    // The incoming contracts are swept via hashlock, along with the timelock spends of the outgoing ones.
+    {
+        let wallet = taker.get_wallet();
+        assert_eq!(wallet.get_swapcoins_count(), 0);
+        let all_utxos = wallet.get_all_utxo().unwrap();
+        let seed_balance = wallet.balance_descriptor_utxo(Some(&all_utxos)).unwrap();
+        assert_eq!(seed_balance, Amount::from_btc(0.15428058).unwrap());
+    }
+
     // ## Fee Tracking and Workflow:
     //
     // ### Fee Breakdown:
@@ -96,7 +96,17 @@ fn test_dns() {
     ];
     verify_addresses(&all_addresses);
 
-    // Persistence check
+    // Persistence check: the address book is written to disk on shutdown, and loaded on restart.
+    let output = Command::new("./target/debug/directory-cli")
+        .arg("stop")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    process.wait().unwrap();
+
+    let mut process = start_dns(&data_dir, &bitcoind);
+    verify_addresses(&all_addresses);
+
     process.kill().expect("Failed to kill directoryd process");
     process.wait().unwrap();
 }
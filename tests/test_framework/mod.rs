@@ -301,6 +301,7 @@ pub fn verify_swap_results(
         assert!(
             seed_balance == Amount::from_btc(0.14497).unwrap() // Successful coinswap
                 || seed_balance == Amount::from_btc(0.14993232).unwrap() // Recovery via timelock
+                || seed_balance == Amount::from_btc(0.15428058).unwrap() // Recovery via timelock and hashlock
                 || seed_balance == Amount::from_btc(0.15).unwrap(), // No spending
             "Taker seed balance mismatch"
         );
@@ -315,16 +316,20 @@ pub fn verify_swap_results(
         assert_eq!(fidelity_balance, Amount::ZERO);
 
         // Check balance difference
-        let balance_diff = org_taker_spend_balance
-            .checked_sub(spendable_balance)
-            .unwrap();
-
-        assert!(
-            balance_diff == Amount::from_sat(64358) // Successful coinswap
-                || balance_diff == Amount::from_sat(6768) // Recovery via timelock
-                || balance_diff == Amount::ZERO, // No spending
-            "Taker spendable balance change mismatch"
-        );
+        match org_taker_spend_balance.checked_sub(spendable_balance) {
+            Some(balance_diff) => assert!(
+                balance_diff == Amount::from_sat(64358) // Successful coinswap
+                    || balance_diff == Amount::from_sat(6768) // Recovery via timelock
+                    || balance_diff == Amount::ZERO, // No spending
+                "Taker spendable balance change mismatch"
+            ),
+            // Recovery via timelock, and the incoming contracts claimed via hashlock.
+            None => assert_eq!(
+                spendable_balance - org_taker_spend_balance,
+                Amount::from_sat(428058),
+                "Taker spendable balance change mismatch"
+            ),
+        }
     }
 
     // Check Maker balances